@@ -0,0 +1,205 @@
+// License below.
+//! Implements breakpoints for the debug REPL.
+//!
+//! A breakpoint stops the emulator right before the CPU
+//! executes the instruction at its address, regardless of
+//! whether the CPU is in ARM or THUMB state. An optional
+//! condition has to evaluate to non-zero as well.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::slice;
+use super::super::hardware::Gba;
+use super::expr::{Expr, ExprError};
+
+
+/// A single breakpoint.
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    /// The number identifying this breakpoint.
+    pub id: u32,
+
+    /// The address of the instruction to stop at.
+    pub addr: u32,
+
+    /// The condition's source text and parsed expression.
+    pub condition: Option<(String, Expr)>,
+
+    /// Disabled breakpoints never stop the emulator.
+    pub enabled: bool,
+
+    /// How often this breakpoint stopped the emulator.
+    pub hits: u32,
+
+    /// Temporary breakpoints are removed once the emulator stops.
+    pub temporary: bool,
+}
+
+
+/// Manages all breakpoints of a debugging session.
+#[derive(Debug, Clone)]
+pub struct BreakpointList {
+    breakpoints: Vec<Breakpoint>,
+    next_id: u32,
+}
+
+impl BreakpointList {
+    /// Creates an empty breakpoint list.
+    pub fn new() -> BreakpointList {
+        BreakpointList {
+            breakpoints: Vec::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Get the number of breakpoints.
+    pub fn len(&self) -> usize { self.breakpoints.len() }
+
+    /// Checks whether there are no breakpoints.
+    pub fn is_empty(&self) -> bool { self.breakpoints.is_empty() }
+
+    /// Iterates over all breakpoints in the order of their IDs.
+    pub fn iter(&self) -> slice::Iter<Breakpoint> { self.breakpoints.iter() }
+
+    /// Get the breakpoint with the given ID, if any.
+    pub fn get(&self, id: u32) -> Option<&Breakpoint> {
+        self.breakpoints.iter().find(|b| b.id == id)
+    }
+
+    /// Adds a new enabled breakpoint.
+    ///
+    /// # Params
+    /// - `addr`: The address of the instruction to stop at.
+    /// - `condition`: An optional expression that must be
+    ///   non-zero for the breakpoint to stop the emulator.
+    ///
+    /// # Returns
+    /// - `Ok` with the new breakpoint's ID.
+    /// - `Err` if the condition is no valid expression.
+    pub fn add(&mut self, addr: u32, condition: Option<&str>) -> Result<u32, ExprError> {
+        self.insert(addr, condition, false)
+    }
+
+    /// Adds a new temporary breakpoint.
+    ///
+    /// Temporary breakpoints let commands like `next` run
+    /// until some address is reached, even if interrupts are
+    /// handled in between. They are not meant to be listed.
+    ///
+    /// # Params
+    /// - `addr`: The address of the instruction to stop at.
+    /// - `condition`: An optional expression that must be
+    ///   non-zero for the breakpoint to stop the emulator.
+    ///
+    /// # Returns
+    /// - `Ok` with the new breakpoint's ID.
+    /// - `Err` if the condition is no valid expression.
+    pub fn add_temporary(&mut self, addr: u32, condition: Option<&str>) -> Result<u32, ExprError> {
+        self.insert(addr, condition, true)
+    }
+
+    fn insert(&mut self, addr: u32, condition: Option<&str>, temporary: bool) -> Result<u32, ExprError> {
+        let condition = match condition {
+            Some(s) => Some((s.trim().to_string(), try!(Expr::parse(s)))),
+            None    => None,
+        };
+        let id = self.next_id;
+        self.next_id += 1;
+        self.breakpoints.push(Breakpoint {
+            id: id,
+            addr: addr,
+            condition: condition,
+            enabled: true,
+            hits: 0,
+            temporary: temporary,
+        });
+        Ok(id)
+    }
+
+    /// Removes a breakpoint.
+    ///
+    /// # Returns
+    /// `true` if there was such a breakpoint.
+    pub fn remove(&mut self, id: u32) -> bool {
+        let len = self.breakpoints.len();
+        self.breakpoints.retain(|b| b.id != id);
+        len != self.breakpoints.len()
+    }
+
+    /// Removes all breakpoints.
+    pub fn clear(&mut self) { self.breakpoints.clear(); }
+
+    /// Removes all temporary breakpoints.
+    pub fn clear_temporary(&mut self) { self.breakpoints.retain(|b| !b.temporary); }
+
+    /// Enables or disables a breakpoint.
+    ///
+    /// # Returns
+    /// `true` if there is such a breakpoint.
+    pub fn set_enabled(&mut self, id: u32, enabled: bool) -> bool {
+        match self.breakpoints.iter_mut().find(|b| b.id == id) {
+            Some(b) => { b.enabled = enabled; true },
+            None    => false,
+        }
+    }
+
+    /// Checks whether any breakpoint stops the emulator
+    /// before the next pipeline step.
+    ///
+    /// Breakpoints only trigger if the next pipeline step
+    /// actually executes an instruction. A condition that
+    /// cannot be evaluated stops the emulator as well, such
+    /// that the user notices the broken condition.
+    ///
+    /// # Params
+    /// - `gba`: The GBA about to run the next pipeline step.
+    ///
+    /// # Returns
+    /// The ID of the triggered breakpoint, if any.
+    pub fn check(&mut self, gba: &Gba) -> Option<u32> {
+        let cpu = gba.cpu_arm7tdmi();
+        if !cpu.is_about_to_execute() { return None; }
+        let addr = cpu.executing_address();
+
+        for b in self.breakpoints.iter_mut().filter(|b| b.enabled & (b.addr == addr)) {
+            let stop = match b.condition {
+                Some((ref src, ref e)) => match e.eval(gba) {
+                    Ok(x)  => x != 0,
+                    Err(e) => { error!("Failed evaluating the condition `{}` of breakpoint {:X}:\n{}", src, b.id, e); true },
+                },
+                None => true,
+            };
+            if stop {
+                b.hits += 1;
+                return Some(b.id);
+            }
+        }
+        None
+    }
+}
+
+impl Default for BreakpointList {
+    fn default() -> BreakpointList { BreakpointList::new() }
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -0,0 +1,129 @@
+// License below.
+//! Implements disassembling instructions from the GBA memory system.
+//!
+//! Instructions are read without side effects via the bus,
+//! such that disassembling never changes the emulated machine.
+//! Lines are formatted just like `--dasm-bios-arm/thumb` do.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::fmt::Write;
+use std::ops::Range;
+use super::super::hardware::Gba;
+use super::super::hardware::cpu::{State, ArmInstruction, ArmOpcode, ThumbInstruction, ThumbOpcode};
+
+
+/// Calculates the target of an ARM state B or BL instruction.
+///
+/// # Params
+/// - `addr`: The address of the instruction.
+/// - `inst`: The instruction itself.
+///
+/// # Returns
+/// The branch target, or `None` if this is no B or BL.
+pub fn arm_branch_target(addr: u32, inst: &ArmInstruction) -> Option<u32> {
+    match inst.opcode() {
+        ArmOpcode::B_BL => Some(addr.wrapping_add(8).wrapping_add(inst.branch_offset() as u32)),
+        _ => None,
+    }
+}
+
+/// Calculates the target of a THUMB state branch instruction.
+///
+/// # Params
+/// - `addr`: The address of the instruction.
+/// - `inst`: The instruction itself.
+/// - `next`: The following halfword. Needed to calculate the
+///   target of the two halfword long branch with link.
+///
+/// # Returns
+/// The branch target, or `None` if this is no branch or
+/// the second half of a long branch with link.
+pub fn thumb_branch_target(addr: u32, inst: &ThumbInstruction, next: u16) -> Option<u32> {
+    let pc = addr.wrapping_add(4);
+    match inst.opcode() {
+        ThumbOpcode::BranchConditionOffs => Some(pc.wrapping_add(inst.offs9() as u32)),
+        ThumbOpcode::BranchOffs          => Some(pc.wrapping_add(inst.offs12() as u32)),
+        ThumbOpcode::BranchLongOffs if !inst.is_low_offset_and_branch() && ((next & 0xF800) == 0xF800) => {
+            let hi = (inst.long_offs_part() << 21) >> 9;
+            let lo = ((next & 0x7FF) as i32) << 1;
+            Some(pc.wrapping_add(hi as u32).wrapping_add(lo as u32))
+        },
+        _ => None,
+    }
+}
+
+/// Disassembles a section of the GBA memory system.
+///
+/// Symbols are printed as labels before the instruction at
+/// their address, and branch targets with a symbol name are
+/// annotated. The instruction about to be executed is marked.
+///
+/// # Params
+/// - `gba`: The GBA whose memory to disassemble.
+/// - `range`: The addresses to disassemble, excluding the end.
+/// - `state`: Whether to decode ARM or THUMB instructions.
+///
+/// # Returns
+/// One line per instruction or label.
+pub fn disassemble(gba: &Gba, range: Range<u32>, state: State) -> Vec<String> {
+    let bus = gba.bus();
+    let cpu = gba.cpu_arm7tdmi();
+    let pc = cpu.executing_address();
+    let (width, align) = match state { State::ARM => (4, !3), State::THUMB => (2, !1) };
+    let mut lines = Vec::new();
+    let mut addr = range.start & align;
+
+    while addr < range.end {
+        if let Some(sym) = gba.symbols().symbol_at(addr) { lines.push(format!("{}:", sym.name)); }
+        let mut line = String::new();
+        let (text, target) = match state {
+            State::ARM => match bus.peek_word(addr) {
+                Ok(w) => match ArmInstruction::decode(w as u32) {
+                    Ok(inst) => (format!("{}", inst), arm_branch_target(addr, &inst)),
+                    Err(_)   => (format!("{:08X}", w), None),
+                },
+                Err(_) => ("????????".to_string(), None),
+            },
+            State::THUMB => match bus.peek_halfword(addr) {
+                Ok(h) => match ThumbInstruction::decode(h as u16) {
+                    Ok(inst) => {
+                        let next = bus.peek_halfword(addr.wrapping_add(2)).unwrap_or(0) as u16;
+                        (format!("{}", inst), thumb_branch_target(addr, &inst, next))
+                    },
+                    Err(_) => (format!("{:#06X}", h as u16), None),
+                },
+                Err(_) => ("??????".to_string(), None),
+            },
+        };
+        let _ = write!(line, "{:06X} - {}", addr, text);
+        if let Some(l) = target.and_then(|t| gba.symbols().label(t)) { let _ = write!(line, "\t{}", l); }
+        if addr == pc { line.push_str("\t<= PC"); }
+        lines.push(line);
+
+        addr = match addr.checked_add(width) { Some(a) => a, None => break };
+    }
+    lines
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -0,0 +1,567 @@
+// License below.
+//! Parses and evaluates debugger expressions.
+//!
+//! Expressions compute a value from the current machine
+//! state, e.g. the breakpoint condition
+//! `r0 == 3 && [03001000].h > 10`. All values are 32 bit
+//! unsigned integers with wrapping arithmetic. Comparisons
+//! and logical operators result in either `0` or `1`.
+//!
+//! Numbers are hexadecimal, with or without `0x` prefix,
+//! just like everywhere else in the debug REPL. Decimal
+//! numbers are prefixed with `#` like in the disassembly,
+//! e.g. `#100`, and binary numbers with `%`, e.g. `%101`.
+//! Words like `0b101` are rejected, as they could be meant
+//! as either. Any other word that is no register refers to
+//! a symbol's address. Symbols named like hexadecimal
+//! numbers, e.g. `add`, take precedence over the number.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::char;
+use std::error;
+use std::fmt;
+use std::ops::Range;
+use super::super::hardware::{Gba, GbaError};
+use super::super::hardware::cpu::{Arm7Tdmi, PSR};
+
+
+/// All named CPSR bits, accessible as `cpsr.N` etc.
+pub const CPSR_BIT_NAMES: &'static [(&'static str, u32)] = &[
+    ("n", 31), ("z", 30), ("c", 29), ("v", 28), ("i", 7), ("f", 6), ("t", 5),
+];
+
+/// All register names besides `r0` to `r15`.
+pub const REGISTER_NAMES: &'static [(&'static str, usize)] = &[
+    ("sp", Arm7Tdmi::SP), ("lr", Arm7Tdmi::LR), ("pc", Arm7Tdmi::PC),
+];
+
+
+/// Width of a memory access, e.g. `[addr].h`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Width {
+    #[doc = "8 bit access, `.b`."]      Byte,
+    #[doc = "16 bit access, `.h`."]     Halfword,
+    #[doc = "32 bit access, `.w`."]     Word,
+}
+
+/// Operators with a single operand.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum UnaryOp {
+    #[doc = "Two's complement `-x`."]   Neg,
+    #[doc = "Bitwise NOT `~x`."]        Not,
+    #[doc = "Logical NOT `!x`."]        LogicalNot,
+}
+
+/// Operators with two operands.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BinaryOp {
+    #[doc = "`a * b`"]  Mul,
+    #[doc = "`a / b`"]  Div,
+    #[doc = "`a % b`"]  Rem,
+    #[doc = "`a + b`"]  Add,
+    #[doc = "`a - b`"]  Sub,
+    #[doc = "`a << b`"] Shl,
+    #[doc = "`a >> b`"] Shr,
+    #[doc = "`a < b`"]  Lt,
+    #[doc = "`a <= b`"] Le,
+    #[doc = "`a > b`"]  Gt,
+    #[doc = "`a >= b`"] Ge,
+    #[doc = "`a == b`"] Eq,
+    #[doc = "`a != b`"] Ne,
+    #[doc = "`a & b`"]  And,
+    #[doc = "`a ^ b`"]  Xor,
+    #[doc = "`a | b`"]  Or,
+    #[doc = "`a && b`"] LogicalAnd,
+    #[doc = "`a || b`"] LogicalOr,
+}
+
+/// All binary operators with their binding power.
+///
+/// Two character operators come first, such
+/// that the tokeniser finds them before their
+/// single character prefixes.
+const BINARY_OPS: &'static [(&'static str, BinaryOp, u8)] = &[
+    ("||", BinaryOp::LogicalOr, 1), ("&&", BinaryOp::LogicalAnd, 2),
+    ("==", BinaryOp::Eq, 6), ("!=", BinaryOp::Ne, 6),
+    ("<=", BinaryOp::Le, 7), (">=", BinaryOp::Ge, 7),
+    ("<<", BinaryOp::Shl, 8), (">>", BinaryOp::Shr, 8),
+    ("|",  BinaryOp::Or,  3), ("^",  BinaryOp::Xor, 4), ("&",  BinaryOp::And, 5),
+    ("<",  BinaryOp::Lt,  7), (">",  BinaryOp::Gt,  7),
+    ("+",  BinaryOp::Add, 9), ("-",  BinaryOp::Sub, 9),
+    ("*",  BinaryOp::Mul, 10), ("/", BinaryOp::Div, 10), ("%", BinaryOp::Rem, 10),
+];
+
+/// Punctuation that is no binary operator.
+const PUNCTUATION: &'static [&'static str] = &["!", "~", "(", ")", "[", "]", "."];
+
+
+/// A parsed expression.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr {
+    /// A constant value.
+    Number(u32),
+
+    /// A GPR of the current mode.
+    Register(usize),
+
+    /// The whole CPSR.
+    Cpsr,
+
+    /// A single CPSR bit, given by its index.
+    CpsrBit(u32),
+
+    /// The address of a symbol, looked up when evaluating.
+    ///
+    /// Without such a symbol, names like `add` are
+    /// evaluated as hexadecimal numbers.
+    Symbol(String),
+
+    /// The value stored at an address.
+    Memory(Box<Expr>, Width),
+
+    /// An operator applied to a single value.
+    Unary(UnaryOp, Box<Expr>),
+
+    /// An operator applied to two values.
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Parses an expression.
+    ///
+    /// # Params
+    /// - `s`: The expression's source text.
+    ///
+    /// # Returns
+    /// - `Ok` if the whole text is a valid expression.
+    /// - `Err` describing the first syntax error.
+    pub fn parse(s: &str) -> Result<Expr, ExprError> {
+        let mut p = Parser { tokens: try!(tokenise(s)), pos: 0 };
+        let e = try!(p.expression(0));
+        match p.next() {
+            Some(t) => Err(ExprError::Unexpected(t)),
+            None    => Ok(e),
+        }
+    }
+
+    /// Evaluates the expression using the GBA's current state.
+    ///
+    /// Logical operators short-circuit, so the right hand
+    /// side of e.g. `r0 != 0 && [r0].w == 1` does not read
+    /// memory unless `r0` is non-zero. Memory is read using
+    /// `Bus::peek_*`, so evaluating never triggers watchpoints.
+    ///
+    /// # Params
+    /// - `gba`: The GBA to inspect.
+    ///
+    /// # Returns
+    /// - `Ok` with the expression's value.
+    /// - `Err` if dividing by zero or a memory access failed.
+    pub fn eval(&self, gba: &Gba) -> Result<u32, ExprError> {
+        Ok(match *self {
+            Expr::Number(x)     => x,
+            Expr::Register(i)   => gba.cpu_arm7tdmi().register(i) as u32,
+            Expr::Cpsr          => gba.cpu_arm7tdmi().cpsr().0,
+            Expr::CpsrBit(b)    => (gba.cpu_arm7tdmi().cpsr().0 >> b) & 1,
+            Expr::Symbol(ref n) => match gba.symbols().address_of(n).or_else(|| parse_number(n)) {
+                Some(x) => x,
+                None => return Err(ExprError::UnknownName(n.clone())),
+            },
+            Expr::Memory(ref a, w) => {
+                let addr = try!(a.eval(gba));
+                let bus = gba.bus();
+                try!(match w {
+                    Width::Byte     => bus.peek_byte(addr),
+                    Width::Halfword => bus.peek_halfword(addr),
+                    Width::Word     => bus.peek_word(addr),
+                }) as u32
+            },
+            Expr::Unary(op, ref x) => {
+                let x = try!(x.eval(gba));
+                match op {
+                    UnaryOp::Neg        => x.wrapping_neg(),
+                    UnaryOp::Not        => !x,
+                    UnaryOp::LogicalNot => (x == 0) as u32,
+                }
+            },
+            Expr::Binary(BinaryOp::LogicalAnd, ref a, ref b) => {
+                (try!(a.eval(gba)) != 0 && try!(b.eval(gba)) != 0) as u32
+            },
+            Expr::Binary(BinaryOp::LogicalOr, ref a, ref b) => {
+                (try!(a.eval(gba)) != 0 || try!(b.eval(gba)) != 0) as u32
+            },
+            Expr::Binary(op, ref a, ref b) => {
+                let (a, b) = (try!(a.eval(gba)), try!(b.eval(gba)));
+                match op {
+                    BinaryOp::Mul => a.wrapping_mul(b),
+                    BinaryOp::Div => if b == 0 { return Err(ExprError::DivisionByZero); } else { a / b },
+                    BinaryOp::Rem => if b == 0 { return Err(ExprError::DivisionByZero); } else { a % b },
+                    BinaryOp::Add => a.wrapping_add(b),
+                    BinaryOp::Sub => a.wrapping_sub(b),
+                    BinaryOp::Shl => if b < 32 { a << b } else { 0 },
+                    BinaryOp::Shr => if b < 32 { a >> b } else { 0 },
+                    BinaryOp::Lt  => (a <  b) as u32,
+                    BinaryOp::Le  => (a <= b) as u32,
+                    BinaryOp::Gt  => (a >  b) as u32,
+                    BinaryOp::Ge  => (a >= b) as u32,
+                    BinaryOp::Eq  => (a == b) as u32,
+                    BinaryOp::Ne  => (a != b) as u32,
+                    BinaryOp::And => a & b,
+                    BinaryOp::Xor => a ^ b,
+                    BinaryOp::Or  => a | b,
+                    BinaryOp::LogicalAnd | BinaryOp::LogicalOr => unreachable!(),
+                }
+            },
+        })
+    }
+
+    /// Stores a value into the register, CPSR bits, or memory
+    /// this expression refers to.
+    ///
+    /// Registers and CPSR go through the CPU's accessors, such
+    /// that banked registers and ARM/THUMB state stay coherent.
+    /// Memory is written via the bus without triggering any
+    /// watchpoints, but with all IO register side effects.
+    ///
+    /// # Params
+    /// - `gba`: The GBA to modify.
+    /// - `x`: The value to store.
+    ///
+    /// # Returns
+    /// - `Ok` if the value has been stored.
+    /// - `Err` if this is no register, CPSR, or memory, if
+    ///   the new CPSR mode is invalid, if the T bit would
+    ///   change, or if storing failed.
+    pub fn assign(&self, gba: &mut Gba, x: u32) -> Result<(), ExprError> {
+        match *self {
+            Expr::Register(i) => { gba.cpu_arm7tdmi_mut().set_register(i, x as i32); Ok(()) },
+            Expr::Cpsr | Expr::CpsrBit(_) => {
+                let old = gba.cpu_arm7tdmi().cpsr().0;
+                let new = if let Expr::CpsrBit(b) = *self { (old & !(1 << b)) | ((x & 1) << b) } else { x };
+                if PSR(new).state() != PSR(old).state() { return Err(ExprError::StateChange); }
+                if gba.cpu_arm7tdmi_mut().set_cpsr(new) { Ok(()) } else { Err(ExprError::InvalidCpsr(new)) }
+            },
+            Expr::Memory(ref a, w) => {
+                let addr = try!(a.eval(gba));
+                let mut bus = gba.bus_mut();
+                Ok(try!(match w {
+                    Width::Byte     => bus.poke_byte(addr, x as i32),
+                    Width::Halfword => bus.poke_halfword(addr, x as i32),
+                    Width::Word     => bus.poke_word(addr, x as i32),
+                }))
+            },
+            _ => Err(ExprError::NotAssignable),
+        }
+    }
+}
+
+
+/// Errors while parsing or evaluating an expression.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ExprError {
+    /// The expression ends where another token is expected.
+    UnexpectedEnd,
+
+    /// A token that does not fit the syntax.
+    Unexpected(String),
+
+    /// A name that is neither a register nor any other known name.
+    UnknownName(String),
+
+    /// A word starting with a digit or `#` that is no valid number.
+    InvalidNumber(String),
+
+    /// A word like `0b101` that is either binary or hexadecimal.
+    AmbiguousNumber(String),
+
+    /// Divided by zero while evaluating.
+    DivisionByZero,
+
+    /// A memory access failed while evaluating.
+    Memory(GbaError),
+
+    /// Assigned to something else than a register, CPSR, or memory.
+    NotAssignable,
+
+    /// Assigned a CPSR value with invalid mode bits.
+    InvalidCpsr(u32),
+
+    /// Assigned a CPSR value switching between ARM and THUMB state.
+    StateChange,
+}
+
+impl error::Error for ExprError {
+    fn description(&self) -> &str {
+        match *self {
+            ExprError::UnexpectedEnd   => "Unexpected end of expression.",
+            ExprError::Unexpected(_)   => "Unexpected token in expression.",
+            ExprError::UnknownName(_)  => "Unknown name in expression.",
+            ExprError::InvalidNumber(_) => "Invalid number in expression.",
+            ExprError::AmbiguousNumber(_) => "Ambiguous number in expression.",
+            ExprError::DivisionByZero  => "Division by zero.",
+            ExprError::Memory(_)       => "Invalid memory access in expression.",
+            ExprError::NotAssignable   => "Cannot assign to expression.",
+            ExprError::InvalidCpsr(_)  => "Invalid CPSR mode bits.",
+            ExprError::StateChange     => "Cannot switch between ARM and THUMB state.",
+        }
+    }
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExprError::UnexpectedEnd      => write!(f, "The expression ends unexpectedly."),
+            ExprError::Unexpected(ref t)  => write!(f, "Unexpected `{}` in expression.", t),
+            ExprError::UnknownName(ref n) => write!(f, "Unknown name `{}` in expression.", n),
+            ExprError::InvalidNumber(ref n) => write!(f, "Invalid number `{}` in expression.", n),
+            ExprError::AmbiguousNumber(ref n) => write!(f, "Ambiguous number `{}`, write `%{}` for binary or `0x{}` for hexadecimal.",
+                                                        n, &n[2..], n),
+            ExprError::DivisionByZero     => write!(f, "Division by zero in expression."),
+            ExprError::Memory(ref e)      => write!(f, "{}", e),
+            ExprError::NotAssignable      => write!(f, "Only registers, CPSR, and memory can be assigned to."),
+            ExprError::InvalidCpsr(x)     => write!(f, "CPSR {:#010X} has invalid mode bits.", x),
+            ExprError::StateChange        => write!(f, "The T bit cannot be changed, as THUMB code is not executed yet."),
+        }
+    }
+}
+
+impl From<GbaError> for ExprError {
+    fn from(e: GbaError) -> ExprError { ExprError::Memory(e) }
+}
+
+
+/// Parses and evaluates an expression at once.
+pub fn eval_str(s: &str, gba: &Gba) -> Result<u32, ExprError> {
+    Expr::parse(s).and_then(|e| e.eval(gba))
+}
+
+/// Parses a range of two expressions like `A..B`.
+///
+/// Any omitted bound is replaced by its default value, e.g.
+/// `..B` is `default_start..B`.
+///
+/// # Params
+/// - `s`: The range's source text.
+/// - `gba`: The GBA to evaluate the bounds with.
+/// - `default_start`: The start if omitted.
+/// - `default_end`: The end if omitted.
+///
+/// # Returns
+/// - `Ok` with the evaluated range.
+/// - `Err` if there is no `..` or any bound is invalid.
+pub fn eval_range(s: &str, gba: &Gba, default_start: u32, default_end: u32) -> Result<Range<u32>, ExprError> {
+    let i = match s.find("..") { Some(i) => i, None => return Err(ExprError::Unexpected(s.trim().to_string())) };
+    let (a, b) = (s[..i].trim(), s[(i + 2)..].trim());
+    let start = if a.is_empty() { default_start } else { try!(eval_str(a, gba)) };
+    let end   = if b.is_empty() { default_end   } else { try!(eval_str(b, gba)) };
+    Ok(start..end)
+}
+
+
+/// Ways to format a value for `print`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Format {
+    #[doc = "Hexadecimal, e.g. `0x0000002A`."]  Hex,
+    #[doc = "Unsigned and signed decimal."]     Dec,
+    #[doc = "Binary, e.g. `%101010`."]          Bin,
+    #[doc = "A quoted character, e.g. `'*'`."]  Char,
+}
+
+impl Format {
+    /// Parses a format letter, i.e. `x`, `d`, `b`, or `c`.
+    pub fn from_letter(s: &str) -> Option<Format> {
+        match s {
+            "x" => Some(Format::Hex),
+            "d" => Some(Format::Dec),
+            "b" => Some(Format::Bin),
+            "c" => Some(Format::Char),
+            _ => None,
+        }
+    }
+
+    /// Formats a value.
+    pub fn format(self, x: u32) -> String {
+        match self {
+            Format::Hex  => format!("{:#010X}", x),
+            Format::Dec  => if (x as i32) < 0 { format!("{} ({})", x, x as i32) } else { format!("{}", x) },
+            Format::Bin  => format!("%{:b}", x),
+            Format::Char => match char::from_u32(x) {
+                Some(c) => format!("'{}'", c.escape_default()),
+                None    => "<no character>".to_string(),
+            },
+        }
+    }
+}
+
+
+/// Splits an expression into words and punctuation.
+///
+/// A word is any run of letters, digits, and underscores.
+/// Whether a word is a number or a name is up to the parser.
+fn tokenise(s: &str) -> Result<Vec<String>, ExprError> {
+    let mut tokens = Vec::new();
+    let mut rest = s.trim_start();
+    while !rest.is_empty() {
+        // Decimal numbers start with `#`.
+        let prefix = if rest.starts_with('#') { 1 } else { 0 };
+        let word_len = prefix + rest[prefix..].find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len() - prefix);
+        let len = if word_len > 0 { word_len }
+            else if let Some(&(op, _, _)) = BINARY_OPS.iter().find(|&&(op, _, _)| rest.starts_with(op)) { op.len() }
+            else if let Some(p) = PUNCTUATION.iter().find(|p| rest.starts_with(*p)) { p.len() }
+            else { return Err(ExprError::Unexpected(rest.chars().next().unwrap_or(' ').to_string())); };
+        tokens.push(rest[..len].to_string());
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Parses a number like `1F`, `0x1F`, `#31`, or `%11111`.
+fn parse_number(w: &str) -> Option<u32> {
+    let (digits, radix) =
+             if w.starts_with('#') { (&w[1..], 10) }
+        else if w.starts_with('%') { (&w[1..], 2) }
+        else if w.starts_with("0x") || w.starts_with("0X") { (&w[2..], 16) }
+        else { (w, 16) };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) { return None; }
+    u32::from_str_radix(digits, radix).ok()
+}
+
+/// Checks whether a word has to be a number, i.e. starts with a digit or `#`.
+fn is_number_like(w: &str) -> bool {
+    w.starts_with('#') || w.starts_with(|c: char| c.is_digit(10))
+}
+
+/// Checks whether a number looks like C's binary numbers, e.g. `0b101`.
+fn is_ambiguous_number(w: &str) -> bool {
+    (w.starts_with("0b") || w.starts_with("0B")) && (w.len() > 2) && w[2..].chars().all(|c| c == '0' || c == '1')
+}
+
+/// Parses a register name like `r3` or `sp`.
+fn parse_register(w: &str) -> Option<usize> {
+    let w = w.to_lowercase();
+    if let Some(&(_, i)) = REGISTER_NAMES.iter().find(|&&(n, _)| n == w) { return Some(i); }
+    if !w.starts_with('r') { return None; }
+    match usize::from_str_radix(&w[1..], 10) {
+        Ok(i) if i < 16 => Some(i),
+        _ => None,
+    }
+}
+
+
+/// A recursive descent parser using precedence climbing.
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> { self.tokens.get(self.pos).map(|t| &t[..]) }
+
+    fn next(&mut self) -> Option<String> {
+        let t = self.tokens.get(self.pos).cloned();
+        if t.is_some() { self.pos += 1; }
+        t
+    }
+
+    fn expect(&mut self, tok: &str) -> Result<(), ExprError> {
+        match self.next() {
+            Some(ref t) if t == tok => Ok(()),
+            Some(t) => Err(ExprError::Unexpected(t)),
+            None    => Err(ExprError::UnexpectedEnd),
+        }
+    }
+
+    /// Parses all binary operators binding stronger than `min_power`.
+    fn expression(&mut self, min_power: u8) -> Result<Expr, ExprError> {
+        let mut lhs = try!(self.unary());
+        loop {
+            let (op, power) = match self.peek().and_then(|t| BINARY_OPS.iter().find(|&&(op, _, _)| op == t)) {
+                Some(&(_, op, power)) if power > min_power => (op, power),
+                _ => return Ok(lhs),
+            };
+            self.pos += 1;
+            let rhs = try!(self.expression(power));
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ExprError> {
+        let op = match self.peek() {
+            Some("-") => UnaryOp::Neg,
+            Some("~") => UnaryOp::Not,
+            Some("!") => UnaryOp::LogicalNot,
+            _ => return self.primary(),
+        };
+        self.pos += 1;
+        Ok(Expr::Unary(op, Box::new(try!(self.unary()))))
+    }
+
+    fn primary(&mut self) -> Result<Expr, ExprError> {
+        let t = match self.next() { Some(t) => t, None => return Err(ExprError::UnexpectedEnd) };
+        match &t[..] {
+            "(" => {
+                let e = try!(self.expression(0));
+                try!(self.expect(")"));
+                Ok(e)
+            },
+            "[" => {
+                let addr = try!(self.expression(0));
+                try!(self.expect("]"));
+                let width = if self.peek() == Some(".") {
+                    self.pos += 1;
+                    match self.next().map(|w| w.to_lowercase()) {
+                        Some(ref w) if w == "b" => Width::Byte,
+                        Some(ref w) if w == "h" => Width::Halfword,
+                        Some(ref w) if w == "w" => Width::Word,
+                        Some(w) => return Err(ExprError::Unexpected(w)),
+                        None    => return Err(ExprError::UnexpectedEnd),
+                    }
+                } else { Width::Word };
+                Ok(Expr::Memory(Box::new(addr), width))
+            },
+            "%" => match self.next() {
+                Some(w) => parse_number(&format!("%{}", w)).map(Expr::Number).ok_or_else(|| ExprError::InvalidNumber(format!("%{}", w))),
+                None    => Err(ExprError::UnexpectedEnd),
+            },
+            w if w.starts_with('#') || w.chars().all(|c| c.is_alphanumeric() || c == '_') => self.word(w),
+            _ => Err(ExprError::Unexpected(t.clone())),
+        }
+    }
+
+    fn word(&mut self, w: &str) -> Result<Expr, ExprError> {
+        if is_ambiguous_number(w) { return Err(ExprError::AmbiguousNumber(w.to_string())); }
+        if is_number_like(w) { return parse_number(w).map(Expr::Number).ok_or_else(|| ExprError::InvalidNumber(w.to_string())); }
+        if let Some(i) = parse_register(w) { return Ok(Expr::Register(i)); }
+        if w.to_lowercase() != "cpsr" { return Ok(Expr::Symbol(w.to_string())); }
+        if self.peek() != Some(".") { return Ok(Expr::Cpsr); }
+        self.pos += 1;
+        let bit = match self.next() { Some(b) => b, None => return Err(ExprError::UnexpectedEnd) };
+        match CPSR_BIT_NAMES.iter().find(|&&(n, _)| n == bit.to_lowercase()) {
+            Some(&(_, b)) => Ok(Expr::CpsrBit(b)),
+            None => Err(ExprError::UnknownName(format!("cpsr.{}", bit))),
+        }
+    }
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
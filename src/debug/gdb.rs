@@ -0,0 +1,558 @@
+// License below.
+//! Implements a stub for the GDB remote serial protocol.
+//!
+//! This lets e.g. `arm-none-eabi-gdb` debug the emulated
+//! game via `target remote localhost:PORT`. The stub serves
+//! a single connection, handling one packet at a time. While
+//! the emulator runs, GDB may interrupt it with Ctrl-C.
+//!
+//! GDB gets a target description with the ARM core registers
+//! `r0` to `pc` and `cpsr`, so it knows whether the CPU is in
+//! ARM or THUMB state. Other than on real hardware, `pc` is
+//! the address of the instruction executed next.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::cmp;
+use std::fmt::Write as FmtWrite;
+use std::io;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use super::super::hardware::Gba;
+use super::super::hardware::cpu::Arm7Tdmi;
+use super::super::hardware::watchpoint::WatchKind;
+use super::breakpoint::BreakpointList;
+
+
+/// The number of registers in the `g` packet, i.e. `r0` to `pc` and `cpsr`.
+pub const GDB_REGISTERS: usize = 17;
+
+/// The register number of CPSR in `p` and `P` packets.
+pub const GDB_CPSR: usize = 16;
+
+/// Interrupt byte sent by GDB on Ctrl-C.
+pub const GDB_INTERRUPT: u8 = 0x03;
+
+/// The largest packet GDB may send, as announced by `qSupported`.
+pub const GDB_PACKET_SIZE: usize = 0x1000;
+
+/// How many pipeline steps to run between checking for Ctrl-C.
+const INTERRUPT_POLL_STEPS: u32 = 0x4000;
+
+/// The target description sent via `qXfer:features:read`.
+pub const TARGET_XML: &'static str = "<?xml version=\"1.0\"?>\
+<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+<target version=\"1.0\">\
+<architecture>arm</architecture>\
+<feature name=\"org.gnu.gdb.arm.core\">\
+<reg name=\"r0\" bitsize=\"32\"/>\
+<reg name=\"r1\" bitsize=\"32\"/>\
+<reg name=\"r2\" bitsize=\"32\"/>\
+<reg name=\"r3\" bitsize=\"32\"/>\
+<reg name=\"r4\" bitsize=\"32\"/>\
+<reg name=\"r5\" bitsize=\"32\"/>\
+<reg name=\"r6\" bitsize=\"32\"/>\
+<reg name=\"r7\" bitsize=\"32\"/>\
+<reg name=\"r8\" bitsize=\"32\"/>\
+<reg name=\"r9\" bitsize=\"32\"/>\
+<reg name=\"r10\" bitsize=\"32\"/>\
+<reg name=\"r11\" bitsize=\"32\"/>\
+<reg name=\"r12\" bitsize=\"32\"/>\
+<reg name=\"sp\" bitsize=\"32\" type=\"data_ptr\"/>\
+<reg name=\"lr\" bitsize=\"32\"/>\
+<reg name=\"pc\" bitsize=\"32\" type=\"code_ptr\"/>\
+<reg name=\"cpsr\" bitsize=\"32\"/>\
+</feature>\
+</target>";
+
+
+/// What the stub should do after handling a packet.
+#[derive(Debug, PartialEq, Clone)]
+pub enum GdbAction {
+    #[doc = "Send the reply and wait for the next packet."]      Reply(String),
+    #[doc = "Execute a single instruction, then report."]        Step,
+    #[doc = "Run until stopped for any reason, then report."]    Continue,
+    #[doc = "Reply `OK` and end the session."]                   Detach,
+    #[doc = "End the session without replying."]                 Kill,
+}
+
+
+/// Calculates the checksum of a packet's payload.
+pub fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0_u8, |sum, &b| sum.wrapping_add(b))
+}
+
+/// Frames a payload as `$payload#checksum`.
+///
+/// The characters `$`, `#`, `}`, and `*` are escaped.
+pub fn encode_packet(payload: &str) -> Vec<u8> {
+    let mut data = Vec::with_capacity(payload.len() + 4);
+    for &b in payload.as_bytes() {
+        match b {
+            b'$' | b'#' | b'}' | b'*' => { data.push(b'}'); data.push(b ^ 0x20); },
+            _ => data.push(b),
+        }
+    }
+    let sum = checksum(&data);
+    let mut packet = Vec::with_capacity(data.len() + 4);
+    packet.push(b'$');
+    packet.extend_from_slice(&data);
+    packet.extend_from_slice(format!("#{:02x}", sum).as_bytes());
+    packet
+}
+
+/// Formats a word as little endian hex bytes.
+fn hex_word(x: u32) -> String {
+    format!("{:02x}{:02x}{:02x}{:02x}", x as u8, (x >> 8) as u8, (x >> 16) as u8, (x >> 24) as u8)
+}
+
+/// Parses a number of 1 to 8 hex digits.
+///
+/// Packets are parsed as bytes, as GDB may
+/// send anything, even invalid UTF-8.
+fn parse_hex(s: &[u8]) -> Option<u32> {
+    if s.is_empty() || (s.len() > 8) { return None; }
+    s.iter().fold(Some(0_u32), |x, &b| match (x, (b as char).to_digit(16)) {
+        (Some(x), Some(d)) => Some((x << 4) | d),
+        _ => None,
+    })
+}
+
+/// Parses a little endian word of 8 hex digits.
+fn parse_hex_word(s: &[u8]) -> Option<u32> {
+    if s.len() != 8 { return None; }
+    parse_hex(s).map(u32::swap_bytes)
+}
+
+/// Parses a pair of hex numbers like `addr,len`.
+fn parse_pair(s: &[u8]) -> Option<(u32, u32)> {
+    let mut it = s.splitn(2, |&b| b == b',');
+    match (it.next().and_then(parse_hex), it.next().and_then(parse_hex)) {
+        (Some(a), Some(b)) => Some((a, b)),
+        _ => None,
+    }
+}
+
+
+/// Serves the GDB remote serial protocol for a single GBA.
+pub struct GdbStub {
+    breakpoints: BreakpointList,
+}
+
+impl GdbStub {
+    /// Creates a new stub without any breakpoints.
+    pub fn new() -> GdbStub {
+        GdbStub {
+            breakpoints: BreakpointList::new(),
+        }
+    }
+
+    /// Waits for GDB to connect on localhost and serves it.
+    ///
+    /// # Params
+    /// - `gba`: The GBA to debug.
+    /// - `port`: The TCP port to listen on.
+    ///
+    /// # Returns
+    /// - `Ok` after GDB detached or killed the session.
+    /// - `Err` if the connection failed.
+    pub fn serve(&mut self, gba: &mut Gba, port: u16) -> io::Result<()> {
+        let listener = try!(TcpListener::bind(("127.0.0.1", port)));
+        info!("Waiting for GDB to connect to localhost:{}.", port);
+        let (stream, peer) = try!(listener.accept());
+        info!("GDB connected from {}.", peer);
+        let mut conn = Connection::new(stream);
+
+        while let Some(packet) = try!(conn.read_packet()) {
+            if packet.len() > GDB_PACKET_SIZE {
+                warn!("GDB packet exceeds {} bytes.", GDB_PACKET_SIZE);
+                try!(conn.write_packet("E01"));
+                continue;
+            }
+            match self.handle_packet(gba, &packet) {
+                GdbAction::Reply(r) => {
+                    try!(conn.write_packet(&r));
+                    if &packet[..] == b"QStartNoAckMode" { conn.no_ack = true; }
+                },
+                GdbAction::Step => {
+                    let r = self.step(gba);
+                    try!(conn.write_packet(&r));
+                },
+                GdbAction::Continue => {
+                    let r = self.resume(gba, || conn.poll_interrupt());
+                    try!(conn.write_packet(&r));
+                },
+                GdbAction::Detach => { try!(conn.write_packet("OK")); break; },
+                GdbAction::Kill => break,
+            }
+        }
+        info!("GDB disconnected.");
+        Ok(())
+    }
+
+    /// Handles a single packet's payload.
+    ///
+    /// # Returns
+    /// What to do next. Unsupported packets get an empty
+    /// reply, malformed ones an `E01` reply.
+    pub fn handle_packet(&mut self, gba: &mut Gba, packet: &[u8]) -> GdbAction {
+        let (cmd, args) = match packet.split_first() { Some((&c, a)) => (c, a), None => (0, packet) };
+        let reply = match cmd {
+            b'?' => "S05".to_string(),
+            b'g' => self.read_registers(gba),
+            b'G' => self.write_registers(gba, args),
+            b'p' => self.read_register(gba, args),
+            b'P' => self.write_register(gba, args),
+            b'm' => self.read_memory(gba, args),
+            b'M' => self.write_memory(gba, args),
+            b'Z' => self.insert_point(gba, args),
+            b'z' => self.remove_point(gba, args),
+            b'H' => "OK".to_string(),
+            b's' | b'c' => {
+                if !args.is_empty() {
+                    match parse_hex(args) {
+                        Some(addr) => gba.cpu_arm7tdmi_mut().set_register(Arm7Tdmi::PC, addr as i32),
+                        None       => return GdbAction::Reply("E01".to_string()),
+                    }
+                }
+                return if cmd == b's' { GdbAction::Step } else { GdbAction::Continue };
+            },
+            b'D' => return GdbAction::Detach,
+            b'k' => return GdbAction::Kill,
+            b'q' | b'Q' => self.query(packet),
+            _ => String::new(),
+        };
+        GdbAction::Reply(reply)
+    }
+
+    fn query(&self, packet: &[u8]) -> String {
+        const TARGET_XML_READ: &'static [u8] = b"qXfer:features:read:target.xml:";
+        if packet.starts_with(b"qSupported") {
+            return format!("PacketSize={:x};qXfer:features:read+;QStartNoAckMode+", GDB_PACKET_SIZE);
+        }
+        if packet.starts_with(TARGET_XML_READ) {
+            let range = &packet[TARGET_XML_READ.len()..];
+            return match parse_pair(range) {
+                Some((offs, len)) => {
+                    let xml = TARGET_XML.as_bytes();
+                    let start = cmp::min(offs as usize, xml.len());
+                    let end   = cmp::min(start.saturating_add(len as usize), xml.len());
+                    let part  = String::from_utf8_lossy(&xml[start..end]);
+                    format!("{}{}", if end < xml.len() { "m" } else { "l" }, part)
+                },
+                None => "E01".to_string(),
+            };
+        }
+        match packet {
+            b"QStartNoAckMode" => "OK",
+            b"qAttached"       => "1",
+            b"qC"              => "QC1",
+            b"qfThreadInfo"    => "m1",
+            b"qsThreadInfo"    => "l",
+            b"qSymbol::"       => "OK",
+            _ => "",
+        }.to_string()
+    }
+
+    fn register_value(gba: &Gba, i: usize) -> u32 {
+        let cpu = gba.cpu_arm7tdmi();
+        match i {
+            Arm7Tdmi::PC => cpu.executing_address(),
+            GDB_CPSR     => cpu.cpsr().0,
+            _            => cpu.register(i) as u32,
+        }
+    }
+
+    fn set_register_value(gba: &mut Gba, i: usize, x: u32) -> bool {
+        if GdbStub::register_value(gba, i) == x { return true; }
+        let cpu = gba.cpu_arm7tdmi_mut();
+        match i {
+            GDB_CPSR => cpu.set_cpsr(x),
+            _        => { cpu.set_register(i, x as i32); true },
+        }
+    }
+
+    fn read_registers(&self, gba: &Gba) -> String {
+        (0..GDB_REGISTERS).map(|i| hex_word(GdbStub::register_value(gba, i))).collect()
+    }
+
+    fn write_registers(&self, gba: &mut Gba, args: &[u8]) -> String {
+        if args.len() < GDB_REGISTERS * 8 { return "E01".to_string(); }
+        let mut values = [0_u32; GDB_REGISTERS];
+        for (x, word) in values.iter_mut().zip(args.chunks(8)) {
+            *x = match parse_hex_word(word) { Some(x) => x, None => return "E01".to_string() };
+        }
+        // Write CPSR first, as it may switch register banks.
+        if !GdbStub::set_register_value(gba, GDB_CPSR, values[GDB_CPSR]) { return "E01".to_string(); }
+        for (i, &x) in values[..GDB_CPSR].iter().enumerate() {
+            GdbStub::set_register_value(gba, i, x);
+        }
+        "OK".to_string()
+    }
+
+    fn read_register(&self, gba: &Gba, args: &[u8]) -> String {
+        match parse_hex(args) {
+            Some(i) if (i as usize) < GDB_REGISTERS => hex_word(GdbStub::register_value(gba, i as usize)),
+            _ => "E01".to_string(),
+        }
+    }
+
+    fn write_register(&self, gba: &mut Gba, args: &[u8]) -> String {
+        let mut it = args.splitn(2, |&b| b == b'=');
+        let i = it.next().and_then(parse_hex).map(|i| i as usize);
+        let x = it.next().and_then(parse_hex_word);
+        match (i, x) {
+            (Some(i), Some(x)) if i < GDB_REGISTERS => {
+                if GdbStub::set_register_value(gba, i, x) { "OK" } else { "E01" }.to_string()
+            },
+            _ => "E01".to_string(),
+        }
+    }
+
+    fn read_memory(&self, gba: &Gba, args: &[u8]) -> String {
+        let (addr, len) = match parse_pair(args) { Some(p) => p, None => return "E01".to_string() };
+        if (len as usize) > GDB_PACKET_SIZE / 2 { return "E01".to_string(); }
+        let bus = gba.bus();
+        let mut reply = String::with_capacity(len as usize * 2);
+        for i in 0..len {
+            match bus.peek_byte(addr.wrapping_add(i)) {
+                Ok(b)  => { let _ = write!(reply, "{:02x}", b as u8); },
+                Err(_) => return if i == 0 { "E01".to_string() } else { reply },
+            }
+        }
+        reply
+    }
+
+    fn write_memory(&self, gba: &mut Gba, args: &[u8]) -> String {
+        let mut it = args.splitn(2, |&b| b == b':');
+        let (addr, len) = match it.next().and_then(parse_pair) { Some(p) => p, None => return "E01".to_string() };
+        let data = it.next().unwrap_or(&[]);
+        if (len as usize) > GDB_PACKET_SIZE / 2 { return "E01".to_string(); }
+        if data.len() != (len as usize) * 2 { return "E01".to_string(); }
+        let mut bus = gba.bus_mut();
+        for (i, hex) in data.chunks(2).enumerate() {
+            let b = match parse_hex(hex) { Some(b) => b, None => return "E01".to_string() };
+            if bus.poke_byte(addr.wrapping_add(i as u32), b as i32).is_err() { return "E01".to_string(); }
+        }
+        "OK".to_string()
+    }
+
+    /// Parses `type,addr,kind` of `Z` and `z` packets.
+    fn parse_point(args: &[u8]) -> Option<(u8, u32, u32)> {
+        let mut it = args.splitn(3, |&b| b == b',');
+        let t = match it.next() { Some(&[t]) if (t as char).is_digit(10) => t - b'0', _ => return None };
+        let rest = match it.next().and_then(|a| it.next().map(|k| (a, k))) { Some(r) => r, None => return None };
+        let kind = rest.1.split(|&b| b == b';').next().unwrap_or(&[]);
+        match (parse_hex(rest.0), parse_hex(kind)) {
+            (Some(addr), Some(kind)) => Some((t, addr, kind)),
+            _ => None,
+        }
+    }
+
+    fn watch_kind(t: u8) -> Option<WatchKind> {
+        match t { 2 => Some(WatchKind::Write), 3 => Some(WatchKind::Read), 4 => Some(WatchKind::Access), _ => None }
+    }
+
+    fn insert_point(&mut self, gba: &mut Gba, args: &[u8]) -> String {
+        match GdbStub::parse_point(args) {
+            // Software and hardware breakpoints are the same to us.
+            Some((0, addr, _)) | Some((1, addr, _)) => {
+                if self.breakpoints.iter().all(|b| b.addr != addr) { let _ = self.breakpoints.add(addr, None); }
+                "OK".to_string()
+            },
+            Some((t, addr, len)) => match GdbStub::watch_kind(t) {
+                Some(kind) => {
+                    gba.bus_mut().watchpoints_mut().add(addr..addr.saturating_add(cmp::max(len, 1)), kind, None);
+                    "OK".to_string()
+                },
+                None => String::new(),
+            },
+            None => "E01".to_string(),
+        }
+    }
+
+    fn remove_point(&mut self, gba: &mut Gba, args: &[u8]) -> String {
+        match GdbStub::parse_point(args) {
+            Some((0, addr, _)) | Some((1, addr, _)) => {
+                let ids: Vec<u32> = self.breakpoints.iter().filter(|b| b.addr == addr).map(|b| b.id).collect();
+                for id in ids { self.breakpoints.remove(id); }
+                "OK".to_string()
+            },
+            Some((t, addr, len)) => match GdbStub::watch_kind(t) {
+                Some(kind) => {
+                    let end = addr.saturating_add(cmp::max(len, 1));
+                    let mut bus = gba.bus_mut();
+                    let wps = bus.watchpoints_mut();
+                    let id = wps.iter().find(|w| (w.range.start == addr) & (w.range.end == end) & (w.kind == kind)).map(|w| w.id);
+                    if let Some(id) = id { wps.remove(id); }
+                    "OK".to_string()
+                },
+                None => String::new(),
+            },
+            None => "E01".to_string(),
+        }
+    }
+
+    /// Runs a single pipeline step and checks for watchpoint hits.
+    ///
+    /// # Returns
+    /// A stop reply if the emulator must stop.
+    fn pipeline_step(&self, gba: &mut Gba) -> Option<String> {
+        if let Err(e) = gba.cpu_arm7tdmi_mut().pipeline_step() {
+            error!("{}", e);
+            return Some("S04".to_string());
+        }
+        let hit = gba.bus_mut().watchpoints_mut().take_hit();
+        hit.map(|hit| {
+            let kind = gba.bus().watchpoints().get(hit.id).map_or(WatchKind::Access, |w| w.kind);
+            let name = match kind { WatchKind::Write => "watch", WatchKind::Read => "rwatch", WatchKind::Access => "awatch" };
+            format!("T05{}:{:08x};", name, hit.addr)
+        })
+    }
+
+    /// Executes a single instruction, including any pipeline
+    /// refills around it.
+    ///
+    /// # Returns
+    /// The stop reply to send.
+    pub fn step(&mut self, gba: &mut Gba) -> String {
+        for pass in 0..2 {
+            let mut n = 0;
+            while !gba.cpu_arm7tdmi().is_about_to_execute() && !gba.cpu_arm7tdmi().is_sleeping() && (n < 0x200) {
+                if let Some(r) = self.pipeline_step(gba) { return r; }
+                n += 1;
+            }
+            if pass == 0 { if let Some(r) = self.pipeline_step(gba) { return r; } }
+        }
+        "S05".to_string()
+    }
+
+    /// Runs until hitting a breakpoint or watchpoint, an error
+    /// occurs, or until GDB interrupts.
+    ///
+    /// # Params
+    /// - `interrupted`: Checks whether GDB sent Ctrl-C.
+    ///
+    /// # Returns
+    /// The stop reply to send.
+    pub fn resume<F>(&mut self, gba: &mut Gba, mut interrupted: F) -> String where F: FnMut() -> bool {
+        let mut steps = 0_u32;
+        loop {
+            if let Some(r) = self.pipeline_step(gba) { return r; }
+            if self.breakpoints.check(gba).is_some() { return "S05".to_string(); }
+            steps = steps.wrapping_add(1);
+            if (steps % INTERRUPT_POLL_STEPS) == 0 && interrupted() { return "S02".to_string(); }
+        }
+    }
+}
+
+impl Default for GdbStub {
+    fn default() -> GdbStub { GdbStub::new() }
+}
+
+
+/// A TCP connection speaking GDB's packet framing.
+struct Connection {
+    stream: TcpStream,
+    no_ack: bool,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Connection {
+        Connection {
+            stream: stream,
+            no_ack: false,
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut b = [0_u8; 1];
+        match try!(self.stream.read(&mut b)) {
+            0 => Ok(None),
+            _ => Ok(Some(b[0])),
+        }
+    }
+
+    /// Reads the next valid packet's payload, acknowledging it.
+    ///
+    /// At most one byte more than `GDB_PACKET_SIZE` is kept,
+    /// so that oversized packets can be rejected.
+    ///
+    /// # Returns
+    /// - `Ok(Some)`: The unescaped payload.
+    /// - `Ok(None)`: GDB closed the connection.
+    fn read_packet(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            // Skip acknowledgements and stray interrupts until a packet starts.
+            loop {
+                match try!(self.read_byte()) {
+                    Some(b'$') => break,
+                    Some(_)    => {},
+                    None       => return Ok(None),
+                }
+            }
+            let mut data = Vec::new();
+            let (mut sum, mut escaped) = (0_u8, false);
+            loop {
+                let b = match try!(self.read_byte()) {
+                    Some(b'#') => break,
+                    Some(b)    => b,
+                    None       => return Ok(None),
+                };
+                sum = sum.wrapping_add(b);
+                let b = if escaped { escaped = false; b ^ 0x20 }
+                        else if b == b'}' { escaped = true; continue; }
+                        else { b };
+                if data.len() <= GDB_PACKET_SIZE { data.push(b); }
+            }
+            let mut hex = [0_u8; 2];
+            try!(self.stream.read_exact(&mut hex));
+
+            if self.no_ack || parse_hex(&hex) == Some(sum as u32) {
+                if !self.no_ack { try!(self.stream.write_all(b"+")); }
+                return Ok(Some(data));
+            }
+            warn!("GDB packet with invalid checksum.");
+            try!(self.stream.write_all(b"-"));
+        }
+    }
+
+    fn write_packet(&mut self, payload: &str) -> io::Result<()> {
+        try!(self.stream.write_all(&encode_packet(payload)));
+        self.stream.flush()
+    }
+
+    /// Checks without blocking whether GDB sent Ctrl-C.
+    fn poll_interrupt(&mut self) -> bool {
+        if self.stream.set_nonblocking(true).is_err() { return false; }
+        let mut b = [0_u8; 1];
+        let res = self.stream.read(&mut b);
+        let _ = self.stream.set_nonblocking(false);
+        match res {
+            Ok(1) => b[0] == GDB_INTERRUPT,
+            _     => false,
+        }
+    }
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
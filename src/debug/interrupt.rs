@@ -0,0 +1,74 @@
+// License below.
+//! Implements catching Ctrl-C while the emulator runs.
+//!
+//! Ctrl-C is only caught as long as the guard returned by
+//! `catch_interrupts` lives, so that it still terminates
+//! the process anywhere else, e.g. at the REPL prompt.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use super::super::libc;
+
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+
+/// Restores the previous Ctrl-C handler when dropped.
+pub struct InterruptGuard {
+    #[cfg(unix)]
+    previous: libc::sighandler_t,
+}
+
+impl Drop for InterruptGuard {
+    #[cfg(unix)]
+    fn drop(&mut self) {
+        unsafe { libc::signal(libc::SIGINT, self.previous); }
+    }
+
+    #[cfg(not(unix))]
+    fn drop(&mut self) {}
+}
+
+
+/// Catches Ctrl-C instead of terminating the process.
+///
+/// Discards any earlier interrupts. Interrupts are
+/// recorded until taken via `take_interrupt`.
+#[cfg(unix)]
+pub fn catch_interrupts() -> InterruptGuard {
+    extern "C" fn on_interrupt(_: libc::c_int) { INTERRUPTED.store(true, Ordering::SeqCst); }
+    take_interrupt();
+    let handler: extern "C" fn(libc::c_int) = on_interrupt;
+    InterruptGuard { previous: unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) } }
+}
+
+/// Catches Ctrl-C instead of terminating the process.
+#[cfg(not(unix))]
+pub fn catch_interrupts() -> InterruptGuard { take_interrupt(); InterruptGuard {} }
+
+/// Checks whether Ctrl-C was pressed since the last call.
+pub fn take_interrupt() -> bool { INTERRUPTED.swap(false, Ordering::SeqCst) }
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -0,0 +1,492 @@
+// License below.
+//! Implements a minimal line editor for the debug REPL.
+//!
+//! Supports moving the cursor, a persistent history, reverse
+//! incremental search via Ctrl-R, and tab completion. If the
+//! standard input or output is no terminal, e.g. when piping
+//! commands into the REPL, lines are read as they are.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::io;
+use std::io::{Read, Write, BufRead, BufReader};
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::mem;
+#[cfg(unix)]
+use super::super::libc;
+
+
+/// The maximum number of history entries kept in memory.
+pub const MAX_HISTORY: usize = 1000;
+
+
+/// A list of previously entered lines, optionally backed by a file.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// Creates an empty history not backed by any file.
+    pub fn new() -> History {
+        History { entries: Vec::new(), path: None }
+    }
+
+    /// Loads a history file and appends all new entries to it.
+    ///
+    /// A missing file is created as soon as the first entry
+    /// is added. Files much longer than `MAX_HISTORY` lines
+    /// are cut down to the most recent entries.
+    ///
+    /// # Params
+    /// - `path`: The history file.
+    pub fn load(path: &Path) -> History {
+        let mut entries: Vec<String> = match File::open(path) {
+            Ok(f) => BufReader::new(f).lines().filter_map(|l| l.ok()).filter(|l| !l.is_empty()).collect(),
+            Err(_) => Vec::new(),
+        };
+        let total = entries.len();
+        if total > MAX_HISTORY { entries.drain(..(total - MAX_HISTORY)); }
+        let mut h = History { entries: entries, path: Some(path.to_path_buf()) };
+        if total > (2 * MAX_HISTORY) { h.rewrite(); }
+        h
+    }
+
+    /// Get the number of entries.
+    pub fn len(&self) -> usize { self.entries.len() }
+
+    /// Checks whether there are no entries.
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+    /// Get an entry, where 0 is the oldest one.
+    pub fn get(&self, i: usize) -> Option<&str> { self.entries.get(i).map(|e| &e[..]) }
+
+    /// Adds an entry, unless it is empty or repeats the last one.
+    pub fn push(&mut self, line: &str) {
+        if line.trim().is_empty() || (self.entries.last().map(|e| &e[..]) == Some(line)) { return; }
+        if self.entries.len() >= MAX_HISTORY { self.entries.remove(0); }
+        self.entries.push(line.to_string());
+
+        let res = match self.path {
+            Some(ref p) => OpenOptions::new().create(true).append(true).open(p)
+                           .and_then(|mut f| writeln!(f, "{}", line)),
+            None => Ok(()),
+        };
+        if let Err(e) = res {
+            warn!("Failed to write the REPL history: {}", e);
+            self.path = None;
+        }
+    }
+
+    /// Finds the most recent entry containing some text.
+    ///
+    /// # Params
+    /// - `query`: The text to search for.
+    /// - `before`: Only entries older than this index are searched.
+    ///
+    /// # Returns
+    /// The index of the entry, or `None` if nothing matches.
+    pub fn search(&self, query: &str, before: usize) -> Option<usize> {
+        let end = ::std::cmp::min(before, self.entries.len());
+        self.entries[..end].iter().rposition(|e| e.contains(query))
+    }
+
+    fn rewrite(&mut self) {
+        let res = match self.path {
+            Some(ref p) => File::create(p).and_then(|mut f| {
+                for e in &self.entries { try!(writeln!(f, "{}", e)); }
+                Ok(())
+            }),
+            None => Ok(()),
+        };
+        if let Err(e) = res { warn!("Failed to write the REPL history: {}", e); }
+    }
+}
+
+
+/// Finds where the word to complete begins.
+///
+/// Words consist of letters, digits, `_`, `$`, and `.`, such
+/// that e.g. `cpsr.Z` is a single word. Ranges like `A..B`
+/// are split into their parts, though.
+///
+/// # Params
+/// - `line`: The characters of the current line.
+/// - `pos`: The cursor position within the line.
+///
+/// # Returns
+/// The index of the word's first character.
+pub fn word_start(line: &[char], pos: usize) -> usize {
+    let is_word = |c: char| c.is_alphanumeric() || (c == '_') || (c == '$') || (c == '.');
+    let mut start = pos;
+    while (start > 0) && is_word(line[start - 1]) { start -= 1; }
+    match line[start..pos].windows(2).rposition(|w| w == ['.', '.']) {
+        Some(i) => start + i + 2,
+        None => start,
+    }
+}
+
+/// Filters completion candidates by a prefix.
+///
+/// # Returns
+/// All distinct candidates starting with `word`, sorted.
+pub fn complete_word(word: &str, candidates: Vec<String>) -> Vec<String> {
+    let mut v: Vec<String> = candidates.into_iter().filter(|c| c.starts_with(word)).collect();
+    v.sort();
+    v.dedup();
+    v
+}
+
+/// Get the longest prefix shared by all words.
+pub fn common_prefix(words: &[String]) -> String {
+    let first = match words.first() { Some(w) => w, None => return String::new() };
+    let mut len = first.len();
+    for w in &words[1..] {
+        len = first.char_indices().zip(w.chars())
+                   .take_while(|&((_, a), b)| a == b)
+                   .last().map_or(0, |((i, a), _)| i + a.len_utf8())
+                   .min(len);
+    }
+    first[..len].to_string()
+}
+
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Key {
+    Char(char), Enter, Tab, Backspace, Delete,
+    Left, Right, Up, Down, Home, End,
+    CtrlC, CtrlD, CtrlG, CtrlK, CtrlR, CtrlU, CtrlW,
+    Other,
+}
+
+fn next_byte<R: Read>(input: &mut R) -> io::Result<Option<u8>> {
+    let mut b = [0_u8; 1];
+    match try!(input.read(&mut b)) {
+        0 => Ok(None),
+        _ => Ok(Some(b[0])),
+    }
+}
+
+fn read_key<R: Read>(input: &mut R) -> io::Result<Option<Key>> {
+    let b = match try!(next_byte(input)) { Some(b) => b, None => return Ok(None) };
+    Ok(Some(match b {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7F | 0x08 => Key::Backspace,
+        0x01 => Key::Home,
+        0x02 => Key::Left,
+        0x03 => Key::CtrlC,
+        0x04 => Key::CtrlD,
+        0x05 => Key::End,
+        0x06 => Key::Right,
+        0x07 => Key::CtrlG,
+        0x0B => Key::CtrlK,
+        0x0E => Key::Down,
+        0x10 => Key::Up,
+        0x12 => Key::CtrlR,
+        0x15 => Key::CtrlU,
+        0x17 => Key::CtrlW,
+        0x1B => try!(read_escape(input)),
+        b if b < 0x20 => Key::Other,
+        b if b < 0x80 => Key::Char(b as char),
+        b => {
+            // Collect the remaining bytes of a UTF-8 sequence.
+            let mut bytes = vec![b];
+            for _ in 1..(!b).leading_zeros().min(4) {
+                match try!(next_byte(input)) { Some(c) => bytes.push(c), None => break }
+            }
+            match String::from_utf8(bytes).ok().and_then(|s| s.chars().next()) {
+                Some(c) => Key::Char(c),
+                None => Key::Other,
+            }
+        },
+    }))
+}
+
+fn read_escape<R: Read>(input: &mut R) -> io::Result<Key> {
+    match try!(next_byte(input)) {
+        Some(b'[') | Some(b'O') => {},
+        _ => return Ok(Key::Other),
+    }
+    let mut arg = 0_u32;
+    loop {
+        let b = match try!(next_byte(input)) { Some(b) => b, None => return Ok(Key::Other) };
+        return Ok(match b {
+            b if (b >= b'0') && (b <= b'9') => { arg = arg * 10 + (b - b'0') as u32; continue },
+            b';' => continue,
+            b'A' => Key::Up,
+            b'B' => Key::Down,
+            b'C' => Key::Right,
+            b'D' => Key::Left,
+            b'H' => Key::Home,
+            b'F' => Key::End,
+            b'~' => match arg { 1 | 7 => Key::Home, 3 => Key::Delete, 4 | 8 => Key::End, _ => Key::Other },
+            _ => Key::Other,
+        });
+    }
+}
+
+
+#[cfg(unix)]
+fn is_terminal() -> bool {
+    unsafe { (libc::isatty(libc::STDIN_FILENO) == 1) && (libc::isatty(libc::STDOUT_FILENO) == 1) }
+}
+
+#[cfg(not(unix))]
+fn is_terminal() -> bool { false }
+
+// Puts the terminal into raw mode until dropped.
+#[cfg(unix)]
+struct RawMode { original: libc::termios }
+
+#[cfg(unix)]
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        unsafe {
+            let mut t: libc::termios = mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut t) != 0 { return Err(io::Error::last_os_error()); }
+            let original = t;
+            t.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+            t.c_iflag &= !(libc::IXON | libc::ICRNL);
+            t.c_cc[libc::VMIN] = 1;
+            t.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &t) != 0 { return Err(io::Error::last_os_error()); }
+            Ok(RawMode { original: original })
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &self.original); }
+    }
+}
+
+#[cfg(not(unix))]
+struct RawMode;
+
+#[cfg(not(unix))]
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        Err(io::Error::new(io::ErrorKind::Other, "Raw terminal mode is not supported."))
+    }
+}
+
+
+/// Reads lines from the standard input, with editing if it is a terminal.
+///
+/// Besides the usual cursor keys, these keys are supported:
+///
+/// - Up/Down, Ctrl-P/N: Browse the history.
+/// - Ctrl-R: Search the history. Press again for older matches,
+///   and Ctrl-G to cancel.
+/// - Tab: Complete the current word, or list all candidates.
+/// - Ctrl-A/E: Go to the start/end of the line.
+/// - Ctrl-K/U/W: Delete until the end/start of the line or the previous word.
+/// - Ctrl-C: Discard the line.
+/// - Ctrl-D: Quit on an empty line.
+pub struct LineEditor {
+    history: History,
+    terminal: bool,
+}
+
+impl Default for LineEditor {
+    fn default() -> LineEditor { LineEditor::new() }
+}
+
+impl LineEditor {
+    /// Creates a new line editor with an empty history.
+    pub fn new() -> LineEditor {
+        LineEditor { history: History::new(), terminal: is_terminal() }
+    }
+
+    /// Loads a history file and keeps appending new entries to it.
+    pub fn load_history(&mut self, path: &Path) { self.history = History::load(path); }
+
+    /// Get the history of entered lines.
+    pub fn history(&self) -> &History { &self.history }
+
+    /// Checks whether lines are read from a terminal with editing.
+    pub fn is_terminal(&self) -> bool { self.terminal }
+
+    /// Reads a single line.
+    ///
+    /// # Params
+    /// - `prompt`: Printed before the line. Must not contain line breaks.
+    /// - `complete`: Gets all text before the word to complete and
+    ///   returns all possible words. They will be filtered by the
+    ///   word's prefix afterwards.
+    ///
+    /// # Returns
+    /// The line without trailing line break, or `None` at
+    /// the end of the input.
+    pub fn read_line<F>(&mut self, prompt: &str, complete: F) -> io::Result<Option<String>>
+    where F: Fn(&str) -> Vec<String> {
+        print!("{}", prompt);
+        try!(io::stdout().flush());
+        if !self.terminal { return LineEditor::read_plain(); }
+
+        let raw = try!(RawMode::enable());
+        let line = self.read_edited(prompt, complete);
+        drop(raw);
+        if let Ok(Some(ref l)) = line { self.history.push(l); }
+        line
+    }
+
+    fn read_plain() -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if try!(io::stdin().read_line(&mut line)) == 0 { return Ok(None); }
+        while line.ends_with('\n') || line.ends_with('\r') { line.pop(); }
+        Ok(Some(line))
+    }
+
+    fn read_edited<F>(&self, prompt: &str, complete: F) -> io::Result<Option<String>>
+    where F: Fn(&str) -> Vec<String> {
+        let stdin = io::stdin();
+        let mut input = stdin.lock();
+        let mut buf: Vec<char> = Vec::new();
+        let mut pos = 0;
+        let mut browse = self.history.len();
+        let mut edited: Vec<char> = Vec::new();
+        let mut search: Option<(String, Option<usize>)> = None;
+
+        loop {
+            let key = match try!(read_key(&mut input)) {
+                Some(k) => k,
+                None => if buf.is_empty() { return Ok(None) } else { Key::Enter },
+            };
+
+            // Reverse search swallows keys until accepting or cancelling.
+            if let Some((mut query, mut found)) = search.take() {
+                let searching = match key {
+                    Key::Char(c) => {
+                        query.push(c);
+                        found = self.history.search(&query, found.map_or(self.history.len(), |i| i + 1));
+                        true
+                    },
+                    Key::Backspace => {
+                        query.pop();
+                        found = self.history.search(&query, self.history.len());
+                        true
+                    },
+                    Key::CtrlR => {
+                        let before = found.unwrap_or(self.history.len());
+                        found = self.history.search(&query, before).or(found);
+                        true
+                    },
+                    Key::CtrlG | Key::CtrlC => { try!(redraw(prompt, &buf, pos)); continue; },
+                    _ => {
+                        // Accept the match and handle the key as usual.
+                        if let Some(e) = found.and_then(|i| self.history.get(i)) {
+                            buf = e.chars().collect();
+                            pos = buf.len();
+                        }
+                        false
+                    },
+                };
+                if searching {
+                    try!(redraw_search(&query, found.and_then(|i| self.history.get(i))));
+                    search = Some((query, found));
+                    continue;
+                }
+            }
+
+            match key {
+                Key::Enter => break,
+                Key::Char(c) => { buf.insert(pos, c); pos += 1; },
+                Key::Backspace => if pos > 0 { pos -= 1; buf.remove(pos); },
+                Key::Delete => if pos < buf.len() { buf.remove(pos); },
+                Key::Left => if pos > 0 { pos -= 1; },
+                Key::Right => if pos < buf.len() { pos += 1; },
+                Key::Home => pos = 0,
+                Key::End => pos = buf.len(),
+                Key::CtrlK => buf.truncate(pos),
+                Key::CtrlU => { buf.drain(..pos); pos = 0; },
+                Key::CtrlW => {
+                    let mut start = pos;
+                    while (start > 0) && buf[start - 1].is_whitespace() { start -= 1; }
+                    while (start > 0) && !buf[start - 1].is_whitespace() { start -= 1; }
+                    buf.drain(start..pos);
+                    pos = start;
+                },
+                Key::CtrlC => { buf.clear(); pos = 0; print!("^C\n"); },
+                Key::CtrlD => if buf.is_empty() { print!("\n"); return Ok(None); },
+                Key::Up | Key::Down => {
+                    let next = match key {
+                        Key::Up if browse > 0 => browse - 1,
+                        Key::Down if browse < self.history.len() => browse + 1,
+                        _ => continue,
+                    };
+                    if browse == self.history.len() { edited = buf.clone(); }
+                    buf = match self.history.get(next) { Some(e) => e.chars().collect(), None => edited.clone() };
+                    pos = buf.len();
+                    browse = next;
+                },
+                Key::CtrlR => {
+                    try!(redraw_search("", None));
+                    search = Some((String::new(), None));
+                    continue;
+                },
+                Key::Tab => {
+                    let start = word_start(&buf, pos);
+                    let before: String = buf[..start].iter().cloned().collect();
+                    let word: String = buf[start..pos].iter().cloned().collect();
+                    let matches = complete_word(&word, complete(&before));
+                    let insert = match matches.len() {
+                        0 => String::new(),
+                        1 => format!("{} ", &matches[0][word.len()..]),
+                        _ => common_prefix(&matches)[word.len()..].to_string(),
+                    };
+                    if insert.is_empty() && (matches.len() > 1) {
+                        print!("\n{}\n", matches.join("  "));
+                    }
+                    for c in insert.chars() { buf.insert(pos, c); pos += 1; }
+                },
+                Key::CtrlG | Key::Other => {},
+            }
+            try!(redraw(prompt, &buf, pos));
+        }
+
+        print!("\n");
+        try!(io::stdout().flush());
+        Ok(Some(buf.into_iter().collect()))
+    }
+}
+
+fn redraw(prompt: &str, buf: &[char], pos: usize) -> io::Result<()> {
+    let text: String = buf.iter().cloned().collect();
+    print!("\r{}{}\x1B[K", prompt, text);
+    if pos < buf.len() { print!("\x1B[{}D", buf.len() - pos); }
+    io::stdout().flush()
+}
+
+fn redraw_search(query: &str, found: Option<&str>) -> io::Result<()> {
+    print!("\r(reverse-i-search)`{}': {}\x1B[K", query, found.unwrap_or(""));
+    io::stdout().flush()
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -0,0 +1,47 @@
+// License below.
+//! Implements the debugger facilities behind the debug REPL.
+//!
+//! Nothing in here changes how the GBA is emulated. The
+//! debugger only looks at the machine in between pipeline
+//! steps and tells its frontend when to stop running it.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+pub use self::expr::*;
+pub use self::breakpoint::*;
+pub use self::disasm::*;
+pub use self::step::*;
+
+pub mod expr;
+pub mod breakpoint;
+pub mod disasm;
+pub mod gdb;
+pub mod interrupt;
+pub mod lineedit;
+pub mod script;
+pub mod step;
+
+#[cfg(test)]
+mod test;
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -0,0 +1,116 @@
+// License below.
+//! Implements parsing debug REPL scripts.
+//!
+//! A script contains one REPL command per line. Lines
+//! starting with `#` are comments. Commands between a
+//! `repeat N {` line and a `}` line are run N times,
+//! where N is an expression evaluated when the block is
+//! entered. Blocks may be nested.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::error;
+use std::fmt;
+use std::mem;
+
+
+/// A single step of a script.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Statement {
+    #[doc = "A REPL command."]                                  Command(String),
+    #[doc = "Statements to run as often as an expression says."] Repeat(String, Vec<Statement>),
+}
+
+/// Errors when parsing a script. Line numbers start at 1.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ScriptError {
+    /// A `}` without any open block.
+    UnexpectedBrace(usize),
+
+    /// A block that is never closed.
+    MissingBrace(usize),
+
+    /// A `repeat {` without any count.
+    MissingCount(usize),
+}
+
+impl error::Error for ScriptError {
+    fn description(&self) -> &str {
+        match *self {
+            ScriptError::UnexpectedBrace(_) => "Unexpected `}` in script.",
+            ScriptError::MissingBrace(_)    => "Missing `}` in script.",
+            ScriptError::MissingCount(_)    => "Missing repeat count in script.",
+        }
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ScriptError::UnexpectedBrace(l) => write!(f, "Line {}: There is no block to close.", l),
+            ScriptError::MissingBrace(l)    => write!(f, "Line {}: The block is never closed.", l),
+            ScriptError::MissingCount(l)    => write!(f, "Line {}: Expected `repeat N {{`.", l),
+        }
+    }
+}
+
+
+/// Parses a script.
+///
+/// # Params
+/// - `text`: The script's contents.
+///
+/// # Returns
+/// All top level statements, or the first syntax error.
+pub fn parse(text: &str) -> Result<Vec<Statement>, ScriptError> {
+    // Open blocks with their count, first line, and statements so far.
+    let mut blocks: Vec<(String, usize, Vec<Statement>)> = Vec::new();
+    let mut current = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+
+        if line == "}" {
+            let (count, _, outer) = match blocks.pop() { Some(b) => b, None => return Err(ScriptError::UnexpectedBrace(i + 1)) };
+            let body = mem::replace(&mut current, outer);
+            current.push(Statement::Repeat(count, body));
+        }
+        else if line.starts_with("repeat") && line.ends_with('{') {
+            let count = line[6..(line.len() - 1)].trim();
+            if count.is_empty() { return Err(ScriptError::MissingCount(i + 1)); }
+            let outer = mem::replace(&mut current, Vec::new());
+            blocks.push((count.to_string(), i + 1, outer));
+        }
+        else {
+            current.push(Statement::Command(line.to_string()));
+        }
+    }
+
+    match blocks.pop() {
+        Some((_, l, _)) => Err(ScriptError::MissingBrace(l)),
+        None => Ok(current),
+    }
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
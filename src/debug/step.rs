@@ -0,0 +1,94 @@
+// License below.
+//! Implements helpers for stepping over function calls.
+//!
+//! Commands like `next` and `finish` do not step through
+//! code themselves. They find out where the current call
+//! returns to and let a temporary breakpoint do the rest.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use super::super::hardware::Gba;
+use super::super::hardware::cpu::{Arm7Tdmi, State, ArmInstruction, ArmOpcode, ThumbInstruction, ThumbOpcode};
+
+
+/// `mov lr, pc` and `add lr, pc, #0` in ARM state, excluding the condition.
+const ARM_LINK_PC: &'static [u32] = &[0x01A0E00F, 0x028FE000];
+
+
+/// Checks whether an ARM state instruction writes PC, like `bx`,
+/// `mov pc, r12`, or `ldr pc, [r0, #-4]`.
+fn arm_writes_pc(inst: &ArmInstruction) -> bool {
+    match inst.opcode() {
+        ArmOpcode::BX             => true,
+        ArmOpcode::DataProcessing => inst.Rd() == Arm7Tdmi::PC,
+        ArmOpcode::LDR_STR        => inst.is_load() && (inst.Rd() == Arm7Tdmi::PC),
+        _ => false,
+    }
+}
+
+
+/// Finds the return address of a call about to be executed.
+///
+/// Calls are ARM state `bl`, the ARMv4T idiom `mov lr, pc`
+/// followed by e.g. `bx`, and the two halfword THUMB state `bl`.
+///
+/// # Params
+/// - `gba`: The GBA about to run the next pipeline step.
+///
+/// # Returns
+/// The address right after the call, or `None` if the next
+/// pipeline step executes anything but a call.
+pub fn call_return_address(gba: &Gba) -> Option<u32> {
+    let cpu = gba.cpu_arm7tdmi();
+    if !cpu.is_about_to_execute() { return None; }
+    let addr = cpu.executing_address();
+    let bus = gba.bus();
+
+    match cpu.state() {
+        State::ARM => {
+            let raw = match bus.peek_word(addr) { Ok(w) => w as u32, Err(_) => return None };
+            if ARM_LINK_PC.contains(&(raw & 0x0FFFFFFF)) {
+                let next = bus.peek_word(addr.wrapping_add(4)).ok().and_then(|w| ArmInstruction::decode(w as u32).ok());
+                return match next {
+                    Some(ref i) if arm_writes_pc(i) => Some(addr.wrapping_add(8)),
+                    _ => None,
+                };
+            }
+            match ArmInstruction::decode(raw) {
+                Ok(ref i) if (i.opcode() == ArmOpcode::B_BL) && i.is_branch_with_link() => Some(addr.wrapping_add(4)),
+                _ => None,
+            }
+        },
+        State::THUMB => {
+            let raw  = match bus.peek_halfword(addr) { Ok(h) => h as u16, Err(_) => return None };
+            let next = bus.peek_halfword(addr.wrapping_add(2)).unwrap_or(0) as u16;
+            match ThumbInstruction::decode(raw) {
+                Ok(ref i) if (i.opcode() == ThumbOpcode::BranchLongOffs) && !i.is_low_offset_and_branch()
+                          && ((next & 0xF800) == 0xF800) => Some(addr.wrapping_add(4)),
+                _ => None,
+            }
+        },
+    }
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -0,0 +1,415 @@
+// License below.
+#![allow(missing_docs)]
+
+use super::*;
+use super::super::hardware::Gba;
+use super::super::hardware::cpu::ThumbInstruction;
+use super::super::hardware::test_util::counting_gba;
+
+// Runs until any breakpoint triggers, but at least one step.
+fn run_to_break(gba: &mut Gba, bps: &mut BreakpointList) -> u32 {
+    for _ in 0..1000 {
+        gba.cpu_arm7tdmi_mut().pipeline_step().unwrap();
+        if let Some(id) = bps.check(gba) { return id; }
+    }
+    panic!("No breakpoint triggered.");
+}
+
+fn eval(gba: &Gba, s: &str) -> Result<u32, ExprError> {
+    Expr::parse(s).and_then(|e| e.eval(gba))
+}
+
+#[test]
+pub fn expressions() {
+    use super::super::hardware::elf::Symbol;
+
+    let mut gba = counting_gba();
+    for _ in 0..20 { gba.cpu_arm7tdmi_mut().pipeline_step().unwrap(); }
+    while gba.cpu_arm7tdmi().executing_address() != 0x02000004 {
+        gba.cpu_arm7tdmi_mut().pipeline_step().unwrap();
+    }
+    let r0 = gba.cpu_arm7tdmi().register(0) as u32;
+    assert!(r0 > 1);
+
+    assert_eq!(eval(&gba, "1 + 2 * 3"), Ok(7));
+    assert_eq!(eval(&gba, "(1 + 2) * 3"), Ok(9));
+    assert_eq!(eval(&gba, "10 - 1 - 1"), Ok(0xE));
+    assert_eq!(eval(&gba, "0x10 >> 2 == 4 && !0"), Ok(1));
+    assert_eq!(eval(&gba, "-1"), Ok(0xFFFFFFFF));
+    assert_eq!(eval(&gba, "~FF & FFF | 1 ^ 3"), Ok(0xF02));
+    assert_eq!(eval(&gba, "R0"), Ok(r0));
+    assert_eq!(eval(&gba, "r1 == 03000000 + r0 * 4"), Ok(1));
+    assert_eq!(eval(&gba, "sp"), Ok(0x03007F00));
+    assert_eq!(eval(&gba, "[03000000]"), Ok(1));
+    assert_eq!(eval(&gba, "[r1 - 4].w == r0"), Ok(1));
+    assert_eq!(eval(&gba, "[02000000].h"), Ok(0x1403));
+    assert_eq!(eval(&gba, "[0x02000003].b"), Ok(0xE3));
+    assert_eq!(eval(&gba, "cpsr & 1F"), Ok(0x1F));
+    assert_eq!(eval(&gba, "cpsr.T || cpsr.i"), Ok(0));
+
+    // Logical operators short-circuit.
+    assert_eq!(eval(&gba, "0 && 1 / 0"), Ok(0));
+    assert_eq!(eval(&gba, "1 / 0"), Err(ExprError::DivisionByZero));
+    assert_eq!(eval(&gba, "r16"), Err(ExprError::UnknownName("r16".to_string())));
+    assert_eq!(eval(&gba, "cpsr.Q"), Err(ExprError::UnknownName("cpsr.Q".to_string())));
+    assert_eq!(eval(&gba, "[0].q"), Err(ExprError::Unexpected("q".to_string())));
+    assert_eq!(eval(&gba, "(1"), Err(ExprError::UnexpectedEnd));
+    assert_eq!(eval(&gba, "1 2"), Err(ExprError::Unexpected("2".to_string())));
+    assert_eq!(eval(&gba, "1 $ 2"), Err(ExprError::Unexpected("$".to_string())));
+
+    // Decimal, binary, and symbols.
+    assert_eq!(eval(&gba, "#10 + %101 + 0x10 + 0X1f"), Ok(62));
+    assert_eq!(eval(&gba, "5 % %11 + r0 % 1"), Ok(2));
+    assert_eq!(eval(&gba, "0b12 + 0B000002"), Ok(0xB12 + 0xB000002));
+    assert_eq!(eval(&gba, "0b101"), Err(ExprError::AmbiguousNumber("0b101".to_string())));
+    assert_eq!(eval(&gba, "0B000000"), Err(ExprError::AmbiguousNumber("0B000000".to_string())));
+    assert_eq!(eval(&gba, "0x0b101"), Ok(0xB101));
+    assert_eq!(eval(&gba, "%12"), Err(ExprError::InvalidNumber("%12".to_string())));
+    assert_eq!(eval(&gba, "#1F"), Err(ExprError::InvalidNumber("#1F".to_string())));
+    assert_eq!(eval(&gba, "0x"), Err(ExprError::InvalidNumber("0x".to_string())));
+    assert_eq!(eval(&gba, "# 1"), Err(ExprError::InvalidNumber("#".to_string())));
+    assert_eq!(eval(&gba, "face"), Ok(0xFACE));
+    assert_eq!(eval(&gba, "add"), Ok(0xADD));
+    gba.symbols_mut().insert(Symbol { name: "add".to_string(), addr: 0x02000004, size: 12, thumb: false });
+    assert_eq!(eval(&gba, "add + 1"), Ok(0x02000005));
+    assert_eq!(eval(&gba, "0add + 0xadd"), Ok(0x15BA));
+    assert_eq!(eval(&gba, "counter"), Err(ExprError::UnknownName("counter".to_string())));
+    gba.symbols_mut().insert(Symbol { name: "counter".to_string(), addr: 0x03000000, size: 4, thumb: false });
+    assert_eq!(eval(&gba, "[counter + 4 * (r0 - 1)] == r0"), Ok(1));
+    assert_eq!(eval_range("counter..counter+#16", &gba, 0, 0), Ok(0x03000000..0x03000010));
+    assert_eq!(eval_range("..4", &gba, 2, 0), Ok(2..4));
+    assert!(eval_range("4", &gba, 0, 0).is_err());
+
+    assert_eq!(Format::Hex.format(42), "0x0000002A");
+    assert_eq!(Format::Dec.format(42), "42");
+    assert_eq!(Format::Dec.format(0xFFFFFFFF), "4294967295 (-1)");
+    assert_eq!(Format::Bin.format(42), "%101010");
+    assert_eq!(Format::Char.format(42), "'*'");
+    assert_eq!(Format::Char.format(10), "'\\n'");
+}
+
+#[test]
+pub fn breakpoints() {
+    let mut gba = counting_gba();
+    let mut bps = BreakpointList::new();
+    let a = bps.add(0x02000008, None).unwrap();
+    let b = bps.add(0x02000004, Some("r0 == 3")).unwrap();
+    assert!(bps.add(0x02000004, Some("r0 ==")).is_err());
+    assert_eq!(bps.len(), 2);
+
+    // Stops right before executing the instruction.
+    assert_eq!(run_to_break(&mut gba, &mut bps), a);
+    assert_eq!(gba.cpu_arm7tdmi().executing_address(), 0x02000008);
+    assert_eq!(gba.cpu_arm7tdmi().register(0), 1);
+    assert_eq!(run_to_break(&mut gba, &mut bps), a);
+    assert_eq!(gba.cpu_arm7tdmi().register(0), 2);
+
+    // Conditions are checked, disabled breakpoints ignored.
+    assert!(bps.set_enabled(a, false));
+    assert_eq!(run_to_break(&mut gba, &mut bps), b);
+    assert_eq!(gba.cpu_arm7tdmi().register(0), 3);
+    assert_eq!(bps.get(a).unwrap().hits, 2);
+    assert_eq!(bps.get(b).unwrap().hits, 1);
+
+    assert!(bps.remove(b));
+    assert!(!bps.remove(b));
+    assert!(!bps.set_enabled(b, true));
+    assert!(bps.set_enabled(a, true));
+    assert_eq!(run_to_break(&mut gba, &mut bps), a);
+    assert_eq!(gba.cpu_arm7tdmi().register(0), 4);
+}
+
+
+
+fn reply(stub: &mut gdb::GdbStub, gba: &mut Gba, packet: &str) -> String {
+    reply_bytes(stub, gba, packet.as_bytes())
+}
+
+fn reply_bytes(stub: &mut gdb::GdbStub, gba: &mut Gba, packet: &[u8]) -> String {
+    match stub.handle_packet(gba, packet) {
+        gdb::GdbAction::Reply(r) => r,
+        a => panic!("Unexpected action {:?} for `{:?}`.", a, packet),
+    }
+}
+
+#[test]
+pub fn gdb_packets() {
+    assert_eq!(gdb::checksum(b"OK"), 0x9A);
+    assert_eq!(gdb::encode_packet("OK"), b"$OK#9a".to_vec());
+    assert_eq!(gdb::encode_packet("a#b"), b"$a}\x03b#43".to_vec());
+
+    let mut gba = counting_gba();
+    let mut stub = gdb::GdbStub::new();
+    assert_eq!(reply(&mut stub, &mut gba, "?"), "S05");
+
+    // Registers: r0..r15 and CPSR, little endian.
+    let regs = reply(&mut stub, &mut gba, "g");
+    assert_eq!(regs.len(), 17 * 8);
+    assert_eq!(&regs[(15 * 8)..(16 * 8)], "00000002");
+    assert_eq!(reply(&mut stub, &mut gba, "pf"), "00000002");
+    assert_eq!(reply(&mut stub, &mut gba, "P3=34120000"), "OK");
+    assert_eq!(gba.cpu_arm7tdmi().register(3), 0x1234);
+    assert_eq!(reply(&mut stub, &mut gba, "P10=00000000"), "E01");
+    assert_eq!(reply(&mut stub, &mut gba, "P10=3f000000"), "E01");
+    assert_eq!(reply(&mut stub, &mut gba, &format!("G{}3f000000", &regs[..(16 * 8)])), "E01");
+    assert_eq!(reply(&mut stub, &mut gba, "P10=1f000000"), "OK");
+
+    // Memory.
+    assert_eq!(reply(&mut stub, &mut gba, "m2000000,4"), "0314a0e3");
+    assert_eq!(reply(&mut stub, &mut gba, "M3000000,2:beef"), "OK");
+    assert_eq!(gba.bus().peek_halfword(0x03000000).unwrap() as u16, 0xEFBE);
+    assert_eq!(reply(&mut stub, &mut gba, "m6000000,4"), "E01");
+    assert_eq!(reply(&mut stub, &mut gba, "M6000000,1:00"), "E01");
+    assert_eq!(reply(&mut stub, &mut gba, "m2000000,800").len(), 0x1000);
+    assert_eq!(reply(&mut stub, &mut gba, "m2000000,801"), "E01");
+    assert_eq!(reply(&mut stub, &mut gba, "m2000000,ffffffff"), "E01");
+
+    // Target description in chunks.
+    let head = reply(&mut stub, &mut gba, "qXfer:features:read:target.xml:0,10");
+    assert_eq!(head, format!("m{}", &gdb::TARGET_XML[..0x10]));
+    let tail = reply(&mut stub, &mut gba, "qXfer:features:read:target.xml:10,10000");
+    assert_eq!(tail, format!("l{}", &gdb::TARGET_XML[0x10..]));
+
+    // Stepping and breakpoints.
+    assert_eq!(stub.handle_packet(&mut gba, b"s"), gdb::GdbAction::Step);
+    assert_eq!(stub.step(&mut gba), "S05");
+    assert_eq!(gba.cpu_arm7tdmi().executing_address(), 0x02000004);
+    assert_eq!(reply(&mut stub, &mut gba, "Z0,200000c,4"), "OK");
+    assert_eq!(stub.handle_packet(&mut gba, b"c"), gdb::GdbAction::Continue);
+    assert_eq!(stub.resume(&mut gba, || false), "S05");
+    assert_eq!(gba.cpu_arm7tdmi().executing_address(), 0x0200000C);
+    assert_eq!(reply(&mut stub, &mut gba, "z0,200000c,4"), "OK");
+
+    // Watchpoints.
+    assert_eq!(reply(&mut stub, &mut gba, "Z2,3000008,4"), "OK");
+    assert_eq!(stub.resume(&mut gba, || false), "T05watch:03000008;");
+    assert_eq!(reply(&mut stub, &mut gba, "z2,3000008,4"), "OK");
+    assert!(gba.bus().watchpoints().is_empty());
+    assert_eq!(stub.resume(&mut gba, || true), "S02");
+}
+
+
+#[test]
+pub fn gdb_malformed_packets() {
+    let mut gba = counting_gba();
+    let mut stub = gdb::GdbStub::new();
+    let regs = reply(&mut stub, &mut gba, "g");
+
+    // Non-ASCII and invalid UTF-8 bytes.
+    assert_eq!(reply(&mut stub, &mut gba, "\u{E9}"), "");
+    assert_eq!(reply(&mut stub, &mut gba, "m\u{E9},4"), "E01");
+    assert_eq!(reply(&mut stub, &mut gba, "M3000000,1:\u{E9}"), "E01");
+    assert_eq!(reply(&mut stub, &mut gba, "P\u{E9}=00000000"), "E01");
+    assert_eq!(reply(&mut stub, &mut gba, &format!("G\u{E9}{}", &regs[2..])), "E01");
+    assert_eq!(reply(&mut stub, &mut gba, "Z0,\u{E9},4"), "E01");
+    assert_eq!(reply(&mut stub, &mut gba, "c\u{E9}"), "E01");
+    assert_eq!(reply_bytes(&mut stub, &mut gba, b"m\xFF,4"), "E01");
+    assert_eq!(reply_bytes(&mut stub, &mut gba, b"\xFF"), "");
+
+    // Odd-length and oversized hex data.
+    assert_eq!(reply(&mut stub, &mut gba, "M3000000,2:bee"), "E01");
+    assert_eq!(reply(&mut stub, &mut gba, "M3000000,1:b"), "E01");
+    assert_eq!(reply(&mut stub, &mut gba, "P3=3412000"), "E01");
+    assert_eq!(reply(&mut stub, &mut gba, &format!("G{}", &regs[..(regs.len() - 1)])), "E01");
+    assert_eq!(reply(&mut stub, &mut gba, "m2000000,123456789"), "E01");
+    assert_eq!(reply(&mut stub, &mut gba, ""), "");
+
+    // Nothing has changed.
+    assert_eq!(reply(&mut stub, &mut gba, "g"), regs);
+    assert_eq!(gba.bus().peek_word(0x03000000).unwrap(), 0);
+}
+
+
+#[test]
+pub fn disassembly() {
+    use super::super::hardware::cpu::State;
+    use super::super::hardware::elf::Symbol;
+
+    let mut gba = counting_gba();
+    gba.symbols_mut().insert(Symbol { name: "loop".to_string(), addr: 0x02000004, size: 12, thumb: false });
+    let lines = disassemble(&gba, 0x02000000..0x02000010, State::ARM);
+    assert_eq!(lines.len(), 5);
+    assert!(lines[0].starts_with("2000000 - ") && lines[0].ends_with("<= PC"));
+    assert_eq!(lines[1], "loop:");
+    assert!(lines[4].starts_with("200000C - ") && lines[4].ends_with("\tb\t#-8\t<loop>"));
+
+    // THUMB branches, including the two halfword BL.
+    {
+        let mut bus = gba.bus_mut();
+        bus.store_halfword(0x03000000, 0xE7FE_u16 as i32).unwrap(); // b .
+        bus.store_halfword(0x03000002, 0xF7FF_u16 as i32).unwrap(); // bl 0x03000000
+        bus.store_halfword(0x03000004, 0xFFFD_u16 as i32).unwrap();
+        bus.store_halfword(0x03000006, 0xD0FB_u16 as i32).unwrap(); // beq 0x03000000
+    }
+    let h = |a: u32| ThumbInstruction::decode(gba.bus().peek_halfword(a).unwrap() as u16).unwrap();
+    assert_eq!(thumb_branch_target(0x03000000, &h(0x03000000), 0), Some(0x03000000));
+    assert_eq!(thumb_branch_target(0x03000002, &h(0x03000002), 0xFFFD), Some(0x03000000));
+    assert_eq!(thumb_branch_target(0x03000004, &h(0x03000004), 0), None);
+    assert_eq!(thumb_branch_target(0x03000006, &h(0x03000006), 0), Some(0x03000000));
+    assert_eq!(disassemble(&gba, 0x03000001..0x03000008, State::THUMB).len(), 4);
+}
+
+
+#[test]
+pub fn assignments() {
+    use super::super::hardware::cpu::{Arm7Tdmi, Mode, State};
+
+    let mut gba = counting_gba();
+    let assign = |gba: &mut Gba, lhs: &str, x: u32| Expr::parse(lhs).and_then(|e| e.assign(gba, x));
+
+    assign(&mut gba, "r3", 0x1234).unwrap();
+    assert_eq!(gba.cpu_arm7tdmi().register(3), 0x1234);
+    assign(&mut gba, "cpsr.z", 1).unwrap();
+    assert_eq!(eval(&gba, "cpsr.Z"), Ok(1));
+    assign(&mut gba, "cpsr.Z", 0).unwrap();
+    assert_eq!(eval(&gba, "cpsr.Z"), Ok(0));
+
+    // Switching modes banks SP.
+    let sp = gba.cpu_arm7tdmi().register(Arm7Tdmi::SP);
+    let cpsr = gba.cpu_arm7tdmi().cpsr().0;
+    assign(&mut gba, "cpsr", (cpsr & !0x1F) | Mode::IRQ.as_bits()).unwrap();
+    assert_eq!(gba.cpu_arm7tdmi().mode(), Mode::IRQ);
+    assign(&mut gba, "sp", 0x03007FA0).unwrap();
+    assign(&mut gba, "cpsr", cpsr).unwrap();
+    assert_eq!(gba.cpu_arm7tdmi().register(Arm7Tdmi::SP), sp);
+    assert_eq!(assign(&mut gba, "cpsr", 0), Err(ExprError::InvalidCpsr(0)));
+
+    // THUMB code is not executed yet.
+    assert_eq!(assign(&mut gba, "cpsr.t", 1), Err(ExprError::StateChange));
+    assert_eq!(assign(&mut gba, "cpsr", cpsr | 0x20), Err(ExprError::StateChange));
+    assert_eq!(gba.cpu_arm7tdmi().state(), State::ARM);
+    assign(&mut gba, "cpsr.t", 0).unwrap();
+
+    // Memory, registers as addresses, and invalid targets.
+    assign(&mut gba, "[03000010].w", 0xDEADBEEF).unwrap();
+    assign(&mut gba, "[03000010].b", 0x42).unwrap();
+    assert_eq!(eval(&gba, "[03000010]"), Ok(0xDEADBE42));
+    assign(&mut gba, "[r3 + 02FFFDCC].h", 0xCAFE).unwrap();
+    assert_eq!(eval(&gba, "[03001000].h"), Ok(0xCAFE));
+    assert_eq!(assign(&mut gba, "r1 + 1", 0), Err(ExprError::NotAssignable));
+}
+
+
+#[test]
+pub fn stepping_over_calls() {
+    use super::super::hardware::cpu::Mode;
+
+    let mut gba = Gba::new();
+    gba.boot_post_bios(0x02000000);
+    {
+        let mut bus = gba.bus_mut();
+        for (i, &w) in [
+            0xEB000001_u32, // 0000  bl sub
+            0xEAFFFFFE,     // 0004  b .
+            0xE1A00000,     // 0008  nop
+            //        sub:
+            0xE2800001,     // 000C  add r0, r0, #1
+            0xE12FFF1E,     // 0010  bx lr
+        ].iter().enumerate() {
+            bus.store_word(0x02000000 + (i as u32 * 4), w as i32).unwrap();
+        }
+    }
+    while !gba.cpu_arm7tdmi().is_about_to_execute() { gba.cpu_arm7tdmi_mut().pipeline_step().unwrap(); }
+    assert_eq!(call_return_address(&gba), Some(0x02000004));
+
+    let mut bps = BreakpointList::new();
+    bps.add(0x0200000C, None).unwrap();
+    run_to_break(&mut gba, &mut bps);
+    {
+        let frames = gba.cpu_arm7tdmi().call_stack().iter().cloned().collect::<Vec<_>>();
+        assert_eq!(frames.len(), 1);
+        assert_eq!((frames[0].call_site, frames[0].target, frames[0].return_addr), (0x02000000, 0x0200000C, 0x02000004));
+        assert_eq!(frames[0].mode, Mode::System);
+        assert_eq!(frames[0].exception, None);
+    }
+    bps.clear();
+
+    let id = bps.add_temporary(0x02000004, Some("sp >= 0")).unwrap();
+    bps.add(0x02000008, None).unwrap();
+    assert_eq!(run_to_break(&mut gba, &mut bps), id);
+    assert!(gba.cpu_arm7tdmi().call_stack().is_empty());
+    assert_eq!(gba.cpu_arm7tdmi().register(0), 1);
+    assert_eq!(call_return_address(&gba), None);
+    bps.clear_temporary();
+    assert_eq!(bps.len(), 1);
+}
+
+#[test]
+pub fn line_editing() {
+    use super::lineedit::*;
+    use std::env;
+    use std::process;
+    use std::fs;
+
+    let path = env::temp_dir().join(format!("gbars-{}-test.history", process::id()));
+    let _ = fs::remove_file(&path);
+    let mut h = History::load(&path);
+    for l in ["break main", "", "continue", "continue", "print r0"].iter() { h.push(l); }
+    assert_eq!(h.len(), 3);
+    assert_eq!(h.search("r", 3), Some(2));
+    assert_eq!(h.search("r", 2), Some(0));
+    assert_eq!(h.search("cont", 1), None);
+
+    let h = History::load(&path);
+    assert_eq!(h.get(0), Some("break main"));
+    assert_eq!(h.get(2), Some("print r0"));
+    fs::remove_file(&path).unwrap();
+
+    let line: Vec<char> = "hex 0..ma".chars().collect();
+    assert_eq!(word_start(&line, line.len()), 7);
+    let line: Vec<char> = "print cpsr.Z".chars().collect();
+    assert_eq!(word_start(&line, line.len()), 6);
+
+    let words = vec!["main".to_string(), "loop".to_string(), "main_loop".to_string(), "main".to_string()];
+    let m = complete_word("ma", words);
+    assert_eq!(m, vec!["main".to_string(), "main_loop".to_string()]);
+    assert_eq!(common_prefix(&m), "main");
+    assert_eq!(common_prefix(&[]), "");
+}
+
+#[test]
+pub fn scripts() {
+    use super::script::*;
+
+    let text = "# Count a bit.\n\
+                break main\n\
+                \n\
+                repeat #3 {\n\
+                  continue\n\
+                  repeat 2 {\n\
+                    next\n\
+                  }\n\
+                }\n\
+                assert r0 == #3\n";
+    assert_eq!(parse(text), Ok(vec![
+        Statement::Command("break main".to_string()),
+        Statement::Repeat("#3".to_string(), vec![
+            Statement::Command("continue".to_string()),
+            Statement::Repeat("2".to_string(), vec![Statement::Command("next".to_string())]),
+        ]),
+        Statement::Command("assert r0 == #3".to_string()),
+    ]));
+    assert_eq!(parse("next\n}\n"), Err(ScriptError::UnexpectedBrace(2)));
+    assert_eq!(parse("repeat 2 {\nrepeat 3 {\n}\n"), Err(ScriptError::MissingBrace(1)));
+    assert_eq!(parse("repeat {\n}\n"), Err(ScriptError::MissingCount(1)));
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -5,20 +5,39 @@
 #![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
 #![warn(missing_docs)]
 
-use std::cell::RefCell;
+use std::cell::{RefCell, Ref};
+use std::io;
 use std::rc::Rc;
 
 use super::memory::*;
 use super::gamepak::*;
 use super::ioregs::*;
 use super::error::*;
+use super::savestate::{SaveState, StateWriter, StateReader};
+use super::watchpoint::WatchpointList;
 
 // TODO how to handle aborts?
 /// Implements the memory and bus system of the GBA.
+///
+/// Loads from unmapped addresses return the most recently
+/// fetched opcode, as the bus still holds it. The BIOS ROM
+/// can only be read while executing BIOS code. Otherwise,
+/// any BIOS load returns the last opcode fetched from the
+/// BIOS instead.
+///
+/// All loads and stores, except for instruction fetches and
+/// the debugger's `peek_*` loads, are checked against the
+/// memory watchpoints.
 pub struct Bus {
     bios_rom: Rc<RefCell<BiosRom>>,
+    wram_on_board: WramOnBoard,
+    wram_on_chip: WramOnChip,
     ioregs: IoRegisters,
     game_pak: Rc<RefCell<GamePak>>,
+    open_bus: u32,
+    last_bios_opcode: u32,
+    executing_bios: bool,
+    watchpoints: RefCell<WatchpointList>,
 }
 
 impl Bus {
@@ -26,11 +45,77 @@ impl Bus {
     pub fn new(gpak: Rc<RefCell<GamePak>>, bios: Rc<RefCell<BiosRom>>) -> Bus {
         Bus {
             bios_rom: bios,
+            wram_on_board: WramOnBoard::new(),
+            wram_on_chip: WramOnChip::new(),
             ioregs: IoRegisters::new(),
             game_pak: gpak,
+            open_bus: 0,
+            last_bios_opcode: 0,
+            executing_bios: true,
+            watchpoints: RefCell::new(WatchpointList::new()),
         }
     }
 
+    /// Get the on-board WRAM.
+    pub fn wram_on_board(&self) -> &WramOnBoard { &self.wram_on_board }
+
+    /// Get the on-board WRAM.
+    pub fn wram_on_board_mut(&mut self) -> &mut WramOnBoard { &mut self.wram_on_board }
+
+    /// Get the on-chip WRAM.
+    pub fn wram_on_chip(&self) -> &WramOnChip { &self.wram_on_chip }
+
+    /// Get the on-chip WRAM.
+    pub fn wram_on_chip_mut(&mut self) -> &mut WramOnChip { &mut self.wram_on_chip }
+
+    /// Get the IO registers.
+    pub fn ioregs(&self) -> &IoRegisters { &self.ioregs }
+
+    /// Get the IO registers.
+    pub fn ioregs_mut(&mut self) -> &mut IoRegisters { &mut self.ioregs }
+
+    /// Get the memory watchpoints.
+    pub fn watchpoints(&self) -> Ref<WatchpointList> { self.watchpoints.borrow() }
+
+    /// Get the memory watchpoints.
+    pub fn watchpoints_mut(&mut self) -> &mut WatchpointList { self.watchpoints.get_mut() }
+
+    /// Fetches an ARM state instruction from the memory system.
+    ///
+    /// Unlike `load_word`, this remembers the fetched opcode
+    /// for open bus loads and the BIOS read protection.
+    ///
+    /// ## Params
+    /// - `addr`: The address of the instruction.
+    ///
+    /// ## Returns
+    /// - `Ok`: The fetched opcode.
+    /// - `Err(InvalidMemoryBusWidth)`: The memory-mapped device cannot load words.
+    pub fn fetch_word(&mut self, addr: u32) -> Result<i32, GbaError> {
+        self.executing_bios = Bus::is_bios_address(addr);
+        let opcode = try!(self.load_word_raw(addr, false));
+        self.latch_opcode(opcode as u32);
+        Ok(opcode)
+    }
+
+    /// Fetches a THUMB state instruction from the memory system.
+    ///
+    /// Unlike `load_halfword`, this remembers the fetched opcode
+    /// for open bus loads and the BIOS read protection.
+    ///
+    /// ## Params
+    /// - `addr`: The address of the instruction.
+    ///
+    /// ## Returns
+    /// - `Ok`: The fetched opcode.
+    /// - `Err(InvalidMemoryBusWidth)`: The memory-mapped device cannot load halfwords.
+    pub fn fetch_halfword(&mut self, addr: u32) -> Result<i32, GbaError> {
+        self.executing_bios = Bus::is_bios_address(addr);
+        let opcode = try!(self.load_halfword_raw(addr, false));
+        self.latch_opcode(((opcode as u32) << 16) | (opcode as u32 & 0xFFFF));
+        Ok(opcode)
+    }
+
     /// Loads a word from the memory system.
     ///
     /// The given address will be rounded down to the next word-aligned
@@ -48,23 +133,38 @@ impl Bus {
     ///
     /// ## Returns
     /// - `Ok`: The loaded word.
-    /// - `Err(InvalidPhysicalAddress)`: The given address is not part of the memory map.
     /// - `Err(InvalidMemoryBusWidth)`: The memory-mapped device cannot load words.
     pub fn load_word(&self, addr: u32) -> Result<i32, GbaError> {
-        match PhysicalAddress::from_u32(addr) {
-            PhysicalAddress::BiosROM(p)       => Ok(self.bios_rom.borrow().read_word(p) as i32),
-            PhysicalAddress::OnBoardWRAM(_)   => unimplemented!(),
-            PhysicalAddress::OnChipWRAM(_)    => unimplemented!(),
-            PhysicalAddress::RegistersIO(p)   => Ok(self.ioregs.read_word(p) as i32),
-            PhysicalAddress::PaletteRAM(_)    => unimplemented!(),
-            PhysicalAddress::VRAM(_)          => unimplemented!(),
-            PhysicalAddress::AttributesOBJ(_) => unimplemented!(),
-            PhysicalAddress::GamePak0ROM(_)   => unimplemented!(),
-            PhysicalAddress::GamePak1ROM(_)   => unimplemented!(),
-            PhysicalAddress::GamePak2ROM(_)   => unimplemented!(),
-            PhysicalAddress::GamePakSRAM(p)   => Err(GbaError::InvalidMemoryBusWidth(p, 32)),
-            PhysicalAddress::Invalid(p)       => Err(GbaError::InvalidPhysicalAddress(p)),
-        }
+        let x = try!(self.load_word_raw(addr, false));
+        self.watchpoints.borrow_mut().record(addr & !0b11, 32, false, x as u32, x as u32);
+        Ok(x)
+    }
+
+    /// Loads a word like `load_word`, but without triggering
+    /// watchpoints or the BIOS read protection.
+    ///
+    /// This lets debuggers inspect memory without any side
+    /// effects on the emulated machine. Video memory is not
+    /// emulated yet and yields `Err(InvalidPhysicalAddress)`.
+    pub fn peek_word(&self, addr: u32) -> Result<i32, GbaError> { self.load_word_raw(addr, true) }
+
+    fn load_word_raw(&self, addr: u32, peek: bool) -> Result<i32, GbaError> {
+        let word = match PhysicalAddress::from_u32(addr & !0b11) {
+            PhysicalAddress::BiosROM(p)       => self.load_bios_word(p, peek),
+            PhysicalAddress::OnBoardWRAM(p)   => self.wram_on_board.read_word(p),
+            PhysicalAddress::OnChipWRAM(p)    => self.wram_on_chip.read_word(p),
+            PhysicalAddress::RegistersIO(p)   => self.ioregs.read_word(p),
+            PhysicalAddress::PaletteRAM(_)    => return Bus::load_video_memory(addr, peek),
+            PhysicalAddress::VRAM(_)          => return Bus::load_video_memory(addr, peek),
+            PhysicalAddress::AttributesOBJ(_) => return Bus::load_video_memory(addr, peek),
+            PhysicalAddress::GamePak0ROM(p)   => self.game_pak.borrow().load_rom_word(p),
+            PhysicalAddress::GamePak1ROM(p)   => self.game_pak.borrow().load_rom_word(p),
+            PhysicalAddress::GamePak2ROM(p)   => self.game_pak.borrow().load_rom_word(p),
+            PhysicalAddress::GamePakSRAM(p)   => return Err(GbaError::InvalidMemoryBusWidth(p, 32)),
+            PhysicalAddress::Invalid(_)       => self.open_bus,
+        };
+        // Misaligned loads rotate the aligned word, whatever the region.
+        Ok(word.rotate_right(8 * (addr & 0b11)) as i32)
     }
 
     /// Stores a word in the memory system.
@@ -82,17 +182,33 @@ impl Bus {
     /// - `Err(InvalidMemoryBusWidth)`: The memory-mapped device cannot store words.
     /// - `Err(InvalidRomAccess)`: Tried storing data into a ROM.
     pub fn store_word(&mut self, addr: u32, data: i32) -> Result<(), GbaError> {
+        let aligned = addr & !0b11;
+        let watched = self.watchpoints.get_mut().watches(aligned, 32, true);
+        let old = if watched { self.peek_word(aligned).unwrap_or(0) as u32 } else { 0 };
+        try!(self.store_word_raw(addr, data, false));
+        if watched { self.watchpoints.get_mut().record(aligned, 32, true, old, data as u32); }
+        Ok(())
+    }
+
+    /// Stores a word like `store_word`, but without triggering watchpoints.
+    ///
+    /// This lets debuggers modify memory without stopping
+    /// at their own stores. Video memory is not emulated
+    /// yet and yields `Err(InvalidPhysicalAddress)`.
+    pub fn poke_word(&mut self, addr: u32, data: i32) -> Result<(), GbaError> { self.store_word_raw(addr, data, true) }
+
+    fn store_word_raw(&mut self, addr: u32, data: i32, poke: bool) -> Result<(), GbaError> {
         match PhysicalAddress::from_u32(addr) {
             PhysicalAddress::BiosROM(p)       => Err(GbaError::InvalidRomAccess(p)),
-            PhysicalAddress::OnBoardWRAM(_)   => unimplemented!(),
-            PhysicalAddress::OnChipWRAM(_)    => unimplemented!(),
-            PhysicalAddress::RegistersIO(p)   => Ok(self.ioregs.write_word(p, data as u32)),
-            PhysicalAddress::PaletteRAM(_)    => unimplemented!(),
-            PhysicalAddress::VRAM(_)          => unimplemented!(),
-            PhysicalAddress::AttributesOBJ(_) => unimplemented!(),
-            PhysicalAddress::GamePak0ROM(_)   => unimplemented!(),
-            PhysicalAddress::GamePak1ROM(_)   => unimplemented!(),
-            PhysicalAddress::GamePak2ROM(_)   => unimplemented!(),
+            PhysicalAddress::OnBoardWRAM(p)   => Ok(self.wram_on_board.write_word(p, data as u32)),
+            PhysicalAddress::OnChipWRAM(p)    => Ok(self.wram_on_chip.write_word(p, data as u32)),
+            PhysicalAddress::RegistersIO(p)   => Ok(self.ioregs.store_word(p, data as u32)),
+            PhysicalAddress::PaletteRAM(_)    => Bus::store_video_memory(addr, poke),
+            PhysicalAddress::VRAM(_)          => Bus::store_video_memory(addr, poke),
+            PhysicalAddress::AttributesOBJ(_) => Bus::store_video_memory(addr, poke),
+            PhysicalAddress::GamePak0ROM(p)   => self.store_game_pak_rom(p, data as u32, 32),
+            PhysicalAddress::GamePak1ROM(p)   => self.store_game_pak_rom(p, data as u32, 32),
+            PhysicalAddress::GamePak2ROM(p)   => self.store_game_pak_rom(p, data as u32, 32),
             PhysicalAddress::GamePakSRAM(p)   => Err(GbaError::InvalidMemoryBusWidth(p, 32)),
             PhysicalAddress::Invalid(p)       => Err(GbaError::InvalidPhysicalAddress(p)),
         }
@@ -105,22 +221,35 @@ impl Bus {
     ///
     /// ## Returns
     /// - `Ok`: The loaded byte.
-    /// - `Err(InvalidPhysicalAddress)`: The given address is not part of the memory map.
     /// - `Err(InvalidMemoryBusWidth)`: The memory-mapped device cannot load bytes.
     pub fn load_byte(&self, addr: u32) -> Result<i32, GbaError> {
+        let x = try!(self.load_byte_raw(addr, false));
+        self.watchpoints.borrow_mut().record(addr, 8, false, x as u32, x as u32);
+        Ok(x)
+    }
+
+    /// Loads a byte like `load_byte`, but without triggering
+    /// watchpoints or the BIOS read protection.
+    ///
+    /// This lets debuggers inspect memory without any side
+    /// effects on the emulated machine. Video memory is not
+    /// emulated yet and yields `Err(InvalidPhysicalAddress)`.
+    pub fn peek_byte(&self, addr: u32) -> Result<i32, GbaError> { self.load_byte_raw(addr, true) }
+
+    fn load_byte_raw(&self, addr: u32, peek: bool) -> Result<i32, GbaError> {
         match PhysicalAddress::from_u32(addr) {
-            PhysicalAddress::BiosROM(p)       => Ok(self.bios_rom.borrow().read_byte(p) as u32 as i32),
-            PhysicalAddress::OnBoardWRAM(_)   => unimplemented!(),
-            PhysicalAddress::OnChipWRAM(_)    => unimplemented!(),
+            PhysicalAddress::BiosROM(p)       => Ok((self.load_bios_word(p, peek) >> Bus::latched_shift(p, 8)) as u8 as i32),
+            PhysicalAddress::OnBoardWRAM(p)   => Ok(self.wram_on_board.read_byte(p) as u32 as i32),
+            PhysicalAddress::OnChipWRAM(p)    => Ok(self.wram_on_chip.read_byte(p) as u32 as i32),
             PhysicalAddress::RegistersIO(p)   => Ok(self.ioregs.read_byte(p) as u32 as i32),
-            PhysicalAddress::PaletteRAM(_)    => unimplemented!(),
-            PhysicalAddress::VRAM(_)          => unimplemented!(),
-            PhysicalAddress::AttributesOBJ(_) => unimplemented!(),
-            PhysicalAddress::GamePak0ROM(_)   => unimplemented!(),
-            PhysicalAddress::GamePak1ROM(_)   => unimplemented!(),
-            PhysicalAddress::GamePak2ROM(_)   => unimplemented!(),
-            PhysicalAddress::GamePakSRAM(p)   => Ok(self.game_pak.borrow().sram().read_byte(p) as u32 as i32),
-            PhysicalAddress::Invalid(p)       => Err(GbaError::InvalidPhysicalAddress(p)),
+            PhysicalAddress::PaletteRAM(_)    => Bus::load_video_memory(addr, peek),
+            PhysicalAddress::VRAM(_)          => Bus::load_video_memory(addr, peek),
+            PhysicalAddress::AttributesOBJ(_) => Bus::load_video_memory(addr, peek),
+            PhysicalAddress::GamePak0ROM(p)   => Ok(self.game_pak.borrow().load_rom_byte(p) as u32 as i32),
+            PhysicalAddress::GamePak1ROM(p)   => Ok(self.game_pak.borrow().load_rom_byte(p) as u32 as i32),
+            PhysicalAddress::GamePak2ROM(p)   => Ok(self.game_pak.borrow().load_rom_byte(p) as u32 as i32),
+            PhysicalAddress::GamePakSRAM(p)   => Ok(self.game_pak.borrow().load_sram_byte(p) as u32 as i32),
+            PhysicalAddress::Invalid(p)       => Ok((self.open_bus >> Bus::latched_shift(p, 8)) as u8 as i32),
         }
     }
 
@@ -136,19 +265,34 @@ impl Bus {
     /// - `Err(InvalidMemoryBusWidth)`: The memory-mapped device cannot store bytes.
     /// - `Err(InvalidRomAccess)`: Tried storing data into a ROM.
     pub fn store_byte(&mut self, addr: u32, data: i32) -> Result<(), GbaError> {
+        let watched = self.watchpoints.get_mut().watches(addr, 8, true);
+        let old = if watched { self.peek_byte(addr).unwrap_or(0) as u32 } else { 0 };
+        try!(self.store_byte_raw(addr, data, false));
+        if watched { self.watchpoints.get_mut().record(addr, 8, true, old, data as u32 & 0xFF); }
+        Ok(())
+    }
+
+    /// Stores a byte like `store_byte`, but without triggering watchpoints.
+    ///
+    /// This lets debuggers modify memory without stopping
+    /// at their own stores. Video memory is not emulated
+    /// yet and yields `Err(InvalidPhysicalAddress)`.
+    pub fn poke_byte(&mut self, addr: u32, data: i32) -> Result<(), GbaError> { self.store_byte_raw(addr, data, true) }
+
+    fn store_byte_raw(&mut self, addr: u32, data: i32, poke: bool) -> Result<(), GbaError> {
         let byte = (data & 0xFF) as u8;
         match PhysicalAddress::from_u32(addr) {
             PhysicalAddress::BiosROM(p)       => Err(GbaError::InvalidRomAccess(p)),
-            PhysicalAddress::OnBoardWRAM(_)   => unimplemented!(),
-            PhysicalAddress::OnChipWRAM(_)    => unimplemented!(),
-            PhysicalAddress::RegistersIO(p)   => Ok(self.ioregs.write_byte(p, byte)),
-            PhysicalAddress::PaletteRAM(_)    => unimplemented!(),
-            PhysicalAddress::VRAM(_)          => unimplemented!(),
-            PhysicalAddress::AttributesOBJ(_) => unimplemented!(),
-            PhysicalAddress::GamePak0ROM(_)   => unimplemented!(),
-            PhysicalAddress::GamePak1ROM(_)   => unimplemented!(),
-            PhysicalAddress::GamePak2ROM(_)   => unimplemented!(),
-            PhysicalAddress::GamePakSRAM(p)   => Ok(self.game_pak.borrow_mut().sram_mut().write_byte(p, byte)),
+            PhysicalAddress::OnBoardWRAM(p)   => Ok(self.wram_on_board.write_byte(p, byte)),
+            PhysicalAddress::OnChipWRAM(p)    => Ok(self.wram_on_chip.write_byte(p, byte)),
+            PhysicalAddress::RegistersIO(p)   => Ok(self.ioregs.store_byte(p, byte)),
+            PhysicalAddress::PaletteRAM(_)    => Bus::store_video_memory(addr, poke),
+            PhysicalAddress::VRAM(_)          => Bus::store_video_memory(addr, poke),
+            PhysicalAddress::AttributesOBJ(_) => Bus::store_video_memory(addr, poke),
+            PhysicalAddress::GamePak0ROM(p)   => self.store_game_pak_rom(p, byte as u32, 8),
+            PhysicalAddress::GamePak1ROM(p)   => self.store_game_pak_rom(p, byte as u32, 8),
+            PhysicalAddress::GamePak2ROM(p)   => self.store_game_pak_rom(p, byte as u32, 8),
+            PhysicalAddress::GamePakSRAM(p)   => Ok(self.game_pak.borrow_mut().store_sram_byte(p, byte)),
             PhysicalAddress::Invalid(p)       => Err(GbaError::InvalidPhysicalAddress(p)),
         }
     }
@@ -162,23 +306,36 @@ impl Bus {
     ///
     /// ## Returns
     /// - `Ok`: The loaded halfword.
-    /// - `Err(InvalidPhysicalAddress)`: The given address is not part of the memory map.
     /// - `Err(InvalidMemoryBusWidth)`: The memory-mapped device cannot load halfwords.
     pub fn load_halfword(&self, addr: u32) -> Result<i32, GbaError> {
+        let x = try!(self.load_halfword_raw(addr, false));
+        self.watchpoints.borrow_mut().record(addr & !0b01, 16, false, x as u32, x as u32);
+        Ok(x)
+    }
+
+    /// Loads a halfword like `load_halfword`, but without triggering
+    /// watchpoints or the BIOS read protection.
+    ///
+    /// This lets debuggers inspect memory without any side
+    /// effects on the emulated machine. Video memory is not
+    /// emulated yet and yields `Err(InvalidPhysicalAddress)`.
+    pub fn peek_halfword(&self, addr: u32) -> Result<i32, GbaError> { self.load_halfword_raw(addr, true) }
+
+    fn load_halfword_raw(&self, addr: u32, peek: bool) -> Result<i32, GbaError> {
         if 0 != (addr & 0b01) { warn!("Reading missaligned halfword address {:#010X}.", addr); }
         match PhysicalAddress::from_u32(addr) {
-            PhysicalAddress::BiosROM(p)       => Ok(self.bios_rom.borrow().read_halfword(p) as u32 as i32),
-            PhysicalAddress::OnBoardWRAM(_)   => unimplemented!(),
-            PhysicalAddress::OnChipWRAM(_)    => unimplemented!(),
+            PhysicalAddress::BiosROM(p)       => Ok((self.load_bios_word(p, peek) >> Bus::latched_shift(p, 16)) as u16 as i32),
+            PhysicalAddress::OnBoardWRAM(p)   => Ok(self.wram_on_board.read_halfword(p) as u32 as i32),
+            PhysicalAddress::OnChipWRAM(p)    => Ok(self.wram_on_chip.read_halfword(p) as u32 as i32),
             PhysicalAddress::RegistersIO(p)   => Ok(self.ioregs.read_halfword(p) as u32 as i32),
-            PhysicalAddress::PaletteRAM(_)    => unimplemented!(),
-            PhysicalAddress::VRAM(_)          => unimplemented!(),
-            PhysicalAddress::AttributesOBJ(_) => unimplemented!(),
-            PhysicalAddress::GamePak0ROM(_)   => unimplemented!(),
-            PhysicalAddress::GamePak1ROM(_)   => unimplemented!(),
-            PhysicalAddress::GamePak2ROM(_)   => unimplemented!(),
+            PhysicalAddress::PaletteRAM(_)    => Bus::load_video_memory(addr, peek),
+            PhysicalAddress::VRAM(_)          => Bus::load_video_memory(addr, peek),
+            PhysicalAddress::AttributesOBJ(_) => Bus::load_video_memory(addr, peek),
+            PhysicalAddress::GamePak0ROM(p)   => Ok(self.game_pak.borrow().load_rom_halfword(p) as u32 as i32),
+            PhysicalAddress::GamePak1ROM(p)   => Ok(self.game_pak.borrow().load_rom_halfword(p) as u32 as i32),
+            PhysicalAddress::GamePak2ROM(p)   => Ok(self.game_pak.borrow().load_rom_halfword(p) as u32 as i32),
             PhysicalAddress::GamePakSRAM(p)   => Err(GbaError::InvalidMemoryBusWidth(p, 16)),
-            PhysicalAddress::Invalid(p)       => Err(GbaError::InvalidPhysicalAddress(p)),
+            PhysicalAddress::Invalid(p)       => Ok((self.open_bus >> Bus::latched_shift(p, 16)) as u16 as i32),
         }
     }
 
@@ -196,23 +353,137 @@ impl Bus {
     /// - `Err(InvalidMemoryBusWidth)`: The memory-mapped device cannot store halfwords.
     /// - `Err(InvalidRomAccess)`: Tried storing data into a ROM.
     pub fn store_halfword(&mut self, addr: u32, data: i32) -> Result<(), GbaError> {
+        let aligned = addr & !0b01;
+        let watched = self.watchpoints.get_mut().watches(aligned, 16, true);
+        let old = if watched { self.peek_halfword(aligned).unwrap_or(0) as u32 } else { 0 };
+        try!(self.store_halfword_raw(addr, data, false));
+        if watched { self.watchpoints.get_mut().record(aligned, 16, true, old, data as u32 & 0xFFFF); }
+        Ok(())
+    }
+
+    /// Stores a halfword like `store_halfword`, but without triggering watchpoints.
+    ///
+    /// This lets debuggers modify memory without stopping
+    /// at their own stores. Video memory is not emulated
+    /// yet and yields `Err(InvalidPhysicalAddress)`.
+    pub fn poke_halfword(&mut self, addr: u32, data: i32) -> Result<(), GbaError> { self.store_halfword_raw(addr, data, true) }
+
+    fn store_halfword_raw(&mut self, addr: u32, data: i32, poke: bool) -> Result<(), GbaError> {
         if 0 != (addr & 0b01) { warn!("Reading missaligned halfword address {:#010X}.", addr); }
         let halfword = (data & 0xFFFF) as u16;
         match PhysicalAddress::from_u32(addr) {
             PhysicalAddress::BiosROM(p)       => Err(GbaError::InvalidRomAccess(p)),
-            PhysicalAddress::OnBoardWRAM(_)   => unimplemented!(),
-            PhysicalAddress::OnChipWRAM(_)    => unimplemented!(),
-            PhysicalAddress::RegistersIO(p)   => Ok(self.ioregs.write_halfword(p, halfword)),
-            PhysicalAddress::PaletteRAM(_)    => unimplemented!(),
-            PhysicalAddress::VRAM(_)          => unimplemented!(),
-            PhysicalAddress::AttributesOBJ(_) => unimplemented!(),
-            PhysicalAddress::GamePak0ROM(_)   => unimplemented!(),
-            PhysicalAddress::GamePak1ROM(_)   => unimplemented!(),
-            PhysicalAddress::GamePak2ROM(_)   => unimplemented!(),
+            PhysicalAddress::OnBoardWRAM(p)   => Ok(self.wram_on_board.write_halfword(p, halfword)),
+            PhysicalAddress::OnChipWRAM(p)    => Ok(self.wram_on_chip.write_halfword(p, halfword)),
+            PhysicalAddress::RegistersIO(p)   => Ok(self.ioregs.store_halfword(p, halfword)),
+            PhysicalAddress::PaletteRAM(_)    => Bus::store_video_memory(addr, poke),
+            PhysicalAddress::VRAM(_)          => Bus::store_video_memory(addr, poke),
+            PhysicalAddress::AttributesOBJ(_) => Bus::store_video_memory(addr, poke),
+            PhysicalAddress::GamePak0ROM(p)   => self.store_game_pak_rom(p, halfword as u32, 16),
+            PhysicalAddress::GamePak1ROM(p)   => self.store_game_pak_rom(p, halfword as u32, 16),
+            PhysicalAddress::GamePak2ROM(p)   => self.store_game_pak_rom(p, halfword as u32, 16),
             PhysicalAddress::GamePakSRAM(p)   => Err(GbaError::InvalidMemoryBusWidth(p, 16)),
             PhysicalAddress::Invalid(p)       => Err(GbaError::InvalidPhysicalAddress(p)),
         }
     }
+
+    /// Copies a block of data into the memory system.
+    ///
+    /// Unlike the `store_*` functions, this also writes
+    /// into the GamePak ROM. It is meant for loading
+    /// executables, not for emulating the bus.
+    ///
+    /// ## Params
+    /// - `addr`: The address of the first byte to write.
+    /// - `data`: The data to copy.
+    ///
+    /// ## Returns
+    /// - `Ok`: Copying succeeded.
+    /// - `Err(InvalidPhysicalAddress)`: The data does not fit into
+    ///   a single WRAM or ROM area, or wraps around a WRAM mirror.
+    pub fn write_image(&mut self, addr: u32, data: &[u8]) -> Result<(), GbaError> {
+        let len = data.len() as u32;
+        let last = try!(addr.checked_add(len.saturating_sub(1)).ok_or(GbaError::InvalidPhysicalAddress(addr)));
+        let ok = match (PhysicalAddress::from_u32(addr), PhysicalAddress::from_u32(last)) {
+            (PhysicalAddress::OnBoardWRAM(p), PhysicalAddress::OnBoardWRAM(q))
+            if (p <= q) && ((p as usize + data.len()) <= WRAM_ON_BOARD_LEN as usize) => {
+                self.wram_on_board.bytes_mut(p)[..data.len()].copy_from_slice(data); true
+            },
+            (PhysicalAddress::OnChipWRAM(p), PhysicalAddress::OnChipWRAM(q))
+            if (p <= q) && ((p as usize + data.len()) <= WRAM_ON_CHIP_LEN as usize) => {
+                self.wram_on_chip.bytes_mut(p)[..data.len()].copy_from_slice(data); true
+            },
+            (PhysicalAddress::GamePak0ROM(p), PhysicalAddress::GamePak0ROM(_)) => {
+                self.game_pak.borrow_mut().rom_mut().write_image(p, data)
+            },
+            _ => false,
+        };
+        if ok { Ok(()) } else { Err(GbaError::InvalidPhysicalAddress(addr)) }
+    }
+
+    fn is_bios_address(addr: u32) -> bool {
+        match PhysicalAddress::from_u32(addr) { PhysicalAddress::BiosROM(_) => true, _ => false }
+    }
+
+    fn latch_opcode(&mut self, opcode: u32) {
+        self.open_bus = opcode;
+        if self.executing_bios { self.last_bios_opcode = opcode; }
+    }
+
+    // Protected BIOS loads see the last opcode fetched from the BIOS.
+    fn load_bios_word(&self, offs: u32, peek: bool) -> u32 {
+        if self.executing_bios | peek { self.bios_rom.borrow().read_word(offs & !0b11) }
+        else { self.last_bios_opcode }
+    }
+
+    // Video memory is not emulated yet, but debuggers may still peek at it.
+    fn load_video_memory(addr: u32, peek: bool) -> Result<i32, GbaError> {
+        if peek { Err(GbaError::InvalidPhysicalAddress(addr)) } else { unimplemented!() }
+    }
+
+    // Video memory is not emulated yet, but debuggers may still try to poke it.
+    fn store_video_memory(addr: u32, poke: bool) -> Result<(), GbaError> {
+        if poke { Err(GbaError::InvalidPhysicalAddress(addr)) } else { unimplemented!() }
+    }
+
+    // Narrow loads of a latched word see the addressed bytes.
+    fn latched_shift(addr: u32, width: u32) -> u32 {
+        (addr & (0b100 - (width / 8))) * 8
+    }
+
+    // Only the GamePak's GPIO port is writable in the ROM area.
+    fn store_game_pak_rom(&mut self, offs: u32, data: u32, width: u8) -> Result<(), GbaError> {
+        let mut gpak = self.game_pak.borrow_mut();
+        let ok = match width {
+            32 => gpak.store_rom_halfword(offs & !0b11, data as u16)
+                | gpak.store_rom_halfword((offs & !0b11) + 2, (data >> 16) as u16),
+            16 => gpak.store_rom_halfword(offs, data as u16),
+            _  => gpak.store_rom_halfword(offs, (data & 0xFF) as u16),
+        };
+        if ok { Ok(()) } else { Err(GbaError::InvalidRomAccess(offs)) }
+    }
+}
+
+// The BIOS and GamePak are shared with `Gba`, which saves them.
+impl SaveState for Bus {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(self.wram_on_board.bytes(0));
+        w.write_bytes(self.wram_on_chip.bytes(0));
+        self.ioregs.save_state(w);
+        w.write_u32(self.open_bus);
+        w.write_u32(self.last_bios_opcode);
+        w.write_bool(self.executing_bios);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> io::Result<()> {
+        try!(r.read_bytes(self.wram_on_board.bytes_mut(0)));
+        try!(r.read_bytes(self.wram_on_chip.bytes_mut(0)));
+        try!(self.ioregs.load_state(r));
+        self.open_bus         = try!(r.read_u32());
+        self.last_bios_opcode = try!(r.read_u32());
+        self.executing_bios   = try!(r.read_bool());
+        Ok(())
+    }
 }
 
 
@@ -0,0 +1,81 @@
+// License below.
+//! Implements checksums used to identify ROMs and patches.
+//!
+//! IPS, UPS and BPS patches as well as save states identify
+//! the files they belong to by a CRC32 as used by ZIP and
+//! PNG, i.e. with the reversed polynomial `0xEDB88320`.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+
+/// The reversed CRC32 polynomial.
+pub const CRC32_POLYNOMIAL: u32 = 0xEDB88320;
+
+
+/// Calculates a CRC32 piece by piece.
+pub struct Crc32 {
+    table: [u32; 256],
+    crc: u32,
+}
+
+impl Crc32 {
+    /// Creates a new CRC32 calculator.
+    pub fn new() -> Crc32 {
+        let mut table = [0_u32; 256];
+        for i in 0..256 {
+            let mut c = i as u32;
+            for _ in 0..8 {
+                c = if 0 != (c & 0b1) { CRC32_POLYNOMIAL ^ (c >> 1) } else { c >> 1 };
+            }
+            table[i] = c;
+        }
+        Crc32 { table: table, crc: 0xFFFFFFFF }
+    }
+
+    /// Feeds more bytes into the CRC32.
+    pub fn update(&mut self, data: &[u8]) {
+        let mut c = self.crc;
+        for &b in data {
+            c = self.table[((c ^ (b as u32)) & 0xFF) as usize] ^ (c >> 8);
+        }
+        self.crc = c;
+    }
+
+    /// Get the CRC32 of all bytes fed so far.
+    pub fn finish(&self) -> u32 { !self.crc }
+}
+
+impl Default for Crc32 {
+    fn default() -> Crc32 { Crc32::new() }
+}
+
+
+/// Calculates the CRC32 of a block of bytes.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut c = Crc32::new();
+    c.update(data);
+    c.finish()
+}
+
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -0,0 +1,174 @@
+// License below.
+//! Implements a shadow call stack for backtraces.
+//!
+//! The CPU does not know about functions, so calls and
+//! returns are guessed from the executed branches. Calls
+//! are `bl` and any branch right after setting up LR, like
+//! `mov lr, pc` followed by `bx r0`. Returns are branches
+//! via LR, e.g. `bx lr` or `mov pc, lr`, and popping PC off
+//! the stack. Exceptions push frames as well.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::slice;
+use super::{Arm7Tdmi, Mode, Exception, ArmDPOP};
+use super::super::arminstruction::{ArmInstruction, ArmOpcode};
+use super::super::thumbinstruction::{ThumbInstruction, ThumbOpcode, HiRegisterOp};
+
+
+/// The maximum number of frames kept. Deeper frames are
+/// dropped, such that runaway recursion cannot eat up memory.
+pub const MAX_CALL_DEPTH: usize = 256;
+
+
+/// A single entry of the shadow call stack.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CallFrame {
+    /// The calling instruction, or the one where an exception occurred.
+    pub call_site: u32,
+
+    /// The address of the called function or exception vector.
+    pub target: u32,
+
+    /// Where execution continues after returning.
+    pub return_addr: u32,
+
+    /// The CPU mode the frame was entered in.
+    pub mode: Mode,
+
+    /// The exception that entered this frame, if any.
+    pub exception: Option<Exception>,
+}
+
+
+/// Tracks nested calls and exceptions.
+#[derive(Debug, Clone, Default)]
+pub struct CallStack {
+    frames: Vec<CallFrame>,
+}
+
+impl CallStack {
+    /// Creates an empty call stack.
+    pub fn new() -> CallStack {
+        CallStack { frames: Vec::new() }
+    }
+
+    /// Get the number of frames.
+    pub fn len(&self) -> usize { self.frames.len() }
+
+    /// Checks whether there are no frames.
+    pub fn is_empty(&self) -> bool { self.frames.is_empty() }
+
+    /// Iterates over all frames, from the outermost one to the innermost one.
+    pub fn iter(&self) -> slice::Iter<CallFrame> { self.frames.iter() }
+
+    /// Get the innermost frame, if any.
+    pub fn top(&self) -> Option<&CallFrame> { self.frames.last() }
+
+    /// Removes all frames.
+    pub fn clear(&mut self) { self.frames.clear(); }
+
+    /// Enters a new frame, dropping the outermost one if full.
+    pub fn push(&mut self, frame: CallFrame) {
+        if self.frames.len() >= MAX_CALL_DEPTH { self.frames.remove(0); }
+        self.frames.push(frame);
+    }
+
+    /// Leaves frames after returning to an address.
+    ///
+    /// All frames up to the innermost one returning to that
+    /// address are removed. If no frame returns there, e.g.
+    /// because the call happened before tracking started,
+    /// the stack stays as it is.
+    ///
+    /// # Returns
+    /// The number of removed frames.
+    pub fn ret(&mut self, target: u32) -> usize {
+        match self.frames.iter().rposition(|f| f.return_addr == target) {
+            Some(i) => { let n = self.frames.len() - i; self.frames.truncate(i); n },
+            None => 0,
+        }
+    }
+}
+
+
+/// How an instruction about to be executed changes control flow.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Branch {
+    #[doc = "Anything but a call or return."]                 None,
+    #[doc = "A call returning to the given address."]         Call(u32),
+    #[doc = "A return to wherever the branch ends up."]       Return,
+}
+
+impl Branch {
+    /// Guesses the kind of branch an ARM state instruction does, if taken.
+    ///
+    /// # Params
+    /// - `addr`: The address of the instruction.
+    /// - `inst`: The instruction itself.
+    /// - `lr`: The value of LR before executing the instruction.
+    pub fn classify_arm(addr: u32, inst: &ArmInstruction, lr: u32) -> Branch {
+        let next = addr.wrapping_add(4);
+        let writes_pc = match inst.opcode() {
+            ArmOpcode::B_BL => return if inst.is_branch_with_link() { Branch::Call(next) } else { Branch::None },
+            ArmOpcode::BX => {
+                if inst.Rm() == Arm7Tdmi::LR { return Branch::Return; }
+                true
+            },
+            ArmOpcode::DataProcessing if inst.Rd() == Arm7Tdmi::PC => {
+                let via_lr = if inst.dpop() == ArmDPOP::MOV { inst.is_shift_field_register() && (inst.Rm() == Arm7Tdmi::LR) }
+                             else { inst.Rn() == Arm7Tdmi::LR };
+                if via_lr { return Branch::Return; }
+                true
+            },
+            ArmOpcode::LDR_STR if inst.is_load() && (inst.Rd() == Arm7Tdmi::PC) => {
+                if inst.Rn() == Arm7Tdmi::SP { return Branch::Return; }
+                true
+            },
+            ArmOpcode::LDM_STM if inst.is_load() && ((inst.register_map() & (1 << Arm7Tdmi::PC)) != 0) => return Branch::Return,
+            _ => false,
+        };
+        if writes_pc && (lr == next) { Branch::Call(next) } else { Branch::None }
+    }
+
+    /// Guesses the kind of branch a THUMB state instruction does, if taken.
+    ///
+    /// # Params
+    /// - `addr`: The address of the instruction.
+    /// - `inst`: The instruction itself.
+    pub fn classify_thumb(addr: u32, inst: &ThumbInstruction) -> Branch {
+        match inst.opcode() {
+            // Only the second half of BL actually branches.
+            ThumbOpcode::BranchLongOffs if inst.is_low_offset_and_branch() => Branch::Call(addr.wrapping_add(2)),
+            ThumbOpcode::HiRegOpBx => match inst.op_HiRegOpBx() {
+                HiRegisterOp::BxRsHs if inst.Hs() == Arm7Tdmi::LR => Branch::Return,
+                HiRegisterOp::MovNoFlags if (inst.Hd() == Arm7Tdmi::PC) && (inst.Hs() == Arm7Tdmi::LR) => Branch::Return,
+                _ => Branch::None,
+            },
+            ThumbOpcode::PushPopRegs if inst.is_load() && inst.is_storing_LR_loading_PC() => Branch::Return,
+            _ => Branch::None,
+        }
+    }
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
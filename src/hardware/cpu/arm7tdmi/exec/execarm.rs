@@ -113,7 +113,7 @@ impl Arm7Tdmi {
 
         if inst.Rd() == Arm7Tdmi::PC { // FIXME really error or just ignore?
             if self.mode == Mode::User { error!("USR has no SPSR."); return Err(GbaError::PrivilegedUserCode); }
-            self.cpsr = self.spsr[self.mode as u8 as usize];
+            self.restore_cpsr();
         }
 
         Ok(if inst.Rd() == Arm7Tdmi::PC { CpuAction::FlushPipeline } else { CpuAction::None })
@@ -143,8 +143,8 @@ impl Arm7Tdmi {
                 if self.cpsr.state() != s { warn!("MSR_Reg changed the T bit!"); }
             }
             // Mode might have changed.
-            let old_mode = self.cpsr.mode();
-            self.change_mode(old_mode);
+            let new_mode = self.cpsr.mode();
+            self.change_mode(new_mode);
         }
         Ok(CpuAction::None)
     }
@@ -176,7 +176,7 @@ impl Arm7Tdmi {
 
              if !inst.is_pre_indexed()       { self.gpr[inst.Rn()] = base.wrapping_add(offs) as i32; }
         else if  inst.is_auto_incrementing() { self.gpr[inst.Rn()] = base as i32; }
-        Ok(CpuAction::None)
+        Ok(if inst.is_load() & (inst.Rd() == Arm7Tdmi::PC) { CpuAction::FlushPipeline } else { CpuAction::None })
     }
 
     fn execute_ldrh_strh(&mut self, inst: ArmInstruction, imm: bool) -> Result<CpuAction, GbaError> {
@@ -213,6 +213,7 @@ impl Arm7Tdmi {
         let psr   = inst.is_enforcing_user_mode();
         let offs  = if inst.is_pre_indexed() == inst.is_offset_added() { (4_u32, 0) } else { (0_u32, 4) };
         let mut addr = if inst.is_offset_added() { base } else { base.wrapping_sub(bytes) }; // Go back N regs if decr.
+        addr &= !0b11; // Unlike LDR, LDM never rotates misaligned words.
 
         // Write back Rn now to avoid special cases with loading Rn.
         if inst.is_auto_incrementing() {
@@ -236,11 +237,10 @@ impl Arm7Tdmi {
         // Handle mode change.
         if r15 & psr & inst.is_load() {
             if self.mode == Mode::User { warn!("USR mode has no SPSR."); return Err(GbaError::PrivilegedUserCode); }
-            let new_mode = self.spsr[self.mode as u8 as usize].mode();
-            self.change_mode(new_mode);
+            self.restore_cpsr();
         }
 
-        Ok(CpuAction::None)
+        Ok(if r15 & inst.is_load() { CpuAction::FlushPipeline } else { CpuAction::None })
     }
 
     fn execute_ldm_stm_user_bank(&mut self, rmap: u16, mut addr: u32, offs: (u32, u32), load: bool) -> Result<CpuAction, GbaError> {
@@ -304,18 +304,21 @@ impl Arm7Tdmi {
     fn execute_swi(&mut self, inst: ArmInstruction) -> Result<CpuAction, GbaError> {
         debug!("{}", inst);
         if self.optimise_swi {
-            unimplemented!()
-        } else {
-            self.exception(Exception::SoftwareInterrupt);
-            Ok(CpuAction::FlushPipeline)
+            let num = BiosFunction::number_from_arm_comment(inst.comment());
+            match BiosFunction::decode(num) {
+                Some(func) => if let Some(action) = try!(self.execute_hle_swi(func)) { return Ok(action); },
+                None       => warn!("Unknown BIOS function {:#04X}.", num),
+            }
         }
+        self.exception(Exception::SoftwareInterrupt);
+        Ok(CpuAction::FlushPipeline)
     }
 
     fn execute_unknown(&mut self, inst: ArmInstruction) -> Result<CpuAction, GbaError> {
         error!("No offering to co-processors implemented yet."); // TODO
         debug!("{}", inst);
         self.exception(Exception::UndefinedInstruction);
-        Ok(CpuAction::None)
+        Ok(CpuAction::FlushPipeline)
     }
 }
 
@@ -52,8 +52,8 @@ impl Arm7Tdmi {
             ArmDPOP::RSB                => { Arm7Tdmi::alu_sub_carry_overflow(op2, op1, &mut cf, &mut vf) },
             ArmDPOP::ADD | ArmDPOP::CMN => { Arm7Tdmi::alu_add_carry_overflow(op1, op2, &mut cf, &mut vf) },
             ArmDPOP::ADC                => { Arm7Tdmi::alu_add_carry_overflow(op1, op2.wrapping_add(c), &mut cf, &mut vf) },
-            ArmDPOP::SBC                => { Arm7Tdmi::alu_sub_carry_overflow(op1, op2.wrapping_sub(1-c), &mut cf, &mut vf) },
-            ArmDPOP::RSC                => { Arm7Tdmi::alu_sub_carry_overflow(op2, op1.wrapping_sub(1-c), &mut cf, &mut vf) },
+            ArmDPOP::SBC                => { Arm7Tdmi::alu_sub_carry_overflow(op1, op2.wrapping_add(1-c), &mut cf, &mut vf) },
+            ArmDPOP::RSC                => { Arm7Tdmi::alu_sub_carry_overflow(op2, op1.wrapping_add(1-c), &mut cf, &mut vf) },
             ArmDPOP::ORR                => { op1 | op2 },
             ArmDPOP::MOV                => { op2 },
             ArmDPOP::BIC                => { op1 & !op2 },
@@ -78,8 +78,9 @@ impl Arm7Tdmi {
     }
 
     fn alu_sub_carry_overflow(a: i32, b: i32, c: &mut bool, v: &mut bool) -> i32 {
+        // The ARM's carry flag is an inverted borrow flag.
         let res64: u64 = (a as u32 as u64).wrapping_sub(b as u32 as u64);
-        *c = 0 != (res64 & (1 << 32));
+        *c = 0 == (res64 & (1 << 32));
         let x = a.overflowing_sub(b);
         *v = x.1;
         x.0
@@ -0,0 +1,299 @@
+// License below.
+//! Implements the BIOS decompression functions.
+//!
+//! All compressed data starts with a 32-bit header:
+//!
+//! ```text
+//! Bit   Expl.
+//! 0-3   Huffman: Data size in bits, 4 or 8.
+//!       Diff: Unit size in bytes, 1 or 2.
+//! 4-7   Compression type: 1=LZ77, 2=Huffman, 3=RunLength, 8=Diff.
+//! 8-31  Decompressed size in bytes.
+//! ```
+//!
+//! The decompressors read their input through a callback,
+//! so that they work on the bus as well as on plain ROM
+//! data for inspecting a game's assets.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::fmt;
+use super::BiosFunction;
+use super::super::Arm7Tdmi;
+use super::super::super::super::error::*;
+
+
+/// All compression formats supported by the BIOS.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Compression {
+    #[doc = "LZ77 with 12-bit displacements."]              LZ77,
+    #[doc = "Huffman coding of 4 or 8 bit data units."]     Huffman(u8),
+    #[doc = "Run-length encoding."]                         RunLength,
+    #[doc = "Delta filter on bytes."]                       Diff8,
+    #[doc = "Delta filter on halfwords."]                   Diff16,
+}
+
+impl Compression {
+    /// Detects the compression format of a data header.
+    ///
+    /// # Params
+    /// - `header`: The first word of the compressed data.
+    ///
+    /// # Returns
+    /// - `Some`: The compression format.
+    /// - `None`: The header is invalid.
+    pub fn from_header(header: u32) -> Option<Compression> {
+        match ((header >> 4) & 0xF, header & 0xF) {
+            (1, _) => Some(Compression::LZ77),
+            (2, 4) => Some(Compression::Huffman(4)),
+            (2, 8) => Some(Compression::Huffman(8)),
+            (3, _) => Some(Compression::RunLength),
+            (8, 1) => Some(Compression::Diff8),
+            (8, 2) => Some(Compression::Diff16),
+            _ => None,
+        }
+    }
+
+    /// Get the decompressed size in bytes of a data header.
+    pub fn decompressed_size(header: u32) -> usize { (header >> 8) as usize }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Compression::LZ77       => write!(f, "LZ77"),
+            Compression::Huffman(n) => write!(f, "{}-bit Huffman", n),
+            Compression::RunLength  => write!(f, "run-length"),
+            Compression::Diff8      => write!(f, "8-bit diff"),
+            Compression::Diff16     => write!(f, "16-bit diff"),
+        }
+    }
+}
+
+
+/// Decompresses data like the BIOS.
+///
+/// The compression format given overrides the header's
+/// type, just like each BIOS function blindly assumes
+/// its own format.
+///
+/// # Params
+/// - `kind`: The compression format.
+/// - `addr`: The address of the data header.
+/// - `load`: Loads a single byte from an address.
+///
+/// # Returns
+/// - `Ok`: The decompressed data.
+/// - `Err`: Loading a byte failed.
+pub fn decompress<F>(kind: Compression, addr: u32, mut load: F) -> Result<Vec<u8>, GbaError>
+where F: FnMut(u32) -> Result<u8, GbaError> {
+    let header = try!(load_word(addr, &mut load));
+    let size = Compression::decompressed_size(header);
+    let data = addr.wrapping_add(4);
+    match kind {
+        Compression::LZ77       => decompress_lz77(data, size, &mut load),
+        Compression::Huffman(n) => decompress_huffman(data, size, n, &mut load),
+        Compression::RunLength  => decompress_run_length(data, size, &mut load),
+        Compression::Diff8      => unfilter_diff8(data, size, &mut load),
+        Compression::Diff16     => unfilter_diff16(data, size, &mut load),
+    }
+}
+
+fn load_word<F>(addr: u32, load: &mut F) -> Result<u32, GbaError>
+where F: FnMut(u32) -> Result<u8, GbaError> {
+    let mut w = 0_u32;
+    for i in 0..4 { w |= (try!(load(addr.wrapping_add(i))) as u32) << (i * 8); }
+    Ok(w)
+}
+
+fn decompress_lz77<F>(mut src: u32, size: usize, load: &mut F) -> Result<Vec<u8>, GbaError>
+where F: FnMut(u32) -> Result<u8, GbaError> {
+    let mut out = Vec::with_capacity(size);
+    while out.len() < size {
+        let flags = try!(load(src));
+        src = src.wrapping_add(1);
+        for bit in (0..8).rev() {
+            if out.len() >= size { break; }
+            if 0 == (flags & (1 << bit)) {
+                out.push(try!(load(src)));
+                src = src.wrapping_add(1);
+                continue;
+            }
+
+            // Copy 3..18 bytes from 1..4096 bytes back.
+            let b0 = try!(load(src)) as usize;
+            let b1 = try!(load(src.wrapping_add(1))) as usize;
+            src = src.wrapping_add(2);
+            let len  = (b0 >> 4) + 3;
+            let disp = (((b0 & 0xF) << 8) | b1) + 1;
+            for _ in 0..len {
+                if out.len() >= size { break; }
+                let x = if disp <= out.len() { out[out.len() - disp] } else {
+                    warn!("LZ77 displacement {} before the output's start.", disp); 0
+                };
+                out.push(x);
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn decompress_huffman<F>(src: u32, size: usize, bits: u8, load: &mut F) -> Result<Vec<u8>, GbaError>
+where F: FnMut(u32) -> Result<u8, GbaError> {
+    if (bits != 4) & (bits != 8) {
+        warn!("Huffman data with invalid unit size {}.", bits);
+        return Ok(Vec::new());
+    }
+
+    // Each node has a 6-bit offset to its children and
+    // two flags telling whether a child is a data leaf.
+    let tree_size = (try!(load(src)) as u32 + 1) * 2;
+    let root = src.wrapping_add(1);
+    let mut stream = src.wrapping_add(tree_size);
+    let mut node = root;
+    let mut node_value = try!(load(root));
+
+    let mut out = Vec::with_capacity(size);
+    let mut nibble: Option<u8> = None;
+    while out.len() < size {
+        let word = try!(load_word(stream, load));
+        stream = stream.wrapping_add(4);
+        for bit in (0..32).rev() {
+            let right = 0 != (word & (1 << bit));
+            let child = (node & !1).wrapping_add(((node_value & 0x3F) as u32 + 1) * 2) + (right as u32);
+            let is_leaf = 0 != (node_value & if right { 0x40 } else { 0x80 });
+            node_value = try!(load(child));
+            if !is_leaf { node = child; continue; }
+
+            // Found a data unit. 4-bit units are packed low nibble first.
+            if bits == 8 { out.push(node_value); }
+            else if let Some(lo) = nibble.take() { out.push(lo | ((node_value & 0xF) << 4)); }
+            else { nibble = Some(node_value & 0xF); }
+
+            node = root;
+            node_value = try!(load(root));
+            if out.len() >= size { break; }
+        }
+    }
+    Ok(out)
+}
+
+fn decompress_run_length<F>(mut src: u32, size: usize, load: &mut F) -> Result<Vec<u8>, GbaError>
+where F: FnMut(u32) -> Result<u8, GbaError> {
+    let mut out = Vec::with_capacity(size);
+    while out.len() < size {
+        let flag = try!(load(src));
+        src = src.wrapping_add(1);
+        if 0 != (flag & 0x80) {
+            // Repeat a single byte 3..130 times.
+            let x = try!(load(src));
+            src = src.wrapping_add(1);
+            for _ in 0..((flag & 0x7F) as usize + 3) { out.push(x); }
+        } else {
+            // Copy 1..128 bytes.
+            for _ in 0..((flag & 0x7F) as usize + 1) {
+                out.push(try!(load(src)));
+                src = src.wrapping_add(1);
+            }
+        }
+    }
+    out.truncate(size);
+    Ok(out)
+}
+
+fn unfilter_diff8<F>(src: u32, size: usize, load: &mut F) -> Result<Vec<u8>, GbaError>
+where F: FnMut(u32) -> Result<u8, GbaError> {
+    let mut out = Vec::with_capacity(size);
+    let mut x = 0_u8;
+    for i in 0..size {
+        x = x.wrapping_add(try!(load(src.wrapping_add(i as u32))));
+        out.push(x);
+    }
+    Ok(out)
+}
+
+fn unfilter_diff16<F>(src: u32, size: usize, load: &mut F) -> Result<Vec<u8>, GbaError>
+where F: FnMut(u32) -> Result<u8, GbaError> {
+    let mut out = Vec::with_capacity(size);
+    let mut x = 0_u16;
+    for i in 0..(size / 2) {
+        let addr = src.wrapping_add(i as u32 * 2);
+        let lo = try!(load(addr)) as u16;
+        let hi = try!(load(addr.wrapping_add(1))) as u16;
+        x = x.wrapping_add(lo | (hi << 8));
+        out.push(x as u8);
+        out.push((x >> 8) as u8);
+    }
+    Ok(out)
+}
+
+
+impl Arm7Tdmi {
+    /// Executes any of the BIOS decompression functions.
+    ///
+    /// The `*Vram` functions only write halfwords, as
+    /// VRAM ignores byte writes. If the output size is
+    /// odd, the last byte is thus never written.
+    /// `HuffUnComp` always writes words.
+    pub fn hle_decompress(&mut self, func: BiosFunction) -> Result<(), GbaError> {
+        let src = self.gpr[0] as u32;
+        let dst = self.gpr[1] as u32;
+        if Arm7Tdmi::hle_is_bios_source(src) { warn!("{} from BIOS area {:#010X}.", func, src); return Ok(()); }
+
+        let header = try!(self.bus.borrow().load_word(src)) as u32;
+        let (kind, width) = match func {
+            BiosFunction::LZ77UnCompWram       => (Compression::LZ77, 8),
+            BiosFunction::LZ77UnCompVram       => (Compression::LZ77, 16),
+            BiosFunction::HuffUnComp           => (Compression::Huffman((header & 0xF) as u8), 32),
+            BiosFunction::RLUnCompWram         => (Compression::RunLength, 8),
+            BiosFunction::RLUnCompVram         => (Compression::RunLength, 16),
+            BiosFunction::Diff8bitUnFilterWram => (Compression::Diff8, 8),
+            BiosFunction::Diff8bitUnFilterVram => (Compression::Diff8, 16),
+            BiosFunction::Diff16bitUnFilter    => (Compression::Diff16, 16),
+            _ => { error!("{} is no decompression function.", func); return Ok(()); },
+        };
+
+        let data = {
+            let bus = self.bus.borrow();
+            try!(decompress(kind, src, |a| bus.load_byte(a).map(|x| x as u8)))
+        };
+
+        let mut bus = self.bus.borrow_mut();
+        match width {
+            8 => for (i, &x) in data.iter().enumerate() {
+                try!(bus.store_byte(dst.wrapping_add(i as u32), x as i32));
+            },
+            16 => for (i, x) in data.chunks(2).enumerate() {
+                if x.len() < 2 { break; }
+                try!(bus.store_halfword(dst.wrapping_add(i as u32 * 2), (x[0] as i32) | ((x[1] as i32) << 8)));
+            },
+            _ => for (i, x) in data.chunks(4).enumerate() {
+                let w = x.iter().enumerate().fold(0_u32, |w, (k, &b)| w | ((b as u32) << (k * 8)));
+                try!(bus.store_word(dst.wrapping_add(i as u32 * 4), w as i32));
+            },
+        }
+        Ok(())
+    }
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
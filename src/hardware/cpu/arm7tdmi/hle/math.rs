@@ -0,0 +1,167 @@
+// License below.
+//! Implements the arithmetic behind the BIOS math functions.
+//!
+//! All functions are pure and return exactly what the
+//! BIOS leaves in the registers, so that they can be
+//! tested without setting up a whole CPU.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::f64::consts::PI;
+
+
+/// Implements `Div`.
+///
+/// Dividing by zero does not hang the BIOS.
+/// Its division loop simply ends up with a
+/// quotient of `±1`.
+///
+/// # Params
+/// - `num`: The signed numerator.
+/// - `den`: The signed denominator.
+///
+/// # Returns
+/// A tuple `(r0, r1, r3)` of quotient, remainder,
+/// and absolute quotient.
+pub fn bios_div(num: i32, den: i32) -> (i32, i32, i32) {
+    if den == 0 {
+        warn!("BIOS division of {} by zero.", num);
+        return (if num < 0 { -1 } else { 1 }, num, 1);
+    }
+    let quot = num.wrapping_div(den);
+    (quot, num.wrapping_rem(den), if quot < 0 { quot.wrapping_neg() } else { quot })
+}
+
+/// Implements `Sqrt`.
+///
+/// # Params
+/// - `x`: An unsigned 32-bit number.
+///
+/// # Returns
+/// The integer square root, rounded down.
+pub fn bios_sqrt(x: u32) -> u32 {
+    let mut rem = x;
+    let mut root = 0_u32;
+    let mut bit = 1_u32 << 30;
+    while bit > x { bit >>= 2; }
+    while bit != 0 {
+        if rem >= root + bit {
+            rem  -= root + bit;
+            root  = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+/// Implements `ArcTan`.
+///
+/// The BIOS evaluates a polynomial approximation
+/// and leaves intermediate results in R1 and R3.
+///
+/// # Params
+/// - `tan`: The tangent as signed 1.1.14 fixed point number.
+///
+/// # Returns
+/// A tuple `(r0, r1, r3)`, where `r0` is the angle
+/// in the range `-0x4000..0x4000`, i.e. `-PI/2..PI/2`.
+pub fn bios_arctan(tan: i32) -> (i32, i32, i32) {
+    let a = -(tan.wrapping_mul(tan) >> 14);
+    let mut b = (0xA9_i32.wrapping_mul(a) >> 14) + 0x390;
+    for &c in &[0x91C, 0xFB6, 0x16AA, 0x2081, 0x3651, 0xA2F9] {
+        b = (b.wrapping_mul(a) >> 14) + c;
+    }
+    (tan.wrapping_mul(b) >> 16, a, b)
+}
+
+/// Implements `ArcTan2`.
+///
+/// # Params
+/// - `x`: The signed 1.1.14 X coordinate.
+/// - `y`: The signed 1.1.14 Y coordinate.
+///
+/// # Returns
+/// A tuple `(r0, r1)`, where `r0` is the angle in the
+/// range `0x0000..0xFFFF`, i.e. `0..2*PI`. R1 is only
+/// changed if the BIOS had to call `ArcTan`.
+pub fn bios_arctan2(x: i32, y: i32) -> (i32, i32) {
+    // Adds or subtracts the arc tangent of a ratio to a base angle.
+    let atan = |tan: i32, base: i32, sub: bool| -> (i32, i32) {
+        let (r0, r1, _) = bios_arctan(tan);
+        let angle = if sub { base.wrapping_sub(r0) } else { base.wrapping_add(r0) };
+        (angle & 0xFFFF, r1)
+    };
+    let ratio_yx = || y.wrapping_shl(14).wrapping_div(x);
+    let ratio_xy = || x.wrapping_shl(14).wrapping_div(y);
+
+    if y == 0 { return (if x >= 0 { 0x0000 } else { 0x8000 }, y); }
+    if x == 0 { return (if y >= 0 { 0x4000 } else { 0xC000 }, y); }
+
+    if y >= 0 {
+        if      (x >= 0) & ( x >=  y) { atan(ratio_yx(), 0x0000, false) }
+        else if (x <  0) & (-x >=  y) { atan(ratio_yx(), 0x8000, false) }
+        else                          { atan(ratio_xy(), 0x4000, true) }
+    } else {
+        if      (x <= 0) & (-x >  -y) { atan(ratio_yx(), 0x8000, false) }
+        else if (x >  0) & ( x >= -y) { atan(ratio_yx(), 0x10000, false) }
+        else                          { atan(ratio_xy(), 0xC000, true) }
+    }
+}
+
+/// Looks up the BIOS' sine table.
+///
+/// # Params
+/// - `angle`: The angle, where `0x100` is a full circle.
+///
+/// # Returns
+/// The sine as signed 1.1.14 fixed point number.
+pub fn bios_sine(angle: u8) -> i32 {
+    (((angle as f64) * PI / 128.0).sin() * 16384.0).round() as i32
+}
+
+/// Calculates a rotation/scaling matrix like `BgAffineSet` and `ObjAffineSet`.
+///
+/// # Params
+/// - `sx`: The 8.8 fixed point X scaling factor.
+/// - `sy`: The 8.8 fixed point Y scaling factor.
+/// - `theta`: The angle, where `0x10000` is a full circle.
+///   Only the upper 8 bits are used.
+///
+/// # Returns
+/// The 8.8 fixed point matrix entries `[PA, PB, PC, PD]`.
+pub fn bios_affine_matrix(sx: i16, sy: i16, theta: u16) -> [i16; 4] {
+    let angle = (theta >> 8) as u8;
+    let sin = bios_sine(angle);
+    let cos = bios_sine(angle.wrapping_add(0x40));
+    let (sx, sy) = (sx as i32, sy as i32);
+    [
+        ((sx * cos) >> 14) as i16,
+        ((-sx * sin) >> 14) as i16,
+        ((sy * sin) >> 14) as i16,
+        ((sy * cos) >> 14) as i16,
+    ]
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
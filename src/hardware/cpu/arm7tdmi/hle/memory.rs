@@ -0,0 +1,199 @@
+// License below.
+//! Implements BIOS functions that operate on the memory system.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use super::super::Arm7Tdmi;
+use super::math::*;
+use super::super::super::super::error::*;
+
+/// `CpuSet` and `CpuFastSet` fill instead of copy if this is set.
+const SET_FILL_BIT: u32 = 1 << 24;
+
+/// `CpuSet` copies words instead of halfwords if this is set.
+const SET_WORDS_BIT: u32 = 1 << 26;
+
+/// Mask for the transfer count of `CpuSet` and `CpuFastSet`.
+const SET_COUNT_MASK: u32 = 0x001FFFFF;
+
+/// `BitUnPack` also offsets zero data if this is set.
+const UNPACK_ZERO_BIT: u32 = 1 << 31;
+
+impl Arm7Tdmi {
+    /// Executes `CpuSet`, copying or filling halfwords or words.
+    pub fn hle_cpu_set(&mut self) -> Result<(), GbaError> {
+        let src  = self.gpr[0] as u32;
+        let dst  = self.gpr[1] as u32;
+        let ctrl = self.gpr[2] as u32;
+        let count = ctrl & SET_COUNT_MASK;
+        let fill = 0 != (ctrl & SET_FILL_BIT);
+        if Arm7Tdmi::hle_is_bios_source(src) { warn!("CpuSet from BIOS area {:#010X}.", src); return Ok(()); }
+
+        let mut bus = self.bus.borrow_mut();
+        if 0 != (ctrl & SET_WORDS_BIT) {
+            let (src, dst) = (src & !0b11, dst & !0b11);
+            let value = try!(bus.load_word(src));
+            for i in 0..count {
+                let data = if fill { value } else { try!(bus.load_word(src.wrapping_add(i * 4))) };
+                try!(bus.store_word(dst.wrapping_add(i * 4), data));
+            }
+        } else {
+            let (src, dst) = (src & !0b1, dst & !0b1);
+            let value = try!(bus.load_halfword(src));
+            for i in 0..count {
+                let data = if fill { value } else { try!(bus.load_halfword(src.wrapping_add(i * 2))) };
+                try!(bus.store_halfword(dst.wrapping_add(i * 2), data));
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes `CpuFastSet`, copying or filling blocks of 8 words.
+    pub fn hle_cpu_fast_set(&mut self) -> Result<(), GbaError> {
+        let src  = (self.gpr[0] as u32) & !0b11;
+        let dst  = (self.gpr[1] as u32) & !0b11;
+        let ctrl = self.gpr[2] as u32;
+        let count = ((ctrl & SET_COUNT_MASK) + 7) & !7; // Rounded up to blocks of 8 words.
+        let fill = 0 != (ctrl & SET_FILL_BIT);
+        if Arm7Tdmi::hle_is_bios_source(src) { warn!("CpuFastSet from BIOS area {:#010X}.", src); return Ok(()); }
+
+        let mut bus = self.bus.borrow_mut();
+        let value = try!(bus.load_word(src));
+        for i in 0..count {
+            let data = if fill { value } else { try!(bus.load_word(src.wrapping_add(i * 4))) };
+            try!(bus.store_word(dst.wrapping_add(i * 4), data));
+        }
+        Ok(())
+    }
+
+    /// Executes `BgAffineSet` for an array of BG parameters.
+    pub fn hle_bg_affine_set(&mut self) -> Result<(), GbaError> {
+        let mut src = self.gpr[0] as u32;
+        let mut dst = self.gpr[1] as u32;
+        let count   = self.gpr[2] as u32;
+
+        let mut bus = self.bus.borrow_mut();
+        for _ in 0..count {
+            // Source: ox, oy (19.8), cx, cy (16.0), sx, sy (8.8), theta.
+            let ox    = try!(bus.load_word(src));
+            let oy    = try!(bus.load_word(src.wrapping_add(4)));
+            let cx    = try!(bus.load_halfword(src.wrapping_add( 8))) as i16 as i32;
+            let cy    = try!(bus.load_halfword(src.wrapping_add(10))) as i16 as i32;
+            let sx    = try!(bus.load_halfword(src.wrapping_add(12))) as i16;
+            let sy    = try!(bus.load_halfword(src.wrapping_add(14))) as i16;
+            let theta = try!(bus.load_halfword(src.wrapping_add(16))) as u16;
+
+            // Destination: PA, PB, PC, PD (8.8), x, y (19.8).
+            let p = bios_affine_matrix(sx, sy, theta);
+            let (pa, pb, pc, pd) = (p[0] as i32, p[1] as i32, p[2] as i32, p[3] as i32);
+            for (i, &x) in p.iter().enumerate() {
+                try!(bus.store_halfword(dst.wrapping_add(i as u32 * 2), x as i32));
+            }
+            try!(bus.store_word(dst.wrapping_add( 8), ox.wrapping_sub(pa * cx + pb * cy)));
+            try!(bus.store_word(dst.wrapping_add(12), oy.wrapping_sub(pc * cx + pd * cy)));
+
+            src = src.wrapping_add(20);
+            dst = dst.wrapping_add(16);
+        }
+        Ok(())
+    }
+
+    /// Executes `ObjAffineSet` for an array of OBJ parameters.
+    pub fn hle_obj_affine_set(&mut self) -> Result<(), GbaError> {
+        let mut src = self.gpr[0] as u32;
+        let mut dst = self.gpr[1] as u32;
+        let count   = self.gpr[2] as u32;
+        let stride  = self.gpr[3] as u32;
+
+        let mut bus = self.bus.borrow_mut();
+        for _ in 0..count {
+            // Source: sx, sy (8.8), theta, padding.
+            let sx    = try!(bus.load_halfword(src)) as i16;
+            let sy    = try!(bus.load_halfword(src.wrapping_add(2))) as i16;
+            let theta = try!(bus.load_halfword(src.wrapping_add(4))) as u16;
+
+            // Destination: PA, PB, PC, PD, each `stride` bytes apart.
+            for &x in &bios_affine_matrix(sx, sy, theta) {
+                try!(bus.store_halfword(dst, x as i32));
+                dst = dst.wrapping_add(stride);
+            }
+            src = src.wrapping_add(8);
+        }
+        Ok(())
+    }
+
+    /// Executes `BitUnPack`, expanding data to wider units.
+    pub fn hle_bit_unpack(&mut self) -> Result<(), GbaError> {
+        let mut src = self.gpr[0] as u32;
+        let mut dst = self.gpr[1] as u32;
+        let info    = self.gpr[2] as u32;
+
+        let mut bus = self.bus.borrow_mut();
+        let mut src_len = try!(bus.load_halfword(info)) as u32;
+        let src_width   = try!(bus.load_byte(info.wrapping_add(2))) as u32;
+        let dst_width   = try!(bus.load_byte(info.wrapping_add(3))) as u32;
+        let offset      = try!(bus.load_word(info.wrapping_add(4))) as u32;
+
+        let valid_src = match src_width { 1 | 2 | 4 | 8 => true, _ => false };
+        let valid_dst = match dst_width { 1 | 2 | 4 | 8 | 16 | 32 => true, _ => false };
+        if !(valid_src & valid_dst) {
+            warn!("BitUnPack with invalid widths {} -> {}.", src_width, dst_width);
+            return Ok(());
+        }
+
+        let mut input = 0_u32;
+        let mut input_bits = 0;
+        let mut output = 0_u32;
+        let mut output_bits = 0;
+        while (src_len > 0) | (input_bits > 0) {
+            if input_bits == 0 {
+                input = try!(bus.load_byte(src)) as u32;
+                input_bits = 8;
+                src = src.wrapping_add(1);
+                src_len -= 1;
+            }
+
+            let mut unit = input & ((1 << src_width) - 1);
+            input >>= src_width;
+            input_bits -= src_width;
+            if (unit != 0) | (0 != (offset & UNPACK_ZERO_BIT)) {
+                unit = unit.wrapping_add(offset & !UNPACK_ZERO_BIT);
+            }
+
+            output |= unit << output_bits;
+            output_bits += dst_width;
+            if output_bits == 32 {
+                try!(bus.store_word(dst, output as i32));
+                dst = dst.wrapping_add(4);
+                output = 0;
+                output_bits = 0;
+            }
+        }
+
+        self.gpr[0] = src as i32;
+        self.gpr[1] = dst as i32;
+        Ok(())
+    }
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
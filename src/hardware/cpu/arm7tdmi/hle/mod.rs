@@ -0,0 +1,246 @@
+// License below.
+//! Implements high-level emulation of the GBA's BIOS functions.
+//!
+//! Instead of jumping into the BIOS ROM, a `SWI` instruction
+//! can be handled by native functions mimicking the BIOS
+//! routines, including their side effects on the registers.
+//! This is faster and lets games run without a BIOS dump.
+//!
+//! Any BIOS function not listed here falls back to a regular
+//! software interrupt exception, i.e. the BIOS ROM handles it.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::fmt;
+use std::mem;
+use super::{Arm7Tdmi, CpuAction};
+use super::super::super::error::*;
+use super::super::super::ioregs::{Interrupt, LowPowerMode};
+
+pub use self::math::*;
+pub use self::decompress::*;
+
+pub mod math;
+pub mod decompress;
+mod memory;
+
+#[cfg(test)]
+mod test;
+
+
+/// Address of the interrupt flags checked by `IntrWait`.
+///
+/// A game's IRQ handler is expected to set the flags of
+/// all handled interrupts here, just like in `IF`.
+pub const BIOS_INTERRUPT_FLAGS: u32 = 0x03007FF8;
+
+
+/// All functions offered by the GBA BIOS via `SWI`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[repr(u8)]
+pub enum BiosFunction {
+    #[doc = "Clears some IWRAM and restarts the game."]        SoftReset = 0x00,
+    #[doc = "Clears selected memory areas and IO registers."]  RegisterRamReset,
+    #[doc = "Waits in low-power mode for any interrupt."]      Halt,
+    #[doc = "Stops the system until a wake-up interrupt."]     Stop,
+    #[doc = "Waits for selected interrupts."]                  IntrWait,
+    #[doc = "Waits for the next V-Blank interrupt."]           VBlankIntrWait,
+    #[doc = "Signed division `r0/r1`."]                        Div,
+    #[doc = "Signed division `r1/r0`."]                        DivArm,
+    #[doc = "Unsigned integer square root."]                   Sqrt,
+    #[doc = "Arc tangent of a 1.1.14 tangent."]                ArcTan,
+    #[doc = "Arc tangent of a point `(x, y)`."]                ArcTan2,
+    #[doc = "Copies or fills memory in halfwords or words."]   CpuSet,
+    #[doc = "Copies or fills memory in blocks of 8 words."]    CpuFastSet,
+    #[doc = "Calculates the BIOS checksum."]                   GetBiosChecksum,
+    #[doc = "Calculates BG rotation/scaling parameters."]      BgAffineSet,
+    #[doc = "Calculates OBJ rotation/scaling parameters."]     ObjAffineSet,
+    #[doc = "Expands bit-packed data."]                        BitUnPack,
+    #[doc = "LZ77 decompression using 8-bit writes."]          LZ77UnCompWram,
+    #[doc = "LZ77 decompression using 16-bit writes."]         LZ77UnCompVram,
+    #[doc = "Huffman decompression."]                          HuffUnComp,
+    #[doc = "Run-length decompression using 8-bit writes."]    RLUnCompWram,
+    #[doc = "Run-length decompression using 16-bit writes."]   RLUnCompVram,
+    #[doc = "8-bit delta unfilter using 8-bit writes."]        Diff8bitUnFilterWram,
+    #[doc = "8-bit delta unfilter using 16-bit writes."]       Diff8bitUnFilterVram,
+    #[doc = "16-bit delta unfilter."]                          Diff16bitUnFilter,
+    #[doc = "Ramps the sound bias level."]                     SoundBias,
+    #[doc = "Initialises the sound driver."]                   SoundDriverInit,
+    #[doc = "Configures the sound driver."]                    SoundDriverMode,
+    #[doc = "Runs the sound driver's main routine."]           SoundDriverMain,
+    #[doc = "Resets sound DMA on V-Blank."]                    SoundDriverVSync,
+    #[doc = "Clears all direct sound channels."]               SoundChannelClear,
+    #[doc = "Converts a MIDI key to a frequency."]             MidiKey2Freq,
+    #[doc = "Undocumented music player function."]            MusicPlayerOpen,
+    #[doc = "Undocumented music player function."]            MusicPlayerStart,
+    #[doc = "Undocumented music player function."]            MusicPlayerStop,
+    #[doc = "Undocumented music player function."]            MusicPlayerContinue,
+    #[doc = "Undocumented music player function."]            MusicPlayerFadeOut,
+    #[doc = "Sends a multiboot image to slave GBAs."]          MultiBoot,
+    #[doc = "Restarts the GBA including the boot logo."]       HardReset,
+    #[doc = "Writes any value to HALTCNT."]                    CustomHalt,
+    #[doc = "Stops sound DMA on V-Blank."]                     SoundDriverVSyncOff,
+    #[doc = "Restarts sound DMA on V-Blank."]                  SoundDriverVSyncOn,
+    #[doc = "Get the sound driver's jump table."]              SoundGetJumpList,
+}
+
+impl BiosFunction {
+    /// Decodes a BIOS function number.
+    ///
+    /// # Params
+    /// - `num`: The BIOS function number.
+    ///
+    /// # Returns
+    /// - `Some`: The BIOS function.
+    /// - `None`: The BIOS does not offer such a function.
+    pub fn decode(num: u8) -> Option<BiosFunction> {
+        if num <= (BiosFunction::SoundGetJumpList as u8) { Some(unsafe { mem::transmute(num) }) }
+        else { None }
+    }
+
+    /// Extracts the BIOS function number of an ARM state `SWI`.
+    ///
+    /// The BIOS only looks at bits 16..23 of the comment
+    /// field of an ARM state `SWI` instruction.
+    ///
+    /// # Params
+    /// - `comment`: The 24-bit comment field.
+    pub fn number_from_arm_comment(comment: u32) -> u8 { (comment >> 16) as u8 }
+}
+
+impl fmt::Display for BiosFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} ({:#04X})", self, *self as u8)
+    }
+}
+
+
+impl Arm7Tdmi {
+    /// BIOS checksum of the GBA BIOS as returned by `GetBiosChecksum`.
+    pub const BIOS_CHECKSUM: u32 = 0xBAAE187F;
+
+    /// Executes a BIOS function natively.
+    ///
+    /// # Params
+    /// - `func`: The BIOS function to execute.
+    ///
+    /// # Returns
+    /// - `Ok(Some)`: The function has been executed. The pipeline
+    ///   should continue as given.
+    /// - `Ok(None)`: There is no high-level emulation of this function.
+    ///   Execute the BIOS routine instead.
+    /// - `Err`: Some memory access failed.
+    pub fn execute_hle_swi(&mut self, func: BiosFunction) -> Result<Option<CpuAction>, GbaError> {
+        match func {
+            BiosFunction::Halt            => { self.low_power = Some(LowPowerMode::Halt); },
+            BiosFunction::Stop            => { self.low_power = Some(LowPowerMode::Stop); },
+            BiosFunction::CustomHalt      => { self.low_power = Some(Arm7Tdmi::hle_low_power_mode(self.gpr[2])); },
+            BiosFunction::IntrWait        => {
+                let (discard, wanted) = (self.gpr[0] != 0, self.gpr[1] as u16);
+                return self.hle_intr_wait(discard, wanted).map(Some);
+            },
+            BiosFunction::VBlankIntrWait  => {
+                self.gpr[0] = 1;
+                self.gpr[1] = Interrupt::VBlank.mask() as i32;
+                return self.hle_intr_wait(true, Interrupt::VBlank.mask()).map(Some);
+            },
+            BiosFunction::Div             => { self.hle_div(0, 1); },
+            BiosFunction::DivArm          => { self.hle_div(1, 0); },
+            BiosFunction::Sqrt            => { self.gpr[0] = bios_sqrt(self.gpr[0] as u32) as i32; },
+            BiosFunction::ArcTan          => { self.hle_arctan(); },
+            BiosFunction::ArcTan2         => { self.hle_arctan2(); },
+            BiosFunction::CpuSet          => { try!(self.hle_cpu_set()); },
+            BiosFunction::CpuFastSet      => { try!(self.hle_cpu_fast_set()); },
+            BiosFunction::GetBiosChecksum => { self.hle_get_bios_checksum(); },
+            BiosFunction::BgAffineSet     => { try!(self.hle_bg_affine_set()); },
+            BiosFunction::ObjAffineSet    => { try!(self.hle_obj_affine_set()); },
+            BiosFunction::BitUnPack       => { try!(self.hle_bit_unpack()); },
+            BiosFunction::LZ77UnCompWram       | BiosFunction::LZ77UnCompVram       |
+            BiosFunction::HuffUnComp           | BiosFunction::RLUnCompWram         |
+            BiosFunction::RLUnCompVram         | BiosFunction::Diff8bitUnFilterWram |
+            BiosFunction::Diff8bitUnFilterVram | BiosFunction::Diff16bitUnFilter    => {
+                try!(self.hle_decompress(func));
+            },
+            _ => { return Ok(None); },
+        }
+        Ok(Some(CpuAction::None))
+    }
+
+    fn hle_low_power_mode(haltcnt: i32) -> LowPowerMode {
+        if 0 == (haltcnt & 0x80) { LowPowerMode::Halt } else { LowPowerMode::Stop }
+    }
+
+    /// Implements `IntrWait` by halting and then executing
+    /// the `SWI` again after each interrupt, until any of
+    /// the wanted BIOS interrupt flags is set.
+    fn hle_intr_wait(&mut self, discard: bool, wanted: u16) -> Result<CpuAction, GbaError> {
+        let mut bus = self.bus.borrow_mut();
+        bus.ioregs_mut().set_interrupt_master_enable(true);
+
+        let mut flags = try!(bus.load_halfword(BIOS_INTERRUPT_FLAGS)) as u16;
+        if discard & !self.intr_waiting { flags &= !wanted; }
+        if 0 != (flags & wanted) {
+            try!(bus.store_halfword(BIOS_INTERRUPT_FLAGS, (flags & !wanted) as i32));
+            self.intr_waiting = false;
+            return Ok(CpuAction::None);
+        }
+        try!(bus.store_halfword(BIOS_INTERRUPT_FLAGS, flags as i32));
+
+        self.intr_waiting = true;
+        self.low_power = Some(LowPowerMode::Halt);
+        self.gpr[Arm7Tdmi::PC] = self.executing_address() as i32;
+        Ok(CpuAction::FlushPipeline)
+    }
+
+    /// The BIOS refuses to read itself when copying or decompressing data.
+    fn hle_is_bios_source(src: u32) -> bool { 0 == (src & 0x0E000000) }
+
+    fn hle_div(&mut self, num: usize, den: usize) {
+        let (quot, rem, abs) = bios_div(self.gpr[num], self.gpr[den]);
+        self.gpr[0] = quot;
+        self.gpr[1] = rem;
+        self.gpr[3] = abs;
+    }
+
+    fn hle_arctan(&mut self) {
+        let (r0, r1, r3) = bios_arctan(self.gpr[0]);
+        self.gpr[0] = r0;
+        self.gpr[1] = r1;
+        self.gpr[3] = r3;
+    }
+
+    fn hle_arctan2(&mut self) {
+        let (r0, r1) = bios_arctan2(self.gpr[0], self.gpr[1]);
+        self.gpr[0] = r0;
+        self.gpr[1] = r1;
+        self.gpr[3] = 0x170;
+    }
+
+    fn hle_get_bios_checksum(&mut self) {
+        self.gpr[0] = Arm7Tdmi::BIOS_CHECKSUM as i32;
+        self.gpr[1] = 1;
+        self.gpr[3] = 0x4000;
+    }
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
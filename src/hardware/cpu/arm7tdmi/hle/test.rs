@@ -0,0 +1,173 @@
+// License below.
+#![allow(missing_docs)]
+
+use super::*;
+use super::super::super::super::error::GbaError;
+use super::super::super::super::memory::BiosRom;
+use super::super::super::super::ioregs::{Interrupt, LowPowerMode};
+use super::super::super::super::test_util;
+
+fn new_cpu() -> Arm7Tdmi { test_util::new_cpu(BiosRom::new()).0 }
+
+#[test]
+pub fn bios_function_decoding() {
+    assert_eq!(BiosFunction::decode(0x00), Some(BiosFunction::SoftReset));
+    assert_eq!(BiosFunction::decode(0x0B), Some(BiosFunction::CpuSet));
+    assert_eq!(BiosFunction::decode(0x2A), Some(BiosFunction::SoundGetJumpList));
+    assert_eq!(BiosFunction::decode(0x2B), None);
+    assert_eq!(BiosFunction::number_from_arm_comment(0x060000), 0x06);
+}
+
+#[test]
+pub fn bios_math() {
+    assert_eq!(bios_div(7, -2), (-3, 1, 3));
+    assert_eq!(bios_div(-7, 0), (-1, -7, 1));
+    assert_eq!(bios_div(i32::min_value(), -1), (i32::min_value(), 0, i32::min_value()));
+
+    assert_eq!(bios_sqrt(0), 0);
+    assert_eq!(bios_sqrt(15), 3);
+    assert_eq!(bios_sqrt(16), 4);
+    assert_eq!(bios_sqrt(0xFFFFFFFF), 0xFFFF);
+
+    assert_eq!(bios_arctan(0), (0, 0, 0xA2F9));
+    let (atan, _, _) = bios_arctan(0x4000);
+    assert!((atan - 0x2000).abs() < 0x10);
+
+    assert_eq!(bios_arctan2(0x4000, 0), (0x0000, 0));
+    assert_eq!(bios_arctan2(-0x4000, 0), (0x8000, 0));
+    assert_eq!(bios_arctan2(0, 0x4000).0, 0x4000);
+    assert_eq!(bios_arctan2(0, -0x4000).0, 0xC000);
+    for &(x, y, angle) in &[(0x4000, 0x4000, 0x2000), (-0x4000, 0x4000, 0x6000),
+                            (-0x4000, -0x4000, 0xA000), (0x4000, -0x4000, 0xE000)] {
+        assert!((bios_arctan2(x, y).0 - angle).abs() < 0x10);
+    }
+
+    assert_eq!(bios_sine(0x40), 0x4000);
+    assert_eq!(bios_affine_matrix(0x100, 0x100, 0x0000), [0x100, 0, 0, 0x100]);
+    assert_eq!(bios_affine_matrix(0x100, 0x200, 0x4000), [0, -0x100, 0x200, 0]);
+}
+
+#[test]
+pub fn bios_memory_functions() {
+    let mut cpu = new_cpu();
+    try_store_word(&cpu, 0x03000000, 0x12345678);
+    try_store_word(&cpu, 0x03000004, 0x0000ABCD);
+
+    // CpuSet: Copy two halfwords, then fill 3 words.
+    cpu.gpr[0] = 0x03000000; cpu.gpr[1] = 0x02000000; cpu.gpr[2] = 2;
+    assert_eq!(cpu.execute_hle_swi(BiosFunction::CpuSet), Ok(Some(CpuAction::None)));
+    assert_eq!(cpu.bus.borrow().load_word(0x02000000), Ok(0x12345678));
+    cpu.gpr[0] = 0x03000004; cpu.gpr[1] = 0x02000010; cpu.gpr[2] = (1 << 24) | (1 << 26) | 3;
+    assert_eq!(cpu.execute_hle_swi(BiosFunction::CpuSet), Ok(Some(CpuAction::None)));
+    assert_eq!(cpu.bus.borrow().load_word(0x02000018), Ok(0x0000ABCD));
+    assert_eq!(cpu.bus.borrow().load_word(0x0200001C), Ok(0));
+
+    // CpuFastSet: Rounds up to 8 words.
+    cpu.gpr[0] = 0x03000004; cpu.gpr[1] = 0x02000100; cpu.gpr[2] = (1 << 24) | 1;
+    assert_eq!(cpu.execute_hle_swi(BiosFunction::CpuFastSet), Ok(Some(CpuAction::None)));
+    assert_eq!(cpu.bus.borrow().load_word(0x0200011C), Ok(0x0000ABCD));
+    assert_eq!(cpu.bus.borrow().load_word(0x02000120), Ok(0));
+
+    // BitUnPack: 1 byte of 1bpp into 4bpp, offsetting non-zero units by 1.
+    try_store_word(&cpu, 0x03000010, 0x000000A5);
+    try_store_word(&cpu, 0x03000020, 0x04010001);
+    try_store_word(&cpu, 0x03000024, 0x00000001);
+    cpu.gpr[0] = 0x03000010; cpu.gpr[1] = 0x02000200; cpu.gpr[2] = 0x03000020;
+    assert_eq!(cpu.execute_hle_swi(BiosFunction::BitUnPack), Ok(Some(CpuAction::None)));
+    assert_eq!(cpu.bus.borrow().load_word(0x02000200), Ok(0x20200202));
+    assert_eq!((cpu.gpr[0], cpu.gpr[1]), (0x03000011, 0x02000204));
+
+    cpu.gpr[3] = 0;
+    assert_eq!(cpu.execute_hle_swi(BiosFunction::GetBiosChecksum), Ok(Some(CpuAction::None)));
+    assert_eq!((cpu.gpr[0] as u32, cpu.gpr[3]), (Arm7Tdmi::BIOS_CHECKSUM, 0x4000));
+    assert_eq!(cpu.execute_hle_swi(BiosFunction::SoftReset), Ok(None));
+}
+
+#[test]
+pub fn bios_decompression() {
+    fn unpack(data: &[u8]) -> Vec<u8> {
+        let kind = Compression::from_header(data[0] as u32).unwrap();
+        decompress(kind, 0, |a| data.get(a as usize).cloned().ok_or(GbaError::InvalidPhysicalAddress(a))).unwrap()
+    }
+    let lz77 = [0x10, 0x08, 0x00, 0x00, 0x20, 0x41, 0x42, 0x30, 0x01];
+    let huff = [0x28, 0x03, 0x00, 0x00, 0x01, 0xC0, 0x41, 0x42, 0x00, 0x00, 0x00, 0x40];
+    let rl   = [0x30, 0x05, 0x00, 0x00, 0x81, 0x41, 0x00, 0x42];
+    assert_eq!(unpack(&lz77), b"ABABABAB");
+    assert_eq!(unpack(&huff), b"ABA");
+    assert_eq!(unpack(&rl), b"AAAAB");
+    assert_eq!(unpack(&[0x81, 0x04, 0x00, 0x00, 1, 1, 1, 1]), [1, 2, 3, 4]);
+    assert_eq!(unpack(&[0x82, 0x04, 0x00, 0x00, 0x00, 0x01, 0x01, 0x00]), [0x00, 0x01, 0x01, 0x01]);
+    assert_eq!(Compression::from_header(0x00000840), None);
+    assert!(decompress(Compression::LZ77, 0, |a| lz77[..6].get(a as usize).cloned()
+                       .ok_or(GbaError::InvalidPhysicalAddress(a))).is_err());
+
+    // The VRAM variant only writes complete halfwords.
+    let mut cpu = new_cpu();
+    for (i, &x) in rl.iter().enumerate() { cpu.bus.borrow_mut().store_byte(0x03000000 + i as u32, x as i32).unwrap(); }
+    try_store_word(&cpu, 0x02000004, 0x7F7F7F7F);
+    cpu.gpr[0] = 0x03000000; cpu.gpr[1] = 0x02000000;
+    assert_eq!(cpu.execute_hle_swi(BiosFunction::RLUnCompVram), Ok(Some(CpuAction::None)));
+    assert_eq!(cpu.bus.borrow().load_word(0x02000000), Ok(0x41414141));
+    assert_eq!(cpu.bus.borrow().load_word(0x02000004), Ok(0x7F7F7F7F));
+    assert_eq!(cpu.execute_hle_swi(BiosFunction::RLUnCompWram), Ok(Some(CpuAction::None)));
+    assert_eq!(cpu.bus.borrow().load_word(0x02000004), Ok(0x7F7F7F42));
+}
+
+#[test]
+pub fn bios_wait_functions() {
+    let mut cpu = new_cpu();
+    cpu.boot_post_bios(0x08000000);
+    try_store_word(&cpu, 0x04000200, 0x0001); // IE: V-Blank.
+
+    // Halting sleeps until an enabled interrupt is requested.
+    assert_eq!(cpu.execute_hle_swi(BiosFunction::Halt), Ok(Some(CpuAction::None)));
+    assert_eq!(cpu.low_power_mode(), Some(LowPowerMode::Halt));
+    cpu.bus.borrow_mut().ioregs_mut().request_interrupt(Interrupt::Timer0);
+    cpu.pipeline_step().unwrap();
+    assert!(cpu.is_sleeping());
+    cpu.bus.borrow_mut().ioregs_mut().request_interrupt(Interrupt::VBlank);
+    cpu.pipeline_step().unwrap();
+    assert!(!cpu.is_sleeping());
+
+    // IF is write-1-to-clear and HALTCNT requests a low-power mode.
+    cpu.bus.borrow_mut().store_halfword(0x04000202, 0x0001).unwrap();
+    assert_eq!(cpu.bus.borrow().ioregs().pending_interrupts(), 0);
+    cpu.bus.borrow_mut().store_byte(0x04000301, 0x80).unwrap();
+    assert_eq!(cpu.bus.borrow_mut().ioregs_mut().take_low_power_request(), Some(LowPowerMode::Stop));
+
+    // VBlankIntrWait discards old flags, then waits for new ones.
+    try_store_word(&cpu, BIOS_INTERRUPT_FLAGS, 0x0001);
+    assert_eq!(cpu.execute_hle_swi(BiosFunction::VBlankIntrWait), Ok(Some(CpuAction::FlushPipeline)));
+    assert!(cpu.is_sleeping());
+    assert!(cpu.bus.borrow().ioregs().interrupt_master_enable());
+    assert_eq!(cpu.bus.borrow().load_halfword(BIOS_INTERRUPT_FLAGS), Ok(0x0000));
+    assert_eq!(cpu.execute_hle_swi(BiosFunction::VBlankIntrWait), Ok(Some(CpuAction::FlushPipeline)));
+    try_store_word(&cpu, BIOS_INTERRUPT_FLAGS, 0x0003);
+    assert_eq!(cpu.execute_hle_swi(BiosFunction::VBlankIntrWait), Ok(Some(CpuAction::None)));
+    assert_eq!(cpu.bus.borrow().load_halfword(BIOS_INTERRUPT_FLAGS), Ok(0x0002));
+}
+
+fn try_store_word(cpu: &Arm7Tdmi, addr: u32, data: i32) {
+    cpu.bus.borrow_mut().store_word(addr, data).unwrap();
+}
+
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -11,21 +11,31 @@ use super::arminstruction::ArmInstruction;
 use super::thumbinstruction::ThumbInstruction;
 use super::super::bus::*;
 use super::super::error::*;
+use super::super::ioregs::{LowPowerMode, STOP_WAKE_UP_INTERRUPTS};
 
 pub use self::exception::*;
 pub use self::psr::*;
 pub use self::exec::*;
 pub use self::diff::*;
+pub use self::hle::*;
 pub use self::display::*;
+pub use self::callstack::*;
 
 pub mod exception;
 pub mod psr;
 pub mod exec;
 pub mod diff;
+pub mod hle;
+pub mod callstack;
 
 mod display;
+mod state;
+
+#[cfg(test)]
+mod test;
 
 /// Decides what the CPU should do after executing an instruction.
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum CpuAction {
     #[doc = "Continue execution normally."]                         None,
     #[doc = "PC has changed, don't modify it, flush the pipeline."] FlushPipeline,
@@ -43,6 +53,7 @@ pub struct Arm7Tdmi {
     fetched_arm: u32,
     decoded_thumb: ThumbInstruction,
     fetched_thumb: u16,
+    pipeline_refill: u8,
 
     // Register backups for mode changes.
     gpr_r8_r12_fiq: [i32; 5],
@@ -58,6 +69,13 @@ pub struct Arm7Tdmi {
     optimise_swi: bool,
     delay_cycles: u8,
 
+    // Low-power state.
+    low_power: Option<LowPowerMode>,
+    intr_waiting: bool,
+
+    // Debugging.
+    call_stack: CallStack,
+
     // Connected devices.
     bus: Rc<RefCell<Bus>>,
 }
@@ -82,6 +100,15 @@ impl Arm7Tdmi {
     /// due to pipelining and other things.
     pub const PC: usize = 15;
 
+    /// User and system mode stack pointer after booting.
+    pub const SP_USER_POST_BIOS: u32 = 0x03007F00;
+
+    /// IRQ mode stack pointer after booting.
+    pub const SP_IRQ_POST_BIOS: u32 = 0x03007FA0;
+
+    /// Supervisor mode stack pointer after booting.
+    pub const SP_SUPERVISOR_POST_BIOS: u32 = 0x03007FE0;
+
     /// Creates a new CPU where all registers are zeroed.
     pub fn new(bus: Rc<RefCell<Bus>>) -> Arm7Tdmi {
         Arm7Tdmi {
@@ -93,6 +120,7 @@ impl Arm7Tdmi {
             fetched_arm: ArmInstruction::NOP_RAW,
             decoded_thumb: ThumbInstruction::nop(),
             fetched_thumb: ThumbInstruction::NOP_RAW,
+            pipeline_refill: 0,
 
             gpr_r8_r12_fiq: [0; 5],
             gpr_r8_r12_other: [0; 5],
@@ -106,10 +134,104 @@ impl Arm7Tdmi {
             optimise_swi: false,
             delay_cycles: 0,
 
+            low_power: None,
+            intr_waiting: false,
+
+            call_stack: CallStack::new(),
+
             bus: bus,
         }
     }
 
+    /// Get the current value of a GPR of the current mode.
+    pub fn register(&self, i: usize) -> i32 { self.gpr[i] }
+
+    /// Get the current CPSR.
+    pub fn cpsr(&self) -> PSR { self.cpsr }
+
+    /// Get the current execution state, i.e. ARM or THUMB.
+    pub fn state(&self) -> State { self.state }
+
+    /// Get the current CPU mode.
+    pub fn mode(&self) -> Mode { self.mode }
+
+    /// Get the shadow call stack tracking calls and exceptions.
+    pub fn call_stack(&self) -> &CallStack { &self.call_stack }
+
+    /// Get the address of the instruction executed next.
+    ///
+    /// Due to pipelining, this is PC minus two instructions,
+    /// or less while the pipeline is still being refilled.
+    pub fn executing_address(&self) -> u32 {
+        let pc = self.gpr[Arm7Tdmi::PC] as u32;
+        let width = if self.state == State::ARM { 4 } else { 2 };
+        pc.wrapping_sub(width * (2 - self.pipeline_refill as u32))
+    }
+
+    /// Overrides a GPR of the current mode, e.g. for debuggers.
+    ///
+    /// Writing PC flushes the pipeline, such that the CPU
+    /// continues executing at the new address.
+    pub fn set_register(&mut self, i: usize, x: i32) {
+        if i == Arm7Tdmi::PC {
+            let mask = if self.state == State::ARM { !0b11 } else { !0b01 };
+            self.gpr[i] = x & mask;
+            self.flush_pipeline();
+        }
+        else { self.gpr[i] = x; }
+    }
+
+    /// Overrides CPSR, e.g. for debuggers.
+    ///
+    /// Changing the mode swaps the banked registers. The T bit
+    /// cannot be changed, as THUMB code is not executed yet.
+    ///
+    /// # Returns
+    /// `false` without changing anything if the mode bits are
+    /// invalid or the T bit would change.
+    pub fn set_cpsr(&mut self, x: u32) -> bool {
+        let mut psr = PSR(x);
+        psr.clear_reserved_bits();
+        let new_mode = match psr.try_mode() { Some(m) => m, None => return false };
+        if psr.state() != self.state { return false; }
+        self.change_mode(new_mode);
+        self.cpsr = psr;
+        true
+    }
+
+    /// Checks whether the next pipeline step executes the
+    /// instruction at `executing_address`.
+    ///
+    /// This is not the case while sleeping, stalling, or
+    /// executing the pseudo NOPs of a pipeline refill.
+    pub fn is_about_to_execute(&self) -> bool {
+        (self.delay_cycles == 0) & (self.pipeline_refill == 0) & self.low_power.is_none()
+    }
+
+    /// Get the low-power mode the CPU is sleeping in, if any.
+    pub fn low_power_mode(&self) -> Option<LowPowerMode> { self.low_power }
+
+    /// Checks whether the CPU sleeps in a low-power mode.
+    ///
+    /// A sleeping CPU does nothing until an interrupt wakes
+    /// it up. While halted, each pipeline step skips all idle
+    /// cycles until the LCD's next H-Blank or scanline.
+    pub fn is_sleeping(&self) -> bool { self.low_power.is_some() }
+
+    /// Checks whether the CPU sleeps without any emulated
+    /// interrupt source being able to wake it up.
+    ///
+    /// Only key presses or the debugger can wake such a CPU,
+    /// so stepping it any further just wastes time. This is
+    /// always the case in `Stop` mode, as the LCD stops too.
+    pub fn sleeps_until_input(&self) -> bool {
+        match self.low_power {
+            Some(LowPowerMode::Halt) => 0 == self.bus.borrow().ioregs().scheduled_interrupts(),
+            Some(LowPowerMode::Stop) => true,
+            None => false,
+        }
+    }
+
     /// Checks whether optimising BIOS functions is enabled.
     pub fn is_swi_optimised(&self) -> bool { self.optimise_swi }
 
@@ -117,7 +239,8 @@ impl Arm7Tdmi {
     ///
     /// If `true`, a `SWI` instruction causes optimised functions
     /// to be called instead of emulating the BIOS routines in
-    /// the BIOS ROM area.
+    /// the BIOS ROM area. BIOS functions without an optimised
+    /// version are still executed by the BIOS ROM.
     pub fn set_swi_optimised(&mut self, optimise: bool) { self.optimise_swi = optimise; }
 
     /// Resets the CPU.
@@ -134,31 +257,143 @@ impl Arm7Tdmi {
         self.state = State::ARM;
         self.irq_disable = true;
         self.fiq_disable = true;
+
+        self.low_power = None;
+        self.intr_waiting = false;
+        self.call_stack.clear();
+        self.flush_pipeline();
+    }
+
+    /// Sets up the register state the BIOS leaves behind after booting.
+    ///
+    /// All GPRs are zeroed, the stack pointers of the user,
+    /// IRQ, and supervisor modes are set up, and the CPU
+    /// continues in system mode with interrupts enabled.
+    ///
+    /// # Params
+    /// - `entry`: The address to start executing ARM code at.
+    pub fn boot_post_bios(&mut self, entry: u32) {
+        self.gpr = [0; 16];
+        self.gpr_r8_r12_fiq   = [0; 5];
+        self.gpr_r8_r12_other = [0; 5];
+        self.gpr_r13_all      = [0; 7];
+        self.gpr_r14_all      = [0; 7];
+        self.gpr_r13_all[Mode::User       as u8 as usize] = Arm7Tdmi::SP_USER_POST_BIOS as i32;
+        self.gpr_r13_all[Mode::System     as u8 as usize] = Arm7Tdmi::SP_USER_POST_BIOS as i32;
+        self.gpr_r13_all[Mode::IRQ        as u8 as usize] = Arm7Tdmi::SP_IRQ_POST_BIOS as i32;
+        self.gpr_r13_all[Mode::Supervisor as u8 as usize] = Arm7Tdmi::SP_SUPERVISOR_POST_BIOS as i32;
+        self.spsr = [PSR::default(); 7];
+
+        self.cpsr = PSR::default();
+        self.cpsr.set_mode(Mode::System);
+        self.cpsr.set_state(State::ARM);
+        self.cpsr.enable_irq();
+        self.cpsr.enable_fiq();
+        self.mode = Mode::System;
+        self.state = State::ARM;
+        self.irq_disable = false;
+        self.fiq_disable = false;
+        self.delay_cycles = 0;
+        self.low_power = None;
+        self.intr_waiting = false;
+        self.call_stack.clear();
+
+        self.gpr[Arm7Tdmi::SP] = Arm7Tdmi::SP_USER_POST_BIOS as i32;
+        self.gpr[Arm7Tdmi::PC] = entry as i32;
+        self.flush_pipeline();
     }
 
     /// Causes an exception, switching execution modes and states.
+    ///
+    /// LR is set up such that handlers return via `movs pc, lr`
+    /// after SWIs and undefined instructions, `subs pc, lr, #4`
+    /// after interrupts and prefetch aborts, and `subs pc, lr, #8`
+    /// after data aborts. The caller has to flush the pipeline.
     pub fn exception(&mut self, ex: Exception) {
-        self.change_mode(ex.mode_on_entry()); // Also sets LR.
+        // Interrupts and aborts return to the interrupted instruction, others to the next one.
+        let call_site = self.executing_address();
+        let width = if self.state == State::ARM { 4 } else { 2 };
+        let (resume, ret_addr) = match ex {
+            Exception::NormalInterrupt | Exception::FastInterrupt |
+            Exception::PrefetchAbort => (call_site, call_site.wrapping_add(4)),
+            Exception::DataAbort     => (call_site, call_site.wrapping_add(8)),
+            _ => (call_site.wrapping_add(width), call_site.wrapping_add(width)),
+        };
+        self.call_stack.push(CallFrame {
+            call_site: call_site,
+            target: ex.vector_address(),
+            return_addr: resume,
+            mode: ex.mode_on_entry(),
+            exception: Some(ex),
+        });
+
+        let old_cpsr = self.cpsr;
+        self.change_mode(ex.mode_on_entry());
+        self.spsr[self.mode as u8 as usize] = old_cpsr;
+        self.gpr[Arm7Tdmi::LR] = ret_addr as i32;
         self.cpsr.set_state(State::ARM);
         self.state = State::ARM;
         self.cpsr.disable_irq();
         if ex.disable_fiq_on_entry() { self.cpsr.disable_fiq(); }
-        // TODO LR = PC + whatevs
         self.gpr[Arm7Tdmi::PC] = ex.vector_address() as i32;
     }
 
+    /// Enters an IRQ exception.
+    ///
+    /// Returning via `subs pc, lr, #4` continues with
+    /// the instruction that has been interrupted.
+    fn interrupt(&mut self) {
+        self.exception(Exception::NormalInterrupt);
+        self.flush_pipeline();
+    }
+
+    /// Wakes the CPU up and enters pending IRQs.
+    ///
+    /// IRQs are only entered once the pipeline is
+    /// filled with actual instructions, so that the
+    /// return address is known.
+    ///
+    /// # Returns
+    /// `false` if the CPU keeps sleeping.
+    fn poll_interrupts(&mut self) -> bool {
+        let (pending, master_enable) = {
+            let bus = self.bus.borrow();
+            (bus.ioregs().pending_interrupts(), bus.ioregs().interrupt_master_enable())
+        };
+
+        match self.low_power {
+            Some(LowPowerMode::Halt) if pending == 0 => { return false; },
+            Some(LowPowerMode::Stop) if (pending & STOP_WAKE_UP_INTERRUPTS) == 0 => { return false; },
+            Some(m) => { debug!("Waking up from {:?} mode.", m); self.low_power = None; },
+            None => {},
+        }
+
+        if master_enable & (pending != 0) & !self.cpsr.irq_disabled() & (self.pipeline_refill == 0) {
+            self.interrupt();
+        }
+        true
+    }
+
+    /// Returns from an exception by restoring CPSR from SPSR.
+    fn restore_cpsr(&mut self) {
+        let spsr = self.spsr[self.mode as u8 as usize];
+        self.change_mode(spsr.mode());
+        self.cpsr  = spsr;
+        self.state = spsr.state();
+    }
+
+    /// Switches the register banks to another mode.
     fn change_mode(&mut self, new_mode: Mode) {
-        let current_mi = self.mode as u8 as usize;
-        let next_mi    =  new_mode as u8 as usize;
+        // User and System mode share all registers.
+        let bank = |m: Mode| if m == Mode::System { Mode::User as u8 as usize } else { m as u8 as usize };
+        let current_mi = bank(self.mode);
+        let next_mi    = bank(new_mode);
 
-        // Save banked registers R13, R14, SPSR.
-        let ret_addr = self.gpr[Arm7Tdmi::PC]; // FIXME TODO add special offset by exception type
+        // Swap banked registers R13, R14.
         self.gpr_r14_all[current_mi] = self.gpr[14];
-        self.gpr_r14_all[next_mi]    = ret_addr;
-        self.gpr[14]                 = ret_addr;
         self.gpr_r13_all[current_mi] = self.gpr[13];
+        self.gpr[14]                 = self.gpr_r14_all[next_mi];
         self.gpr[13]                 = self.gpr_r13_all[next_mi];
-        self.spsr[next_mi]           = self.cpsr;
 
         // Now the banked registers R8..R12.
         if (new_mode == Mode::FIQ) ^ (self.mode == Mode::FIQ) {
@@ -177,11 +412,28 @@ impl Arm7Tdmi {
         self.mode = new_mode;
     }
 
+    /// Updates the call stack after taking a branch.
+    fn track_branch(&mut self, addr: u32, branch: Branch) {
+        let target = (self.gpr[Arm7Tdmi::PC] as u32) & !1;
+        match branch {
+            Branch::Call(ret) => self.call_stack.push(CallFrame {
+                call_site: addr,
+                target: target,
+                return_addr: ret,
+                mode: self.mode,
+                exception: None,
+            }),
+            Branch::Return => { self.call_stack.ret(target); },
+            Branch::None => {},
+        }
+    }
+
     fn flush_pipeline(&mut self) {
         self.decoded_arm   =   ArmInstruction::nop();
         self.fetched_arm   =   ArmInstruction::NOP_RAW;
         self.decoded_thumb = ThumbInstruction::nop();
         self.fetched_thumb = ThumbInstruction::NOP_RAW;
+        self.pipeline_refill = 2;
     }
 
     #[inline]
@@ -202,15 +454,33 @@ impl Arm7Tdmi {
     /// fills all pipeline stages with pseudo NOP
     /// instructions, i.e. instructions without any (side)
     /// effects.
+    ///
+    /// Each pipeline step takes one cycle of LCD timing.
+    /// While sleeping in a low-power mode, a pipeline step
+    /// just checks whether an interrupt wakes the CPU up.
+    /// If not, a halted CPU skips ahead to the next LCD event.
     pub fn pipeline_step(&mut self) -> Result<(), GbaError> {
+        if self.low_power != Some(LowPowerMode::Stop) {
+            let mut bus = self.bus.borrow_mut();
+            let idle = self.low_power.is_some() & (bus.ioregs().pending_interrupts() == 0);
+            let cycles = if idle { bus.ioregs().cycles_until_lcd_event() } else { 1 };
+            bus.ioregs_mut().advance_lcd(cycles);
+        }
         if self.delay_cycles > 0 {
             self.delay_cycles -= 1;
             return Ok(());
         }
+        if !self.poll_interrupts() { return Ok(()); }
+
+        // Remember calls and returns for backtraces.
+        let addr = self.executing_address();
+        let branch = if self.pipeline_refill != 0 { Branch::None }
+            else if self.state == State::ARM { Branch::classify_arm(addr, &self.decoded_arm, self.gpr[Arm7Tdmi::LR] as u32) }
+            else { Branch::classify_thumb(addr, &self.decoded_thumb) };
 
         let action: CpuAction = if self.state == State::ARM {
             // Fetch.
-            let new_fetched_arm = try!(self.bus.borrow().load_word(self.gpr[Arm7Tdmi::PC] as u32)) as u32;
+            let new_fetched_arm = try!(self.bus.borrow_mut().fetch_word(self.gpr[Arm7Tdmi::PC] as u32)) as u32;
             // Decode.
             let new_decoded_arm = try!(ArmInstruction::decode(self.fetched_arm));
             try!(new_decoded_arm.check_is_valid());
@@ -225,7 +495,7 @@ impl Arm7Tdmi {
             action
         } else {
             // Fetch.
-            let new_fetched_thumb = try!(self.bus.borrow().load_halfword(self.gpr[Arm7Tdmi::PC] as u32)) as u16;
+            let new_fetched_thumb = try!(self.bus.borrow_mut().fetch_halfword(self.gpr[Arm7Tdmi::PC] as u32)) as u16;
             // Decode.
             let new_decoded_thumb = try!(ThumbInstruction::decode(self.fetched_thumb));
 
@@ -237,8 +507,19 @@ impl Arm7Tdmi {
         };
 
         match action {
-            CpuAction::None          => self.increment_pc(),
-            CpuAction::FlushPipeline => self.flush_pipeline(),
+            CpuAction::None          => {
+                self.increment_pc();
+                self.pipeline_refill = self.pipeline_refill.saturating_sub(1);
+            },
+            CpuAction::FlushPipeline => {
+                self.flush_pipeline();
+                self.track_branch(addr, branch);
+            },
+        }
+
+        // Writing HALTCNT sends the CPU to sleep.
+        if let Some(m) = self.bus.borrow_mut().ioregs_mut().take_low_power_request() {
+            self.low_power = Some(m);
         }
 
         Ok(())
@@ -94,21 +94,33 @@ impl PSR {
 
     /// Converts the mode bit pattern to a mode enum.
     pub fn mode(&self) -> Mode {
-        match self.0 & PSR::MODE_MASK {
-            PSR::MODE_USER       => Mode::User,
-            PSR::MODE_FIQ        => Mode::FIQ,
-            PSR::MODE_IRQ        => Mode::IRQ,
-            PSR::MODE_SUPERVISOR => Mode::Supervisor,
-            PSR::MODE_ABORT      => Mode::Abort,
-            PSR::MODE_UNDEFINED  => Mode::Undefined,
-            PSR::MODE_SYSTEM     => Mode::System,
-            _ => {
+        match self.try_mode() {
+            Some(m) => m,
+            None => {
                 error!("PSR: Unrecognised mode bit pattern {:#010b}.", self.0 & PSR::MODE_MASK);
                 panic!("Aborting due to illegal mode bits.");
             },
         }
     }
 
+    /// Converts the mode bit pattern to a mode enum.
+    ///
+    /// # Returns
+    /// - `Some`: The encoded mode.
+    /// - `None`: The mode bits do not encode any valid mode.
+    pub fn try_mode(&self) -> Option<Mode> {
+        match self.0 & PSR::MODE_MASK {
+            PSR::MODE_USER       => Some(Mode::User),
+            PSR::MODE_FIQ        => Some(Mode::FIQ),
+            PSR::MODE_IRQ        => Some(Mode::IRQ),
+            PSR::MODE_SUPERVISOR => Some(Mode::Supervisor),
+            PSR::MODE_ABORT      => Some(Mode::Abort),
+            PSR::MODE_UNDEFINED  => Some(Mode::Undefined),
+            PSR::MODE_SYSTEM     => Some(Mode::System),
+            _ => None,
+        }
+    }
+
     /// Sets or clears the state bit
     /// depending on the new state.
     pub fn set_state(&mut self, s: State) {
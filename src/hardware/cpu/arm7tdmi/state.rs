@@ -0,0 +1,114 @@
+// License below.
+//! Implements save states for the ARM7TDMI.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::io;
+use std::mem;
+use super::*;
+use super::super::super::savestate::*;
+
+fn save_regs(w: &mut StateWriter, regs: &[i32]) {
+    for &r in regs { w.write_u32(r as u32); }
+}
+
+fn load_regs(r: &mut StateReader, regs: &mut [i32]) -> io::Result<()> {
+    for x in regs.iter_mut() { *x = try!(r.read_u32()) as i32; }
+    Ok(())
+}
+
+fn load_mode(r: &mut StateReader) -> io::Result<Mode> {
+    let m = try!(r.read_u8());
+    if m > (Mode::System as u8) { return Err(invalid_state("Unknown CPU mode.")); }
+    Ok(unsafe { mem::transmute(m) })
+}
+
+impl SaveState for Arm7Tdmi {
+    fn save_state(&self, w: &mut StateWriter) {
+        save_regs(w, &self.gpr);
+        w.write_u32(self.cpsr.0);
+        for psr in &self.spsr { w.write_u32(psr.0); }
+
+        w.write_u32(self.decoded_arm.raw());
+        w.write_u32(self.fetched_arm);
+        w.write_u16(self.decoded_thumb.raw());
+        w.write_u16(self.fetched_thumb);
+        w.write_u8(self.pipeline_refill);
+
+        save_regs(w, &self.gpr_r8_r12_fiq);
+        save_regs(w, &self.gpr_r8_r12_other);
+        save_regs(w, &self.gpr_r13_all);
+        save_regs(w, &self.gpr_r14_all);
+
+        w.write_u8(self.mode as u8);
+        w.write_u8(self.state as u8);
+        w.write_bool(self.irq_disable);
+        w.write_bool(self.fiq_disable);
+        w.write_u8(self.delay_cycles);
+
+        w.write_u8(match self.low_power {
+            None                     => 0,
+            Some(LowPowerMode::Halt) => 1,
+            Some(LowPowerMode::Stop) => 2,
+        });
+        w.write_bool(self.intr_waiting);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> io::Result<()> {
+        // The call stack is debugging information and not saved.
+        self.call_stack.clear();
+        try!(load_regs(r, &mut self.gpr));
+        self.cpsr = PSR(try!(r.read_u32()));
+        for psr in self.spsr.iter_mut() { *psr = PSR(try!(r.read_u32())); }
+
+        let decode_err = |_| invalid_state("Undecodable instruction in the pipeline.");
+        self.decoded_arm     = try!(ArmInstruction::decode(try!(r.read_u32())).map_err(&decode_err));
+        self.fetched_arm     = try!(r.read_u32());
+        self.decoded_thumb   = try!(ThumbInstruction::decode(try!(r.read_u16())).map_err(&decode_err));
+        self.fetched_thumb   = try!(r.read_u16());
+        self.pipeline_refill = try!(r.read_u8());
+
+        try!(load_regs(r, &mut self.gpr_r8_r12_fiq));
+        try!(load_regs(r, &mut self.gpr_r8_r12_other));
+        try!(load_regs(r, &mut self.gpr_r13_all));
+        try!(load_regs(r, &mut self.gpr_r14_all));
+
+        self.mode  = try!(load_mode(r));
+        self.state = if 0 == try!(r.read_u8()) { State::ARM } else { State::THUMB };
+        self.irq_disable  = try!(r.read_bool());
+        self.fiq_disable  = try!(r.read_bool());
+        self.delay_cycles = try!(r.read_u8());
+
+        self.low_power = match try!(r.read_u8()) {
+            0 => None,
+            1 => Some(LowPowerMode::Halt),
+            2 => Some(LowPowerMode::Stop),
+            _ => { return Err(invalid_state("Unknown low-power mode.")); },
+        };
+        self.intr_waiting = try!(r.read_bool());
+        Ok(())
+    }
+}
+
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
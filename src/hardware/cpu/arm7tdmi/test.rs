@@ -0,0 +1,215 @@
+// License below.
+#![allow(missing_docs)]
+
+use byteorder::{ByteOrder, LittleEndian};
+use super::*;
+use super::super::super::ioregs::Interrupt;
+use super::super::super::memory::{BiosRom, RawBytes};
+use super::super::super::test_util::{new_cpu, load_program};
+use super::super::thumbinstruction::ThumbInstruction;
+
+// Loads an ARM program ending in `b .` into on-board WRAM,
+// and BIOS code starting at the SWI vector.
+fn boot(program: &[u32], bios: &[u32]) -> Arm7Tdmi {
+    let mut rom = BiosRom::new();
+    for (i, &w) in bios.iter().enumerate() { LittleEndian::write_u32(rom.bytes_mut(8 + (i as u32 * 4)), w); }
+    let (mut cpu, bus) = new_cpu(rom);
+    load_program(&bus, 0x02000000, program);
+    load_program(&bus, 0x02000000 + (program.len() as u32 * 4), &[0xEAFFFFFE]);
+    cpu.boot_post_bios(0x02000000);
+    cpu
+}
+
+// Runs an ARM program from on-board WRAM until it ends in `b .`.
+fn run(program: &[u32], bios: &[u32]) -> Arm7Tdmi {
+    let mut cpu = boot(program, bios);
+    for _ in 0..200 { cpu.pipeline_step().unwrap(); }
+    cpu
+}
+
+// Steps until the instruction at `addr` is executed next.
+fn step_to(cpu: &mut Arm7Tdmi, addr: u32) {
+    for _ in 0..200 {
+        if cpu.is_about_to_execute() && (cpu.executing_address() == addr) { return; }
+        cpu.pipeline_step().unwrap();
+    }
+    panic!("Never reached {:#010X}.", addr);
+}
+
+fn flags(cpsr: i32) -> u32 { (cpsr as u32) >> 28 }
+
+#[test]
+pub fn alu_flags() {
+    let cpu = run(&[
+        0xE3A00005, // mov  r0, #5
+        0xE2501003, // subs r1, r0, #3
+        0xE10F2000, // mrs  r2, cpsr
+        0xE2503007, // subs r3, r0, #7
+        0xE10F4000, // mrs  r4, cpsr
+        0xE2C05001, // sbc  r5, r0, #1
+        0xE3500005, // cmp  r0, #5
+        0xE10F6000, // mrs  r6, cpsr
+        0xE2C07001, // sbc  r7, r0, #1
+        0xE2E0800A, // rsc  r8, r0, #10
+        0xE2509006, // subs r9, r0, #6
+        0xE2E0A00A, // rsc  r10, r0, #10
+        0xE3A0B102, // mov  r11, #0x80000000
+        0xE25BC001, // subs r12, r11, #1
+        0xE10F0000, // mrs  r0, cpsr
+    ], &[]);
+
+    // The carry flag is set if nothing was borrowed.
+    assert_eq!((cpu.register(1), flags(cpu.register(2))), (2, 0b0010));
+    assert_eq!((cpu.register(3), flags(cpu.register(4))), (-2, 0b1000));
+    assert_eq!(flags(cpu.register(6)), 0b0110);
+    assert_eq!(flags(cpu.register(0)), 0b0011);
+    assert_eq!(cpu.register(12), 0x7FFFFFFF);
+
+    // SBC and RSC subtract the inverted carry.
+    assert_eq!(cpu.register(5), 3);
+    assert_eq!(cpu.register(7), 4);
+    assert_eq!(cpu.register(8), 5);
+    assert_eq!(cpu.register(9), -1);
+    assert_eq!(cpu.register(10), 4);
+}
+
+#[test]
+pub fn misaligned_block_transfers() {
+    let mut cpu = boot(&[
+        0xE3A00402, // mov   r0, #0x02000000
+        0xE2800C01, // add   r0, r0, #0x100
+        0xE2800002, // add   r0, r0, #2
+        0xE8900006, // ldmia r0, {r1, r2}
+        0xE5903000, // ldr   r3, [r0]
+    ], &[]);
+    cpu.bus.borrow_mut().store_word(0x02000100, 0x11223344).unwrap();
+    cpu.bus.borrow_mut().store_word(0x02000104, 0x55667788).unwrap();
+    for _ in 0..200 { cpu.pipeline_step().unwrap(); }
+
+    // LDM ignores the low address bits, LDR rotates.
+    assert_eq!(cpu.register(1), 0x11223344);
+    assert_eq!(cpu.register(2), 0x55667788);
+    assert_eq!(cpu.register(3), 0x33441122);
+}
+
+#[test]
+pub fn exception_entry_and_return() {
+    let cpu = run(&[
+        0xE3A00001, // mov  r0, #1
+        0xEF000000, // swi  #0
+        0xE3A02007, // mov  r2, #7
+    ], &[
+        // SWI vector:
+        0xE14F3000, // mrs  r3, spsr
+        0xE10F6000, // mrs  r6, cpsr
+        0xE1A0400E, // mov  r4, lr
+        0xE1A0500D, // mov  r5, sp
+        0xE1B0F00E, // movs pc, lr
+    ]);
+
+    // Inside the handler.
+    assert_eq!(cpu.register(3) & 0xFF, 0x1F);
+    assert_eq!(cpu.register(6) & 0xFF, 0x93);
+    assert_eq!(cpu.register(4), 0x02000008);
+    assert_eq!(cpu.register(5) as u32, Arm7Tdmi::SP_SUPERVISOR_POST_BIOS);
+
+    // Back in System mode with its own banked registers.
+    assert_eq!(cpu.register(2), 7);
+    assert_eq!(cpu.mode(), Mode::System);
+    assert_eq!(cpu.cpsr().0 & 0xFF, 0x1F);
+    assert_eq!(cpu.register(Arm7Tdmi::SP) as u32, Arm7Tdmi::SP_USER_POST_BIOS);
+    assert_eq!(cpu.register(Arm7Tdmi::LR), 0);
+}
+
+
+#[test]
+pub fn exception_frames() {
+    let mut cpu = boot(&[
+        0xE3A00001, // mov  r0, #1
+        0xEF000000, // swi  #0
+        0xE3A02007, // mov  r2, #7
+    ], &[
+        0xE1B0F00E, // 0008  movs pc, lr          SWI
+        0xEAFFFFFE, // 000C  b .
+        0xEAFFFFFE, // 0010  b .
+        0xEAFFFFFE, // 0014  b .
+        0xE25EF004, // 0018  subs pc, lr, #4      IRQ
+    ]);
+
+    // SWI returns to the next instruction.
+    step_to(&mut cpu, 0x00000008);
+    assert_eq!(cpu.call_stack().iter().cloned().collect::<Vec<_>>(), vec![CallFrame {
+        call_site: 0x02000004,
+        target: 0x00000008,
+        return_addr: 0x02000008,
+        mode: Mode::Supervisor,
+        exception: Some(Exception::SoftwareInterrupt),
+    }]);
+    step_to(&mut cpu, 0x0200000C);
+    assert!(cpu.call_stack().is_empty());
+
+    // IRQs return to the interrupted instruction.
+    {
+        let mut bus = cpu.bus.borrow_mut();
+        bus.ioregs_mut().store_halfword(0x200, Interrupt::VBlank.mask());
+        bus.ioregs_mut().set_interrupt_master_enable(true);
+        bus.ioregs_mut().request_interrupt(Interrupt::VBlank);
+    }
+    step_to(&mut cpu, 0x00000018);
+    assert_eq!(cpu.call_stack().iter().cloned().collect::<Vec<_>>(), vec![CallFrame {
+        call_site: 0x0200000C,
+        target: 0x00000018,
+        return_addr: 0x0200000C,
+        mode: Mode::IRQ,
+        exception: Some(Exception::NormalInterrupt),
+    }]);
+    cpu.bus.borrow_mut().ioregs_mut().set_interrupt_master_enable(false);
+    step_to(&mut cpu, 0x0200000C);
+    assert!(cpu.call_stack().is_empty());
+    assert_eq!(cpu.mode(), Mode::System);
+}
+
+#[test]
+pub fn thumb_calls() {
+    let t = |raw: u16| ThumbInstruction::decode(raw).unwrap();
+
+    // Only the second half of `bl` branches.
+    assert_eq!(Branch::classify_thumb(0x08000100, &t(0xF000)), Branch::None);
+    assert_eq!(Branch::classify_thumb(0x08000102, &t(0xF80E)), Branch::Call(0x08000104));
+    assert_eq!(Branch::classify_thumb(0x08000120, &t(0xB500)), Branch::None);   // push {lr}
+    assert_eq!(Branch::classify_thumb(0x08000122, &t(0xBC10)), Branch::None);   // pop {r4}
+    assert_eq!(Branch::classify_thumb(0x08000124, &t(0xBD00)), Branch::Return); // pop {pc}
+    assert_eq!(Branch::classify_thumb(0x08000124, &t(0x4770)), Branch::Return); // bx lr
+
+    let mut stack = CallStack::new();
+    stack.push(CallFrame {
+        call_site: 0x08000102,
+        target: 0x08000120,
+        return_addr: 0x08000104,
+        mode: Mode::System,
+        exception: None,
+    });
+    assert_eq!(stack.ret(0x08000200), 0);
+    assert_eq!(stack.len(), 1);
+    assert_eq!(stack.ret(0x08000104), 1);
+    assert!(stack.is_empty());
+}
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
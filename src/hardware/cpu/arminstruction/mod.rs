@@ -147,6 +147,9 @@ impl ArmInstruction {
         ArmInstruction { raw: ArmInstruction::NOP_RAW, op: ArmOpcode::DataProcessing }
     }
 
+    /// Get the raw 32-bit instruction this has been decoded from.
+    pub fn raw(&self) -> u32 { self.raw }
+
     /// Decodes a raw 32-bit integer as an ARM instruction.
     ///
     /// Unknown instructions do not result in a decoding
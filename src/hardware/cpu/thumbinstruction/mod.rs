@@ -127,6 +127,12 @@ impl ThumbInstruction {
         ThumbInstruction { raw: ThumbInstruction::NOP_RAW, op: ThumbOpcode::HiRegOpBx }
     }
 
+    /// Get the raw 16-bit instruction this has been decoded from.
+    pub fn raw(&self) -> u16 { self.raw }
+
+    /// Get the decoded opcode of the THUMB instruction.
+    pub fn opcode(&self) -> ThumbOpcode { self.op }
+
     /// Decodes a raw 16-bit integer as a THUMB instruction.
     #[cfg_attr(feature="clippy", allow(if_same_then_else))] // Order of checks matters a lot here, false positive.
     pub fn decode(raw: u16) -> Result<ThumbInstruction, GbaError> {
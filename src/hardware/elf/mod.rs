@@ -0,0 +1,269 @@
+// License below.
+//! Implements loading ELF executables.
+//!
+//! Toolchains like devkitARM link ELF files first and only
+//! then `objcopy` them into raw ROM images. Loading the ELF
+//! file directly has two advantages:
+//!
+//! - Code and data can be placed anywhere in the memory map,
+//!   e.g. in EWRAM or IWRAM, not just in the GamePak ROM.
+//! - The symbol table can be imported for debugging.
+//!
+//! Only 32-bit Little Endian ARM executables are supported.
+//! Segments are loaded to their physical addresses, i.e. their
+//! load addresses, just like `objcopy` would do. Start-up code
+//! then copies e.g. `.data` from ROM to its run address.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::io;
+use std::io::Read;
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+use std::str;
+use byteorder::{ByteOrder, LittleEndian};
+
+pub use self::symbols::*;
+
+pub mod symbols;
+
+#[cfg(test)]
+mod test;
+
+
+/// Magic number at the start of every ELF file.
+pub const ELF_MAGIC: [u8; 4] = [0x7F, 0x45, 0x4C, 0x46];
+
+/// `e_ident[EI_CLASS]` of 32-bit ELF files.
+pub const ELF_CLASS_32: u8 = 1;
+
+/// `e_ident[EI_DATA]` of Little Endian ELF files.
+pub const ELF_DATA_LSB: u8 = 1;
+
+/// `e_machine` of ARM executables.
+pub const ELF_MACHINE_ARM: u16 = 40;
+
+/// Program header type of loadable segments.
+pub const PT_LOAD: u32 = 1;
+
+/// Section header type of symbol tables.
+pub const SHT_SYMTAB: u32 = 2;
+
+/// Symbol type of data objects.
+pub const STT_OBJECT: u8 = 1;
+
+/// Symbol type of functions.
+pub const STT_FUNC: u8 = 2;
+
+/// Section index of undefined symbols.
+pub const SHN_UNDEF: u16 = 0;
+
+const ELF_HEADER_LEN: usize = 52;
+const PROGRAM_HEADER_LEN: usize = 32;
+const SECTION_HEADER_LEN: usize = 40;
+const SYMBOL_LEN: usize = 16;
+
+
+/// An error caused while parsing an ELF file.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ElfError {
+    /// The file has no ELF magic number.
+    NotElf,
+
+    /// The file is no 32-bit Little Endian ELF file.
+    UnsupportedFormat,
+
+    /// The file is for another machine than ARM.
+    UnsupportedMachine(u16),
+
+    /// A header or table points beyond the end of the file.
+    Truncated(usize),
+}
+
+impl error::Error for ElfError {
+    fn description(&self) -> &str {
+        match *self {
+            ElfError::NotElf                => "Not an ELF file.",
+            ElfError::UnsupportedFormat     => "Unsupported ELF format.",
+            ElfError::UnsupportedMachine(_) => "Unsupported ELF machine.",
+            ElfError::Truncated(_)          => "Truncated ELF file.",
+        }
+    }
+}
+
+impl fmt::Display for ElfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ElfError::NotElf                => write!(f, "Not an ELF file."),
+            ElfError::UnsupportedFormat     => write!(f, "Only 32-bit Little Endian ELF files are supported."),
+            ElfError::UnsupportedMachine(x) => write!(f, "Expected an ARM executable, found machine {}.", x),
+            ElfError::Truncated(x)          => write!(f, "The ELF file ends unexpectedly at {:#X}.", x),
+        }
+    }
+}
+
+
+/// A loadable segment of an ELF file.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ElfSegment {
+    /// The physical address to load the segment to.
+    pub addr: u32,
+
+    /// The segment's contents.
+    pub data: Vec<u8>,
+
+    /// The segment's size in memory.
+    ///
+    /// Bytes beyond `data.len()` are zero-filled, e.g. `.bss`.
+    pub mem_size: u32,
+}
+
+
+/// The loadable parts of an ELF executable.
+#[derive(Debug, Clone)]
+pub struct ElfFile {
+    /// The address execution starts at.
+    pub entry: u32,
+
+    /// All `PT_LOAD` segments.
+    pub segments: Vec<ElfSegment>,
+
+    /// All function and object symbols.
+    pub symbols: SymbolTable,
+}
+
+impl ElfFile {
+    /// Checks whether a file starts with the ELF magic number.
+    pub fn is_elf_file(fp: &Path) -> bool {
+        let mut magic = [0_u8; 4];
+        match File::open(fp).and_then(|mut f| f.read_exact(&mut magic)) {
+            Ok(_)  => magic == ELF_MAGIC,
+            Err(_) => false,
+        }
+    }
+
+    /// Loads and parses an ELF file.
+    ///
+    /// # Returns
+    /// - `Ok`: The parsed file.
+    /// - `Err`: The file is unreadable or not a supported ELF file.
+    pub fn load_from_file(fp: &Path) -> io::Result<ElfFile> {
+        trace!("Loading ELF file `{}`.", fp.display());
+        let mut data = Vec::new();
+        try!(try!(File::open(fp)).read_to_end(&mut data));
+        ElfFile::parse(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Parses an ELF file.
+    pub fn parse(data: &[u8]) -> Result<ElfFile, ElfError> {
+        if !data.starts_with(&ELF_MAGIC) { return Err(ElfError::NotElf); }
+        let hdr = try!(slice(data, 0, ELF_HEADER_LEN));
+        if (hdr[4] != ELF_CLASS_32) | (hdr[5] != ELF_DATA_LSB) { return Err(ElfError::UnsupportedFormat); }
+        let machine = LittleEndian::read_u16(&hdr[18..]);
+        if machine != ELF_MACHINE_ARM { return Err(ElfError::UnsupportedMachine(machine)); }
+
+        let entry     = LittleEndian::read_u32(&hdr[24..]);
+        let phoff     = LittleEndian::read_u32(&hdr[28..]) as usize;
+        let shoff     = LittleEndian::read_u32(&hdr[32..]) as usize;
+        let phentsize = LittleEndian::read_u16(&hdr[42..]) as usize;
+        let phnum     = LittleEndian::read_u16(&hdr[44..]) as usize;
+        let shentsize = LittleEndian::read_u16(&hdr[46..]) as usize;
+        let shnum     = LittleEndian::read_u16(&hdr[48..]) as usize;
+
+        // Collect all loadable segments.
+        let mut segments = Vec::new();
+        for i in 0..phnum {
+            let ph = try!(slice(data, phoff + i * phentsize, PROGRAM_HEADER_LEN));
+            if LittleEndian::read_u32(&ph[0..]) != PT_LOAD { continue; }
+            let offset   = LittleEndian::read_u32(&ph[4..]) as usize;
+            let paddr    = LittleEndian::read_u32(&ph[12..]);
+            let filesz   = LittleEndian::read_u32(&ph[16..]) as usize;
+            let mem_size = LittleEndian::read_u32(&ph[20..]);
+            if (filesz == 0) & (mem_size == 0) { continue; }
+            segments.push(ElfSegment {
+                addr: paddr,
+                data: try!(slice(data, offset, filesz)).to_vec(),
+                mem_size: mem_size,
+            });
+        }
+
+        // Import the symbol table, if any.
+        let mut symbols = SymbolTable::new();
+        for i in 0..shnum {
+            let sh = try!(slice(data, shoff + i * shentsize, SECTION_HEADER_LEN));
+            if LittleEndian::read_u32(&sh[4..]) != SHT_SYMTAB { continue; }
+            let offset = LittleEndian::read_u32(&sh[16..]) as usize;
+            let size   = LittleEndian::read_u32(&sh[20..]) as usize;
+            let link   = LittleEndian::read_u32(&sh[24..]) as usize;
+            let strsh  = try!(slice(data, shoff + link * shentsize, SECTION_HEADER_LEN));
+            let strtab = try!(slice(data, LittleEndian::read_u32(&strsh[16..]) as usize,
+                                          LittleEndian::read_u32(&strsh[20..]) as usize));
+            try!(import_symbols(try!(slice(data, offset, size)), strtab, &mut symbols));
+        }
+
+        Ok(ElfFile { entry: entry, segments: segments, symbols: symbols })
+    }
+}
+
+fn import_symbols(symtab: &[u8], strtab: &[u8], symbols: &mut SymbolTable) -> Result<(), ElfError> {
+    for sym in symtab.chunks(SYMBOL_LEN) {
+        if sym.len() < SYMBOL_LEN { break; }
+        let name_offs = LittleEndian::read_u32(&sym[0..]) as usize;
+        let value     = LittleEndian::read_u32(&sym[4..]);
+        let size      = LittleEndian::read_u32(&sym[8..]);
+        let kind      = sym[12] & 0xF;
+        let shndx     = LittleEndian::read_u16(&sym[14..]);
+        if (name_offs == 0) | (shndx == SHN_UNDEF) | (kind > STT_FUNC) { continue; }
+
+        // Skip mapping symbols like `$a` and local labels like `.L1`.
+        let name = try!(c_str(strtab, name_offs));
+        if name.is_empty() | name.starts_with('$') | name.starts_with('.') { continue; }
+
+        let thumb = (kind == STT_FUNC) & (0 != (value & 0b1));
+        symbols.insert(Symbol {
+            name: name.to_owned(),
+            addr: if thumb { value & !0b1 } else { value },
+            size: size,
+            thumb: thumb,
+        });
+    }
+    Ok(())
+}
+
+fn slice(data: &[u8], offs: usize, len: usize) -> Result<&[u8], ElfError> {
+    match offs.checked_add(len) {
+        Some(end) if end <= data.len() => Ok(&data[offs..end]),
+        _ => Err(ElfError::Truncated(offs)),
+    }
+}
+
+fn c_str(data: &[u8], offs: usize) -> Result<&str, ElfError> {
+    let bytes = try!(slice(data, offs, data.len().saturating_sub(offs)));
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    Ok(str::from_utf8(&bytes[..len]).unwrap_or(""))
+}
+
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -0,0 +1,134 @@
+// License below.
+//! Implements a symbol table for debugging.
+//!
+//! Symbols are imported from ELF files and used to
+//! print function names instead of bare addresses.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::collections::{BTreeMap, HashMap};
+use std::collections::Bound::{Included, Unbounded};
+
+
+/// A named address.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Symbol {
+    /// The symbol's name, e.g. `AgbMain`.
+    pub name: String,
+
+    /// The symbol's address without the THUMB bit.
+    pub addr: u32,
+
+    /// The size of the function or object in bytes.
+    ///
+    /// Zero for plain labels.
+    pub size: u32,
+
+    /// `true` if this is a THUMB state function.
+    pub thumb: bool,
+}
+
+
+/// Maps addresses to names and back.
+#[derive(Debug, Default, Clone)]
+pub struct SymbolTable {
+    by_addr: BTreeMap<u32, Symbol>,
+    by_name: HashMap<String, u32>,
+}
+
+impl SymbolTable {
+    /// Creates a new empty symbol table.
+    pub fn new() -> SymbolTable {
+        SymbolTable { by_addr: BTreeMap::new(), by_name: HashMap::new() }
+    }
+
+    /// Removes all symbols.
+    pub fn clear(&mut self) {
+        self.by_addr.clear();
+        self.by_name.clear();
+    }
+
+    /// The number of imported symbols.
+    pub fn len(&self) -> usize { self.by_addr.len() }
+
+    /// Checks whether no symbols have been imported.
+    pub fn is_empty(&self) -> bool { self.by_addr.is_empty() }
+
+    /// Adds a symbol.
+    ///
+    /// If there already is a symbol at the same address,
+    /// the one with a size, i.e. the function or object,
+    /// is kept for printing. Both names can be looked up.
+    pub fn insert(&mut self, sym: Symbol) {
+        self.by_name.insert(sym.name.clone(), sym.addr);
+        let keep_old = match self.by_addr.get(&sym.addr) {
+            Some(old) => (old.size != 0) & (sym.size == 0),
+            None => false,
+        };
+        if !keep_old { self.by_addr.insert(sym.addr, sym); }
+    }
+
+    /// Looks up a symbol's address by its name.
+    pub fn address_of(&self, name: &str) -> Option<u32> {
+        self.by_name.get(name).cloned()
+    }
+
+    /// Get the symbol starting exactly at the given address.
+    pub fn symbol_at(&self, addr: u32) -> Option<&Symbol> {
+        self.by_addr.get(&addr)
+    }
+
+    /// Finds the symbol an address belongs to.
+    ///
+    /// # Returns
+    /// - `Some`: The symbol and the address' offset into it.
+    /// - `None`: The address is not covered by any symbol.
+    pub fn lookup(&self, addr: u32) -> Option<(&Symbol, u32)> {
+        match self.by_addr.range((Unbounded, Included(addr))).next_back() {
+            Some((_, sym)) => {
+                let offs = addr - sym.addr;
+                if (sym.size == 0) | (offs < sym.size) { Some((sym, offs)) } else { None }
+            },
+            None => None,
+        }
+    }
+
+    /// Formats an address with its symbol, if any.
+    ///
+    /// # Returns
+    /// E.g. `<AgbMain+0x1C>`, or `None` if there is no symbol.
+    pub fn label(&self, addr: u32) -> Option<String> {
+        self.lookup(addr).map(|(sym, offs)| {
+            if offs == 0 { format!("<{}>", sym.name) }
+            else { format!("<{}+{:#X}>", sym.name, offs) }
+        })
+    }
+
+    /// Iterates over all symbols ordered by address.
+    pub fn iter(&self) -> ::std::collections::btree_map::Values<u32, Symbol> {
+        self.by_addr.values()
+    }
+}
+
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -0,0 +1,134 @@
+// License below.
+#![allow(missing_docs)]
+
+use super::*;
+use super::super::Gba;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process;
+
+fn le16(x: u16, out: &mut Vec<u8>) { out.push(x as u8); out.push((x >> 8) as u8); }
+fn le32(x: u32, out: &mut Vec<u8>) { for i in 0..4 { out.push((x >> (8 * i)) as u8); } }
+
+fn symbol(name: u32, value: u32, size: u32, info: u8, shndx: u16, out: &mut Vec<u8>) {
+    le32(name, out); le32(value, out); le32(size, out);
+    out.push(info); out.push(0); le16(shndx, out);
+}
+
+fn section(kind: u32, offs: u32, size: u32, link: u32, out: &mut Vec<u8>) {
+    le32(0, out); le32(kind, out); le32(0, out); le32(0, out);
+    le32(offs, out); le32(size, out); le32(link, out); le32(0, out);
+    le32(0, out); le32(0, out);
+}
+
+// Builds an ELF with one segment at 0x08000000, a .bss
+// segment in IWRAM, and a few symbols.
+fn build_elf() -> Vec<u8> {
+    let code: Vec<u8> = vec![0x2E, 0x00, 0x00, 0xEA, 0x05, 0x00, 0xA0, 0xE3];
+    let mut strtab = b"\0_start\0$a\0AgbMain\0ThumbFn\0".to_vec();
+    strtab.resize(28, 0); // Keep the section headers word aligned.
+    let mut symtab = Vec::new();
+    symbol(0,  0,          0, 0, 0, &mut symtab); // Null symbol.
+    symbol(1,  0x08000000, 0, 0, 1, &mut symtab); // _start, NOTYPE.
+    symbol(8,  0x08000000, 0, 0, 1, &mut symtab); // $a, mapping symbol.
+    symbol(11, 0x08000004, 4, 2, 1, &mut symtab); // AgbMain, FUNC.
+    symbol(19, 0x08000011, 8, 2, 1, &mut symtab); // ThumbFn, FUNC, THUMB.
+
+    let phoff = 52_u32;
+    let code_offs = phoff + 2 * 32;
+    let sym_offs = code_offs + code.len() as u32;
+    let str_offs = sym_offs + symtab.len() as u32;
+    let shoff = str_offs + strtab.len() as u32;
+
+    let mut elf = ELF_MAGIC.to_vec();
+    elf.extend_from_slice(&[ELF_CLASS_32, ELF_DATA_LSB, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    le16(2, &mut elf); le16(ELF_MACHINE_ARM, &mut elf); le32(1, &mut elf);
+    le32(0x08000000, &mut elf); le32(phoff, &mut elf); le32(shoff, &mut elf);
+    le32(0, &mut elf); le16(52, &mut elf);
+    le16(32, &mut elf); le16(2, &mut elf);
+    le16(40, &mut elf); le16(3, &mut elf); le16(0, &mut elf);
+
+    // Program headers: ROM code and IWRAM .bss.
+    for x in &[PT_LOAD, code_offs, 0x08000000, 0x08000000, code.len() as u32, code.len() as u32, 5, 4] { le32(*x, &mut elf); }
+    for x in &[PT_LOAD, 0, 0x03000000, 0x03000000, 0, 0x100, 6, 4] { le32(*x, &mut elf); }
+
+    elf.extend_from_slice(&code);
+    elf.extend_from_slice(&symtab);
+    elf.extend_from_slice(&strtab);
+
+    // Section headers: null, .symtab, .strtab.
+    section(0, 0, 0, 0, &mut elf);
+    section(SHT_SYMTAB, sym_offs, symtab.len() as u32, 2, &mut elf);
+    section(3, str_offs, strtab.len() as u32, 0, &mut elf);
+    elf
+}
+
+#[test]
+pub fn elf_parsing() {
+    let elf = ElfFile::parse(&build_elf()).unwrap();
+    assert_eq!(elf.entry, 0x08000000);
+    assert_eq!(elf.segments.len(), 2);
+    assert_eq!(elf.segments[0].addr, 0x08000000);
+    assert_eq!(elf.segments[0].data.len(), 8);
+    assert_eq!(elf.segments[1].addr, 0x03000000);
+    assert_eq!(elf.segments[1].mem_size, 0x100);
+
+    assert_eq!(elf.symbols.len(), 3);
+    assert_eq!(elf.symbols.address_of("_start"), Some(0x08000000));
+    assert_eq!(elf.symbols.address_of("$a"), None);
+    assert_eq!(elf.symbols.address_of("ThumbFn"), Some(0x08000010));
+    assert!(elf.symbols.symbol_at(0x08000010).unwrap().thumb);
+
+    let mut broken = build_elf();
+    broken.truncate(100);
+    assert_eq!(ElfFile::parse(&broken).err(), Some(ElfError::Truncated(116)));
+    assert_eq!(ElfFile::parse(b"\x7FELF\x02\x01").err(), Some(ElfError::Truncated(0)));
+    assert_eq!(ElfFile::parse(b"PATCH").err(), Some(ElfError::NotElf));
+}
+
+#[test]
+pub fn symbol_lookup() {
+    let elf = ElfFile::parse(&build_elf()).unwrap();
+    let syms = &elf.symbols;
+    assert_eq!(syms.label(0x08000000), Some("<_start>".to_owned()));
+    assert_eq!(syms.label(0x08000006), Some("<AgbMain+0x2>".to_owned()));
+    assert_eq!(syms.label(0x08000014), Some("<ThumbFn+0x4>".to_owned()));
+    assert_eq!(syms.label(0x08000018), None);
+    assert_eq!(syms.label(0x07FFFFFF), None);
+}
+
+
+#[test]
+pub fn thumb_entry_points() {
+    let fp = env::temp_dir().join(format!("gbars-{}-thumb.elf", process::id()));
+    let mut elf = build_elf();
+    elf[24] = 0x11; // Enter at ThumbFn.
+    fs::File::create(&fp).unwrap().write_all(&elf).unwrap();
+
+    let mut gba = Gba::new();
+    let err = gba.load_elf_file(&fp).unwrap_err();
+    fs::remove_file(&fp).unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(gba.symbols().address_of("_start").is_none());
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
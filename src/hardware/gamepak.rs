@@ -14,6 +14,9 @@
 //! The SRAM chip is where you game's progress will
 //! be saved. The SRAM's contents will be dumped into
 //! a saved game file.
+//!
+//! Extra features like sensors or rumble motors are
+//! implemented as `GamePakPeripheral`s.
 #![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
 #![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
 #![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
@@ -25,10 +28,18 @@ use std::fmt;
 use std::slice;
 use std::io::Read;
 use std::fs::File;
+use std::io::Write;
+use std::ops::Range;
 use std::path::Path;
+use super::checksum::crc32;
+use super::romfile::{read_image_file, ROM_EXTENSIONS};
+use super::patch::{apply_patch, PatchFormat, PatchError};
+use super::cpu::{ArmInstruction, ArmOpcode, ArmCondition};
 use super::memory::GAME_PAK_WS0_ROM_LEN as GAME_PAK_ROM_LEN;
 use super::memory::GAME_PAK_SRAM_LEN;
 use super::memory::{RawBytes, Rom8, Rom16, Rom32, Ram8};
+use super::peripherals::{GamePakPeripheral, GpioPort};
+use super::savestate::{SaveState, StateWriter, StateReader};
 
 
 /// GBA ROMs are at most 32MiB in size.
@@ -58,6 +69,62 @@ pub const GAME_MAKER_CODE_LEN: usize = 2;
 /// Offset of the game's version number in ROM.
 pub const GAME_VERSION_NUMBER: usize = 0xBC;
 
+/// Offset of the ARM branch to the game's entry point.
+pub const ENTRY_BRANCH_OFFSET: usize = 0x00;
+
+/// Offset of the compressed Nintendo logo bitmap.
+pub const NINTENDO_LOGO_OFFSET: usize = 0x04;
+
+/// Size of the Nintendo logo bitmap.
+pub const NINTENDO_LOGO_LEN: usize = 156;
+
+/// CRC32 of a valid Nintendo logo bitmap.
+///
+/// The BIOS refuses to boot ROMs with any other logo.
+pub const NINTENDO_LOGO_CRC32: u32 = 0xD0BEB55E;
+
+/// Offset of the fixed header byte.
+pub const FIXED_VALUE_OFFSET: usize = 0xB2;
+
+/// The value the fixed header byte must have.
+pub const FIXED_VALUE: u8 = 0x96;
+
+/// Offset of the main unit code. Zero for all GBA models.
+pub const MAIN_UNIT_CODE_OFFSET: usize = 0xB3;
+
+/// Offset of the device type. Usually zero.
+pub const DEVICE_TYPE_OFFSET: usize = 0xB4;
+
+/// Zero-filled header bytes before the version number.
+pub const RESERVED_AREA_0: Range<usize> = Range { start: 0xB5, end: 0xBC };
+
+/// Zero-filled header bytes after the complement check.
+pub const RESERVED_AREA_1: Range<usize> = Range { start: 0xBE, end: 0xC0 };
+
+/// Size of the ROM header without the multiboot fields.
+pub const ROM_HEADER_LEN: usize = 0xC0;
+
+/// Offset of the ARM branch to a multiboot image's entry point.
+pub const MULTIBOOT_ENTRY_BRANCH_OFFSET: usize = 0xC0;
+
+/// Offset of the multiboot boot mode, written by the BIOS.
+pub const MULTIBOOT_BOOT_MODE_OFFSET: usize = 0xC4;
+
+/// Offset of the multiboot slave ID, written by the BIOS.
+pub const MULTIBOOT_SLAVE_ID_OFFSET: usize = 0xC5;
+
+/// Offset of the ARM branch to a multiboot image's JOYBUS entry point.
+pub const MULTIBOOT_JOYBUS_ENTRY_BRANCH_OFFSET: usize = 0xE0;
+
+/// Boot mode written by the BIOS after a multiplay transfer.
+pub const MULTIBOOT_BOOT_MODE_MULTIPLAY: u8 = 0x03;
+
+/// Slave ID written by the BIOS for the first slave GBA.
+pub const MULTIBOOT_SLAVE_ID_FIRST: u8 = 0x01;
+
+/// Size of the ROM header including the multiboot fields.
+pub const MULTIBOOT_HEADER_LEN: usize = 0xE4;
+
 
 
 /// Helps making sense of the ROM's header bytes.
@@ -79,15 +146,118 @@ impl<'a> GamePakRomHeader<'a> {
     /// - `true` if the checksums match.
     /// - `false` if the header checksum is invalid.
     pub fn complement_check(&self) -> bool {
-        // Calculate checksum.
+        self.calculated_complement() == self.complement()
+    }
+
+    /// The complement check byte stored in the header.
+    pub fn complement(&self) -> u8 {
+        self.0.raw_bytes[COMPLEMENT_CHECK_OFFSET]
+    }
+
+    /// Calculates what the complement check byte should be.
+    pub fn calculated_complement(&self) -> u8 {
         let mut test = 0_u8;
-        for i in 0xA0..0xBC {
+        for i in 0xA0..0xBD {
             test = test.wrapping_sub(self.0.raw_bytes[i]);
         }
-        test = test.wrapping_sub(0x19_u8);
+        test.wrapping_sub(0x19_u8)
+    }
+
+    /// The raw ARM instruction executed after booting.
+    pub fn entry_branch(&self) -> u32 {
+        self.0.read_word(ENTRY_BRANCH_OFFSET as u32)
+    }
+
+    /// Decodes the game's entry point from the entry branch.
+    ///
+    /// # Returns
+    /// - `Some`: The absolute ROM address jumped to.
+    /// - `None`: The entry branch is not an unconditional `B`.
+    pub fn entry_point(&self) -> Option<u32> {
+        decode_entry_branch(self.entry_branch(), 0x08000000 + ENTRY_BRANCH_OFFSET as u32)
+    }
+
+    /// Calculates the CRC32 of the Nintendo logo bitmap.
+    pub fn logo_checksum(&self) -> u32 {
+        crc32(&self.0.raw_bytes[NINTENDO_LOGO_OFFSET..(NINTENDO_LOGO_OFFSET + NINTENDO_LOGO_LEN)])
+    }
+
+    /// Checks whether the ROM contains a valid Nintendo logo.
+    pub fn logo_check(&self) -> bool {
+        self.logo_checksum() == NINTENDO_LOGO_CRC32
+    }
+
+    /// The fixed header byte. Should be `0x96`.
+    pub fn fixed_value(&self) -> u8 {
+        self.0.raw_bytes[FIXED_VALUE_OFFSET]
+    }
+
+    /// Checks whether the fixed header byte is `0x96`.
+    pub fn fixed_value_check(&self) -> bool {
+        self.fixed_value() == FIXED_VALUE
+    }
+
+    /// The main unit code. Should be zero.
+    pub fn main_unit_code(&self) -> u8 {
+        self.0.raw_bytes[MAIN_UNIT_CODE_OFFSET]
+    }
+
+    /// The device type. Usually zero.
+    ///
+    /// Bit 7 selects where a hardware debugger
+    /// maps its extra memory.
+    pub fn device_type(&self) -> u8 {
+        self.0.raw_bytes[DEVICE_TYPE_OFFSET]
+    }
+
+    /// Checks whether all reserved header bytes are zero.
+    pub fn reserved_check(&self) -> bool {
+        self.0.raw_bytes[RESERVED_AREA_0].iter()
+            .chain(self.0.raw_bytes[RESERVED_AREA_1].iter())
+            .all(|&b| b == 0)
+    }
+
+    /// The raw ARM instruction executed after a multiboot transfer.
+    pub fn multiboot_entry_branch(&self) -> u32 {
+        self.0.read_word(MULTIBOOT_ENTRY_BRANCH_OFFSET as u32)
+    }
+
+    /// Decodes a multiboot image's entry point in EWRAM.
+    ///
+    /// # Returns
+    /// - `Some`: The absolute EWRAM address jumped to.
+    /// - `None`: The entry branch is not an unconditional `B`.
+    pub fn multiboot_entry_point(&self) -> Option<u32> {
+        decode_entry_branch(self.multiboot_entry_branch(), 0x02000000 + MULTIBOOT_ENTRY_BRANCH_OFFSET as u32)
+    }
+
+    /// The multiboot boot mode. Overwritten by the BIOS.
+    pub fn multiboot_boot_mode(&self) -> u8 {
+        self.0.raw_bytes[MULTIBOOT_BOOT_MODE_OFFSET]
+    }
+
+    /// The multiboot slave ID. Overwritten by the BIOS.
+    pub fn multiboot_slave_id(&self) -> u8 {
+        self.0.raw_bytes[MULTIBOOT_SLAVE_ID_OFFSET]
+    }
+
+    /// The raw ARM instruction executed after a JOYBUS transfer.
+    pub fn multiboot_joybus_entry_branch(&self) -> u32 {
+        self.0.read_word(MULTIBOOT_JOYBUS_ENTRY_BRANCH_OFFSET as u32)
+    }
+
+    /// Checks whether the header passes all checks the BIOS does.
+    ///
+    /// Real hardware only checks the Nintendo logo, the fixed
+    /// byte, and the complement check. Everything else is just
+    /// reported by `report`.
+    pub fn is_valid(&self) -> bool {
+        self.logo_check() & self.fixed_value_check() & self.complement_check()
+    }
 
-        // Compare result.
-        test == self.0.raw_bytes[COMPLEMENT_CHECK_OFFSET]
+    /// Get a printable validation report of the whole header.
+    pub fn report(&self) -> GamePakRomHeaderReport<'a> {
+        GamePakRomHeaderReport(GamePakRomHeader(self.0))
     }
 
     /// The currently loaded game's title.
@@ -154,6 +324,62 @@ impl<'a> fmt::Display for GamePakRomHeader<'a> {
 }
 
 
+/// Decodes the target of an unconditional `B` instruction.
+fn decode_entry_branch(raw: u32, addr: u32) -> Option<u32> {
+    match ArmInstruction::decode(raw) {
+        Ok(inst) => {
+            if (inst.opcode() == ArmOpcode::B_BL) & (inst.condition() == ArmCondition::AL) & !inst.is_branch_with_link() {
+                Some(addr.wrapping_add(8).wrapping_add(inst.branch_offset() as u32))
+            } else { None }
+        },
+        Err(_) => None,
+    }
+}
+
+
+/// A human readable validation report of a ROM header.
+pub struct GamePakRomHeaderReport<'a>(GamePakRomHeader<'a>);
+
+impl<'a> GamePakRomHeaderReport<'a> {
+    fn verdict(ok: bool) -> &'static str { if ok { "OK" } else { "BAD" } }
+
+    fn write_branch(f: &mut fmt::Formatter, name: &str, raw: u32, target: Option<u32>) -> fmt::Result {
+        match target {
+            Some(x) => writeln!(f, "{:<18}{:08X} (B {:#010X})", name, raw, x),
+            None    => writeln!(f, "{:<18}{:08X} (not a branch)", name, raw),
+        }
+    }
+}
+
+impl<'a> fmt::Display for GamePakRomHeaderReport<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let h = &self.0;
+        try!(writeln!(f, "{:<18}\"{}\"", "Title:", h.game_title()));
+        try!(writeln!(f, "{:<18}{}", "Game Code:", h.game_code()));
+        try!(writeln!(f, "{:<18}{}", "Maker Code:", h.game_maker_code()));
+        try!(writeln!(f, "{:<18}{}", "Software Version:", h.game_version()));
+        try!(writeln!(f, "{:<18}{} bytes", "ROM Size:", h.rom_size()));
+        try!(Self::write_branch(f, "Entry Branch:", h.entry_branch(), h.entry_point()));
+        try!(writeln!(f, "{:<18}CRC32 {:08X} [{}]", "Nintendo Logo:", h.logo_checksum(), Self::verdict(h.logo_check())));
+        try!(writeln!(f, "{:<18}{:#04X} [{}]", "Fixed Value:", h.fixed_value(), Self::verdict(h.fixed_value_check())));
+        try!(writeln!(f, "{:<18}{:#04X} [{}]", "Main Unit Code:", h.main_unit_code(), Self::verdict(h.main_unit_code() == 0)));
+        try!(writeln!(f, "{:<18}{:#04X}", "Device Type:", h.device_type()));
+        try!(writeln!(f, "{:<18}[{}]", "Reserved Areas:", Self::verdict(h.reserved_check())));
+        if h.complement_check() {
+            try!(writeln!(f, "{:<18}{:#04X} [OK]", "Complement Check:", h.complement()));
+        } else {
+            try!(writeln!(f, "{:<18}{:#04X}, expected {:#04X} [BAD]", "Complement Check:",
+                          h.complement(), h.calculated_complement()));
+        }
+        try!(Self::write_branch(f, "Multiboot Entry:", h.multiboot_entry_branch(), h.multiboot_entry_point()));
+        try!(writeln!(f, "{:<18}{:#04X}", "Boot Mode:", h.multiboot_boot_mode()));
+        try!(writeln!(f, "{:<18}{:#04X}", "Slave ID:", h.multiboot_slave_id()));
+        try!(writeln!(f, "{:<18}{:08X}", "JOYBUS Entry:", h.multiboot_joybus_entry_branch()));
+        write!(f, "{:<18}{}", "Bootable:", if h.is_valid() { "yes" } else { "no" })
+    }
+}
+
+
 /// Implements a GamePak' ROM chip.
 pub struct GamePakRom {
     // Raw memory block. Nothing special here.
@@ -189,8 +415,8 @@ impl GamePakRom {
     /// Loads a ROM from a file.
     ///
     /// Only ROMs up to 32MiB in size are valid.
-    /// Everything beyond that size will be silently
-    /// dropped.
+    /// The file may also be a `.gz` file or a
+    /// `.zip` archive containing a `.gba` file.
     ///
     /// Unused memory is zero-filled.
     ///
@@ -201,6 +427,20 @@ impl GamePakRom {
     /// - `Ok` if loaded successfully.
     /// - `Err` if an error occurred. The previous data might be damaged.
     pub fn load_from_file(&mut self, fp: &Path) -> io::Result<()> {
+        self.load_from_file_entry(fp, None)
+    }
+
+    /// Loads a ROM from a file or a named entry in a `.zip` archive.
+    ///
+    /// # Params
+    /// - `fp`: Path to the ROM file to load.
+    /// - `entry`: Name of the `.zip` entry to load, if any.
+    ///
+    /// # Returns
+    /// - `Ok` if loaded successfully.
+    /// - `Err` if an error occurred or the ROM is larger than 32MiB.
+    ///   The previous data might be damaged.
+    pub fn load_from_file_entry(&mut self, fp: &Path, entry: Option<&str>) -> io::Result<()> {
         // In case an error occurs and data is invalidated.
         self.loaded_rom_len = 0;
         self.loaded_rom_title_len = 0;
@@ -208,11 +448,81 @@ impl GamePakRom {
         // Loads a binary ROM from a given file and
         // fills the remaining space with zero bytes.
         trace!("Loading ROM file `{}`.", fp.display());
-        let rbytes = try!(try!(File::open(fp)).read(&mut *self.raw_bytes));
+        let rbytes = try!(read_image_file(fp, entry, ROM_EXTENSIONS, &mut *self.raw_bytes));
         for i in rbytes..MAX_GBA_ROM_SIZE { self.raw_bytes[i] = 0 };
         self.loaded_rom_len = rbytes;
+        self.decode_title_len();
+
+        // Done.
+        Ok(())
+    }
+
+    /// Calculates the CRC32 of the loaded ROM.
+    ///
+    /// This identifies the game a save state belongs to.
+    pub fn crc32(&self) -> u32 {
+        crc32(&self.raw_bytes[..self.loaded_rom_len])
+    }
+
+    /// Zero-fills the whole ROM and forgets the loaded game.
+    pub fn clear(&mut self) {
+        for i in 0..MAX_GBA_ROM_SIZE { self.raw_bytes[i] = 0 };
+        self.loaded_rom_len = 0;
+        self.loaded_rom_title_len = 0;
+    }
+
+    /// Copies a block of data into the ROM.
+    ///
+    /// This is used to assemble a ROM from e.g. the
+    /// segments of an ELF file. The loaded ROM's size
+    /// grows to cover the new data.
+    ///
+    /// # Params
+    /// - `offs`: The ROM offset to copy the data to.
+    /// - `data`: The data to copy.
+    ///
+    /// # Returns
+    /// `false` if the data does not fit into the ROM.
+    pub fn write_image(&mut self, offs: u32, data: &[u8]) -> bool {
+        let offs = offs as usize;
+        if (offs + data.len()) > MAX_GBA_ROM_SIZE { return false; }
+        self.raw_bytes[offs..(offs + data.len())].copy_from_slice(data);
+        if (offs + data.len()) > self.loaded_rom_len { self.loaded_rom_len = offs + data.len(); }
+        self.decode_title_len();
+        true
+    }
+
+    /// Soft-patches the loaded ROM with an IPS, UPS, or BPS patch.
+    ///
+    /// The patch is applied to a copy of the ROM first. If
+    /// anything goes wrong, the loaded ROM stays unchanged.
+    /// The ROM file itself is never modified.
+    ///
+    /// # Params
+    /// - `fp`: Path to the patch file.
+    ///
+    /// # Returns
+    /// - `Ok`: The format of the applied patch.
+    /// - `Err`: The patch is unreadable, broken, or belongs to another ROM.
+    pub fn apply_patch_file(&mut self, fp: &Path) -> io::Result<PatchFormat> {
+        trace!("Loading patch file `{}`.", fp.display());
+        let mut patch = Vec::new();
+        try!(try!(File::open(fp)).read_to_end(&mut patch));
+        let format = try!(PatchFormat::detect(&patch).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, PatchError::UnknownFormat)
+        }));
+
+        let target = try!(apply_patch(&patch, &self.raw_bytes[..self.loaded_rom_len], MAX_GBA_ROM_SIZE)
+                          .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)));
+        self.raw_bytes[..target.len()].copy_from_slice(&target);
+        for i in target.len()..self.loaded_rom_len { self.raw_bytes[i] = 0 };
+        self.loaded_rom_len = target.len();
+        self.decode_title_len();
+        Ok(format)
+    }
 
-        // Decode the game's title's length without zero bytes.
+    // Decode the game's title's length without zero bytes.
+    fn decode_title_len(&mut self) {
         self.loaded_rom_title_len = GAME_TITLE_MAX_LEN;
         for i in 0..GAME_TITLE_MAX_LEN {
             if self.raw_bytes[GAME_TITLE_OFFSET + i] == 0 {
@@ -220,9 +530,29 @@ impl GamePakRom {
                 break;
             }
         }
+    }
 
-        // Done.
-        Ok(())
+    /// Writes a copy of the loaded ROM with a fixed header checksum.
+    ///
+    /// Only the complement check byte is changed. The ROM
+    /// loaded into the emulator remains untouched.
+    ///
+    /// # Params
+    /// - `fp`: Path to the new ROM file. Must not be the original file.
+    ///
+    /// # Returns
+    /// - `Ok` if written successfully.
+    /// - `Err` if no ROM with a full header is loaded or writing failed.
+    pub fn write_fixed_copy(&self, fp: &Path) -> io::Result<()> {
+        if self.loaded_rom_len < ROM_HEADER_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "The loaded ROM has no complete header."));
+        }
+        trace!("Writing a fixed ROM copy to `{}`.", fp.display());
+        let complement = self.header().calculated_complement();
+        let mut f = try!(File::create(fp));
+        try!(f.write_all(&self.raw_bytes[..COMPLEMENT_CHECK_OFFSET]));
+        try!(f.write_all(&[complement]));
+        f.write_all(&self.raw_bytes[(COMPLEMENT_CHECK_OFFSET + 1)..self.loaded_rom_len])
     }
 }
 
@@ -295,6 +625,8 @@ impl Default for GamePakSram {
 pub struct GamePak {
     rom: GamePakRom,
     sram: GamePakSram,
+    gpio: GpioPort,
+    peripheral: GamePakPeripheral,
 }
 
 impl GamePak {
@@ -303,6 +635,8 @@ impl GamePak {
         GamePak {
             rom: GamePakRom::new(),
             sram: GamePakSram::new(),
+            gpio: GpioPort::new(),
+            peripheral: GamePakPeripheral::None,
         }
     }
 
@@ -320,6 +654,112 @@ impl GamePak {
 
     /// Get the GamePak's SRAM.
     pub fn sram_mut(&mut self) -> &mut GamePakSram { &mut self.sram }
+
+    /// Get the GamePak's extra hardware.
+    pub fn peripheral(&self) -> &GamePakPeripheral { &self.peripheral }
+
+    /// Get the GamePak's extra hardware.
+    ///
+    /// Use this to feed sensors with new input values.
+    pub fn peripheral_mut(&mut self) -> &mut GamePakPeripheral { &mut self.peripheral }
+
+    /// Plugs new extra hardware into the GamePak.
+    ///
+    /// This also resets the GPIO port.
+    pub fn plug_peripheral(&mut self, p: GamePakPeripheral) {
+        debug!("Plugging GamePak peripheral: {}", p);
+        self.peripheral = p;
+        self.gpio = GpioPort::new();
+    }
+
+    /// Plugs in the extra hardware expected by the loaded ROM.
+    ///
+    /// # Returns
+    /// `true` if any extra hardware has been detected.
+    pub fn plug_detected_peripheral(&mut self) -> bool {
+        let p = GamePakPeripheral::detect(self.rom.header().game_code());
+        let detected = match p { GamePakPeripheral::None => false, _ => true };
+        self.plug_peripheral(p);
+        detected
+    }
+
+    fn gpio_halfword(&self, offs: u32) -> Option<u16> {
+        if self.peripheral.uses_gpio() & GpioPort::contains(offs) { self.gpio.read_halfword(offs) }
+        else { None }
+    }
+
+    /// Reads a byte from the ROM area.
+    ///
+    /// This also handles reading the GPIO port.
+    pub fn load_rom_byte(&self, offs: u32) -> u8 {
+        match self.gpio_halfword(offs) {
+            Some(x) => (x >> (8 * (offs & 0b1))) as u8,
+            None    => self.rom.read_byte(offs),
+        }
+    }
+
+    /// Reads a halfword from the ROM area.
+    ///
+    /// This also handles reading the GPIO port.
+    pub fn load_rom_halfword(&self, offs: u32) -> u16 {
+        self.gpio_halfword(offs).unwrap_or_else(|| self.rom.read_halfword(offs))
+    }
+
+    /// Reads a word from the ROM area.
+    ///
+    /// This also handles reading the GPIO port.
+    pub fn load_rom_word(&self, offs: u32) -> u32 {
+        let offs = offs & !0b11;
+        match (self.gpio_halfword(offs), self.gpio_halfword(offs + 2)) {
+            (None, None) => self.rom.read_word(offs),
+            (lo, hi) => {
+                let lo = lo.unwrap_or_else(|| self.rom.read_halfword(offs));
+                let hi = hi.unwrap_or_else(|| self.rom.read_halfword(offs + 2));
+                ((hi as u32) << 16) | (lo as u32)
+            },
+        }
+    }
+
+    /// Writes a halfword into the ROM area.
+    ///
+    /// Only the GPIO port is writable.
+    ///
+    /// # Returns
+    /// - `true`: The GPIO port handled the write.
+    /// - `false`: Tried writing to the actual ROM.
+    pub fn store_rom_halfword(&mut self, offs: u32, data: u16) -> bool {
+        if !(self.peripheral.uses_gpio() & GpioPort::contains(offs)) { return false; }
+        self.gpio.write_halfword(offs, data, &mut self.peripheral);
+        true
+    }
+
+    /// Reads a byte from the SRAM area.
+    ///
+    /// This also handles peripherals mapped into the SRAM area.
+    pub fn load_sram_byte(&self, offs: u32) -> u8 {
+        self.peripheral.load_sram_byte(offs).unwrap_or_else(|| self.sram.read_byte(offs))
+    }
+
+    /// Writes a byte into the SRAM area.
+    ///
+    /// This also handles peripherals mapped into the SRAM area.
+    pub fn store_sram_byte(&mut self, offs: u32, data: u8) {
+        if !self.peripheral.store_sram_byte(offs, data) { self.sram.write_byte(offs, data); }
+    }
+}
+
+impl SaveState for GamePak {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(self.sram.bytes(0));
+        self.gpio.save_state(w);
+        self.peripheral.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> io::Result<()> {
+        try!(r.read_bytes(self.sram.bytes_mut(0)));
+        try!(self.gpio.load_state(r));
+        self.peripheral.load_state(r)
+    }
 }
 
 
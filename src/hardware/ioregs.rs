@@ -5,28 +5,301 @@
 #![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
 #![warn(missing_docs)]
 
+use std::cmp;
+use std::io;
+use super::keypad::KEYS_ALL;
 use super::memory::IO_REGISTERS_LEN;
 use super::memory::{RawBytes, Rom8, Rom16, Rom32, Ram8, Ram16, Ram32};
+use super::savestate::{SaveState, StateWriter, StateReader, invalid_state};
+
+
+/// Offset of the LCD status register `DISPSTAT`.
+pub const DISPSTAT_OFFSET: u32 = 0x004;
+
+/// The read-only `DISPSTAT` flags for V-Blank, H-Blank, and a V-Counter match.
+pub const DISPSTAT_FLAGS: u16 = 0b111;
+
+/// Offset of the read-only scanline counter `VCOUNT`.
+pub const VCOUNT_OFFSET: u32 = 0x006;
+
+/// CPU cycles the LCD spends drawing a scanline before H-Blank.
+pub const HDRAW_CYCLES: u32 = 960;
+
+/// CPU cycles per scanline, including H-Blank.
+pub const SCANLINE_CYCLES: u32 = 1232;
+
+/// The first scanline of V-Blank.
+pub const VBLANK_FIRST_LINE: u16 = 160;
+
+/// Scanlines per frame, including V-Blank.
+pub const FRAME_LINES: u16 = 228;
+
+/// Offset of the read-only keypad status register `KEYINPUT`.
+///
+/// A 0-bit means the corresponding key is pressed.
+pub const KEYINPUT_OFFSET: u32 = 0x130;
+
+/// Offset of the keypad interrupt control register `KEYCNT`.
+pub const KEYCNT_OFFSET: u32 = 0x132;
+
+/// The `KEYCNT` bit enabling the keypad interrupt.
+pub const KEYCNT_IRQ_ENABLE: u16 = 1 << 14;
+
+/// The `KEYCNT` bit requiring all selected keys instead of any.
+pub const KEYCNT_IRQ_AND: u16 = 1 << 15;
+
+/// Offset of the interrupt enable register `IE`.
+pub const IE_OFFSET: u32 = 0x200;
+
+/// Offset of the interrupt request flags register `IF`.
+///
+/// Writing a 1-bit clears the corresponding flag.
+pub const IF_OFFSET: u32 = 0x202;
+
+/// Offset of the interrupt master enable register `IME`.
+pub const IME_OFFSET: u32 = 0x208;
+
+/// Offset of the low-power mode control register `HALTCNT`.
+pub const HALTCNT_OFFSET: u32 = 0x301;
+
+/// All interrupt sources that may wake the CPU from `Stop` mode.
+pub const STOP_WAKE_UP_INTERRUPTS: u16 = (1 << (Interrupt::Serial as u8))
+                                       | (1 << (Interrupt::Keypad as u8))
+                                       | (1 << (Interrupt::GamePak as u8));
+
+
+/// The values of IO registers the BIOS changes while booting.
+///
+/// These are the affine BG matrices' diagonals, `SOUNDBIAS`,
+/// `RCNT`, and `POSTFLG`, as pairs of offset and halfword.
+pub const POST_BIOS_VALUES: &'static [(u32, u16)] = &[
+    (0x020, 0x0100), // BG2PA
+    (0x026, 0x0100), // BG2PD
+    (0x030, 0x0100), // BG3PA
+    (0x036, 0x0100), // BG3PD
+    (0x088, 0x0200), // SOUNDBIAS
+    (0x134, 0x8000), // RCNT
+    (0x300, 0x0001), // POSTFLG
+];
+
+
+/// All interrupt sources in the order of their `IE`/`IF` bits.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[repr(u8)]
+pub enum Interrupt {
+    #[doc = "LCD entered V-Blank."]               VBlank = 0,
+    #[doc = "LCD entered H-Blank."]               HBlank,
+    #[doc = "LCD reached the V-Counter match."]   VCounter,
+    #[doc = "Timer 0 overflowed."]                Timer0,
+    #[doc = "Timer 1 overflowed."]                Timer1,
+    #[doc = "Timer 2 overflowed."]                Timer2,
+    #[doc = "Timer 3 overflowed."]                Timer3,
+    #[doc = "Serial communication."]              Serial,
+    #[doc = "DMA 0 finished."]                    Dma0,
+    #[doc = "DMA 1 finished."]                    Dma1,
+    #[doc = "DMA 2 finished."]                    Dma2,
+    #[doc = "DMA 3 finished."]                    Dma3,
+    #[doc = "Keypad condition met."]              Keypad,
+    #[doc = "GamePak removed or external IRQ."]   GamePak,
+}
+
+impl Interrupt {
+    /// Get the interrupt's bit in `IE` and `IF`.
+    pub fn mask(self) -> u16 { 1 << (self as u8) }
+}
+
+
+/// The low-power modes selectable via `HALTCNT`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LowPowerMode {
+    #[doc = "The CPU sleeps until any enabled interrupt is requested."] Halt,
+    #[doc = "Almost everything sleeps until a keypad, serial, or GamePak interrupt."] Stop,
+}
 
 
 /// All memory-mapped GBA IO registers.
-pub struct IoRegisters(Box<[u8; IO_REGISTERS_LEN as usize]>);
+pub struct IoRegisters {
+    raw: Box<[u8; IO_REGISTERS_LEN as usize]>,
+    low_power_request: Option<LowPowerMode>,
+    lcd_cycles: u32,
+}
 
 impl IoRegisters {
     /// Creates new zero initialised IO registers.
+    ///
+    /// `KEYINPUT` reports all keys as released.
     pub fn new() -> IoRegisters {
-        IoRegisters(box [0; IO_REGISTERS_LEN as usize])
+        let mut io = IoRegisters {
+            raw: box [0; IO_REGISTERS_LEN as usize],
+            low_power_request: None,
+            lcd_cycles: 0,
+        };
+        io.write_halfword(KEYINPUT_OFFSET, KEYS_ALL);
+        io
     }
 
     /// Zero-fills all IO registers.
+    ///
+    /// `KEYINPUT` reports all keys as released.
     pub fn clear(&mut self) {
-        for i in 0..(IO_REGISTERS_LEN as usize) { (*self.0)[i] = 0 };
+        for i in 0..(IO_REGISTERS_LEN as usize) { (*self.raw)[i] = 0 };
+        self.write_halfword(KEYINPUT_OFFSET, KEYS_ALL);
+        self.low_power_request = None;
+        self.lcd_cycles = 0;
+    }
+
+    /// Resets all IO registers to the state the BIOS leaves them in.
+    pub fn init_post_bios(&mut self) {
+        self.clear();
+        for &(offs, data) in POST_BIOS_VALUES { self.write_halfword(offs, data); }
+    }
+
+    /// Stores a byte like the CPU would do.
+    ///
+    /// Unlike `write_byte`, this respects the side
+    /// effects of writing special registers.
+    pub fn store_byte(&mut self, offs: u32, data: u8) {
+        match offs {
+            DISPSTAT_OFFSET => { let old = self.read_byte(offs); self.write_byte(offs, (old & DISPSTAT_FLAGS as u8) | (data & !DISPSTAT_FLAGS as u8)); },
+            x if (x & !1) == VCOUNT_OFFSET => {},
+            x if (x & !1) == IF_OFFSET => { let old = self.read_byte(offs); self.write_byte(offs, old & !data); },
+            x if (x & !1) == KEYINPUT_OFFSET => {},
+            x if (x & !1) == KEYCNT_OFFSET => { self.write_byte(offs, data); self.check_keypad_interrupt(); },
+            HALTCNT_OFFSET => {
+                self.write_byte(offs, data);
+                self.low_power_request = Some(if 0 == (data & 0x80) { LowPowerMode::Halt } else { LowPowerMode::Stop });
+            },
+            _ => self.write_byte(offs, data),
+        }
+    }
+
+    /// Stores a halfword like the CPU would do.
+    pub fn store_halfword(&mut self, offs: u32, data: u16) {
+        let offs = offs & !0b1;
+        self.store_byte(offs,     data as u8);
+        self.store_byte(offs + 1, (data >> 8) as u8);
+    }
+
+    /// Stores a word like the CPU would do.
+    pub fn store_word(&mut self, offs: u32, data: u32) {
+        let offs = offs & !0b11;
+        self.store_halfword(offs,     data as u16);
+        self.store_halfword(offs + 2, (data >> 16) as u16);
+    }
+
+    /// Get all enabled and requested interrupts.
+    pub fn pending_interrupts(&self) -> u16 {
+        self.read_halfword(IE_OFFSET) & self.read_halfword(IF_OFFSET)
+    }
+
+    /// Checks whether `IME` enables interrupts at all.
+    pub fn interrupt_master_enable(&self) -> bool {
+        0 != (self.read_halfword(IME_OFFSET) & 0b1)
+    }
+
+    /// Sets or clears `IME`.
+    pub fn set_interrupt_master_enable(&mut self, enable: bool) {
+        self.write_halfword(IME_OFFSET, enable as u16);
+    }
+
+    /// Requests an interrupt by setting its `IF` bit.
+    pub fn request_interrupt(&mut self, irq: Interrupt) {
+        let flags = self.read_halfword(IF_OFFSET);
+        self.write_halfword(IF_OFFSET, flags | irq.mask());
+    }
+
+    /// Get the mask of all currently pressed keys.
+    pub fn keys(&self) -> u16 {
+        !self.read_halfword(KEYINPUT_OFFSET) & KEYS_ALL
+    }
+
+    /// Updates `KEYINPUT` with the currently pressed keys.
+    ///
+    /// Requests a keypad interrupt if `KEYCNT` enables it
+    /// and the pressed keys meet its condition.
+    ///
+    /// # Params
+    /// - `pressed`: A mask of all pressed keys.
+    pub fn set_keys(&mut self, pressed: u16) {
+        self.write_halfword(KEYINPUT_OFFSET, !pressed & KEYS_ALL);
+        self.check_keypad_interrupt();
+    }
+
+    fn check_keypad_interrupt(&mut self) {
+        let keycnt = self.read_halfword(KEYCNT_OFFSET);
+        if 0 == (keycnt & KEYCNT_IRQ_ENABLE) { return; }
+        let (selected, pressed) = (keycnt & KEYS_ALL, self.keys() & keycnt);
+        let met = if 0 != (keycnt & KEYCNT_IRQ_AND) { (selected != 0) & (pressed == selected) }
+                  else { pressed != 0 };
+        if met { self.request_interrupt(Interrupt::Keypad); }
+    }
+
+    /// Advances the LCD timing by some CPU cycles.
+    ///
+    /// Updates `VCOUNT` and the `DISPSTAT` flags, and requests
+    /// V-Blank, H-Blank, and V-Counter interrupts if `DISPSTAT`
+    /// enables them. Nothing is drawn yet.
+    pub fn advance_lcd(&mut self, mut cycles: u32) {
+        while cycles > 0 {
+            let n = cmp::min(cycles, self.cycles_until_lcd_event());
+            self.lcd_cycles += n;
+            cycles -= n;
+            if self.lcd_cycles == HDRAW_CYCLES { self.enter_hblank(); }
+            else if self.lcd_cycles == SCANLINE_CYCLES { self.enter_scanline(); }
+        }
+    }
+
+    /// Get the number of CPU cycles until the LCD enters
+    /// H-Blank or the next scanline.
+    ///
+    /// Nothing changes in between, so a halted CPU can
+    /// skip this many cycles at once.
+    pub fn cycles_until_lcd_event(&self) -> u32 {
+        if self.lcd_cycles < HDRAW_CYCLES { HDRAW_CYCLES - self.lcd_cycles }
+        else { SCANLINE_CYCLES - self.lcd_cycles }
+    }
+
+    /// Get the mask of interrupts the LCD will request
+    /// on its own, as enabled by both `DISPSTAT` and `IE`.
+    pub fn scheduled_interrupts(&self) -> u16 {
+        let dispstat = self.read_halfword(DISPSTAT_OFFSET);
+        let mut mask = 0;
+        if 0 != (dispstat & 0x0008) { mask |= Interrupt::VBlank.mask(); }
+        if 0 != (dispstat & 0x0010) { mask |= Interrupt::HBlank.mask(); }
+        if 0 != (dispstat & 0x0020) { mask |= Interrupt::VCounter.mask(); }
+        mask & self.read_halfword(IE_OFFSET)
+    }
+
+    fn enter_hblank(&mut self) {
+        let dispstat = self.read_halfword(DISPSTAT_OFFSET) | 0b010;
+        self.write_halfword(DISPSTAT_OFFSET, dispstat);
+        if 0 != (dispstat & 0x0010) { self.request_interrupt(Interrupt::HBlank); }
+    }
+
+    fn enter_scanline(&mut self) {
+        self.lcd_cycles = 0;
+        let line = (self.read_halfword(VCOUNT_OFFSET) + 1) % FRAME_LINES;
+        self.write_halfword(VCOUNT_OFFSET, line);
+
+        let vblank  = (line >= VBLANK_FIRST_LINE) & (line < (FRAME_LINES - 1));
+        let vcmatch = line == (self.read_halfword(DISPSTAT_OFFSET) >> 8);
+        let dispstat = (self.read_halfword(DISPSTAT_OFFSET) & !DISPSTAT_FLAGS)
+                     | (vblank as u16) | ((vcmatch as u16) << 2);
+        self.write_halfword(DISPSTAT_OFFSET, dispstat);
+
+        if (line == VBLANK_FIRST_LINE) & (0 != (dispstat & 0x0008)) { self.request_interrupt(Interrupt::VBlank); }
+        if vcmatch & (0 != (dispstat & 0x0020)) { self.request_interrupt(Interrupt::VCounter); }
+    }
+
+    /// Takes the low-power mode requested by writing `HALTCNT`.
+    pub fn take_low_power_request(&mut self) -> Option<LowPowerMode> {
+        self.low_power_request.take()
     }
 }
 
 impl RawBytes for IoRegisters {
-    fn bytes(&self, offs: u32) -> &[u8] { &(*self.0)[(offs as usize)..] }
-    fn bytes_mut(&mut self, offs: u32) -> &mut [u8] { &mut (*self.0)[(offs as usize)..] }
+    fn bytes(&self, offs: u32) -> &[u8] { &(*self.raw)[(offs as usize)..] }
+    fn bytes_mut(&mut self, offs: u32) -> &mut [u8] { &mut (*self.raw)[(offs as usize)..] }
 }
 
 impl Rom8  for IoRegisters {}
@@ -36,6 +309,31 @@ impl Ram8  for IoRegisters {}
 impl Ram16 for IoRegisters {}
 impl Ram32 for IoRegisters {}
 
+impl SaveState for IoRegisters {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&*self.raw);
+        w.write_u8(match self.low_power_request {
+            None                     => 0,
+            Some(LowPowerMode::Halt) => 1,
+            Some(LowPowerMode::Stop) => 2,
+        });
+        w.write_u32(self.lcd_cycles);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> io::Result<()> {
+        try!(r.read_bytes(&mut *self.raw));
+        self.low_power_request = match try!(r.read_u8()) {
+            0 => None,
+            1 => Some(LowPowerMode::Halt),
+            2 => Some(LowPowerMode::Stop),
+            _ => { return Err(invalid_state("Unknown low-power mode.")); },
+        };
+        self.lcd_cycles = try!(r.read_u32());
+        if self.lcd_cycles >= SCANLINE_CYCLES { return Err(invalid_state("LCD cycle count out of range.")); }
+        Ok(())
+    }
+}
+
 impl Default for IoRegisters {
     fn default() -> IoRegisters { IoRegisters::new() }
 }
@@ -0,0 +1,136 @@
+// License below.
+//! Implements the GBA's keypad and a mapping from frontend inputs to keys.
+//!
+//! Frontends translate whatever they receive, e.g. keyboard
+//! keys or words of a script, into GBA keys using a `KeyMap`,
+//! and then pass the set of pressed keys to `Gba::set_keys`.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::collections::HashMap;
+
+#[cfg(test)]
+mod test;
+
+
+/// A mask of all keys in `KEYINPUT` and `KEYCNT`.
+pub const KEYS_ALL: u16 = 0x03FF;
+
+
+/// All GBA keys in the order of their `KEYINPUT` bits.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[repr(u8)]
+pub enum Key {
+    #[doc = "Button A."]                  A = 0,
+    #[doc = "Button B."]                  B,
+    #[doc = "The select button."]         Select,
+    #[doc = "The start button."]          Start,
+    #[doc = "Right on the D-pad."]        Right,
+    #[doc = "Left on the D-pad."]         Left,
+    #[doc = "Up on the D-pad."]           Up,
+    #[doc = "Down on the D-pad."]         Down,
+    #[doc = "The right shoulder button."] R,
+    #[doc = "The left shoulder button."]  L,
+}
+
+/// All GBA keys with their lower case names.
+pub const KEY_NAMES: &'static [(&'static str, Key)] = &[
+    ("a", Key::A), ("b", Key::B), ("select", Key::Select), ("start", Key::Start),
+    ("right", Key::Right), ("left", Key::Left), ("up", Key::Up), ("down", Key::Down),
+    ("r", Key::R), ("l", Key::L),
+];
+
+impl Key {
+    /// Get the key's bit in `KEYINPUT` and `KEYCNT`.
+    pub fn mask(self) -> u16 { 1 << (self as u8) }
+}
+
+
+/// Maps the inputs of a frontend to GBA keys.
+///
+/// Inputs are arbitrary names like `x` or `return`, compared
+/// case-insensitively. A new key map binds the GBA keys'
+/// own names, e.g. `start`, which suits scripted input.
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    bindings: HashMap<String, Key>,
+}
+
+impl KeyMap {
+    /// Creates a key map binding all GBA key names.
+    pub fn new() -> KeyMap {
+        let mut km = KeyMap::empty();
+        for &(name, key) in KEY_NAMES { km.bind(name, key); }
+        km
+    }
+
+    /// Creates a key map without any bindings.
+    pub fn empty() -> KeyMap {
+        KeyMap { bindings: HashMap::new() }
+    }
+
+    /// Binds an input to a GBA key, replacing any previous binding.
+    pub fn bind(&mut self, input: &str, key: Key) {
+        self.bindings.insert(input.to_lowercase(), key);
+    }
+
+    /// Removes an input's binding.
+    ///
+    /// # Returns
+    /// The key the input was bound to, if any.
+    pub fn unbind(&mut self, input: &str) -> Option<Key> {
+        self.bindings.remove(&input.to_lowercase())
+    }
+
+    /// Get the key an input is bound to.
+    pub fn key(&self, input: &str) -> Option<Key> {
+        self.bindings.get(&input.to_lowercase()).cloned()
+    }
+
+    /// Translates a set of inputs to a mask of pressed keys.
+    ///
+    /// # Params
+    /// - `inputs`: All currently active inputs.
+    ///
+    /// # Returns
+    /// - `Ok`: The mask of all pressed keys.
+    /// - `Err`: The first input that is not bound to any key.
+    pub fn keys<'a, I>(&self, inputs: I) -> Result<u16, &'a str>
+    where I: IntoIterator<Item = &'a str> {
+        let mut mask = 0;
+        for i in inputs {
+            match self.key(i) {
+                Some(k) => { mask |= k.mask(); },
+                None    => { return Err(i); },
+            }
+        }
+        Ok(mask)
+    }
+}
+
+impl Default for KeyMap {
+    fn default() -> KeyMap { KeyMap::new() }
+}
+
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
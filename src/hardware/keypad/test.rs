@@ -0,0 +1,113 @@
+// License below.
+#![allow(missing_docs)]
+
+use super::*;
+use super::super::Gba;
+use super::super::ioregs::*;
+use super::super::memory::Rom16;
+
+fn keypad_irq_requested(io: &IoRegisters) -> bool {
+    0 != (io.read_halfword(IF_OFFSET) & Interrupt::Keypad.mask())
+}
+
+#[test]
+pub fn key_map() {
+    let mut km = KeyMap::new();
+    assert_eq!(km.key("Start"), Some(Key::Start));
+    assert_eq!(km.keys(vec!["a", "UP"]), Ok(Key::A.mask() | Key::Up.mask()));
+    km.bind("x", Key::A);
+    assert_eq!(km.keys(vec!["x", "b"]), Ok(Key::A.mask() | Key::B.mask()));
+    assert_eq!(km.unbind("x"), Some(Key::A));
+    assert_eq!(km.keys(vec!["b", "x"]), Err("x"));
+    assert_eq!(KeyMap::empty().key("a"), None);
+}
+
+#[test]
+pub fn keyinput_is_active_low() {
+    let mut io = IoRegisters::new();
+    assert_eq!(io.read_halfword(KEYINPUT_OFFSET), KEYS_ALL);
+    io.set_keys(Key::A.mask() | Key::L.mask());
+    assert_eq!(io.read_halfword(KEYINPUT_OFFSET), 0x01FE);
+    assert_eq!(io.keys(), Key::A.mask() | Key::L.mask());
+
+    // Games cannot press keys.
+    io.store_halfword(KEYINPUT_OFFSET, 0);
+    assert_eq!(io.read_halfword(KEYINPUT_OFFSET), 0x01FE);
+    io.clear();
+    assert_eq!(io.keys(), 0);
+}
+
+#[test]
+pub fn keypad_interrupt_conditions() {
+    let ab = Key::A.mask() | Key::B.mask();
+
+    // Logical OR: any selected key.
+    let mut io = IoRegisters::new();
+    io.store_halfword(KEYCNT_OFFSET, KEYCNT_IRQ_ENABLE | ab);
+    io.set_keys(Key::Start.mask());
+    assert!(!keypad_irq_requested(&io));
+    io.set_keys(Key::B.mask());
+    assert!(keypad_irq_requested(&io));
+
+    // Logical AND: all selected keys.
+    let mut io = IoRegisters::new();
+    io.store_halfword(KEYCNT_OFFSET, KEYCNT_IRQ_ENABLE | KEYCNT_IRQ_AND | ab);
+    io.set_keys(Key::B.mask());
+    assert!(!keypad_irq_requested(&io));
+    io.set_keys(ab | Key::Start.mask());
+    assert!(keypad_irq_requested(&io));
+
+    // Disabled.
+    let mut io = IoRegisters::new();
+    io.store_halfword(KEYCNT_OFFSET, ab);
+    io.set_keys(ab);
+    assert!(!keypad_irq_requested(&io));
+
+    // Enabling while the condition is met.
+    io.store_halfword(KEYCNT_OFFSET, KEYCNT_IRQ_ENABLE | ab);
+    assert!(keypad_irq_requested(&io));
+}
+
+#[test]
+pub fn wake_up_from_stop() {
+    let mut gba = Gba::new();
+    gba.boot_post_bios(0x02000000);
+    {
+        let mut bus = gba.bus_mut();
+        bus.store_word(0x02000000, 0xEAFFFFFE_u32 as i32).unwrap(); // b 0x02000000
+        bus.store_halfword(0x04000000 + KEYCNT_OFFSET, (KEYCNT_IRQ_ENABLE | Key::Start.mask()) as i32).unwrap();
+        bus.store_halfword(0x04000000 + IE_OFFSET, Interrupt::Keypad.mask() as i32).unwrap();
+        bus.store_byte(0x04000000 + HALTCNT_OFFSET, 0x80).unwrap();
+    }
+    gba.cpu_arm7tdmi_mut().pipeline_step().unwrap();
+    assert!(gba.cpu_arm7tdmi().is_sleeping());
+
+    gba.set_keys(Key::A.mask());
+    gba.cpu_arm7tdmi_mut().pipeline_step().unwrap();
+    assert!(gba.cpu_arm7tdmi().is_sleeping());
+
+    gba.set_keys(Key::Start.mask());
+    gba.cpu_arm7tdmi_mut().pipeline_step().unwrap();
+    assert!(!gba.cpu_arm7tdmi().is_sleeping());
+}
+
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
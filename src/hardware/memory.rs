@@ -7,9 +7,9 @@
 
 use byteorder::{ByteOrder, LittleEndian};
 use std::io;
-use std::io::Read;
-use std::fs::File;
 use std::path::Path;
+use super::romfile::{read_image_file, BIOS_EXTENSIONS, MULTIBOOT_EXTENSIONS};
+use super::openbios::OPEN_BIOS;
 
 
 /// Address of the first byte of BIOS system ROM.
@@ -30,6 +30,9 @@ pub const WRAM_ON_BOARD_LAST: u32 = 0x0203FFFF;
 /// Length of the on-board WRAM area in bytes.
 pub const WRAM_ON_BOARD_LEN: u32 = (WRAM_ON_BOARD_LAST+1) - WRAM_ON_BOARD_FIRST;
 
+/// Address of the last byte mirroring on-board WRAM.
+pub const WRAM_ON_BOARD_MIRROR_LAST: u32 = 0x02FFFFFF;
+
 /// Address of the first byte of on-chip WRAM.
 pub const WRAM_ON_CHIP_FIRST: u32 = 0x03000000;
 
@@ -39,6 +42,12 @@ pub const WRAM_ON_CHIP_LAST: u32 = 0x03007FFF;
 /// Length of the on-chip WRAM area in bytes.
 pub const WRAM_ON_CHIP_LEN: u32 = (WRAM_ON_CHIP_LAST+1) - WRAM_ON_CHIP_FIRST;
 
+/// Address of the last byte mirroring on-chip WRAM.
+///
+/// The BIOS uses the mirror at the very end to find
+/// the user's IRQ handler at `0x03FFFFFC`.
+pub const WRAM_ON_CHIP_MIRROR_LAST: u32 = 0x03FFFFFF;
+
 /// Address of the first byte of IO registers.
 pub const IO_REGISTERS_FIRST: u32 = 0x04000000;
 
@@ -163,17 +172,17 @@ impl PhysicalAddress {
     /// A mapped local address.
     pub fn from_u32(p: u32) -> PhysicalAddress {
         match p {
-            BIOS_ROM_FIRST         ... BIOS_ROM_LAST         => PhysicalAddress::      BiosROM(p - BIOS_ROM_FIRST),
-            WRAM_ON_BOARD_FIRST    ... WRAM_ON_BOARD_LAST    => PhysicalAddress::  OnBoardWRAM(p - WRAM_ON_BOARD_FIRST),
-            WRAM_ON_CHIP_FIRST     ... WRAM_ON_CHIP_LAST     => PhysicalAddress::   OnChipWRAM(p - WRAM_ON_CHIP_FIRST),
-            IO_REGISTERS_FIRST     ... IO_REGISTERS_LAST     => PhysicalAddress::  RegistersIO(p - IO_REGISTERS_FIRST),
-            PALETTE_RAM_FIRST      ... PALETTE_RAM_LAST      => PhysicalAddress::   PaletteRAM(p - PALETTE_RAM_FIRST),
-            VRAM_FIRST             ... VRAM_LAST             => PhysicalAddress::         VRAM(p - VRAM_FIRST),
-            OBJ_ATTRIBUTES_FIRST   ... OBJ_ATTRIBUTES_LAST   => PhysicalAddress::AttributesOBJ(p - OBJ_ATTRIBUTES_FIRST),
-            GAME_PAK_WS0_ROM_FIRST ... GAME_PAK_WS0_ROM_LAST => PhysicalAddress::  GamePak0ROM(p - GAME_PAK_WS0_ROM_FIRST),
-            GAME_PAK_WS1_ROM_FIRST ... GAME_PAK_WS1_ROM_LAST => PhysicalAddress::  GamePak1ROM(p - GAME_PAK_WS1_ROM_FIRST),
-            GAME_PAK_WS2_ROM_FIRST ... GAME_PAK_WS2_ROM_LAST => PhysicalAddress::  GamePak2ROM(p - GAME_PAK_WS2_ROM_FIRST),
-            GAME_PAK_SRAM_FIRST    ... GAME_PAK_SRAM_LAST    => PhysicalAddress::  GamePakSRAM(p - GAME_PAK_SRAM_FIRST),
+            BIOS_ROM_FIRST         ... BIOS_ROM_LAST             => PhysicalAddress::      BiosROM(p - BIOS_ROM_FIRST),
+            WRAM_ON_BOARD_FIRST    ... WRAM_ON_BOARD_MIRROR_LAST => PhysicalAddress::  OnBoardWRAM((p - WRAM_ON_BOARD_FIRST) % WRAM_ON_BOARD_LEN),
+            WRAM_ON_CHIP_FIRST     ... WRAM_ON_CHIP_MIRROR_LAST  => PhysicalAddress::   OnChipWRAM((p - WRAM_ON_CHIP_FIRST) % WRAM_ON_CHIP_LEN),
+            IO_REGISTERS_FIRST     ... IO_REGISTERS_LAST         => PhysicalAddress::  RegistersIO(p - IO_REGISTERS_FIRST),
+            PALETTE_RAM_FIRST      ... PALETTE_RAM_LAST          => PhysicalAddress::   PaletteRAM(p - PALETTE_RAM_FIRST),
+            VRAM_FIRST             ... VRAM_LAST                 => PhysicalAddress::         VRAM(p - VRAM_FIRST),
+            OBJ_ATTRIBUTES_FIRST   ... OBJ_ATTRIBUTES_LAST       => PhysicalAddress::AttributesOBJ(p - OBJ_ATTRIBUTES_FIRST),
+            GAME_PAK_WS0_ROM_FIRST ... GAME_PAK_WS0_ROM_LAST     => PhysicalAddress::  GamePak0ROM(p - GAME_PAK_WS0_ROM_FIRST),
+            GAME_PAK_WS1_ROM_FIRST ... GAME_PAK_WS1_ROM_LAST     => PhysicalAddress::  GamePak1ROM(p - GAME_PAK_WS1_ROM_FIRST),
+            GAME_PAK_WS2_ROM_FIRST ... GAME_PAK_WS2_ROM_LAST     => PhysicalAddress::  GamePak2ROM(p - GAME_PAK_WS2_ROM_FIRST),
+            GAME_PAK_SRAM_FIRST    ... GAME_PAK_SRAM_LAST        => PhysicalAddress::  GamePakSRAM(p - GAME_PAK_SRAM_FIRST),
             _ => PhysicalAddress::Invalid(p),
         }
     }
@@ -328,9 +337,7 @@ pub trait Rom32 : RawBytes {
     /// as bounds checking should be done while converting
     /// global to local addresses.
     fn read_word(&self, offs: u32) -> u32 {
-        let w = LittleEndian::read_u32( self.bytes(offs & !0b11) );
-        if (offs & 0b11) != 0 { w.rotate_right(8 * (offs & 0b11)); }
-        w
+        LittleEndian::read_u32( self.bytes(offs & !0b11) )
     }
 }
 
@@ -353,17 +360,39 @@ pub trait Ram32 : Rom32 {
 
 
 /// Implements the BIOS ROM area.
-pub struct BiosRom(Box<[u8; BIOS_ROM_LEN]>);
+///
+/// Unless a BIOS dump is loaded, the ROM contains
+/// the built-in replacement BIOS.
+pub struct BiosRom {
+    data: Box<[u8; BIOS_ROM_LEN]>,
+    builtin: bool,
+}
 
 impl BiosRom {
-    /// Creates a new zero-filled BIOS ROM.
-    pub fn new() -> BiosRom { BiosRom(box [0_u8; BIOS_ROM_LEN]) }
+    /// Creates a new BIOS ROM containing the built-in replacement BIOS.
+    pub fn new() -> BiosRom {
+        let mut bios = BiosRom { data: box [0_u8; BIOS_ROM_LEN], builtin: false };
+        bios.load_builtin();
+        bios
+    }
+
+    /// Checks whether the ROM contains the built-in replacement BIOS.
+    pub fn is_builtin(&self) -> bool { self.builtin }
+
+    /// Replaces the ROM's contents with the built-in replacement BIOS.
+    ///
+    /// Unused memory is zero-filled.
+    pub fn load_builtin(&mut self) {
+        for i in 0..BIOS_ROM_LEN { self.data[i] = 0 };
+        for (i, &w) in OPEN_BIOS.iter().enumerate() { LittleEndian::write_u32(&mut self.data[(i * 4)..], w); }
+        self.builtin = true;
+    }
 
     /// Loads a ROM from a file.
     ///
-    /// Only ROMs up to 1KiB in size are valid.
-    /// Everything beyond that size will be silently
-    /// dropped.
+    /// Only ROMs up to 16KiB in size are valid.
+    /// The file may also be a `.gz` file or a
+    /// `.zip` archive containing a `.bin` file.
     ///
     /// Unused memory is zero-filled.
     ///
@@ -374,25 +403,122 @@ impl BiosRom {
     /// - `Ok` if loaded successfully.
     /// - `Err` if an error occurred. The previous data might be damaged.
     pub fn load_from_file(&mut self, fp: &Path) -> io::Result<()> {
+        self.load_from_file_entry(fp, None)
+    }
+
+    /// Loads a ROM from a file or a named entry in a `.zip` archive.
+    ///
+    /// # Params
+    /// - `fp`: Path to the ROM file to load.
+    /// - `entry`: Name of the `.zip` entry to load, if any.
+    ///
+    /// # Returns
+    /// - `Ok` if loaded successfully.
+    /// - `Err` if an error occurred or the ROM is larger than 16KiB.
+    ///   The previous data might be damaged.
+    pub fn load_from_file_entry(&mut self, fp: &Path, entry: Option<&str>) -> io::Result<()> {
         // Loads a binary ROM from a given file and
         // fills the remaining space with zero bytes.
         trace!("Loading BIOS ROM file `{}`.", fp.display());
-        let rbytes = try!(try!(File::open(fp)).read(&mut *self.0));
-        for i in rbytes..BIOS_ROM_LEN { self.0[i] = 0 };
+        self.builtin = false;
+        let rbytes = try!(read_image_file(fp, entry, BIOS_EXTENSIONS, &mut *self.data));
+        for i in rbytes..BIOS_ROM_LEN { self.data[i] = 0 };
         Ok(())
     }
 }
 
 impl RawBytes for BiosRom {
-    fn bytes(&self, offs: u32) -> &[u8] { &self.0[(offs as usize)..] }
-    fn bytes_mut(&mut self, offs: u32) -> &mut [u8] { &mut self.0[(offs as usize)..] }
+    fn bytes(&self, offs: u32) -> &[u8] { &self.data[(offs as usize)..] }
+    fn bytes_mut(&mut self, offs: u32) -> &mut [u8] { &mut self.data[(offs as usize)..] }
 }
 impl Rom8  for BiosRom {}
 impl Rom16 for BiosRom {}
 impl Rom32 for BiosRom {}
 
 impl Default for BiosRom {
-    fn default() -> BiosRom { BiosRom(box [0_u8; BIOS_ROM_LEN]) }
+    fn default() -> BiosRom { BiosRom::new() }
+}
+
+
+/// Implements the 256KiB on-board WRAM.
+///
+/// This is where multiboot images are loaded to.
+pub struct WramOnBoard(Box<[u8; WRAM_ON_BOARD_LEN as usize]>);
+
+impl WramOnBoard {
+    /// Creates a new zero-filled on-board WRAM.
+    pub fn new() -> WramOnBoard { WramOnBoard(box [0; WRAM_ON_BOARD_LEN as usize]) }
+
+    /// Zero-fills the whole WRAM.
+    pub fn clear(&mut self) {
+        for i in 0..(WRAM_ON_BOARD_LEN as usize) { (*self.0)[i] = 0 };
+    }
+
+    /// Loads a multiboot image from a file or a `.zip` archive.
+    ///
+    /// Only images up to 256KiB in size are valid.
+    /// Unused memory is zero-filled.
+    ///
+    /// # Params
+    /// - `fp`: Path to the image file to load.
+    /// - `entry`: Name of the `.zip` entry to load, if any.
+    ///
+    /// # Returns
+    /// - `Ok`: The size of the loaded image.
+    /// - `Err` if an error occurred or the image is too large.
+    ///   The previous data might be damaged.
+    pub fn load_from_file_entry(&mut self, fp: &Path, entry: Option<&str>) -> io::Result<usize> {
+        trace!("Loading multiboot image `{}`.", fp.display());
+        let rbytes = try!(read_image_file(fp, entry, MULTIBOOT_EXTENSIONS, &mut *self.0));
+        for i in rbytes..(WRAM_ON_BOARD_LEN as usize) { self.0[i] = 0 };
+        Ok(rbytes)
+    }
+}
+
+impl RawBytes for WramOnBoard {
+    fn bytes(&self, offs: u32) -> &[u8] { &(*self.0)[(offs as usize)..] }
+    fn bytes_mut(&mut self, offs: u32) -> &mut [u8] { &mut (*self.0)[(offs as usize)..] }
+}
+
+impl Rom8  for WramOnBoard {}
+impl Rom16 for WramOnBoard {}
+impl Rom32 for WramOnBoard {}
+impl Ram8  for WramOnBoard {}
+impl Ram16 for WramOnBoard {}
+impl Ram32 for WramOnBoard {}
+
+impl Default for WramOnBoard {
+    fn default() -> WramOnBoard { WramOnBoard::new() }
+}
+
+
+/// Implements the 32KiB on-chip WRAM.
+pub struct WramOnChip(Box<[u8; WRAM_ON_CHIP_LEN as usize]>);
+
+impl WramOnChip {
+    /// Creates a new zero-filled on-chip WRAM.
+    pub fn new() -> WramOnChip { WramOnChip(box [0; WRAM_ON_CHIP_LEN as usize]) }
+
+    /// Zero-fills the whole WRAM.
+    pub fn clear(&mut self) {
+        for i in 0..(WRAM_ON_CHIP_LEN as usize) { (*self.0)[i] = 0 };
+    }
+}
+
+impl RawBytes for WramOnChip {
+    fn bytes(&self, offs: u32) -> &[u8] { &(*self.0)[(offs as usize)..] }
+    fn bytes_mut(&mut self, offs: u32) -> &mut [u8] { &mut (*self.0)[(offs as usize)..] }
+}
+
+impl Rom8  for WramOnChip {}
+impl Rom16 for WramOnChip {}
+impl Rom32 for WramOnChip {}
+impl Ram8  for WramOnChip {}
+impl Ram16 for WramOnChip {}
+impl Ram32 for WramOnChip {}
+
+impl Default for WramOnChip {
+    fn default() -> WramOnChip { WramOnChip::new() }
 }
 
 
@@ -7,20 +7,43 @@
 
 use std::cell::{RefCell, Ref, RefMut};
 use std::rc::Rc;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
 
 use self::cpu::Arm7Tdmi;
 use self::bus::*;
+use self::memory::Ram8;
+use self::savestate::{SaveState, StateWriter, StateReader};
 pub use self::error::*;
 pub use self::gamepak::*;
+pub use self::peripherals::*;
+pub use self::keypad::{Key, KeyMap};
 
 
 pub mod cpu;
 pub mod memory;
+pub mod openbios;
 pub mod gamepak;
+pub mod checksum;
+pub mod romfile;
+pub mod patch;
+pub mod elf;
+pub mod peripherals;
 pub mod error;
 pub mod ioregs;
+pub mod keypad;
+pub mod savestate;
+pub mod rewind;
+pub mod watchpoint;
 pub mod bus;
 
+#[cfg(test)]
+pub mod test_util;
+#[cfg(test)]
+mod test;
+
 
 /// This is the actual GBA emulator. It handles all the virtual hardware,
 /// loads and saves ROMs and SRAMs, executes the CPU instructions, and
@@ -30,6 +53,7 @@ pub struct Gba {
     bus: Rc<RefCell<Bus>>,
     bios: Rc<RefCell<memory::BiosRom>>,
     game_pak: Rc<RefCell<GamePak>>,
+    symbols: elf::SymbolTable,
 }
 
 impl Gba {
@@ -43,6 +67,7 @@ impl Gba {
             bus: bus,
             bios: bios,
             game_pak: gpak,
+            symbols: elf::SymbolTable::new(),
         }
     }
 
@@ -69,6 +94,212 @@ impl Gba {
 
     /// Get a mutable reference to the bus system.
     pub fn bus_mut(&mut self) -> RefMut<Bus> { self.bus.borrow_mut() }
+
+    /// Get the debug symbols of the loaded executable.
+    pub fn symbols(&self) -> &elf::SymbolTable { &self.symbols }
+
+    /// Get the debug symbols of the loaded executable.
+    pub fn symbols_mut(&mut self) -> &mut elf::SymbolTable { &mut self.symbols }
+
+    /// Loads an ELF executable and prepares running it.
+    ///
+    /// All loadable segments are copied to their load
+    /// addresses in GamePak ROM, EWRAM, or IWRAM. The
+    /// symbol table replaces all previous symbols, and
+    /// the CPU starts at the ELF's entry point in the
+    /// post-BIOS state.
+    ///
+    /// # Params
+    /// - `fp`: Path to the ELF file.
+    ///
+    /// # Returns
+    /// - `Ok` if loaded successfully.
+    /// - `Err` if the file is invalid, its entry point is
+    ///   THUMB code, or a segment does not fit. Only in the
+    ///   latter case the previous data might be damaged.
+    pub fn load_elf_file(&mut self, fp: &Path) -> io::Result<()> {
+        let file = try!(elf::ElfFile::load_from_file(fp));
+        if 0 != (file.entry & 0b1) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("The ELF entry point {:#010X} is THUMB code, which is not supported yet.", file.entry)));
+        }
+        self.game_pak.borrow_mut().rom_mut().clear();
+        {
+            let mut bus = self.bus.borrow_mut();
+            for seg in &file.segments {
+                debug!("Loading ELF segment to {:#010X}, {} bytes.", seg.addr, seg.mem_size);
+                let mut data = seg.data.clone();
+                if (seg.mem_size as usize) > data.len() { data.resize(seg.mem_size as usize, 0); }
+                try!(bus.write_image(seg.addr, &data).map_err(|e| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("ELF segment does not fit: {}", e))
+                }));
+            }
+        }
+        self.symbols = file.symbols;
+        self.boot_post_bios(file.entry);
+        Ok(())
+    }
+
+    /// Loads a multiboot image and prepares running it.
+    ///
+    /// The image is loaded into on-board WRAM, just like the
+    /// BIOS would after a multiboot transfer over the link
+    /// cable. The boot mode and slave ID are patched into the
+    /// image's header, and the CPU starts executing the
+    /// branch at `0x020000C0` in the post-BIOS state.
+    ///
+    /// # Params
+    /// - `fp`: Path to the image file to load.
+    /// - `entry`: Name of the `.zip` entry to load, if any.
+    ///
+    /// # Returns
+    /// - `Ok` if loaded successfully.
+    /// - `Err` if an error occurred or the image is too large.
+    pub fn load_multiboot_file(&mut self, fp: &Path, entry: Option<&str>) -> io::Result<()> {
+        {
+            let mut bus = self.bus.borrow_mut();
+            let len = try!(bus.wram_on_board_mut().load_from_file_entry(fp, entry));
+            if len < MULTIBOOT_HEADER_LEN {
+                warn!("The multiboot image has only {} bytes and no complete header.", len);
+            }
+
+            // The BIOS tells the image how it has been booted.
+            let wram = bus.wram_on_board_mut();
+            wram.write_byte(MULTIBOOT_BOOT_MODE_OFFSET as u32, MULTIBOOT_BOOT_MODE_MULTIPLAY);
+            wram.write_byte(MULTIBOOT_SLAVE_ID_OFFSET as u32, MULTIBOOT_SLAVE_ID_FIRST);
+        }
+        self.boot_post_bios(memory::WRAM_ON_BOARD_FIRST + MULTIBOOT_ENTRY_BRANCH_OFFSET as u32);
+        Ok(())
+    }
+
+    /// Get the mask of all currently pressed keys.
+    pub fn keys(&self) -> u16 { self.bus.borrow().ioregs().keys() }
+
+    /// Presses and releases keys on the keypad.
+    ///
+    /// All keys in the mask are pressed, all others are
+    /// released. This raises a keypad interrupt if `KEYCNT`
+    /// asks for it, which also wakes the CPU from `Stop`.
+    ///
+    /// # Params
+    /// - `pressed`: A mask of `Key::mask` bits, e.g. as
+    ///   translated by a `KeyMap`.
+    pub fn set_keys(&mut self, pressed: u16) {
+        self.bus.borrow_mut().ioregs_mut().set_keys(pressed);
+    }
+
+    /// Takes a snapshot of the complete machine.
+    ///
+    /// The save state identifies the loaded ROM by its
+    /// CRC32, but neither contains the ROM nor the BIOS.
+    ///
+    /// # Returns
+    /// The save state including its header.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        savestate::write_header(&mut w, self.game_pak.borrow().rom().crc32());
+        self.save_machine(&mut w);
+        w.into_bytes()
+    }
+
+    /// Restores the complete machine from a snapshot.
+    ///
+    /// # Params
+    /// - `data`: A save state made by `save_state` for the loaded ROM.
+    ///
+    /// # Returns
+    /// - `Ok` if restored successfully.
+    /// - `Err` if the save state has been made for another
+    ///   ROM or format version, or if it is damaged. The
+    ///   machine is left untouched in either case.
+    pub fn load_state(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut r = StateReader::new(data);
+        try!(savestate::read_header(&mut r, self.game_pak.borrow().rom().crc32()));
+        self.load_machine(&mut r)
+    }
+
+    /// Takes a snapshot of the complete machine without a header.
+    ///
+    /// This is cheaper than `save_state`, as the ROM's CRC32
+    /// is not calculated. Snapshots are meant to be restored
+    /// within the same session, e.g. for rewinding.
+    pub fn snapshot(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        self.save_machine(&mut w);
+        w.into_bytes()
+    }
+
+    /// Restores the complete machine from a snapshot.
+    ///
+    /// # Params
+    /// - `data`: A snapshot made by `snapshot` for the loaded ROM.
+    ///
+    /// # Returns
+    /// - `Ok` if restored successfully.
+    /// - `Err` if the snapshot is damaged. The machine
+    ///   is left untouched.
+    pub fn restore_snapshot(&mut self, data: &[u8]) -> io::Result<()> {
+        self.load_machine(&mut StateReader::new(data))
+    }
+
+    fn save_machine(&self, w: &mut StateWriter) {
+        self.cpu.save_state(w);
+        self.bus.borrow().save_state(w);
+        self.game_pak.borrow().save_state(w);
+    }
+
+    fn load_machine(&mut self, r: &mut StateReader) -> io::Result<()> {
+        // Decode into a scratch machine first, such
+        // that damaged states don't change anything.
+        try!(Gba::new().decode_machine(&mut r.clone()));
+        self.decode_machine(r)
+    }
+
+    fn decode_machine(&mut self, r: &mut StateReader) -> io::Result<()> {
+        try!(self.cpu.load_state(r));
+        try!(self.bus.borrow_mut().load_state(r));
+        try!(self.game_pak.borrow_mut().load_state(r));
+        if r.remaining() != 0 { return Err(savestate::invalid_state("Trailing garbage.")); }
+        Ok(())
+    }
+
+    /// Saves a snapshot of the complete machine to a file.
+    ///
+    /// # Params
+    /// - `fp`: Path to the save state file to write.
+    pub fn save_state_file(&self, fp: &Path) -> io::Result<()> {
+        let data = self.save_state();
+        try!(File::create(fp)).write_all(&data)
+    }
+
+    /// Restores the complete machine from a save state file.
+    ///
+    /// # Params
+    /// - `fp`: Path to the save state file to load.
+    ///
+    /// # Returns
+    /// - `Ok` if restored successfully.
+    /// - `Err` just like `load_state`, or if reading the file failed.
+    pub fn load_state_file(&mut self, fp: &Path) -> io::Result<()> {
+        let mut data = Vec::new();
+        try!(try!(File::open(fp)).read_to_end(&mut data));
+        self.load_state(&data)
+    }
+
+    /// Skips the BIOS boot sequence.
+    ///
+    /// The CPU and IO registers are set up just like the
+    /// BIOS leaves them, and the CPU starts at the given
+    /// entry point. The BIOS stays mapped, so `SWI`s
+    /// still work.
+    ///
+    /// # Params
+    /// - `entry`: The address to start executing at,
+    ///   usually `0x08000000` for GamePak ROMs.
+    pub fn boot_post_bios(&mut self, entry: u32) {
+        self.bus.borrow_mut().ioregs_mut().init_post_bios();
+        self.cpu.boot_post_bios(entry);
+    }
 }
 
 impl Default for Gba {
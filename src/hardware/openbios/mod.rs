@@ -0,0 +1,310 @@
+// License below.
+//! Implements a small clean-room replacement for the GBA BIOS.
+//!
+//! Without a BIOS dump, the CPU would start executing
+//! zeros at the reset vector. This BIOS is written from
+//! the publicly documented behaviour of the original and
+//! offers just enough to run most games:
+//!
+//! - The reset vector sets up the stacks like the original
+//!   BIOS and jumps to the GamePak ROM in System mode.
+//! - The IRQ vector saves R0..R3, R12, and LR, and then calls
+//!   the game's IRQ handler stored at `0x03FFFFFC`.
+//! - The SWI vector dispatches through a table of all BIOS
+//!   functions. `SoftReset`, `HardReset`, `Halt`, `Stop`,
+//!   `CustomHalt`, `IntrWait`, `VBlankIntrWait`, `Div`,
+//!   `DivArm`, `Sqrt`, `CpuSet`, `CpuFastSet`, and
+//!   `GetBiosChecksum` are implemented. Everything else
+//!   returns immediately, so games should be run with
+//!   optimised BIOS functions in addition.
+//!
+//! There is no boot logo and the BIOS is ARM code only.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+#[cfg(test)]
+mod test;
+
+
+/// The assembled replacement BIOS, starting at address `0x00000000`.
+pub const OPEN_BIOS: &'static [u32] = &[
+    //       vectors:
+    0xEA000006, // 0000  b reset
+    0xE1B0F00E, // 0004  movs pc, lr                 Undefined instruction: skip it.
+    0xEA00001A, // 0008  b swi_handler
+    0xE25EF004, // 000C  subs pc, lr, #4             Prefetch abort: retry.
+    0xE25EF008, // 0010  subs pc, lr, #8             Data abort: retry.
+    0xEA000001, // 0014  b reset
+    0xEA000010, // 0018  b irq_handler
+    0xE25EF004, // 001C  subs pc, lr, #4             FIQ: ignore.
+    //       reset:
+    0xE3A000D2, // 0020  mov r0, #0xD2               IRQ mode, IRQs and FIQs disabled.
+    0xE129F000, // 0024  msr cpsr_fc, r0
+    0xE59FD348, // 0028  ldr sp, =0x03007FA0
+    0xE3A000D3, // 002C  mov r0, #0xD3               Supervisor mode.
+    0xE129F000, // 0030  msr cpsr_fc, r0
+    0xE59FD340, // 0034  ldr sp, =0x03007FE0
+    0xE3A0001F, // 0038  mov r0, #0x1F               System mode, IRQs and FIQs enabled.
+    0xE129F000, // 003C  msr cpsr_fc, r0
+    0xE59FD338, // 0040  ldr sp, =0x03007F00
+    0xE3A00000, // 0044  mov r0, #0
+    0xE3A01000, // 0048  mov r1, #0
+    0xE3A02000, // 004C  mov r2, #0
+    0xE3A03000, // 0050  mov r3, #0
+    0xE3A0C000, // 0054  mov r12, #0
+    0xE3A0E302, // 0058  mov lr, #0x08000000
+    0xE12FFF1E, // 005C  bx lr
+    //       irq_handler:
+    0xE92D500F, // 0060  stmfd sp!, {r0-r3, r12, lr}
+    0xE3A00301, // 0064  mov r0, #0x04000000
+    0xE28FE000, // 0068  add lr, pc, #0
+    0xE510F004, // 006C  ldr pc, [r0, #-4]           The game's handler at 0x03FFFFFC.
+    0xE8BD500F, // 0070  ldmfd sp!, {r0-r3, r12, lr}
+    0xE25EF004, // 0074  subs pc, lr, #4
+    //       swi_handler:
+    0xE92D5800, // 0078  stmfd sp!, {r11, r12, lr}
+    0xE55EC002, // 007C  ldrb r12, [lr, #-2]         Bits 16..23 of the SWI comment.
+    0xE35C002B, // 0080  cmp r12, #0x2B
+    0x23A0C02B, // 0084  movhs r12, #0x2B            Unknown functions just return.
+    0xE28FB020, // 0088  adr r11, swi_table
+    0xE79BC10C, // 008C  ldr r12, [r11, r12, lsl #2]
+    0xE14FB000, // 0090  mrs r11, spsr
+    0xE92D0800, // 0094  stmfd sp!, {r11}
+    0xE28FE000, // 0098  add lr, pc, #0
+    0xE1A0F00C, // 009C  mov pc, r12
+    0xE8BD0800, // 00A0  ldmfd sp!, {r11}
+    0xE169F00B, // 00A4  msr spsr_fc, r11
+    0xE8BD5800, // 00A8  ldmfd sp!, {r11, r12, lr}
+    0xE1B0F00E, // 00AC  movs pc, lr
+    //       swi_table:
+    0x00000020, // 00B0  .word reset                 0x00 SoftReset
+    0x00000160, // 00B4  .word swi_return            0x01 RegisterRamReset
+    0x00000164, // 00B8  .word halt                  0x02 Halt
+    0x00000174, // 00BC  .word stop                  0x03 Stop
+    0x00000184, // 00C0  .word intr_wait             0x04 IntrWait
+    0x0000017C, // 00C4  .word vblank_intr_wait      0x05 VBlankIntrWait
+    0x000001F0, // 00C8  .word div                   0x06 Div
+    0x000001E4, // 00CC  .word div_arm               0x07 DivArm
+    0x00000280, // 00D0  .word sqrt                  0x08 Sqrt
+    0x00000160, // 00D4  .word swi_return            0x09 ArcTan
+    0x00000160, // 00D8  .word swi_return            0x0A ArcTan2
+    0x000002E0, // 00DC  .word cpu_set               0x0B CpuSet
+    0x000002C4, // 00E0  .word cpu_fast_set          0x0C CpuFastSet
+    0x00000368, // 00E4  .word get_bios_checksum     0x0D GetBiosChecksum
+    0x00000160, // 00E8  .word swi_return            0x0E BgAffineSet
+    0x00000160, // 00EC  .word swi_return            0x0F ObjAffineSet
+    0x00000160, // 00F0  .word swi_return            0x10 BitUnPack
+    0x00000160, // 00F4  .word swi_return            0x11 LZ77UnCompWram
+    0x00000160, // 00F8  .word swi_return            0x12 LZ77UnCompVram
+    0x00000160, // 00FC  .word swi_return            0x13 HuffUnComp
+    0x00000160, // 0100  .word swi_return            0x14 RLUnCompWram
+    0x00000160, // 0104  .word swi_return            0x15 RLUnCompVram
+    0x00000160, // 0108  .word swi_return            0x16 Diff8bitUnFilterWram
+    0x00000160, // 010C  .word swi_return            0x17 Diff8bitUnFilterVram
+    0x00000160, // 0110  .word swi_return            0x18 Diff16bitUnFilter
+    0x00000160, // 0114  .word swi_return            0x19 SoundBias
+    0x00000160, // 0118  .word swi_return            0x1A SoundDriverInit
+    0x00000160, // 011C  .word swi_return            0x1B SoundDriverMode
+    0x00000160, // 0120  .word swi_return            0x1C SoundDriverMain
+    0x00000160, // 0124  .word swi_return            0x1D SoundDriverVSync
+    0x00000160, // 0128  .word swi_return            0x1E SoundChannelClear
+    0x00000160, // 012C  .word swi_return            0x1F MidiKey2Freq
+    0x00000160, // 0130  .word swi_return            0x20 MusicPlayerOpen
+    0x00000160, // 0134  .word swi_return            0x21 MusicPlayerStart
+    0x00000160, // 0138  .word swi_return            0x22 MusicPlayerStop
+    0x00000160, // 013C  .word swi_return            0x23 MusicPlayerContinue
+    0x00000160, // 0140  .word swi_return            0x24 MusicPlayerFadeOut
+    0x00000160, // 0144  .word swi_return            0x25 MultiBoot
+    0x00000020, // 0148  .word reset                 0x26 HardReset
+    0x00000168, // 014C  .word custom_halt           0x27 CustomHalt
+    0x00000160, // 0150  .word swi_return            0x28 SoundDriverVSyncOff
+    0x00000160, // 0154  .word swi_return            0x29 SoundDriverVSyncOn
+    0x00000160, // 0158  .word swi_return            0x2A SoundGetJumpList
+    0x00000160, // 015C  .word swi_return            Unknown functions.
+    //       swi_return:
+    0xE1A0F00E, // 0160  mov pc, lr
+    //       halt:
+    0xE3A02000, // 0164  mov r2, #0
+    //       custom_halt:
+    0xE3A0C301, // 0168  mov r12, #0x04000000
+    0xE5CC2301, // 016C  strb r2, [r12, #0x301]      HALTCNT
+    0xE1A0F00E, // 0170  mov pc, lr
+    //       stop:
+    0xE3A02080, // 0174  mov r2, #0x80
+    0xEAFFFFFA, // 0178  b custom_halt
+    //       vblank_intr_wait:
+    0xE3A00001, // 017C  mov r0, #1
+    0xE3A01001, // 0180  mov r1, #1
+    //       intr_wait:
+    0xE92D4004, // 0184  stmfd sp!, {r2, lr}
+    0xE3A0C301, // 0188  mov r12, #0x04000000
+    0xE3A02001, // 018C  mov r2, #1
+    0xE5CC2208, // 0190  strb r2, [r12, #0x208]      IME
+    0xE10F2000, // 0194  mrs r2, cpsr                Let the game's IRQ handler run.
+    0xE3C22080, // 0198  bic r2, r2, #0x80
+    0xE129F002, // 019C  msr cpsr_fc, r2
+    0xE3500000, // 01A0  cmp r0, #0
+    0x115C20B8, // 01A4  ldrneh r2, [r12, #-8]       Discard old flags at 0x03FFFFF8.
+    0x11C22001, // 01A8  bicne r2, r2, r1
+    0x114C20B8, // 01AC  strneh r2, [r12, #-8]
+    //       intr_wait_check:
+    0xE15C20B8, // 01B0  ldrh r2, [r12, #-8]
+    0xE1120001, // 01B4  tst r2, r1
+    0x1A000002, // 01B8  bne intr_wait_done
+    0xE3A02000, // 01BC  mov r2, #0
+    0xE5CC2301, // 01C0  strb r2, [r12, #0x301]      Halt until the next interrupt.
+    0xEAFFFFF9, // 01C4  b intr_wait_check
+    //       intr_wait_done:
+    0xE1C22001, // 01C8  bic r2, r2, r1
+    0xE14C20B8, // 01CC  strh r2, [r12, #-8]
+    0xE10F2000, // 01D0  mrs r2, cpsr
+    0xE3822080, // 01D4  orr r2, r2, #0x80
+    0xE129F002, // 01D8  msr cpsr_fc, r2
+    0xE8BD4004, // 01DC  ldmfd sp!, {r2, lr}
+    0xE1A0F00E, // 01E0  mov pc, lr
+    //       div_arm:
+    0xE1A03000, // 01E4  mov r3, r0
+    0xE1A00001, // 01E8  mov r0, r1
+    0xE1A01003, // 01EC  mov r1, r3
+    //       div:
+    0xE92D0004, // 01F0  stmfd sp!, {r2}
+    0xE020C001, // 01F4  eor r12, r0, r1             Sign of the quotient.
+    0xE1A0B000, // 01F8  mov r11, r0                 Sign of the remainder.
+    0xE3500000, // 01FC  cmp r0, #0
+    0xB2600000, // 0200  rsblt r0, r0, #0
+    0xE3510000, // 0204  cmp r1, #0
+    0xB2611000, // 0208  rsblt r1, r1, #0
+    0x0A000014, // 020C  beq div_by_zero
+    0xE3A03000, // 0210  mov r3, #0
+    0xE3A02001, // 0214  mov r2, #1
+    //       div_align:
+    0xE3510102, // 0218  cmp r1, #0x80000000
+    0x31510000, // 021C  cmpcc r1, r0
+    0x31A01081, // 0220  movcc r1, r1, lsl #1
+    0x31A02082, // 0224  movcc r2, r2, lsl #1
+    0x3AFFFFFA, // 0228  bcc div_align
+    //       div_loop:
+    0xE1500001, // 022C  cmp r0, r1
+    0x20400001, // 0230  subcs r0, r0, r1
+    0x21833002, // 0234  orrcs r3, r3, r2
+    0xE1B020A2, // 0238  movs r2, r2, lsr #1
+    0x11A010A1, // 023C  movne r1, r1, lsr #1
+    0x1AFFFFF9, // 0240  bne div_loop
+    0xE1A01000, // 0244  mov r1, r0
+    0xE35B0000, // 0248  cmp r11, #0
+    0xB2611000, // 024C  rsblt r1, r1, #0
+    0xE1A00003, // 0250  mov r0, r3
+    0xE35C0000, // 0254  cmp r12, #0
+    0xB2600000, // 0258  rsblt r0, r0, #0
+    0xE8BD0004, // 025C  ldmfd sp!, {r2}
+    0xE1A0F00E, // 0260  mov pc, lr
+    //       div_by_zero:
+    0xE1A0100B, // 0264  mov r1, r11
+    0xE3A03001, // 0268  mov r3, #1
+    0xE3A00001, // 026C  mov r0, #1
+    0xE35B0000, // 0270  cmp r11, #0
+    0xB2600000, // 0274  rsblt r0, r0, #0
+    0xE8BD0004, // 0278  ldmfd sp!, {r2}
+    0xE1A0F00E, // 027C  mov pc, lr
+    //       sqrt:
+    0xE3A03000, // 0280  mov r3, #0
+    0xE3A0C101, // 0284  mov r12, #0x40000000
+    //       sqrt_align:
+    0xE15C0000, // 0288  cmp r12, r0
+    0x81A0C12C, // 028C  movhi r12, r12, lsr #2
+    0x8AFFFFFC, // 0290  bhi sqrt_align
+    //       sqrt_loop:
+    0xE35C0000, // 0294  cmp r12, #0
+    0x0A000007, // 0298  beq sqrt_done
+    0xE083B00C, // 029C  add r11, r3, r12
+    0xE150000B, // 02A0  cmp r0, r11
+    0x2040000B, // 02A4  subcs r0, r0, r11
+    0x21A030A3, // 02A8  movcs r3, r3, lsr #1
+    0x2083300C, // 02AC  addcs r3, r3, r12
+    0x31A030A3, // 02B0  movcc r3, r3, lsr #1
+    0xE1A0C12C, // 02B4  mov r12, r12, lsr #2
+    0xEAFFFFF5, // 02B8  b sqrt_loop
+    //       sqrt_done:
+    0xE1A00003, // 02BC  mov r0, r3
+    0xE1A0F00E, // 02C0  mov pc, lr
+    //       cpu_fast_set:
+    0xE310040E, // 02C4  tst r0, #0x0E000000         Refuse to copy the BIOS.
+    0x01A0F00E, // 02C8  moveq pc, lr
+    0xE3C2C4FF, // 02CC  bic r12, r2, #0xFF000000
+    0xE3CCC60E, // 02D0  bic r12, r12, #0x00E00000
+    0xE28CC007, // 02D4  add r12, r12, #7            Round up to blocks of 8 words.
+    0xE3CCC007, // 02D8  bic r12, r12, #7
+    0xEA000013, // 02DC  b cpu_set_words
+    //       cpu_set:
+    0xE310040E, // 02E0  tst r0, #0x0E000000
+    0x01A0F00E, // 02E4  moveq pc, lr
+    0xE3C2C4FF, // 02E8  bic r12, r2, #0xFF000000
+    0xE3CCC60E, // 02EC  bic r12, r12, #0x00E00000
+    0xE3120301, // 02F0  tst r2, #0x04000000
+    0x1A00000D, // 02F4  bne cpu_set_words
+    0xE3C00001, // 02F8  bic r0, r0, #1
+    0xE3C11001, // 02FC  bic r1, r1, #1
+    0xE3120401, // 0300  tst r2, #0x01000000
+    0x11D030B0, // 0304  ldrneh r3, [r0]
+    0x1A000004, // 0308  bne cpu_set_fill16
+    //       cpu_set_copy16:
+    0xE25CC001, // 030C  subs r12, r12, #1
+    0x41A0F00E, // 0310  movmi pc, lr
+    0xE0D030B2, // 0314  ldrh r3, [r0], #2
+    0xE0C130B2, // 0318  strh r3, [r1], #2
+    0xEAFFFFFA, // 031C  b cpu_set_copy16
+    //       cpu_set_fill16:
+    0xE25CC001, // 0320  subs r12, r12, #1
+    0x41A0F00E, // 0324  movmi pc, lr
+    0xE0C130B2, // 0328  strh r3, [r1], #2
+    0xEAFFFFFB, // 032C  b cpu_set_fill16
+    //       cpu_set_words:
+    0xE3C00003, // 0330  bic r0, r0, #3
+    0xE3C11003, // 0334  bic r1, r1, #3
+    0xE3120401, // 0338  tst r2, #0x01000000
+    0x15903000, // 033C  ldrne r3, [r0]
+    0x1A000004, // 0340  bne cpu_set_fill32
+    //       cpu_set_copy32:
+    0xE25CC001, // 0344  subs r12, r12, #1
+    0x41A0F00E, // 0348  movmi pc, lr
+    0xE4903004, // 034C  ldr r3, [r0], #4
+    0xE4813004, // 0350  str r3, [r1], #4
+    0xEAFFFFFA, // 0354  b cpu_set_copy32
+    //       cpu_set_fill32:
+    0xE25CC001, // 0358  subs r12, r12, #1
+    0x41A0F00E, // 035C  movmi pc, lr
+    0xE4813004, // 0360  str r3, [r1], #4
+    0xEAFFFFFB, // 0364  b cpu_set_fill32
+    //       get_bios_checksum:
+    0xE59F0014, // 0368  ldr r0, =0xBAAE187F
+    0xE3A01001, // 036C  mov r1, #1
+    0xE3A03901, // 0370  mov r3, #0x4000
+    0xE1A0F00E, // 0374  mov pc, lr
+    0x03007FA0, // 0378  .word 0x03007FA0
+    0x03007FE0, // 037C  .word 0x03007FE0
+    0x03007F00, // 0380  .word 0x03007F00
+    0xBAAE187F, // 0384  .word 0xBAAE187F
+];
+
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
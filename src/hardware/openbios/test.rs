@@ -0,0 +1,201 @@
+// License below.
+#![allow(missing_docs)]
+
+use super::super::Gba;
+use super::super::cpu::{Arm7Tdmi, Mode};
+use super::super::ioregs::Interrupt;
+use super::super::memory::{BiosRom, Rom8, Rom16, Rom32};
+use super::super::test_util::{new_cpu, load_program};
+
+fn run_until(cpu: &mut Arm7Tdmi, addr: u32) {
+    for _ in 0..10000 {
+        if cpu.executing_address() == addr { return; }
+        cpu.pipeline_step().unwrap();
+    }
+    panic!("Never reached {:#010X}, stuck at {:#010X}.", addr, cpu.executing_address());
+}
+
+#[test]
+pub fn boot() {
+    assert!(BiosRom::new().is_builtin());
+    let (mut cpu, _) = new_cpu(BiosRom::new());
+    cpu.reset();
+    run_until(&mut cpu, 0x08000000);
+    assert_eq!(cpu.mode(), Mode::System);
+    assert_eq!(cpu.register(13), 0x03007F00);
+    assert!(!cpu.cpsr().irq_disabled());
+}
+
+#[test]
+pub fn software_interrupts() {
+    let (mut cpu, bus) = new_cpu(BiosRom::new());
+    load_program(&bus, 0x02000000, &[
+        0xE3A00064, // 0000  mov r0, #100
+        0xE3E01006, // 0004  mvn r1, #6                  -7
+        0xEF060000, // 0008  swi 0x060000                Div
+        0xE1A04000, // 000C  mov r4, r0
+        0xE1A05001, // 0010  mov r5, r1
+        0xE1A06003, // 0014  mov r6, r3
+        0xE3A00801, // 0018  mov r0, #0x10000
+        0xEF080000, // 001C  swi 0x080000                Sqrt
+        0xE1A07000, // 0020  mov r7, r0
+        0xE3A00402, // 0024  mov r0, #0x02000000         Fill 0x02000100 with 4 words.
+        0xE2801C01, // 0028  add r1, r0, #0x100
+        0xE5800200, // 002C  str r0, [r0, #0x200]
+        0xE2800C02, // 0030  add r0, r0, #0x200
+        0xE3A02405, // 0034  mov r2, #0x05000000
+        0xE2822004, // 0038  add r2, r2, #4
+        0xEF0B0000, // 003C  swi 0x0B0000                CpuSet
+        //       done:
+        0xEAFFFFFE, // 0040  b done
+    ]);
+    cpu.boot_post_bios(0x02000000);
+    run_until(&mut cpu, 0x02000040);
+    assert_eq!((cpu.register(4), cpu.register(5), cpu.register(6)), (-14, 2, 14));
+    assert_eq!(cpu.register(7), 0x100);
+    assert_eq!(cpu.mode(), Mode::System);
+    assert_eq!(cpu.register(13), 0x03007F00);
+    for i in 0..5 {
+        let fill = if i < 4 { 0x02000000 } else { 0 };
+        assert_eq!(bus.borrow().load_word(0x02000100 + i * 4), Ok(fill));
+    }
+}
+
+#[test]
+pub fn interrupt_wait() {
+    let (mut cpu, bus) = new_cpu(BiosRom::new());
+    load_program(&bus, 0x02000000, &[
+        0xE3A00301, // 0000  mov r0, #0x04000000
+        0xE3A01403, // 0004  mov r1, #0x03000000         The IRQ handler below.
+        0xE5001004, // 0008  str r1, [r0, #-4]           0x03FFFFFC
+        0xE3A01001, // 000C  mov r1, #1
+        0xE2802C02, // 0010  add r2, r0, #0x200
+        0xE1C210B0, // 0014  strh r1, [r2]               IE = V-Blank
+        0xE3A08000, // 0018  mov r8, #0
+        0xEF050000, // 001C  swi 0x050000                VBlankIntrWait
+        0xE3A08001, // 0020  mov r8, #1
+        //       done:
+        0xEAFFFFFE, // 0024  b done
+    ]);
+    load_program(&bus, 0x03000000, &[
+        //       irq_handler:
+        0xE3A00301, // 0000  mov r0, #0x04000000
+        0xE2802C02, // 0004  add r2, r0, #0x200
+        0xE3A01001, // 0008  mov r1, #1
+        0xE1C210B2, // 000C  strh r1, [r2, #2]           Acknowledge IF.
+        0xE14010B8, // 0010  strh r1, [r0, #-8]          Set the BIOS flags at 0x03FFFFF8.
+        0xE12FFF1E, // 0014  bx lr
+    ]);
+    cpu.boot_post_bios(0x02000000);
+    for _ in 0..1000 { if cpu.is_sleeping() { break; } cpu.pipeline_step().unwrap(); }
+    assert!(cpu.is_sleeping());
+    assert_eq!(cpu.register(8), 0);
+
+    bus.borrow_mut().ioregs_mut().request_interrupt(Interrupt::VBlank);
+    run_until(&mut cpu, 0x02000024);
+    assert_eq!(cpu.register(8), 1);
+    assert_eq!(cpu.mode(), Mode::System);
+    assert_eq!(bus.borrow().ioregs().pending_interrupts(), 0);
+}
+
+#[test]
+pub fn vblank_wakes_up() {
+    for &hle in &[false, true] {
+        let (mut cpu, bus) = new_cpu(BiosRom::new());
+        load_program(&bus, 0x02000000, &[
+            0xE3A00301, // 0000  mov r0, #0x04000000
+            0xE3A01403, // 0004  mov r1, #0x03000000         The IRQ handler below.
+            0xE5001004, // 0008  str r1, [r0, #-4]           0x03FFFFFC
+            0xE3A01001, // 000C  mov r1, #1
+            0xE2802C02, // 0010  add r2, r0, #0x200
+            0xE1C210B0, // 0014  strh r1, [r2]               IE = V-Blank
+            0xE3A01008, // 0018  mov r1, #8
+            0xE1C010B4, // 001C  strh r1, [r0, #4]           DISPSTAT = V-Blank IRQ
+            0xEF050000, // 0020  swi 0x050000                VBlankIntrWait
+            0xE3A08001, // 0024  mov r8, #1
+            //       done:
+            0xEAFFFFFE, // 0028  b done
+        ]);
+        load_program(&bus, 0x03000000, &[
+            //       irq_handler:
+            0xE3A00301, // 0000  mov r0, #0x04000000
+            0xE2802C02, // 0004  add r2, r0, #0x200
+            0xE3A01001, // 0008  mov r1, #1
+            0xE1C210B2, // 000C  strh r1, [r2, #2]           Acknowledge IF.
+            0xE14010B8, // 0010  strh r1, [r0, #-8]          Set the BIOS flags at 0x03FFFFF8.
+            0xE12FFF1E, // 0014  bx lr
+        ]);
+        cpu.boot_post_bios(0x02000000);
+        cpu.set_swi_optimised(hle);
+
+        // Sleeping skips ahead, so a frame takes few steps.
+        run_until(&mut cpu, 0x02000028);
+        assert_eq!(cpu.register(8), 1);
+        assert_eq!(bus.borrow().ioregs().read_halfword(0x006), 160); // VCOUNT
+        assert!(!cpu.is_sleeping());
+    }
+}
+
+#[test]
+pub fn skip_bios() {
+    let mut gba = Gba::new();
+    load_program(&gba.bus, 0x02000000, &[
+        0xE3A00801, // 0000  mov r0, #0x10000
+        0xEF080000, // 0004  swi 0x080000                Sqrt
+        //       done:
+        0xEAFFFFFE, // 0008  b done
+    ]);
+    gba.boot_post_bios(0x02000000);
+    assert_eq!(gba.bus().ioregs().read_byte(0x300), 1); // POSTFLG
+    assert_eq!(gba.bus().ioregs().read_halfword(0x134), 0x8000); // RCNT
+    run_until(gba.cpu_arm7tdmi_mut(), 0x02000008);
+    assert_eq!(gba.cpu_arm7tdmi().register(0), 0x100);
+    assert_eq!(gba.cpu_arm7tdmi().mode(), Mode::System);
+    assert_eq!(gba.cpu_arm7tdmi().register(13), 0x03007F00);
+}
+
+#[test]
+pub fn bios_read_protection_and_open_bus() {
+    let (mut cpu, bus) = new_cpu(BiosRom::new());
+    load_program(&bus, 0x02000000, &[
+        0xE3A00801, // 0000  mov r0, #0x10000
+        0xEF080000, // 0004  swi 0x080000                Sqrt
+        0xE3A00000, // 0008  mov r0, #0
+        0xE5901000, // 000C  ldr r1, [r0]                Protected BIOS.
+        0xE3A00401, // 0010  mov r0, #0x01000000
+        0xE5902000, // 0014  ldr r2, [r0]                Open bus.
+        0xE1D030B2, // 0018  ldrh r3, [r0, #2]
+        //       done:
+        0xEAFFFFFE, // 001C  b done
+        0xE1A0B00B, // 0020  mov r11, r11
+    ]);
+    cpu.boot_post_bios(0x02000000);
+    run_until(&mut cpu, 0x0200001C);
+
+    // The BIOS returns from a SWI with `movs pc, lr` at 0xAC,
+    // so the last opcode fetched from the BIOS is at 0xB4.
+    let last_bios_opcode = BiosRom::new().read_word(0xB4) as i32;
+    assert_eq!(cpu.register(1), last_bios_opcode);
+    assert_eq!(cpu.register(2), 0xEAFFFFFE_u32 as i32);
+    assert_eq!(cpu.register(3), 0xE1A0);
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -0,0 +1,394 @@
+// License below.
+//! Implements soft-patching ROMs with IPS, UPS, and BPS patches.
+//!
+//! Patches are applied to a copy of the loaded ROM image.
+//! Only if the whole patch has been applied successfully
+//! and all checksums match, the result replaces the ROM.
+//! The ROM file itself is never written to.
+//!
+//! - **IPS** patches are just a list of byte blocks to overwrite.
+//!   They contain no checksums at all.
+//! - **UPS** patches XOR the ROM with a list of byte blocks.
+//! - **BPS** patches copy blocks from the ROM, the patch, or
+//!   already patched data.
+//!
+//! UPS and BPS patches end with CRC32s of the source ROM, the
+//! target ROM, and the patch itself. All of them are checked.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::error;
+use std::fmt;
+use super::checksum::crc32;
+
+#[cfg(test)]
+mod test;
+
+
+/// Magic number of IPS patches.
+pub const IPS_MAGIC: &'static [u8] = b"PATCH";
+
+/// Marks the end of IPS patches.
+pub const IPS_EOF: &'static [u8] = b"EOF";
+
+/// Magic number of UPS patches.
+pub const UPS_MAGIC: &'static [u8] = b"UPS1";
+
+/// Magic number of BPS patches.
+pub const BPS_MAGIC: &'static [u8] = b"BPS1";
+
+/// Size of the CRC32 footer of UPS and BPS patches.
+pub const PATCH_FOOTER_LEN: usize = 12;
+
+
+/// The format of a patch file.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PatchFormat {
+    #[doc = "International Patching System"] Ips,
+    #[doc = "Universal Patching System"]     Ups,
+    #[doc = "Beat Patching System"]          Bps,
+}
+
+impl PatchFormat {
+    /// Detects a patch's format by its magic number.
+    pub fn detect(patch: &[u8]) -> Option<PatchFormat> {
+        if patch.starts_with(IPS_MAGIC) { Some(PatchFormat::Ips) }
+        else if patch.starts_with(UPS_MAGIC) { Some(PatchFormat::Ups) }
+        else if patch.starts_with(BPS_MAGIC) { Some(PatchFormat::Bps) }
+        else { None }
+    }
+}
+
+impl fmt::Display for PatchFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match *self {
+            PatchFormat::Ips => "IPS",
+            PatchFormat::Ups => "UPS",
+            PatchFormat::Bps => "BPS",
+        })
+    }
+}
+
+
+/// An error caused while applying a patch.
+#[derive(Debug, PartialEq, Clone)]
+pub enum PatchError {
+    /// The patch has no known magic number.
+    UnknownFormat,
+
+    /// The patch ends in the middle of a record.
+    Truncated,
+
+    /// The patch reads or writes beyond the ROM.
+    OutOfBounds(usize),
+
+    /// The patched ROM would exceed `.0` bytes.
+    TooLarge(usize),
+
+    /// The ROM has `.1` bytes, but the patch expects `.0` bytes.
+    SourceSizeMismatch(usize, usize),
+
+    /// The ROM's CRC32 is `.1`, but the patch expects `.0`.
+    SourceChecksumMismatch(u32, u32),
+
+    /// The patched ROM's CRC32 is `.1`, but the patch expects `.0`.
+    TargetChecksumMismatch(u32, u32),
+
+    /// The patch's CRC32 is `.1`, but the patch states `.0`.
+    PatchChecksumMismatch(u32, u32),
+}
+
+impl error::Error for PatchError {
+    fn description(&self) -> &str {
+        match *self {
+            PatchError::UnknownFormat               => "Unknown patch format.",
+            PatchError::Truncated                   => "Truncated patch.",
+            PatchError::OutOfBounds(_)              => "Patch accesses data out of bounds.",
+            PatchError::TooLarge(_)                 => "Patched ROM too large.",
+            PatchError::SourceSizeMismatch(_,_)     => "Patch expects a ROM of a different size.",
+            PatchError::SourceChecksumMismatch(_,_) => "Patch expects a different ROM.",
+            PatchError::TargetChecksumMismatch(_,_) => "Patched ROM is broken.",
+            PatchError::PatchChecksumMismatch(_,_)  => "Patch is broken.",
+        }
+    }
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PatchError::UnknownFormat  => write!(f, "Unknown patch format, expected IPS, UPS, or BPS."),
+            PatchError::Truncated      => write!(f, "The patch ends unexpectedly."),
+            PatchError::OutOfBounds(x) => write!(f, "The patch accesses data out of bounds at {:#010X}.", x),
+            PatchError::TooLarge(x)    => write!(f, "The patched ROM would exceed {} bytes.", x),
+            PatchError::SourceSizeMismatch(e,a) => {
+                write!(f, "The patch expects a ROM of {} bytes, but the ROM has {} bytes.", e, a)
+            },
+            PatchError::SourceChecksumMismatch(e,a) => {
+                write!(f, "The patch expects a ROM with CRC32 {:08X}, but the ROM has CRC32 {:08X}.", e, a)
+            },
+            PatchError::TargetChecksumMismatch(e,a) => {
+                write!(f, "The patched ROM should have CRC32 {:08X}, but has CRC32 {:08X}.", e, a)
+            },
+            PatchError::PatchChecksumMismatch(e,a) => {
+                write!(f, "The patch should have CRC32 {:08X}, but has CRC32 {:08X}.", e, a)
+            },
+        }
+    }
+}
+
+
+/// Applies a patch of any supported format.
+///
+/// # Params
+/// - `patch`: The whole patch file.
+/// - `source`: The ROM image to patch.
+/// - `max_len`: The maximum size of the patched ROM.
+///
+/// # Returns
+/// - `Ok`: The patched ROM image.
+/// - `Err`: The patch is invalid or does not belong to this ROM.
+pub fn apply_patch(patch: &[u8], source: &[u8], max_len: usize) -> Result<Vec<u8>, PatchError> {
+    match PatchFormat::detect(patch) {
+        Some(PatchFormat::Ips) => apply_ips(patch, source, max_len),
+        Some(PatchFormat::Ups) => apply_ups(patch, source, max_len),
+        Some(PatchFormat::Bps) => apply_bps(patch, source, max_len),
+        None => Err(PatchError::UnknownFormat),
+    }
+}
+
+/// Applies an IPS patch.
+///
+/// IPS patches may grow the ROM, and may have a
+/// 3 byte truncation size after the `EOF` marker.
+pub fn apply_ips(patch: &[u8], source: &[u8], max_len: usize) -> Result<Vec<u8>, PatchError> {
+    let mut r = PatchReader::new(patch, IPS_MAGIC.len());
+    let mut target = source.to_vec();
+
+    loop {
+        if try!(r.peek(IPS_EOF.len())) == IPS_EOF {
+            r.pos += IPS_EOF.len();
+            break;
+        }
+        let offs = try!(r.read_be(3)) as usize;
+        let len  = try!(r.read_be(2)) as usize;
+        let (len, rle) = if len != 0 { (len, None) } else {
+            (try!(r.read_be(2)) as usize, Some(try!(r.read_be(1)) as u8))
+        };
+        if (offs + len) > max_len { return Err(PatchError::TooLarge(max_len)); }
+        if (offs + len) > target.len() { target.resize(offs + len, 0); }
+        match rle {
+            Some(x) => { for b in &mut target[offs..(offs + len)] { *b = x; } },
+            None    => { target[offs..(offs + len)].copy_from_slice(try!(r.read(len))); },
+        }
+    }
+
+    // Lunar IPS extension to shrink the ROM.
+    if r.remaining() >= 3 {
+        let len = try!(r.read_be(3)) as usize;
+        target.truncate(len);
+    }
+    Ok(target)
+}
+
+/// Applies a UPS patch.
+pub fn apply_ups(patch: &[u8], source: &[u8], max_len: usize) -> Result<Vec<u8>, PatchError> {
+    let (_, target_crc) = try!(check_footer(patch, source));
+    let mut r = PatchReader::new(&patch[..(patch.len() - PATCH_FOOTER_LEN)], UPS_MAGIC.len());
+
+    let source_len = try!(r.read_varint());
+    let target_len = try!(r.read_varint());
+    if source_len != source.len() { return Err(PatchError::SourceSizeMismatch(source_len, source.len())); }
+    if target_len > max_len { return Err(PatchError::TooLarge(max_len)); }
+
+    let mut target = source.to_vec();
+    target.resize(target_len, 0);
+
+    // Each hunk skips some bytes and XORs up to a zero byte.
+    let mut pos = 0_usize;
+    while r.remaining() > 0 {
+        pos = match pos.checked_add(try!(r.read_varint())) {
+            Some(p) => p,
+            None => return Err(PatchError::OutOfBounds(pos)),
+        };
+        loop {
+            let x = try!(r.read_be(1)) as u8;
+            if pos >= target_len {
+                if x == 0 { break; } else { return Err(PatchError::OutOfBounds(pos)); }
+            }
+            target[pos] ^= x;
+            pos += 1;
+            if x == 0 { break; }
+        }
+    }
+
+    check_target(target, target_crc)
+}
+
+/// Applies a BPS patch.
+pub fn apply_bps(patch: &[u8], source: &[u8], max_len: usize) -> Result<Vec<u8>, PatchError> {
+    let (_, target_crc) = try!(check_footer(patch, source));
+    let mut r = PatchReader::new(&patch[..(patch.len() - PATCH_FOOTER_LEN)], BPS_MAGIC.len());
+
+    let source_len   = try!(r.read_varint());
+    let target_len   = try!(r.read_varint());
+    let metadata_len = try!(r.read_varint());
+    if source_len != source.len() { return Err(PatchError::SourceSizeMismatch(source_len, source.len())); }
+    if target_len > max_len { return Err(PatchError::TooLarge(max_len)); }
+    try!(r.read(metadata_len));
+
+    let mut target = Vec::with_capacity(target_len);
+    let mut source_rel = 0_isize;
+    let mut target_rel = 0_isize;
+    while r.remaining() > 0 {
+        let cmd = try!(r.read_varint());
+        let len = (cmd >> 2) + 1;
+        let end = match target.len().checked_add(len) {
+            Some(e) if e <= target_len => e,
+            _ => return Err(PatchError::OutOfBounds(target.len())),
+        };
+        match cmd & 0b11 {
+            // SourceRead
+            0 => {
+                let offs = target.len();
+                if end > source.len() { return Err(PatchError::OutOfBounds(offs)); }
+                target.extend_from_slice(&source[offs..end]);
+            },
+            // TargetRead
+            1 => { target.extend_from_slice(try!(r.read(len))); },
+            // SourceCopy
+            2 => {
+                source_rel = match source_rel.checked_add(try!(r.read_signed_varint())) {
+                    Some(x) => x,
+                    None => return Err(PatchError::OutOfBounds(source_rel as usize)),
+                };
+                if (source_rel < 0) || (len > source.len()) || ((source_rel as usize) > (source.len() - len)) {
+                    return Err(PatchError::OutOfBounds(source_rel as usize));
+                }
+                let offs = source_rel as usize;
+                target.extend_from_slice(&source[offs..(offs + len)]);
+                source_rel += len as isize;
+            },
+            // TargetCopy, may overlap with the bytes being written.
+            _ => {
+                target_rel = match target_rel.checked_add(try!(r.read_signed_varint())) {
+                    Some(x) => x,
+                    None => return Err(PatchError::OutOfBounds(target_rel as usize)),
+                };
+                if (target_rel < 0) | (target_rel as usize >= target.len()) {
+                    return Err(PatchError::OutOfBounds(target_rel as usize));
+                }
+                for _ in 0..len {
+                    let x = target[target_rel as usize];
+                    target.push(x);
+                    target_rel += 1;
+                }
+            },
+        }
+    }
+    if target.len() != target_len { return Err(PatchError::Truncated); }
+
+    check_target(target, target_crc)
+}
+
+/// Validates the patch and source CRC32s of a UPS or BPS patch.
+///
+/// # Returns
+/// The expected source and target CRC32s.
+fn check_footer(patch: &[u8], source: &[u8]) -> Result<(u32, u32), PatchError> {
+    if patch.len() < (4 + PATCH_FOOTER_LEN) { return Err(PatchError::Truncated); }
+    let footer = patch.len() - PATCH_FOOTER_LEN;
+    let mut r = PatchReader::new(patch, footer);
+    let source_crc = try!(r.read_le32());
+    let target_crc = try!(r.read_le32());
+    let patch_crc  = try!(r.read_le32());
+
+    let actual = crc32(&patch[..(patch.len() - 4)]);
+    if actual != patch_crc { return Err(PatchError::PatchChecksumMismatch(patch_crc, actual)); }
+    let actual = crc32(source);
+    if actual != source_crc { return Err(PatchError::SourceChecksumMismatch(source_crc, actual)); }
+    Ok((source_crc, target_crc))
+}
+
+/// Validates the target CRC32 of a UPS or BPS patch.
+fn check_target(target: Vec<u8>, target_crc: u32) -> Result<Vec<u8>, PatchError> {
+    let actual = crc32(&target);
+    if actual != target_crc { return Err(PatchError::TargetChecksumMismatch(target_crc, actual)); }
+    Ok(target)
+}
+
+
+/// Reads the numbers and blocks patches are made of.
+struct PatchReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PatchReader<'a> {
+    fn new(data: &'a [u8], pos: usize) -> PatchReader<'a> {
+        PatchReader { data: data, pos: pos }
+    }
+
+    fn remaining(&self) -> usize { self.data.len().saturating_sub(self.pos) }
+
+    fn peek(&self, len: usize) -> Result<&'a [u8], PatchError> {
+        if self.remaining() < len { Err(PatchError::Truncated) }
+        else { Ok(&self.data[self.pos..(self.pos + len)]) }
+    }
+
+    fn read(&mut self, len: usize) -> Result<&'a [u8], PatchError> {
+        let x = try!(self.peek(len));
+        self.pos += len;
+        Ok(x)
+    }
+
+    fn read_be(&mut self, len: usize) -> Result<u32, PatchError> {
+        Ok(try!(self.read(len)).iter().fold(0, |acc, &b| (acc << 8) | (b as u32)))
+    }
+
+    fn read_le32(&mut self) -> Result<u32, PatchError> {
+        Ok(try!(self.read(4)).iter().rev().fold(0, |acc, &b| (acc << 8) | (b as u32)))
+    }
+
+    /// Decodes the variable length integers of UPS and BPS.
+    fn read_varint(&mut self) -> Result<usize, PatchError> {
+        let mut data = 0_usize;
+        let mut shift = 1_usize;
+        loop {
+            let x = try!(self.read_be(1)) as usize;
+            data = data.wrapping_add((x & 0x7F).wrapping_mul(shift));
+            if 0 != (x & 0x80) { return Ok(data); }
+            shift = shift.wrapping_shl(7);
+            data = data.wrapping_add(shift);
+        }
+    }
+
+    /// Decodes BPS' signed relative offsets.
+    fn read_signed_varint(&mut self) -> Result<isize, PatchError> {
+        let x = try!(self.read_varint());
+        let v = (x >> 1) as isize;
+        Ok(if 0 != (x & 0b1) { -v } else { v })
+    }
+}
+
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
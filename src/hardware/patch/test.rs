@@ -0,0 +1,138 @@
+// License below.
+#![allow(missing_docs)]
+
+use super::*;
+use super::super::checksum::crc32;
+
+fn varint(mut x: usize, out: &mut Vec<u8>) {
+    loop {
+        let b = (x & 0x7F) as u8;
+        x >>= 7;
+        if x == 0 { out.push(0x80 | b); break; }
+        out.push(b);
+        x -= 1;
+    }
+}
+
+fn le32(x: u32, out: &mut Vec<u8>) {
+    for i in 0..4 { out.push((x >> (8 * i)) as u8); }
+}
+
+fn finish(mut p: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+    le32(crc32(source), &mut p);
+    le32(crc32(target), &mut p);
+    let c = crc32(&p);
+    le32(c, &mut p);
+    p
+}
+
+#[test]
+pub fn crc32_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xCBF43926);
+}
+
+#[test]
+pub fn unknown_patch_format() {
+    assert_eq!(apply_patch(b"NOPE", &[0; 4], 16), Err(PatchError::UnknownFormat));
+}
+
+#[test]
+pub fn ips_patching() {
+    let source = [0_u8; 8];
+    let mut p = IPS_MAGIC.to_vec();
+    p.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0x02, 0xAA, 0xBB]); // Copy 2 bytes to 1.
+    p.extend_from_slice(&[0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x03, 0xCC]); // Fill 3 bytes at 8.
+    p.extend_from_slice(IPS_EOF);
+    assert_eq!(apply_patch(&p, &source, 16).unwrap(),
+               vec![0, 0xAA, 0xBB, 0, 0, 0, 0, 0, 0xCC, 0xCC, 0xCC]);
+
+    p.extend_from_slice(&[0x00, 0x00, 0x04]); // Truncate to 4 bytes.
+    assert_eq!(apply_patch(&p, &source, 16).unwrap(), vec![0, 0xAA, 0xBB, 0]);
+    assert_eq!(apply_patch(&p, &source, 10), Err(PatchError::TooLarge(10)));
+    assert_eq!(apply_patch(&p[..9], &source, 16), Err(PatchError::Truncated));
+}
+
+#[test]
+pub fn ups_patching() {
+    let source = b"Hello, World!";
+    let target = b"Hello, Rusty World!";
+    let mut p = UPS_MAGIC.to_vec();
+    varint(source.len(), &mut p);
+    varint(target.len(), &mut p);
+    varint(7, &mut p); // Skip "Hello, ".
+    for i in 7..target.len() {
+        let s = if i < source.len() { source[i] } else { 0 };
+        p.push(s ^ target[i]);
+    }
+    p.push(0);
+    let p = finish(p, source, target);
+    assert_eq!(apply_patch(&p, source, 64).unwrap(), target.to_vec());
+
+    // Wrong ROM.
+    match apply_patch(&p, b"Hello, Moon!!", 64) {
+        Err(PatchError::SourceChecksumMismatch(..)) => {},
+        x => panic!("{:?}", x),
+    }
+
+    // Broken patch.
+    let mut broken = p.clone();
+    broken[8] ^= 1;
+    match apply_patch(&broken, source, 64) {
+        Err(PatchError::PatchChecksumMismatch(..)) => {},
+        x => panic!("{:?}", x),
+    }
+
+    // Skipping beyond the address space.
+    let mut p = UPS_MAGIC.to_vec();
+    varint(4, &mut p);
+    varint(4, &mut p);
+    varint(2, &mut p);
+    p.extend_from_slice(&[1, 0]);
+    varint(::std::usize::MAX - 1, &mut p);
+    p.push(0);
+    let p = finish(p, b"ABCD", b"ABBD");
+    assert_eq!(apply_patch(&p, b"ABCD", 64), Err(PatchError::OutOfBounds(4)));
+}
+
+#[test]
+pub fn bps_patching() {
+    let source = b"ABCDEFGH";
+    let target = b"ABCDxyzxyzEFAB";
+    let mut p = BPS_MAGIC.to_vec();
+    varint(source.len(), &mut p);
+    varint(target.len(), &mut p);
+    varint(0, &mut p);
+    varint(((4 - 1) << 2) | 0, &mut p); // SourceRead "ABCD".
+    varint(((3 - 1) << 2) | 1, &mut p); // TargetRead "xyz".
+    p.extend_from_slice(b"xyz");
+    varint(((3 - 1) << 2) | 3, &mut p); // TargetCopy "xyz" from 4.
+    varint(4 << 1, &mut p);
+    varint(((2 - 1) << 2) | 2, &mut p); // SourceCopy "EF" from 4.
+    varint(4 << 1, &mut p);
+    varint(((2 - 1) << 2) | 2, &mut p); // SourceCopy "AB" from 0.
+    varint((6 << 1) | 1, &mut p);
+    let p = finish(p, source, target);
+    assert_eq!(apply_patch(&p, source, 64).unwrap(), target.to_vec());
+    assert_eq!(apply_patch(&p, source, 8), Err(PatchError::TooLarge(8)));
+}
+
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -0,0 +1,284 @@
+// License below.
+//! Implements extra hardware built into some GamePaks.
+//!
+//! Some games ship with more than just a ROM and an SRAM chip
+//! on their circuit board:
+//!
+//! - **Solar Sensor** in *Boktai*, read through the GPIO port.
+//! - **Tilt Sensor** in *Yoshi Topsy-Turvy*, mapped into the SRAM area.
+//! - **Rumble** in *Drill Dozer* and **Rumble + Gyro Sensor** in
+//!   *WarioWare Twisted*, both driven through the GPIO port.
+//!
+//! The GPIO port is a tiny set of 4-bit registers mapped into the
+//! ROM area at `0x080000C4..0x080000C9`:
+//!
+//! ```text
+//! 0x080000C4: Data      | Pin states, bits 0..3.
+//! 0x080000C6: Direction | 1=OutputFromGBA, 0=InputToGBA, bits 0..3.
+//! 0x080000C8: Control   | 1=RegistersReadable, 0=WriteOnly, bit 0.
+//! ```
+//!
+//! Every peripheral offers setters for its sensor values, so that
+//! front-ends and tests can feed it with whatever input they like.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::fmt;
+use std::io;
+use super::savestate::{SaveState, StateWriter, StateReader, invalid_state};
+
+pub use self::solar::*;
+pub use self::tilt::*;
+pub use self::rumble::*;
+
+pub mod solar;
+pub mod tilt;
+pub mod rumble;
+
+#[cfg(test)]
+mod test;
+
+
+/// ROM offset of the GPIO data register.
+pub const GPIO_DATA_OFFSET: u32 = 0xC4;
+
+/// ROM offset of the GPIO direction register.
+pub const GPIO_DIRECTION_OFFSET: u32 = 0xC6;
+
+/// ROM offset of the GPIO control register.
+pub const GPIO_CONTROL_OFFSET: u32 = 0xC8;
+
+/// ROM offset of the first byte after the GPIO registers.
+pub const GPIO_END_OFFSET: u32 = 0xCA;
+
+/// Only the lower four bits of each GPIO register are wired.
+pub const GPIO_PIN_MASK: u8 = 0b1111;
+
+
+/// A peripheral plugged into a GamePak.
+pub enum GamePakPeripheral {
+    #[doc = "No extra hardware at all."]             None,
+    #[doc = "A GPIO solar sensor as used by Boktai."] SolarSensor(SolarSensor),
+    #[doc = "An accelerometer mapped into SRAM."]     TiltSensor(TiltSensor),
+    #[doc = "A GPIO rumble motor and gyro sensor."]   RumbleGyro(RumbleGyro),
+}
+
+impl GamePakPeripheral {
+    /// Guesses a GamePak's peripheral by its game code.
+    ///
+    /// Only the first three letters of the game code are
+    /// checked, as the fourth one is just the region code.
+    ///
+    /// # Params
+    /// - `code`: The 4 letter game code from the ROM header.
+    ///
+    /// # Returns
+    /// A freshly initialised peripheral or `None`.
+    pub fn detect(code: &str) -> GamePakPeripheral {
+        let prefix: String = code.chars().take(3).collect();
+        match prefix.as_str() {
+            "U3I" | "U32" | "U33" => GamePakPeripheral::SolarSensor(SolarSensor::new()),
+            "KYG" | "KHP"         => GamePakPeripheral::TiltSensor(TiltSensor::new()),
+            "RZW"                 => GamePakPeripheral::RumbleGyro(RumbleGyro::new(true)),
+            "V49"                 => GamePakPeripheral::RumbleGyro(RumbleGyro::new(false)),
+            _                     => GamePakPeripheral::None,
+        }
+    }
+
+    /// Checks whether this peripheral is wired to the GPIO port.
+    pub fn uses_gpio(&self) -> bool {
+        match *self {
+            GamePakPeripheral::SolarSensor(_) | GamePakPeripheral::RumbleGyro(_) => true,
+            GamePakPeripheral::None | GamePakPeripheral::TiltSensor(_) => false,
+        }
+    }
+
+    /// Checks whether this peripheral is mapped into the SRAM area.
+    pub fn uses_sram(&self) -> bool {
+        match *self {
+            GamePakPeripheral::TiltSensor(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Lets the peripheral react on new GPIO pin states.
+    ///
+    /// # Params
+    /// - `pins`: The pin states driven by the GBA.
+    ///
+    /// # Returns
+    /// The pin states driven by the peripheral.
+    fn update_pins(&mut self, pins: u8) -> u8 {
+        match *self {
+            GamePakPeripheral::SolarSensor(ref mut x) => x.update_pins(pins),
+            GamePakPeripheral::RumbleGyro(ref mut x)  => x.update_pins(pins),
+            _ => 0,
+        }
+    }
+
+    /// Reads a byte from the peripheral's SRAM window.
+    ///
+    /// # Returns
+    /// - `Some`: The peripheral handled the read.
+    /// - `None`: The address belongs to the actual SRAM chip.
+    pub fn load_sram_byte(&self, offs: u32) -> Option<u8> {
+        match *self {
+            GamePakPeripheral::TiltSensor(ref x) => x.load_byte(offs),
+            _ => None,
+        }
+    }
+
+    /// Writes a byte into the peripheral's SRAM window.
+    ///
+    /// # Returns
+    /// - `true`: The peripheral handled the write.
+    /// - `false`: The address belongs to the actual SRAM chip.
+    pub fn store_sram_byte(&mut self, offs: u32, data: u8) -> bool {
+        match *self {
+            GamePakPeripheral::TiltSensor(ref mut x) => x.store_byte(offs, data),
+            _ => false,
+        }
+    }
+}
+
+impl Default for GamePakPeripheral {
+    fn default() -> GamePakPeripheral { GamePakPeripheral::None }
+}
+
+impl SaveState for GamePakPeripheral {
+    fn save_state(&self, w: &mut StateWriter) {
+        match *self {
+            GamePakPeripheral::None               => { w.write_u8(0); },
+            GamePakPeripheral::SolarSensor(ref x) => { w.write_u8(1); x.save_state(w); },
+            GamePakPeripheral::TiltSensor(ref x)  => { w.write_u8(2); x.save_state(w); },
+            GamePakPeripheral::RumbleGyro(ref x)  => { w.write_u8(3); x.save_state(w); },
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> io::Result<()> {
+        *self = match try!(r.read_u8()) {
+            0 => GamePakPeripheral::None,
+            1 => GamePakPeripheral::SolarSensor(SolarSensor::new()),
+            2 => GamePakPeripheral::TiltSensor(TiltSensor::new()),
+            3 => GamePakPeripheral::RumbleGyro(RumbleGyro::new(false)),
+            _ => { return Err(invalid_state("Unknown GamePak peripheral.")); },
+        };
+        match *self {
+            GamePakPeripheral::None                   => Ok(()),
+            GamePakPeripheral::SolarSensor(ref mut x) => x.load_state(r),
+            GamePakPeripheral::TiltSensor(ref mut x)  => x.load_state(r),
+            GamePakPeripheral::RumbleGyro(ref mut x)  => x.load_state(r),
+        }
+    }
+}
+
+impl fmt::Display for GamePakPeripheral {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match *self {
+            GamePakPeripheral::None                              => "none",
+            GamePakPeripheral::SolarSensor(_)                    => "Solar Sensor",
+            GamePakPeripheral::TiltSensor(_)                     => "Tilt Sensor",
+            GamePakPeripheral::RumbleGyro(ref x) if x.has_gyro() => "Rumble + Gyro Sensor",
+            GamePakPeripheral::RumbleGyro(_)                     => "Rumble",
+        })
+    }
+}
+
+
+/// Implements the GamePak's GPIO port.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct GpioPort {
+    data: u8,
+    direction: u8,
+    control: u8,
+}
+
+impl GpioPort {
+    /// Creates a new write-only GPIO port with all pins set to input.
+    pub fn new() -> GpioPort {
+        GpioPort { data: 0, direction: 0, control: 0 }
+    }
+
+    /// Checks whether a ROM offset belongs to the GPIO registers.
+    pub fn contains(offs: u32) -> bool {
+        (offs >= GPIO_DATA_OFFSET) & (offs < GPIO_END_OFFSET)
+    }
+
+    /// Checks whether the GPIO registers are currently readable.
+    pub fn is_readable(&self) -> bool { 0 != (self.control & 0b1) }
+
+    /// Reads a GPIO register.
+    ///
+    /// # Params
+    /// - `offs`: A ROM offset. Bit 0 will be masked away.
+    ///
+    /// # Returns
+    /// - `Some`: The register's value.
+    /// - `None`: The port is write-only, i.e. the ROM should be read instead.
+    pub fn read_halfword(&self, offs: u32) -> Option<u16> {
+        if !self.is_readable() { return None; }
+        match offs & !0b1 {
+            GPIO_DATA_OFFSET      => Some(self.data as u16),
+            GPIO_DIRECTION_OFFSET => Some(self.direction as u16),
+            GPIO_CONTROL_OFFSET   => Some(self.control as u16),
+            _ => None,
+        }
+    }
+
+    /// Writes a GPIO register and updates the peripheral.
+    ///
+    /// # Params
+    /// - `offs`: A ROM offset. Bit 0 will be masked away.
+    /// - `data`: The new register value. Only bits 0..3 are used.
+    /// - `dev`: The peripheral connected to the port.
+    pub fn write_halfword(&mut self, offs: u32, data: u16, dev: &mut GamePakPeripheral) {
+        let data = (data as u8) & GPIO_PIN_MASK;
+        match offs & !0b1 {
+            GPIO_DATA_OFFSET      => { self.data = (self.data & !self.direction) | (data & self.direction); },
+            GPIO_DIRECTION_OFFSET => { self.direction = data; },
+            GPIO_CONTROL_OFFSET   => { self.control = data & 0b1; },
+            _ => { return; },
+        }
+
+        // Let the peripheral drive all input pins.
+        let input = dev.update_pins(self.data & self.direction) & !self.direction;
+        self.data = (self.data & self.direction) | (input & GPIO_PIN_MASK);
+    }
+}
+
+impl SaveState for GpioPort {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.data);
+        w.write_u8(self.direction);
+        w.write_u8(self.control);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> io::Result<()> {
+        self.data      = try!(r.read_u8());
+        self.direction = try!(r.read_u8());
+        self.control   = try!(r.read_u8());
+        Ok(())
+    }
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
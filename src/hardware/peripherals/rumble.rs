@@ -0,0 +1,143 @@
+// License below.
+//! Implements the rumble motor and gyro sensor used by
+//! *Drill Dozer* and *WarioWare Twisted*.
+//!
+//! Both are driven through the GPIO port:
+//!
+//! ```text
+//! Pin 0: Sample, output from GBA. Samples the gyro's rotation rate.
+//! Pin 1: Clock, output from GBA. Each falling edge shifts out one bit.
+//! Pin 2: Data, input to GBA. The gyro's sample, MSB first.
+//! Pin 3: Rumble, output from GBA. The motor spins while set.
+//! ```
+//!
+//! The gyro's samples are unsigned 12-bit values where about
+//! `0x6C0` means the GBA is not being rotated.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::io;
+use super::super::savestate::{SaveState, StateWriter, StateReader};
+
+/// Implements a rumble motor with an optional gyro sensor.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct RumbleGyro {
+    gyro: bool,
+    rumble: bool,
+    rotation: i16,
+    sample: u16,
+    clock_high: bool,
+    data_high: bool,
+}
+
+impl RumbleGyro {
+    const PIN_SAMPLE: u8 = 0b0001;
+    const PIN_CLOCK: u8 = 0b0010;
+    const PIN_DATA: u8 = 0b0100;
+    const PIN_RUMBLE: u8 = 0b1000;
+
+    /// Sensor value if the GBA is not being rotated.
+    pub const NEUTRAL: u16 = 0x6C0;
+
+    /// Creates a new motor that does not rumble.
+    ///
+    /// # Params
+    /// - `gyro`: `true` if there also is a gyro sensor.
+    pub fn new(gyro: bool) -> RumbleGyro {
+        RumbleGyro {
+            gyro: gyro,
+            rumble: false,
+            rotation: 0,
+            sample: RumbleGyro::NEUTRAL,
+            clock_high: false,
+            data_high: false,
+        }
+    }
+
+    /// Checks whether there is a gyro sensor.
+    pub fn has_gyro(&self) -> bool { self.gyro }
+
+    /// Checks whether the game currently wants the GBA to rumble.
+    pub fn is_rumbling(&self) -> bool { self.rumble }
+
+    /// Get the current rotation rate.
+    pub fn rotation(&self) -> i16 { self.rotation }
+
+    /// Sets the current rotation rate around the Z axis.
+    ///
+    /// # Params
+    /// - `z`: Positive values rotate clockwise.
+    pub fn set_rotation(&mut self, z: i16) { self.rotation = z; }
+
+    /// Reacts on new pin states.
+    ///
+    /// # Params
+    /// - `pins`: The pin states driven by the GBA.
+    ///
+    /// # Returns
+    /// The pin states driven by the sensor.
+    pub fn update_pins(&mut self, pins: u8) -> u8 {
+        let rumble = 0 != (pins & RumbleGyro::PIN_RUMBLE);
+        if rumble != self.rumble { trace!("Rumble: {}", if rumble { "on" } else { "off" }); }
+        self.rumble = rumble;
+        if !self.gyro { return 0; }
+
+        if 0 != (pins & RumbleGyro::PIN_SAMPLE) {
+            let x = (RumbleGyro::NEUTRAL as i32) + (self.rotation as i32);
+            self.sample = if x < 0 { 0 } else if x > 0x0FFF { 0x0FFF } else { x as u16 };
+        }
+
+        // Shift out a bit on the clock's falling edge.
+        let clock = 0 != (pins & RumbleGyro::PIN_CLOCK);
+        if self.clock_high & !clock {
+            self.data_high = 0 != (self.sample & 0x8000);
+            self.sample <<= 1;
+        }
+        self.clock_high = clock;
+        if self.data_high { RumbleGyro::PIN_DATA } else { 0 }
+    }
+}
+
+
+impl SaveState for RumbleGyro {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.gyro);
+        w.write_bool(self.rumble);
+        w.write_u16(self.rotation as u16);
+        w.write_u16(self.sample);
+        w.write_bool(self.clock_high);
+        w.write_bool(self.data_high);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> io::Result<()> {
+        self.gyro       = try!(r.read_bool());
+        self.rumble     = try!(r.read_bool());
+        self.rotation   = try!(r.read_u16()) as i16;
+        self.sample     = try!(r.read_u16());
+        self.clock_high = try!(r.read_bool());
+        self.data_high  = try!(r.read_bool());
+        Ok(())
+    }
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -0,0 +1,117 @@
+// License below.
+//! Implements the solar sensor used by the *Boktai* series.
+//!
+//! The sensor is a light dependent ADC driven through the GPIO port:
+//!
+//! ```text
+//! Pin 0: Clock, output from GBA. Each rising edge increments the counter.
+//! Pin 1: Reset, output from GBA. Resets the counter and samples the light.
+//! Pin 2: Chip select, output from GBA. The sensor ignores all pins if set.
+//! Pin 3: Flag, input to GBA. Set as soon as the counter reached the sample.
+//! ```
+//!
+//! Games find out the light level by counting clock pulses until
+//! the flag pin goes high. The brighter the sun, the earlier.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::io;
+use super::super::savestate::{SaveState, StateWriter, StateReader};
+
+/// Implements the Boktai solar sensor.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct SolarSensor {
+    light_level: u8,
+    sample: u8,
+    counter: u8,
+    clock_low: bool,
+}
+
+impl SolarSensor {
+    const PIN_CLOCK: u8 = 0b0001;
+    const PIN_RESET: u8 = 0b0010;
+    const PIN_CHIP_SELECT: u8 = 0b0100;
+    const PIN_FLAG: u8 = 0b1000;
+
+    /// Creates a new solar sensor in complete darkness.
+    pub fn new() -> SolarSensor {
+        SolarSensor {
+            light_level: 0,
+            sample: 0xFF,
+            counter: 0,
+            clock_low: false,
+        }
+    }
+
+    /// Get the current light level.
+    pub fn light_level(&self) -> u8 { self.light_level }
+
+    /// Sets a new light level.
+    ///
+    /// The new level will be sampled with the next reset
+    /// pulse sent by the game.
+    ///
+    /// # Params
+    /// - `level`: `0` is complete darkness, `255` is the brightest sun.
+    pub fn set_light_level(&mut self, level: u8) { self.light_level = level; }
+
+    /// Reacts on new pin states.
+    ///
+    /// # Params
+    /// - `pins`: The pin states driven by the GBA.
+    ///
+    /// # Returns
+    /// The pin states driven by the sensor.
+    pub fn update_pins(&mut self, pins: u8) -> u8 {
+        if 0 != (pins & SolarSensor::PIN_CHIP_SELECT) { return 0; }
+
+        if 0 != (pins & SolarSensor::PIN_RESET) {
+            self.counter = 0;
+            self.sample  = 0xFF - self.light_level;
+        }
+        let clock = 0 != (pins & SolarSensor::PIN_CLOCK);
+        if clock & self.clock_low { self.counter = self.counter.wrapping_add(1); }
+        self.clock_low = !clock;
+
+        if self.counter >= self.sample { SolarSensor::PIN_FLAG } else { 0 }
+    }
+}
+
+impl SaveState for SolarSensor {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.light_level);
+        w.write_u8(self.sample);
+        w.write_u8(self.counter);
+        w.write_bool(self.clock_low);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> io::Result<()> {
+        self.light_level = try!(r.read_u8());
+        self.sample      = try!(r.read_u8());
+        self.counter     = try!(r.read_u8());
+        self.clock_low   = try!(r.read_bool());
+        Ok(())
+    }
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -0,0 +1,125 @@
+// License below.
+#![allow(missing_docs)]
+
+use super::*;
+use super::super::gamepak::GamePak;
+
+fn gpio_setup(gpak: &mut GamePak, direction: u16) {
+    assert!(gpak.store_rom_halfword(GPIO_CONTROL_OFFSET, 1));
+    assert!(gpak.store_rom_halfword(GPIO_DIRECTION_OFFSET, direction));
+}
+
+#[test]
+pub fn peripheral_detection() {
+    assert!(GamePakPeripheral::detect("U3IE").uses_gpio());
+    assert!(GamePakPeripheral::detect("KYGP").uses_sram());
+    assert!(GamePakPeripheral::detect("RZWJ").uses_gpio());
+    assert!(!GamePakPeripheral::detect("BMXE").uses_gpio());
+    assert!(!GamePakPeripheral::detect("").uses_sram());
+}
+
+#[test]
+pub fn solar_sensor_adc() {
+    for level in &[0_u8, 0x40, 0xFF] {
+        let mut gpak = GamePak::new();
+        let mut sensor = SolarSensor::new();
+        sensor.set_light_level(*level);
+        gpak.plug_peripheral(GamePakPeripheral::SolarSensor(sensor));
+        gpio_setup(&mut gpak, 0b0111);
+
+        // Reset, then count clock pulses until the flag is set.
+        gpak.store_rom_halfword(GPIO_DATA_OFFSET, 0b0010);
+        gpak.store_rom_halfword(GPIO_DATA_OFFSET, 0b0000);
+        let mut pulses = 0_u32;
+        while 0 == (gpak.load_rom_halfword(GPIO_DATA_OFFSET) & 0b1000) {
+            gpak.store_rom_halfword(GPIO_DATA_OFFSET, 0b0001);
+            gpak.store_rom_halfword(GPIO_DATA_OFFSET, 0b0000);
+            pulses += 1;
+            assert!(pulses <= 0xFF);
+        }
+        assert_eq!(pulses, 0xFF - (*level as u32));
+    }
+}
+
+#[test]
+pub fn gpio_write_only() {
+    let mut gpak = GamePak::new();
+    gpak.plug_peripheral(GamePakPeripheral::SolarSensor(SolarSensor::new()));
+    assert_eq!(gpak.load_rom_halfword(GPIO_DIRECTION_OFFSET), 0);
+    assert!(gpak.store_rom_halfword(GPIO_DIRECTION_OFFSET, 0b0111));
+    assert_eq!(gpak.load_rom_halfword(GPIO_DIRECTION_OFFSET), 0); // Still ROM.
+    assert!(gpak.store_rom_halfword(GPIO_CONTROL_OFFSET, 1));
+    assert_eq!(gpak.load_rom_halfword(GPIO_DIRECTION_OFFSET), 0b0111);
+    assert!(!gpak.store_rom_halfword(0x100, 0));
+}
+
+#[test]
+pub fn tilt_sensor_latching() {
+    let mut gpak = GamePak::new();
+    gpak.plug_peripheral(GamePakPeripheral::TiltSensor(TiltSensor::new()));
+    if let GamePakPeripheral::TiltSensor(ref mut t) = *gpak.peripheral_mut() { t.set_tilt(0x123, -0x3A0); }
+
+    // Nothing changes before latching.
+    assert_eq!(gpak.load_sram_byte(TILT_X_LOW_OFFSET), 0xA0);
+    assert_eq!(gpak.load_sram_byte(TILT_X_HIGH_OFFSET), 0x83);
+
+    gpak.store_sram_byte(TILT_START_OFFSET, 0x55);
+    gpak.store_sram_byte(TILT_LATCH_OFFSET, 0xAA);
+    assert_eq!(gpak.load_sram_byte(TILT_X_LOW_OFFSET), 0xC3);
+    assert_eq!(gpak.load_sram_byte(TILT_X_HIGH_OFFSET), 0x84);
+    assert_eq!(gpak.load_sram_byte(TILT_Y_LOW_OFFSET), 0x00);
+    assert_eq!(gpak.load_sram_byte(TILT_Y_HIGH_OFFSET), 0x00);
+
+    // The actual SRAM is still there.
+    gpak.store_sram_byte(0x10, 0x42);
+    assert_eq!(gpak.load_sram_byte(0x10), 0x42);
+}
+
+#[test]
+pub fn gyro_sensor_and_rumble() {
+    let mut gpak = GamePak::new();
+    gpak.plug_peripheral(GamePakPeripheral::RumbleGyro(RumbleGyro::new(true)));
+    if let GamePakPeripheral::RumbleGyro(ref mut g) = *gpak.peripheral_mut() { g.set_rotation(-0x40); }
+    gpio_setup(&mut gpak, 0b1011);
+
+    // Sample, then shift out 16 bits.
+    gpak.store_rom_halfword(GPIO_DATA_OFFSET, 0b1011);
+    let mut x = 0_u16;
+    for _ in 0..16 {
+        gpak.store_rom_halfword(GPIO_DATA_OFFSET, 0b1000);
+        x = (x << 1) | ((gpak.load_rom_halfword(GPIO_DATA_OFFSET) >> 2) & 1);
+        gpak.store_rom_halfword(GPIO_DATA_OFFSET, 0b1010);
+    }
+    assert_eq!(x, RumbleGyro::NEUTRAL - 0x40);
+
+    match *gpak.peripheral() {
+        GamePakPeripheral::RumbleGyro(ref g) => assert!(g.is_rumbling()),
+        _ => unreachable!(),
+    }
+    gpak.store_rom_halfword(GPIO_DATA_OFFSET, 0);
+    match *gpak.peripheral() {
+        GamePakPeripheral::RumbleGyro(ref g) => assert!(!g.is_rumbling()),
+        _ => unreachable!(),
+    }
+}
+
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
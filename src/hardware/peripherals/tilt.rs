@@ -0,0 +1,177 @@
+// License below.
+//! Implements the tilt sensor used by *Yoshi Topsy-Turvy*.
+//!
+//! The sensor is a 2-axis accelerometer mapped into the SRAM area:
+//!
+//! ```text
+//! 0x0E008000: Write 0x55 to start sampling.
+//! 0x0E008100: Write 0xAA to latch the sampled values.
+//! 0x0E008200: X axis, bits 0..7.
+//! 0x0E008300: X axis, bits 8..11. Bit 7 is always set.
+//! 0x0E008400: Y axis, bits 0..7.
+//! 0x0E008500: Y axis, bits 8..11.
+//! ```
+//!
+//! Both axes are unsigned 12-bit values where about `0x3A0`
+//! means the GBA is held flat.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::io;
+use super::super::savestate::{SaveState, StateWriter, StateReader};
+
+/// SRAM offset where sampling starts.
+pub const TILT_START_OFFSET: u32 = 0x8000;
+
+/// SRAM offset where the sampled values get latched.
+pub const TILT_LATCH_OFFSET: u32 = 0x8100;
+
+/// SRAM offset of the lower X axis bits.
+pub const TILT_X_LOW_OFFSET: u32 = 0x8200;
+
+/// SRAM offset of the upper X axis bits.
+pub const TILT_X_HIGH_OFFSET: u32 = 0x8300;
+
+/// SRAM offset of the lower Y axis bits.
+pub const TILT_Y_LOW_OFFSET: u32 = 0x8400;
+
+/// SRAM offset of the upper Y axis bits.
+pub const TILT_Y_HIGH_OFFSET: u32 = 0x8500;
+
+/// Implements the Yoshi Topsy-Turvy tilt sensor.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct TiltSensor {
+    tilt_x: i16,
+    tilt_y: i16,
+    latched_x: u16,
+    latched_y: u16,
+    sampling: bool,
+}
+
+impl TiltSensor {
+    /// Sensor value of an axis if the GBA is held flat.
+    pub const NEUTRAL: u16 = 0x3A0;
+
+    /// Creates a new tilt sensor held flat.
+    pub fn new() -> TiltSensor {
+        TiltSensor {
+            tilt_x: 0,
+            tilt_y: 0,
+            latched_x: TiltSensor::NEUTRAL,
+            latched_y: TiltSensor::NEUTRAL,
+            sampling: false,
+        }
+    }
+
+    /// Get the current tilt relative to holding the GBA flat.
+    pub fn tilt(&self) -> (i16, i16) { (self.tilt_x, self.tilt_y) }
+
+    /// Sets the current tilt relative to holding the GBA flat.
+    ///
+    /// The new values will be visible to the game as soon as
+    /// it latches a new sample.
+    ///
+    /// # Params
+    /// - `x`: Tilt on the X axis. Positive values tilt to the right.
+    /// - `y`: Tilt on the Y axis. Positive values tilt towards you.
+    pub fn set_tilt(&mut self, x: i16, y: i16) {
+        self.tilt_x = x;
+        self.tilt_y = y;
+    }
+
+    /// Get the latched raw 12-bit sensor values.
+    pub fn latched(&self) -> (u16, u16) { (self.latched_x, self.latched_y) }
+
+    fn to_raw(tilt: i16) -> u16 {
+        let x = (TiltSensor::NEUTRAL as i32) + (tilt as i32);
+        if x < 0 { 0 } else if x > 0x0FFF { 0x0FFF } else { x as u16 }
+    }
+
+    /// Reads a sensor register.
+    ///
+    /// # Params
+    /// - `offs`: An SRAM-local address.
+    ///
+    /// # Returns
+    /// - `Some`: The register's value.
+    /// - `None`: The address belongs to the SRAM chip.
+    pub fn load_byte(&self, offs: u32) -> Option<u8> {
+        match offs {
+            TILT_X_LOW_OFFSET  => Some((self.latched_x & 0xFF) as u8),
+            TILT_X_HIGH_OFFSET => Some(((self.latched_x >> 8) & 0x0F) as u8 | 0x80),
+            TILT_Y_LOW_OFFSET  => Some((self.latched_y & 0xFF) as u8),
+            TILT_Y_HIGH_OFFSET => Some(((self.latched_y >> 8) & 0x0F) as u8),
+            _ => None,
+        }
+    }
+
+    /// Writes a sensor register.
+    ///
+    /// # Params
+    /// - `offs`: An SRAM-local address.
+    /// - `data`: The byte to write.
+    ///
+    /// # Returns
+    /// - `true`: The sensor handled the write.
+    /// - `false`: The address belongs to the SRAM chip.
+    pub fn store_byte(&mut self, offs: u32, data: u8) -> bool {
+        match offs {
+            TILT_START_OFFSET => {
+                if data == 0x55 { self.sampling = true; }
+                else { warn!("Tilt sensor: Unexpected start command {:#04X}.", data); }
+                true
+            },
+            TILT_LATCH_OFFSET => {
+                if (data == 0xAA) & self.sampling {
+                    self.sampling  = false;
+                    self.latched_x = TiltSensor::to_raw(self.tilt_x);
+                    self.latched_y = TiltSensor::to_raw(self.tilt_y);
+                }
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+
+impl SaveState for TiltSensor {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_u16(self.tilt_x as u16);
+        w.write_u16(self.tilt_y as u16);
+        w.write_u16(self.latched_x);
+        w.write_u16(self.latched_y);
+        w.write_bool(self.sampling);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> io::Result<()> {
+        self.tilt_x    = try!(r.read_u16()) as i16;
+        self.tilt_y    = try!(r.read_u16()) as i16;
+        self.latched_x = try!(r.read_u16());
+        self.latched_y = try!(r.read_u16());
+        self.sampling  = try!(r.read_bool());
+        Ok(())
+    }
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
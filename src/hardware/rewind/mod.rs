@@ -0,0 +1,205 @@
+// License below.
+//! Implements rewinding the emulated machine back in time.
+//!
+//! A `RewindBuffer` takes a snapshot of the whole machine every
+//! few frames. Only the newest snapshot is kept as is. Older
+//! snapshots are stored as compressed XOR deltas to their
+//! successors, which are mostly zero and compress very well.
+//! As soon as the buffer exceeds its memory budget, the oldest
+//! deltas are dropped.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::cmp;
+use std::collections::VecDeque;
+use std::io;
+use std::io::{Read, Write};
+use flate2;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use super::Gba;
+
+#[cfg(test)]
+mod test;
+
+
+/// The number of CPU cycles per frame.
+///
+/// A pipeline step is treated as a single cycle.
+pub const CYCLES_PER_FRAME: u32 = 280896;
+
+/// The default memory budget of a rewind buffer in bytes.
+pub const DEFAULT_REWIND_BUDGET: usize = 32 * 1024 * 1024;
+
+
+/// A bounded history of machine snapshots.
+pub struct RewindBuffer {
+    interval: u32,
+    countdown: u32,
+    budget: usize,
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+    deltas_len: usize,
+    dirty: bool,
+}
+
+impl RewindBuffer {
+    /// Creates a new empty rewind buffer.
+    ///
+    /// # Params
+    /// - `frames`: Take a snapshot every this many frames.
+    /// - `budget`: The maximum memory usage in bytes. The
+    ///   newest snapshot is always kept, though.
+    pub fn new(frames: u32, budget: usize) -> RewindBuffer {
+        let interval = cmp::max(frames, 1).saturating_mul(CYCLES_PER_FRAME);
+        RewindBuffer {
+            interval: interval,
+            countdown: interval,
+            budget: budget,
+            latest: None,
+            deltas: VecDeque::new(),
+            deltas_len: 0,
+            dirty: false,
+        }
+    }
+
+    /// Forgets all snapshots.
+    pub fn clear(&mut self) {
+        self.countdown = self.interval;
+        self.latest = None;
+        self.deltas.clear();
+        self.deltas_len = 0;
+        self.dirty = false;
+    }
+
+    /// Get the number of snapshots available.
+    pub fn len(&self) -> usize {
+        if self.latest.is_some() { self.deltas.len() + 1 } else { 0 }
+    }
+
+    /// Checks whether there are no snapshots at all.
+    pub fn is_empty(&self) -> bool { self.latest.is_none() }
+
+    /// Get the memory used by all snapshots in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.deltas_len + self.latest.as_ref().map_or(0, |x| x.len())
+    }
+
+    /// Notifies the buffer that the machine executed a pipeline step.
+    ///
+    /// Takes a snapshot whenever the configured number of
+    /// frames has passed.
+    pub fn step(&mut self, gba: &Gba) -> io::Result<()> {
+        self.dirty = true;
+        self.countdown -= 1;
+        if self.countdown == 0 { self.record(gba) } else { Ok(()) }
+    }
+
+    /// Takes a snapshot of the machine right now.
+    pub fn record(&mut self, gba: &Gba) -> io::Result<()> {
+        let snapshot = gba.snapshot();
+        self.countdown = self.interval;
+        self.dirty = false;
+
+        if let Some(old) = self.latest.take() {
+            if old.len() == snapshot.len() {
+                let delta = try!(compress_xor(&old, &snapshot));
+                self.deltas_len += delta.len();
+                self.deltas.push_back(delta);
+            } else {
+                // The machine's layout changed, e.g. by plugging
+                // in another peripheral. Old deltas are useless.
+                self.deltas.clear();
+                self.deltas_len = 0;
+            }
+        }
+        self.latest = Some(snapshot);
+
+        while (self.memory_usage() > self.budget) & !self.deltas.is_empty() {
+            if let Some(d) = self.deltas.pop_front() { self.deltas_len -= d.len(); }
+        }
+        Ok(())
+    }
+
+    /// Steps backwards in time.
+    ///
+    /// If the machine ran since the newest snapshot, going
+    /// back one step restores that snapshot. Otherwise, the
+    /// one before it is restored. All newer snapshots are
+    /// dropped.
+    ///
+    /// # Params
+    /// - `gba`: The machine to restore.
+    /// - `n`: The number of snapshots to go back.
+    ///
+    /// # Returns
+    /// - `Ok`: The number of snapshots actually gone back.
+    ///   This is less than `n` if the history is too short.
+    /// - `Err` if a snapshot is damaged.
+    pub fn rewind(&mut self, gba: &mut Gba, n: usize) -> io::Result<usize> {
+        if (n == 0) | self.latest.is_none() { return Ok(0); }
+        let (mut done, mut todo) = if self.dirty { (1, n - 1) } else { (0, n) };
+
+        if let Some(mut snapshot) = self.latest.take() {
+            while todo > 0 {
+                let delta = if let Some(d) = self.deltas.pop_back() { d } else { break; };
+                self.deltas_len -= delta.len();
+                try!(decompress_xor(&delta, &mut snapshot));
+                todo -= 1;
+                done += 1;
+            }
+            try!(gba.restore_snapshot(&snapshot));
+            self.latest = Some(snapshot);
+        }
+        self.countdown = self.interval;
+        self.dirty = false;
+        Ok(done)
+    }
+}
+
+impl Default for RewindBuffer {
+    fn default() -> RewindBuffer { RewindBuffer::new(1, DEFAULT_REWIND_BUDGET) }
+}
+
+
+// Compresses `old XOR new`.
+fn compress_xor(old: &[u8], new: &[u8]) -> io::Result<Vec<u8>> {
+    let xor: Vec<u8> = old.iter().zip(new.iter()).map(|(a, b)| a ^ b).collect();
+    let mut enc = DeflateEncoder::new(Vec::new(), flate2::Compression::fast());
+    try!(enc.write_all(&xor));
+    enc.finish()
+}
+
+// Turns the newer snapshot back into the older one.
+fn decompress_xor(delta: &[u8], snapshot: &mut [u8]) -> io::Result<()> {
+    let mut xor = Vec::with_capacity(snapshot.len());
+    try!(DeflateDecoder::new(delta).read_to_end(&mut xor));
+    if xor.len() != snapshot.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Damaged rewind delta."));
+    }
+    for (s, x) in snapshot.iter_mut().zip(xor.iter()) { *s ^= *x; }
+    Ok(())
+}
+
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -0,0 +1,84 @@
+// License below.
+#![allow(missing_docs)]
+
+use super::*;
+use super::super::test_util::counting_gba;
+
+fn run(gba: &mut Gba, rb: &mut RewindBuffer, steps: u32) {
+    for _ in 0..steps {
+        gba.cpu_arm7tdmi_mut().pipeline_step().unwrap();
+        rb.step(gba).unwrap();
+    }
+}
+
+#[test]
+pub fn rewind_snapshots() {
+    let mut gba = counting_gba();
+    let mut rb = RewindBuffer::new(1, DEFAULT_REWIND_BUDGET);
+    let mut states = Vec::new();
+    for _ in 0..4 {
+        rb.record(&gba).unwrap();
+        states.push(gba.snapshot());
+        run(&mut gba, &mut rb, 40);
+    }
+    assert_eq!(rb.len(), 4);
+    assert!(rb.memory_usage() < 2 * states[0].len());
+
+    // The machine ran, so the newest snapshot comes first.
+    assert_eq!(rb.rewind(&mut gba, 1).unwrap(), 1);
+    assert_eq!(gba.snapshot(), states[3]);
+    assert_eq!(rb.rewind(&mut gba, 2).unwrap(), 2);
+    assert_eq!(gba.snapshot(), states[1]);
+    assert_eq!(rb.len(), 2);
+
+    // Going back too far stops at the oldest snapshot.
+    assert_eq!(rb.rewind(&mut gba, 5).unwrap(), 1);
+    assert_eq!(gba.snapshot(), states[0]);
+    assert_eq!(rb.rewind(&mut gba, 1).unwrap(), 0);
+    assert_eq!(gba.snapshot(), states[0]);
+}
+
+#[test]
+pub fn snapshot_interval() {
+    let mut gba = counting_gba();
+    let mut rb = RewindBuffer::new(1, DEFAULT_REWIND_BUDGET);
+    run(&mut gba, &mut rb, CYCLES_PER_FRAME - 1);
+    assert!(rb.is_empty());
+    run(&mut gba, &mut rb, 1);
+    assert_eq!(rb.len(), 1);
+}
+
+#[test]
+pub fn memory_budget() {
+    let mut gba = counting_gba();
+    let mut rb = RewindBuffer::new(1, 0);
+    for _ in 0..3 {
+        rb.record(&gba).unwrap();
+        run(&mut gba, &mut rb, 40);
+    }
+    assert_eq!(rb.len(), 1);
+    assert_eq!(rb.memory_usage(), gba.snapshot().len());
+    rb.clear();
+    assert_eq!(rb.rewind(&mut gba, 1).unwrap(), 0);
+}
+
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
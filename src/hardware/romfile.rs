@@ -0,0 +1,184 @@
+// License below.
+//! Reads ROM images from plain files and compressed archives.
+//!
+//! The GamePak ROM, the BIOS ROM, and multiboot images
+//! can be loaded from:
+//!
+//! - A raw binary file.
+//! - A `.gz` file containing a single image.
+//! - A `.zip` archive. Either a named entry is loaded, or
+//!   the first entry with a matching file extension.
+//!
+//! The file format is detected by its magic number, so the
+//! file extension of the archive itself does not matter.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+use std::fs::File;
+use std::path::Path;
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+
+/// Magic number at the start of a `.zip` archive.
+pub const ZIP_MAGIC: [u8; 4] = [0x50, 0x4B, 0x03, 0x04];
+
+/// Magic number at the start of a `.gz` file.
+pub const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+/// File extensions of GamePak ROM entries in an archive.
+pub const ROM_EXTENSIONS: &'static [&'static str] = &["gba", "agb"];
+
+/// File extensions of BIOS ROM entries in an archive.
+pub const BIOS_EXTENSIONS: &'static [&'static str] = &["bin", "rom"];
+
+/// File extensions of multiboot image entries in an archive.
+pub const MULTIBOOT_EXTENSIONS: &'static [&'static str] = &["mb", "gba"];
+
+
+/// The container format of an image file.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ImageFileFormat {
+    #[doc = "A raw binary image."]      Raw,
+    #[doc = "A gzip compressed image."] Gzip,
+    #[doc = "A zip archive."]           Zip,
+}
+
+impl ImageFileFormat {
+    /// Detects the format of a file by its first few bytes.
+    pub fn detect(magic: &[u8]) -> ImageFileFormat {
+        if magic.starts_with(&ZIP_MAGIC) { ImageFileFormat::Zip }
+        else if magic.starts_with(&GZIP_MAGIC) { ImageFileFormat::Gzip }
+        else { ImageFileFormat::Raw }
+    }
+}
+
+
+/// Reads an image file into a buffer.
+///
+/// The whole image is read, even if the underlying reader
+/// only delivers it in small chunks. Unused bytes at the
+/// end of the buffer are left untouched.
+///
+/// # Params
+/// - `fp`: Path to a raw, `.gz`, or `.zip` file.
+/// - `entry`: Name of the entry to load from a `.zip` archive.
+///   If `None`, the first entry with one of the `extensions` is loaded.
+/// - `extensions`: Lower case file extensions of loadable archive entries.
+/// - `buf`: The buffer to fill. Its size is the maximum image size.
+///
+/// # Returns
+/// - `Ok`: The number of bytes read.
+/// - `Err`: The file is unreadable, has no matching entry, or is too large.
+pub fn read_image_file(fp: &Path, entry: Option<&str>, extensions: &[&str], buf: &mut [u8]) -> io::Result<usize> {
+    let f = try!(File::open(fp));
+    read_image_from(f, &fp.display().to_string(), entry, extensions, buf)
+}
+
+/// Reads an image from any seekable source into a buffer.
+///
+/// Works just like `read_image_file`, but e.g. on
+/// an archive already loaded into memory.
+///
+/// # Params
+/// - `r`: The raw, gzip, or zip data.
+/// - `name`: Names the source in messages.
+/// - `entry`, `extensions`, `buf`: See `read_image_file`.
+pub fn read_image_from<R: Read + Seek>(mut r: R, name: &str, entry: Option<&str>, extensions: &[&str], buf: &mut [u8])
+-> io::Result<usize> {
+    let mut magic = [0_u8; 4];
+    let magic_len = try!(read_full(&mut r, &mut magic));
+    try!(r.seek(SeekFrom::Start(0)));
+
+    match ImageFileFormat::detect(&magic[..magic_len]) {
+        ImageFileFormat::Raw => {
+            if entry.is_some() { warn!("`{}` is no archive. Ignoring the entry name.", name); }
+            read_image(&mut r, buf)
+        },
+        ImageFileFormat::Gzip => {
+            trace!("Decompressing gzip file `{}`.", name);
+            if entry.is_some() { warn!("`{}` is no zip archive. Ignoring the entry name.", name); }
+            let mut gz = GzDecoder::new(r);
+            read_image(&mut gz, buf)
+        },
+        ImageFileFormat::Zip => {
+            let mut zip = try!(ZipArchive::new(r));
+            let index = try!(find_zip_entry(&mut zip, entry, extensions));
+            let mut file = try!(zip.by_index(index));
+            trace!("Decompressing zip entry `{}` from `{}`.", file.name(), name);
+            read_image(&mut file, buf)
+        },
+    }
+}
+
+/// Finds the index of the zip entry to load.
+fn find_zip_entry<R: Read + Seek>(zip: &mut ZipArchive<R>, entry: Option<&str>, extensions: &[&str]) -> io::Result<usize> {
+    for i in 0..zip.len() {
+        let file = try!(zip.by_index(i));
+        let name = file.name();
+        let found = match entry {
+            Some(e) => name == e,
+            None => {
+                let ext = Path::new(name).extension().and_then(|x| x.to_str()).unwrap_or("");
+                extensions.iter().any(|x| x.eq_ignore_ascii_case(ext))
+            },
+        };
+        if found { return Ok(i); }
+    }
+    Err(io::Error::new(io::ErrorKind::NotFound, match entry {
+        Some(e) => format!("The archive contains no entry named `{}`.", e),
+        None    => format!("The archive contains no entry ending in any of {:?}.", extensions),
+    }))
+}
+
+/// Reads a whole image and fails if it does not fit into the buffer.
+fn read_image<R: Read>(r: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let n = try!(read_full(r, buf));
+    if n == buf.len() {
+        let mut probe = [0_u8; 1];
+        if 0 != try!(read_full(r, &mut probe)) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("The image is larger than the maximum of {} bytes.", buf.len())));
+        }
+    }
+    Ok(n)
+}
+
+/// Reads until either the buffer is full or the end is reached.
+fn read_full<R: Read>(r: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match r.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(x) => n += x,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(n)
+}
+
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
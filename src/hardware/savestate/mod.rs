@@ -0,0 +1,224 @@
+// License below.
+//! Implements save states, i.e. snapshots of the complete machine.
+//!
+//! A save state is a little endian binary blob:
+//!
+//! ```text
+//! 0x00: Magic     | "GBArsSST"
+//! 0x08: Version   | The format version, see `SAVE_STATE_VERSION`.
+//! 0x0C: ROM CRC32 | CRC32 of the loaded GamePak ROM.
+//! 0x10: Body      | CPU, bus, and GamePak state in that order.
+//! ```
+//!
+//! The body's layout is defined by the `SaveState`
+//! implementations of all hardware components and
+//! changes with every new format version. Neither the
+//! ROM nor the BIOS are part of a save state.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+mod test;
+
+
+/// Magic bytes identifying a save state.
+pub const SAVE_STATE_MAGIC: &'static [u8; 8] = b"GBArsSST";
+
+/// The current save state format version.
+///
+/// Save states of any other version are rejected.
+pub const SAVE_STATE_VERSION: u32 = 1;
+
+/// Size of the save state header in bytes.
+pub const SAVE_STATE_HEADER_LEN: usize = 0x10;
+
+/// The number of save state slots offered by frontends.
+pub const SAVE_STATE_SLOTS: u8 = 10;
+
+
+/// Implemented by all hardware components that are part of a save state.
+pub trait SaveState {
+    /// Appends the component's state to a save state.
+    fn save_state(&self, w: &mut StateWriter);
+
+    /// Restores the component's state from a save state.
+    ///
+    /// # Returns
+    /// - `Ok` if restored successfully.
+    /// - `Err` if the save state is truncated or invalid.
+    ///   The component's state might be damaged.
+    fn load_state(&mut self, r: &mut StateReader) -> io::Result<()>;
+}
+
+
+/// Serialises hardware state into a byte buffer.
+#[derive(Debug, Default, Clone)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    /// Creates a new empty writer.
+    pub fn new() -> StateWriter { StateWriter { data: Vec::new() } }
+
+    /// Appends a single byte.
+    pub fn write_u8(&mut self, x: u8) { self.data.push(x); }
+
+    /// Appends a boolean as a single byte.
+    pub fn write_bool(&mut self, x: bool) { self.data.push(x as u8); }
+
+    /// Appends a little endian halfword.
+    pub fn write_u16(&mut self, x: u16) {
+        self.data.extend_from_slice(&[x as u8, (x >> 8) as u8]);
+    }
+
+    /// Appends a little endian word.
+    pub fn write_u32(&mut self, x: u32) {
+        self.write_u16(x as u16);
+        self.write_u16((x >> 16) as u16);
+    }
+
+    /// Appends a block of bytes as is.
+    pub fn write_bytes(&mut self, x: &[u8]) { self.data.extend_from_slice(x); }
+
+    /// Get the number of bytes written so far.
+    pub fn len(&self) -> usize { self.data.len() }
+
+    /// Checks whether nothing has been written yet.
+    pub fn is_empty(&self) -> bool { self.data.is_empty() }
+
+    /// Get the serialised state.
+    pub fn into_bytes(self) -> Vec<u8> { self.data }
+}
+
+
+/// Deserialises hardware state from a byte buffer.
+#[derive(Debug, Clone)]
+pub struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    /// Creates a new reader starting at the first byte.
+    pub fn new(data: &'a [u8]) -> StateReader<'a> { StateReader { data: data } }
+
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if self.data.len() < n {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "The save state is truncated."));
+        }
+        let (x, rest) = self.data.split_at(n);
+        self.data = rest;
+        Ok(x)
+    }
+
+    /// Reads a single byte.
+    pub fn read_u8(&mut self) -> io::Result<u8> { self.take(1).map(|x| x[0]) }
+
+    /// Reads a boolean stored as a single byte.
+    pub fn read_bool(&mut self) -> io::Result<bool> { self.read_u8().map(|x| x != 0) }
+
+    /// Reads a little endian halfword.
+    pub fn read_u16(&mut self) -> io::Result<u16> {
+        self.take(2).map(|x| (x[0] as u16) | ((x[1] as u16) << 8))
+    }
+
+    /// Reads a little endian word.
+    pub fn read_u32(&mut self) -> io::Result<u32> {
+        let lo = try!(self.read_u16()) as u32;
+        let hi = try!(self.read_u16()) as u32;
+        Ok(lo | (hi << 16))
+    }
+
+    /// Reads a block of bytes filling the whole buffer.
+    pub fn read_bytes(&mut self, dst: &mut [u8]) -> io::Result<()> {
+        let src = try!(self.take(dst.len()));
+        dst.copy_from_slice(src);
+        Ok(())
+    }
+
+    /// Get the number of bytes not read yet.
+    pub fn remaining(&self) -> usize { self.data.len() }
+}
+
+
+/// Creates an error for invalid values in a save state.
+pub fn invalid_state(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid save state: {}", what))
+}
+
+/// Writes a save state header.
+///
+/// # Params
+/// - `w`: The writer to append the header to.
+/// - `rom_crc`: CRC32 of the loaded GamePak ROM.
+pub fn write_header(w: &mut StateWriter, rom_crc: u32) {
+    w.write_bytes(SAVE_STATE_MAGIC);
+    w.write_u32(SAVE_STATE_VERSION);
+    w.write_u32(rom_crc);
+}
+
+/// Reads and checks a save state header.
+///
+/// # Params
+/// - `r`: The reader to read the header from.
+/// - `rom_crc`: CRC32 of the loaded GamePak ROM.
+///
+/// # Returns
+/// - `Ok` if the save state has been made by this version
+///   of GBArs for the loaded ROM.
+/// - `Err` otherwise.
+pub fn read_header(r: &mut StateReader, rom_crc: u32) -> io::Result<()> {
+    let mut magic = [0; 8];
+    try!(r.read_bytes(&mut magic));
+    if &magic != SAVE_STATE_MAGIC { return Err(invalid_state("This is no GBArs save state.")); }
+
+    let version = try!(r.read_u32());
+    if version != SAVE_STATE_VERSION {
+        return Err(invalid_state(&format!("Unsupported format version {}, expected {}.",
+                                          version, SAVE_STATE_VERSION)));
+    }
+    let crc = try!(r.read_u32());
+    if crc != rom_crc {
+        return Err(invalid_state(&format!("Made for the ROM with CRC32 {:#010X}, but {:#010X} is loaded.",
+                                          crc, rom_crc)));
+    }
+    Ok(())
+}
+
+/// Get the path of a numbered save state slot.
+///
+/// Slots live next to the ROM file, just like SRAM files,
+/// e.g. slot 3 of `game.gba` is `game.ss3`.
+///
+/// # Params
+/// - `base`: Path to the ROM file or any other base path.
+/// - `slot`: The slot number.
+pub fn slot_path(base: &Path, slot: u8) -> PathBuf {
+    base.with_extension(format!("ss{}", slot))
+}
+
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
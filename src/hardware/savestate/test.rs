@@ -0,0 +1,113 @@
+// License below.
+#![allow(missing_docs)]
+
+use super::*;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process;
+use super::super::Gba;
+use super::super::peripherals::{GamePakPeripheral, SolarSensor};
+use super::super::test_util::counting_gba;
+
+fn run(gba: &mut Gba, steps: u32) {
+    for _ in 0..steps { gba.cpu_arm7tdmi_mut().pipeline_step().unwrap(); }
+}
+
+#[test]
+pub fn round_trip() {
+    let mut gba = counting_gba();
+    gba.game_pak_mut().plug_peripheral(GamePakPeripheral::SolarSensor(SolarSensor::new()));
+    run(&mut gba, 50);
+    let state = gba.save_state();
+    assert_eq!(&state[..8], SAVE_STATE_MAGIC);
+
+    run(&mut gba, 50);
+    let later = gba.save_state();
+    let r0_later = gba.cpu_arm7tdmi().register(0);
+    gba.game_pak_mut().plug_peripheral(GamePakPeripheral::None);
+
+    gba.load_state(&state).unwrap();
+    assert_eq!(gba.save_state(), state);
+    assert!(gba.game_pak().peripheral().uses_gpio());
+
+    // Running from the same state gives the same results.
+    run(&mut gba, 50);
+    assert_eq!(gba.cpu_arm7tdmi().register(0), r0_later);
+    assert_eq!(gba.save_state(), later);
+}
+
+#[test]
+pub fn rejects_foreign_states() {
+    let mut gba = counting_gba();
+    let state = gba.save_state();
+
+    let mut bad_magic = state.clone();
+    bad_magic[0] = b'X';
+    assert!(gba.load_state(&bad_magic).is_err());
+
+    let mut bad_version = state.clone();
+    bad_version[8] = bad_version[8].wrapping_add(1);
+    assert!(gba.load_state(&bad_version).is_err());
+
+    let mut bad_crc = state.clone();
+    bad_crc[12] ^= 0xFF;
+    assert!(gba.load_state(&bad_crc).is_err());
+
+    assert!(gba.load_state(&state[..(state.len() - 1)]).is_err());
+    assert!(gba.load_state(&state).is_ok());
+}
+
+#[test]
+pub fn damaged_states_change_nothing() {
+    let fp = env::temp_dir().join(format!("gbars-{}-truncated.ss0", process::id()));
+    let mut gba = counting_gba();
+    gba.game_pak_mut().plug_peripheral(GamePakPeripheral::SolarSensor(SolarSensor::new()));
+    run(&mut gba, 50);
+    let state = gba.save_state();
+    run(&mut gba, 50);
+    let before = gba.save_state();
+
+    // A state cut off in the GamePak's SRAM.
+    fs::File::create(&fp).unwrap().write_all(&state[..(state.len() - 0x100)]).unwrap();
+    assert!(gba.load_state_file(&fp).is_err());
+    fs::remove_file(&fp).unwrap();
+    assert_eq!(gba.save_state(), before);
+
+    // An unknown peripheral at the very end.
+    let mut unknown = state.clone();
+    unknown[state.len() - 5] = 0xFF; // The solar sensor's state has 4 bytes.
+    assert!(gba.load_state(&unknown).is_err());
+    assert_eq!(gba.save_state(), before);
+
+    let snapshot = gba.snapshot();
+    assert!(gba.restore_snapshot(&snapshot[..(snapshot.len() - 1)]).is_err());
+    assert_eq!(gba.save_state(), before);
+}
+
+#[test]
+pub fn slot_paths() {
+    assert_eq!(slot_path(Path::new("roms/game.gba"), 3), PathBuf::from("roms/game.ss3"));
+    assert_eq!(slot_path(Path::new("./GBArs"), 0), PathBuf::from("./GBArs.ss0"));
+}
+
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -0,0 +1,298 @@
+// License below.
+#![allow(missing_docs)]
+
+use super::*;
+use super::memory::{RawBytes, Rom16};
+use super::ioregs::*;
+use std::env;
+use std::fs;
+use std::process;
+use std::path::PathBuf;
+use std::io;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use zip::ZipWriter;
+use zip::write::FileOptions;
+use super::romfile::*;
+
+// A file in the temporary directory, unique per test run.
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("gbars-{}-{}", process::id(), name))
+}
+
+// A ROM header with a bad complement check, entering at 080000C0.
+fn header_rom(name: &str) -> GamePakRom {
+    let mut h = vec![0_u8; ROM_HEADER_LEN];
+    h[0..4].copy_from_slice(&[0x2E, 0x00, 0x00, 0xEA]); // b 080000C0
+    h[GAME_TITLE_OFFSET..(GAME_TITLE_OFFSET + 8)].copy_from_slice(b"TESTGAME");
+    h[GAME_CODE_OFFSET..(GAME_CODE_OFFSET + 4)].copy_from_slice(b"ATGE");
+    h[GAME_MAKER_CODE_OFFSET..(GAME_MAKER_CODE_OFFSET + 2)].copy_from_slice(b"01");
+    h[FIXED_VALUE_OFFSET] = FIXED_VALUE;
+    h[GAME_VERSION_NUMBER] = 2;
+    h[COMPLEMENT_CHECK_OFFSET] = 0x12;
+    h.extend_from_slice(&[0xAB; 0x40]);
+
+    let fp = temp_path(name);
+    fs::File::create(&fp).unwrap().write_all(&h).unwrap();
+    let mut rom = GamePakRom::new();
+    rom.load_from_file(&fp).unwrap();
+    fs::remove_file(&fp).unwrap();
+    rom
+}
+
+#[test]
+pub fn rom_header_model() {
+    let rom = header_rom("model.gba");
+    let h = rom.header();
+    assert_eq!(h.game_title(), "TESTGAME");
+    assert_eq!(h.game_code(), "ATGE");
+    assert_eq!(h.game_maker_code(), "01");
+    assert_eq!(h.game_version(), 2);
+    assert_eq!(h.rom_size(), ROM_HEADER_LEN + 0x40);
+    assert_eq!(h.entry_point(), Some(0x080000C0));
+    assert_eq!(h.multiboot_entry_point(), None);
+    assert!(h.fixed_value_check());
+    assert!(h.reserved_check());
+    assert!(!h.logo_check());
+    assert!(!h.complement_check());
+    assert_eq!(h.complement(), 0x12);
+
+    // 0 - sum(A0..BC) - 19
+    let sum = (0xA0..0xBD).fold(0_u8, |s, i| s.wrapping_add(rom.bytes(0)[i]));
+    assert_eq!(h.calculated_complement(), 0_u8.wrapping_sub(sum).wrapping_sub(0x19));
+    assert!(!h.is_valid());
+
+    let report = format!("{}", h.report());
+    assert!(report.contains("Title:            \"TESTGAME\"\n"));
+    assert!(report.contains("Entry Branch:     EA00002E (B 0x080000C0)\n"));
+    assert!(report.contains(&format!("Complement Check: 0x12, expected {:#04X} [BAD]\n", h.calculated_complement())));
+    assert!(report.contains("Nintendo Logo:    CRC32"));
+    assert!(report.ends_with("Bootable:         no"));
+}
+
+#[test]
+pub fn rom_fixed_copy() {
+    let rom = header_rom("original.gba");
+    let fp = temp_path("fixed.gba");
+    rom.write_fixed_copy(&fp).unwrap();
+
+    let mut fixed = GamePakRom::new();
+    fixed.load_from_file(&fp).unwrap();
+    fs::remove_file(&fp).unwrap();
+    assert!(fixed.header().complement_check());
+    assert_eq!(fixed.header().complement(), rom.header().calculated_complement());
+    assert_eq!(fixed.header().rom_size(), rom.header().rom_size());
+    for i in (0..rom.header().rom_size()).filter(|&i| i != COMPLEMENT_CHECK_OFFSET) {
+        assert_eq!(fixed.bytes(0)[i], rom.bytes(0)[i]);
+    }
+
+    assert!(GamePakRom::new().write_fixed_copy(&fp).is_err());
+    assert!(fs::metadata(&fp).is_err());
+}
+
+
+// Delivers at most 3 bytes per read, like a slow pipe.
+struct Trickle(Cursor<Vec<u8>>);
+
+impl Read for Trickle {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = ::std::cmp::min(3, buf.len());
+        self.0.read(&mut buf[..n])
+    }
+}
+
+impl Seek for Trickle {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> { self.0.seek(pos) }
+}
+
+fn image(len: usize) -> Vec<u8> { (0..len).map(|i| (i * 7) as u8).collect() }
+
+fn read(data: Vec<u8>, entry: Option<&str>, extensions: &[&str], buf: &mut [u8]) -> io::Result<usize> {
+    read_image_from(Trickle(Cursor::new(data)), "test", entry, extensions, buf)
+}
+
+#[test]
+pub fn raw_and_gzip_images() {
+    let data = image(100);
+    let mut buf = [0xEE_u8; 128];
+    assert_eq!(read(data.clone(), None, ROM_EXTENSIONS, &mut buf).unwrap(), 100);
+    assert_eq!(&buf[..100], &data[..]);
+    assert!(buf[100..].iter().all(|&b| b == 0xEE));
+
+    // Exactly fitting is fine, a single byte more is not.
+    assert_eq!(read(data.clone(), None, ROM_EXTENSIONS, &mut buf[..100]).unwrap(), 100);
+    let e = read(data.clone(), None, ROM_EXTENSIONS, &mut buf[..99]).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+    let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+    gz.write_all(&data).unwrap();
+    let gz = gz.finish().unwrap();
+    assert_eq!(ImageFileFormat::detect(&gz), ImageFileFormat::Gzip);
+    let mut buf = [0_u8; 128];
+    assert_eq!(read(gz.clone(), None, ROM_EXTENSIONS, &mut buf).unwrap(), 100);
+    assert_eq!(&buf[..100], &data[..]);
+    assert!(read(gz, None, ROM_EXTENSIONS, &mut buf[..50]).is_err());
+}
+
+#[test]
+pub fn zip_entries() {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for &(name, len) in [("readme.txt", 10), ("game.GBA", 20), ("other.gba", 30)].iter() {
+        zip.start_file(name, FileOptions::default()).unwrap();
+        zip.write_all(&image(len)).unwrap();
+    }
+    let zip = zip.finish().unwrap().into_inner();
+    assert_eq!(ImageFileFormat::detect(&zip), ImageFileFormat::Zip);
+
+    let mut buf = [0_u8; 64];
+    assert_eq!(read(zip.clone(), None, ROM_EXTENSIONS, &mut buf).unwrap(), 20);
+    assert_eq!(&buf[..20], &image(20)[..]);
+    assert_eq!(read(zip.clone(), Some("other.gba"), ROM_EXTENSIONS, &mut buf).unwrap(), 30);
+    assert_eq!(read(zip.clone(), Some("readme.txt"), ROM_EXTENSIONS, &mut buf).unwrap(), 10);
+    assert_eq!(read(zip.clone(), Some("nope.gba"), ROM_EXTENSIONS, &mut buf).unwrap_err().kind(), io::ErrorKind::NotFound);
+    assert_eq!(read(zip.clone(), None, BIOS_EXTENSIONS, &mut buf).unwrap_err().kind(), io::ErrorKind::NotFound);
+    assert_eq!(read(zip, Some("other.gba"), ROM_EXTENSIONS, &mut buf[..29]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
+
+
+#[test]
+pub fn multiboot_images() {
+    let mut img = vec![0_u8; MULTIBOOT_HEADER_LEN + 16];
+    img[0xC0..0xC4].copy_from_slice(&[0x07, 0x00, 0x00, 0xEA]); // b 020000E4
+    img[0xE4..0xE8].copy_from_slice(&[0x2A, 0x00, 0xA0, 0xE3]); // mov r0, #0x2A
+    img[0xE8..0xEC].copy_from_slice(&[0x03, 0x14, 0xA0, 0xE3]); // mov r1, #0x03000000
+    img[0xEC..0xF0].copy_from_slice(&[0x00, 0x00, 0x81, 0xE5]); // str r0, [r1]
+    img[0xF0..0xF4].copy_from_slice(&[0xFE, 0xFF, 0xFF, 0xEA]); // b .
+    let fp = temp_path("image.mb");
+    fs::File::create(&fp).unwrap().write_all(&img).unwrap();
+
+    let mut gba = Gba::new();
+    gba.load_multiboot_file(&fp, None).unwrap();
+    {
+        let bus = gba.bus();
+        assert_eq!(bus.load_word(0x020000E4).unwrap() as u32, 0xE3A0002A);
+        assert_eq!(bus.load_byte(0x02000000 + MULTIBOOT_BOOT_MODE_OFFSET as u32).unwrap() as u8, MULTIBOOT_BOOT_MODE_MULTIPLAY);
+        assert_eq!(bus.load_byte(0x02000000 + MULTIBOOT_SLAVE_ID_OFFSET as u32).unwrap() as u8, MULTIBOOT_SLAVE_ID_FIRST);
+    }
+    for _ in 0..20 { gba.cpu_arm7tdmi_mut().pipeline_step().unwrap(); }
+    assert_eq!(gba.bus().load_word(0x03000000).unwrap(), 0x2A);
+
+    // On-board WRAM limits the image size.
+    fs::File::create(&fp).unwrap().write_all(&vec![0; memory::WRAM_ON_BOARD_LEN as usize + 1]).unwrap();
+    assert!(Gba::new().load_multiboot_file(&fp, None).is_err());
+    fs::remove_file(&fp).unwrap();
+}
+
+#[test]
+pub fn writing_images() {
+    let mut gba = Gba::new();
+    let wram = memory::WRAM_ON_BOARD_LEN as usize;
+    let iwram = memory::WRAM_ON_CHIP_LEN as usize;
+    let mut bus = gba.bus_mut();
+    bus.write_image(0x02000000, &vec![0xAA; wram]).unwrap();
+    bus.write_image(0x02000010, &[0x55; 4]).unwrap();
+    assert_eq!(bus.load_word(0x02000010).unwrap() as u32, 0x55555555);
+    bus.write_image(0x03000000, &vec![0xAA; iwram]).unwrap();
+
+    // Too large for a single area or wrapping around a mirror.
+    assert!(bus.write_image(0x02000000, &vec![0; wram + 1]).is_err());
+    assert!(bus.write_image(0x02000004, &vec![0; wram]).is_err());
+    assert!(bus.write_image(0x03000000, &vec![0; iwram + 1]).is_err());
+    assert!(bus.write_image(0x03007FF0, &[0; 0x20]).is_err());
+    assert!(bus.write_image(0x04000000, &[0; 4]).is_err());
+}
+
+#[test]
+pub fn lcd_timing() {
+    let mut io = IoRegisters::new();
+    let if_clear = |io: &mut IoRegisters| io.store_halfword(IF_OFFSET, 0xFFFF);
+    io.store_halfword(IE_OFFSET, 0x0007);
+    io.store_halfword(DISPSTAT_OFFSET, 0x0538); // All IRQs, V-Counter 5.
+    assert_eq!(io.scheduled_interrupts(), 0x0007);
+
+    io.advance_lcd(HDRAW_CYCLES - 1);
+    assert_eq!((io.read_halfword(DISPSTAT_OFFSET), io.pending_interrupts()), (0x0538, 0));
+    io.advance_lcd(1);
+    assert_eq!(io.read_halfword(DISPSTAT_OFFSET), 0x053A);
+    assert_eq!(io.pending_interrupts(), Interrupt::HBlank.mask());
+    assert_eq!(io.cycles_until_lcd_event(), SCANLINE_CYCLES - HDRAW_CYCLES);
+
+    // The flags and VCOUNT are read-only.
+    io.store_halfword(DISPSTAT_OFFSET, 0x0507);
+    io.store_halfword(VCOUNT_OFFSET, 42);
+    assert_eq!((io.read_halfword(DISPSTAT_OFFSET), io.read_halfword(VCOUNT_OFFSET)), (0x0502, 0));
+    io.store_halfword(DISPSTAT_OFFSET, 0x0538);
+
+    if_clear(&mut io);
+    io.advance_lcd(SCANLINE_CYCLES * 5 - HDRAW_CYCLES);
+    assert_eq!((io.read_halfword(VCOUNT_OFFSET), io.read_halfword(DISPSTAT_OFFSET)), (5, 0x053C));
+    assert_eq!(io.pending_interrupts(), Interrupt::HBlank.mask() | Interrupt::VCounter.mask());
+
+    if_clear(&mut io);
+    io.advance_lcd(SCANLINE_CYCLES * (VBLANK_FIRST_LINE as u32 - 5));
+    assert_eq!((io.read_halfword(VCOUNT_OFFSET), io.read_halfword(DISPSTAT_OFFSET)), (160, 0x0539));
+    assert!(0 != (io.pending_interrupts() & Interrupt::VBlank.mask()));
+
+    // V-Blank ends right before the last line of a frame.
+    io.advance_lcd(SCANLINE_CYCLES * 67);
+    assert_eq!((io.read_halfword(VCOUNT_OFFSET), io.read_halfword(DISPSTAT_OFFSET)), (227, 0x0538));
+    io.advance_lcd(SCANLINE_CYCLES);
+    assert_eq!(io.read_halfword(VCOUNT_OFFSET), 0);
+}
+
+#[test]
+pub fn misaligned_word_loads() {
+    let mut gba = Gba::new();
+    let mut bus = gba.bus_mut();
+    bus.write_image(0x02000010, &[0x44, 0x33, 0x22, 0x11]).unwrap();
+    assert_eq!(bus.load_word(0x02000010).unwrap() as u32, 0x11223344);
+    assert_eq!(bus.load_word(0x02000011).unwrap() as u32, 0x44112233);
+    assert_eq!(bus.load_word(0x02000012).unwrap() as u32, 0x33441122);
+    assert_eq!(bus.load_word(0x02000013).unwrap() as u32, 0x22334411);
+
+    // The BIOS rotates just like any other region.
+    let bios = bus.load_word(0x00000000).unwrap() as u32;
+    assert_eq!(bus.load_word(0x00000002).unwrap() as u32, bios.rotate_right(16));
+}
+
+#[test]
+pub fn peeking_video_memory() {
+    let gba = Gba::new();
+    let bus = gba.bus();
+    for &addr in &[0x05000000_u32, 0x06000000, 0x07000000] {
+        assert_eq!(bus.peek_word(addr), Err(GbaError::InvalidPhysicalAddress(addr)));
+        assert_eq!(bus.peek_halfword(addr + 2), Err(GbaError::InvalidPhysicalAddress(addr + 2)));
+        assert_eq!(bus.peek_byte(addr + 1), Err(GbaError::InvalidPhysicalAddress(addr + 1)));
+    }
+}
+
+#[test]
+pub fn poking_video_memory() {
+    let mut gba = Gba::new();
+    let mut bus = gba.bus_mut();
+    for &addr in &[0x05000000_u32, 0x06000000, 0x07000000] {
+        assert_eq!(bus.poke_word(addr, 1), Err(GbaError::InvalidPhysicalAddress(addr)));
+        assert_eq!(bus.poke_halfword(addr + 2, 1), Err(GbaError::InvalidPhysicalAddress(addr + 2)));
+        assert_eq!(bus.poke_byte(addr + 1, 1), Err(GbaError::InvalidPhysicalAddress(addr + 1)));
+    }
+}
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
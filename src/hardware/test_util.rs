@@ -0,0 +1,62 @@
+// License below.
+//! Fixtures shared by the hardware and debugger tests.
+#![allow(missing_docs)]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use super::Gba;
+use super::bus::Bus;
+use super::cpu::Arm7Tdmi;
+use super::gamepak::GamePak;
+use super::memory::BiosRom;
+
+/// Creates a CPU with an empty GamePak and the given BIOS.
+///
+/// Also returns the bus, as the CPU keeps its own reference private.
+pub fn new_cpu(bios: BiosRom) -> (Arm7Tdmi, Rc<RefCell<Bus>>) {
+    let gpak = Rc::new(RefCell::new(GamePak::new()));
+    let bus  = Rc::new(RefCell::new(Bus::new(gpak, Rc::new(RefCell::new(bios)))));
+    (Arm7Tdmi::new(bus.clone()), bus)
+}
+
+/// Stores ARM instructions or any other words at `addr`.
+pub fn load_program(bus: &RefCell<Bus>, addr: u32, program: &[u32]) {
+    let mut bus = bus.borrow_mut();
+    for (i, &w) in program.iter().enumerate() { bus.store_word(addr + (i as u32 * 4), w as i32).unwrap(); }
+}
+
+/// Counts r0 up and stores it to IWRAM forever.
+///
+/// The program starts at `0x02000000` with the loop at `0x02000004`.
+pub fn counting_gba() -> Gba {
+    let mut gba = Gba::new();
+    gba.boot_post_bios(0x02000000);
+    load_program(&gba.bus, 0x02000000, &[
+        0xE3A01403, // 0000  mov r1, #0x03000000
+        //       loop:
+        0xE2800001, // 0004  add r0, r0, #1
+        0xE4810004, // 0008  str r0, [r1], #4
+        0xEAFFFFFC, // 000C  b loop
+    ]);
+    gba
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -0,0 +1,238 @@
+// License below.
+//! Implements memory watchpoints checked by the bus system.
+//!
+//! Every load and store going through `Bus` is compared
+//! against all enabled watchpoints. A matching access is
+//! remembered as a `WatchHit`, which a debugger takes after
+//! the pipeline step that caused it, i.e. right after the
+//! faulting instruction.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use std::fmt;
+use std::ops::Range;
+use std::slice;
+
+#[cfg(test)]
+mod test;
+
+
+/// The kinds of memory accesses a watchpoint reacts to.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WatchKind {
+    #[doc = "Only loads trigger the watchpoint."]             Read,
+    #[doc = "Only stores trigger the watchpoint."]            Write,
+    #[doc = "Both loads and stores trigger the watchpoint."]  Access,
+}
+
+impl WatchKind {
+    /// Checks whether a load or store triggers this kind of watchpoint.
+    pub fn matches(self, write: bool) -> bool {
+        match self {
+            WatchKind::Read   => !write,
+            WatchKind::Write  => write,
+            WatchKind::Access => true,
+        }
+    }
+}
+
+impl fmt::Display for WatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match *self { WatchKind::Read => "read", WatchKind::Write => "write", WatchKind::Access => "access" })
+    }
+}
+
+
+/// A single watchpoint over a range of addresses.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Watchpoint {
+    /// The number identifying this watchpoint.
+    pub id: u32,
+
+    /// The watched addresses, excluding the end.
+    pub range: Range<u32>,
+
+    /// The kinds of accesses to watch.
+    pub kind: WatchKind,
+
+    /// If given, only accesses of exactly this value trigger.
+    pub value: Option<u32>,
+
+    /// Disabled watchpoints never trigger.
+    pub enabled: bool,
+
+    /// How often this watchpoint triggered.
+    pub hits: u32,
+}
+
+
+/// A memory access that triggered a watchpoint.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct WatchHit {
+    /// The ID of the triggered watchpoint.
+    pub id: u32,
+
+    /// The accessed address, aligned to the access width.
+    pub addr: u32,
+
+    /// The access width in bits.
+    pub width: u8,
+
+    /// `true` for stores, `false` for loads.
+    pub write: bool,
+
+    /// The value before the access.
+    pub old: u32,
+
+    /// The value after the access. Same as `old` for loads.
+    pub new: u32,
+}
+
+
+/// Manages all watchpoints and remembers the first hit.
+#[derive(Debug, Clone)]
+pub struct WatchpointList {
+    watchpoints: Vec<Watchpoint>,
+    next_id: u32,
+    hit: Option<WatchHit>,
+}
+
+impl WatchpointList {
+    /// Creates an empty watchpoint list.
+    pub fn new() -> WatchpointList {
+        WatchpointList {
+            watchpoints: Vec::new(),
+            next_id: 1,
+            hit: None,
+        }
+    }
+
+    /// Get the number of watchpoints.
+    pub fn len(&self) -> usize { self.watchpoints.len() }
+
+    /// Checks whether there are no watchpoints.
+    pub fn is_empty(&self) -> bool { self.watchpoints.is_empty() }
+
+    /// Iterates over all watchpoints in the order of their IDs.
+    pub fn iter(&self) -> slice::Iter<Watchpoint> { self.watchpoints.iter() }
+
+    /// Get the watchpoint with the given ID, if any.
+    pub fn get(&self, id: u32) -> Option<&Watchpoint> {
+        self.watchpoints.iter().find(|w| w.id == id)
+    }
+
+    /// Adds a new enabled watchpoint.
+    ///
+    /// # Params
+    /// - `range`: The watched addresses, excluding the end.
+    /// - `kind`: The kinds of accesses to watch.
+    /// - `value`: If given, only accesses of this value trigger.
+    ///
+    /// # Returns
+    /// The new watchpoint's ID.
+    pub fn add(&mut self, range: Range<u32>, kind: WatchKind, value: Option<u32>) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.watchpoints.push(Watchpoint {
+            id: id,
+            range: range,
+            kind: kind,
+            value: value,
+            enabled: true,
+            hits: 0,
+        });
+        id
+    }
+
+    /// Removes a watchpoint.
+    ///
+    /// # Returns
+    /// `true` if there was such a watchpoint.
+    pub fn remove(&mut self, id: u32) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|w| w.id != id);
+        len != self.watchpoints.len()
+    }
+
+    /// Removes all watchpoints.
+    pub fn clear(&mut self) { self.watchpoints.clear(); }
+
+    /// Enables or disables a watchpoint.
+    ///
+    /// # Returns
+    /// `true` if there is such a watchpoint.
+    pub fn set_enabled(&mut self, id: u32, enabled: bool) -> bool {
+        match self.watchpoints.iter_mut().find(|w| w.id == id) {
+            Some(w) => { w.enabled = enabled; true },
+            None    => false,
+        }
+    }
+
+    /// Checks whether any enabled watchpoint watches an access.
+    ///
+    /// This is cheap and lets the bus skip preparing the old
+    /// value of a store if nobody is interested in it.
+    ///
+    /// # Params
+    /// - `addr`: The aligned address of the access.
+    /// - `width`: The access width in bits.
+    /// - `write`: `true` for stores, `false` for loads.
+    pub fn watches(&self, addr: u32, width: u8, write: bool) -> bool {
+        let end = addr.saturating_add(width as u32 / 8);
+        self.watchpoints.iter().any(|w| {
+            w.enabled & w.kind.matches(write) & (addr < w.range.end) & (w.range.start < end)
+        })
+    }
+
+    /// Records a memory access, if it triggers any watchpoint.
+    ///
+    /// Only the first hit is kept until it is taken, as a
+    /// single instruction may access memory multiple times.
+    ///
+    /// # Params
+    /// - `addr`: The aligned address of the access.
+    /// - `width`: The access width in bits.
+    /// - `write`: `true` for stores, `false` for loads.
+    /// - `old`: The value before the access.
+    /// - `new`: The value after the access.
+    pub fn record(&mut self, addr: u32, width: u8, write: bool, old: u32, new: u32) {
+        let end = addr.saturating_add(width as u32 / 8);
+        for w in self.watchpoints.iter_mut() {
+            if !(w.enabled & w.kind.matches(write) & (addr < w.range.end) & (w.range.start < end)) { continue; }
+            if w.value.map_or(false, |v| v != new) { continue; }
+            w.hits += 1;
+            if self.hit.is_none() {
+                self.hit = Some(WatchHit { id: w.id, addr: addr, width: width, write: write, old: old, new: new });
+            }
+        }
+    }
+
+    /// Takes the first recorded hit, if any.
+    pub fn take_hit(&mut self) -> Option<WatchHit> { self.hit.take() }
+}
+
+impl Default for WatchpointList {
+    fn default() -> WatchpointList { WatchpointList::new() }
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
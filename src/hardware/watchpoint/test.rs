@@ -0,0 +1,87 @@
+// License below.
+#![allow(missing_docs)]
+
+use super::*;
+use super::super::Gba;
+use super::super::test_util::counting_gba;
+
+fn run_to_hit(gba: &mut Gba) -> (u32, WatchHit) {
+    for _ in 0..1000 {
+        let pc = gba.cpu_arm7tdmi().executing_address();
+        gba.cpu_arm7tdmi_mut().pipeline_step().unwrap();
+        if let Some(hit) = gba.bus_mut().watchpoints_mut().take_hit() { return (pc, hit); }
+    }
+    panic!("No watchpoint triggered.");
+}
+
+#[test]
+pub fn watchpoint_ranges() {
+    let mut wl = WatchpointList::new();
+    let a = wl.add(0x03000002..0x03000004, WatchKind::Write, None);
+    let b = wl.add(0x03000010..0x03000011, WatchKind::Read, Some(0x42));
+    assert_eq!(wl.len(), 2);
+
+    assert!(wl.watches(0x03000000, 32, true));
+    assert!(wl.watches(0x03000003, 8, true));
+    assert!(!wl.watches(0x03000000, 16, true));
+    assert!(!wl.watches(0x03000004, 32, true));
+    assert!(!wl.watches(0x03000000, 32, false));
+    assert!(wl.watches(0x03000010, 8, false));
+
+    // Value conditions are checked when recording.
+    wl.record(0x03000010, 8, false, 0x41, 0x41);
+    assert_eq!(wl.take_hit(), None);
+    wl.record(0x03000010, 16, false, 0x42, 0x42);
+    wl.record(0x03000000, 32, true, 1, 2);
+    assert_eq!(wl.take_hit(), Some(WatchHit { id: b, addr: 0x03000010, width: 16, write: false, old: 0x42, new: 0x42 }));
+    assert_eq!(wl.take_hit(), None);
+    assert_eq!(wl.get(a).unwrap().hits, 1);
+
+    assert!(wl.set_enabled(a, false));
+    assert!(!wl.watches(0x03000000, 32, true));
+    assert!(wl.remove(a));
+    assert!(!wl.remove(a));
+}
+
+#[test]
+pub fn bus_watchpoints() {
+    let mut gba = counting_gba();
+    let id = gba.bus_mut().watchpoints_mut().add(0x03000008..0x0300000C, WatchKind::Write, None);
+    gba.bus_mut().store_word(0x03000008, 0x1234).unwrap();
+    assert!(gba.bus_mut().watchpoints_mut().take_hit().is_some());
+
+    // Reports the faulting instruction with old and new values.
+    let (pc, hit) = run_to_hit(&mut gba);
+    assert_eq!(pc, 0x02000008);
+    assert_eq!(hit, WatchHit { id: id, addr: 0x03000008, width: 32, write: true, old: 0x1234, new: 3 });
+    assert_eq!(gba.bus().peek_word(0x03000008), Ok(3));
+
+    // Debugger loads and instruction fetches never trigger.
+    gba.bus_mut().watchpoints_mut().add(0x02000000..0x03000010, WatchKind::Read, None);
+    gba.bus().peek_word(0x03000008).unwrap();
+    for _ in 0..20 { gba.cpu_arm7tdmi_mut().pipeline_step().unwrap(); }
+    assert_eq!(gba.bus_mut().watchpoints_mut().take_hit(), None);
+    gba.bus().load_halfword(0x0300000A).unwrap();
+    assert_eq!(gba.bus_mut().watchpoints_mut().take_hit().map(|h| (h.addr, h.width)), Some((0x0300000A, 16)));
+}
+
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
@@ -28,16 +28,20 @@ extern crate log;
 extern crate argparse;
 extern crate byteorder;
 extern crate term_painter;
+extern crate flate2;
+extern crate zip;
+extern crate libc;
 
 #[cfg(test)]
 extern crate test;
 
-use argparse::{ArgumentParser, Print, Parse, ParseOption, StoreTrue, StoreFalse, StoreOption};
-use std::path::PathBuf;
+use argparse::{ArgumentParser, Print, Collect, Parse, ParseOption, Store, StoreTrue, StoreFalse, StoreOption};
+use std::path::{Path, PathBuf};
 use std::ops::Range;
 use std::process;
 
 pub mod repl;
+pub mod debug;
 pub mod logger;
 pub mod hardware;
 
@@ -55,9 +59,36 @@ pub struct CmdLineArgs {
     /// Accepts `--rom FILE`.
     ///
     /// The ROM file will be loaded immediately after
-    /// initialising the emulator.
+    /// initialising the emulator. ELF executables are
+    /// loaded along with their symbols.
     pub rom_file_path: Option<PathBuf>,
 
+    /// Accepts `--bios-entry NAME`.
+    ///
+    /// If `--bios` is a `.zip` archive, loads the entry
+    /// with the given name instead of the first `.bin`.
+    pub bios_zip_entry: Option<String>,
+
+    /// Accepts `--rom-entry NAME`.
+    ///
+    /// If `--rom` is a `.zip` archive, loads the entry
+    /// with the given name instead of the first `.gba`.
+    pub rom_zip_entry: Option<String>,
+
+    /// Accepts `--multiboot FILE`.
+    ///
+    /// The multiboot image will be loaded into on-board
+    /// WRAM and executed from there, as if it has been
+    /// received over the link cable.
+    pub multiboot_file_path: Option<PathBuf>,
+
+    /// Accepts `--patch FILE` multiple times.
+    ///
+    /// Each IPS, UPS, or BPS patch is applied to the
+    /// loaded `--rom` in the given order. The ROM file
+    /// itself is never modified.
+    pub patch_file_paths: Vec<PathBuf>,
+
     /// Accepts `--log FILE`, defaults to `"./GBArs.log"`.
     pub log_file_path: PathBuf,
 
@@ -85,6 +116,32 @@ pub struct CmdLineArgs {
     /// area and logs the result.
     pub disasm_bios_thumb: Option<String>,
 
+    /// Accepts `--rom-info` as `true`.
+    ///
+    /// If `true`, the `--rom` flag must be given. Logs
+    /// a validation report of the ROM's header.
+    pub rom_info: bool,
+
+    /// Accepts `--fix-header FILE`.
+    ///
+    /// If given, the `--rom` flag must be given. Writes
+    /// a copy of the ROM with a fixed complement check
+    /// byte into the given file. The original ROM file
+    /// is never modified.
+    pub fixed_rom_file_path: Option<PathBuf>,
+
+    /// Accepts `--decompress OFFSET`.
+    ///
+    /// If given, the `--rom` and `--decompress-to` flags
+    /// must be given. Decompresses BIOS compatible data
+    /// found at the given ROM offset.
+    pub decompress_rom_offset: Option<String>,
+
+    /// Accepts `--decompress-to FILE`.
+    ///
+    /// The file to write `--decompress`ed data to.
+    pub decompressed_file_path: Option<PathBuf>,
+
     /// Accepts `-v` or `--verbose` as `true`.
     ///
     /// Also accepts `-q` or `--quiet` as `false`, which is the default value.
@@ -119,6 +176,14 @@ pub struct CmdLineArgs {
     /// code.
     pub optimise_swi: bool,
 
+    /// Accepts `--skip-bios` as `true`.
+    ///
+    /// If `true`, the BIOS boot sequence is skipped and
+    /// the CPU starts at the GamePak ROM's entry point
+    /// with the registers set up like the BIOS would.
+    /// The BIOS stays mapped to handle `SWI`s.
+    pub skip_bios: bool,
+
     /// Accepts `-l` or `--load-sram` as `true`.
     ///
     /// If `true`, the `--rom` flag must be given. GBArs
@@ -126,6 +191,52 @@ pub struct CmdLineArgs {
     /// changing the file extension.
     pub load_sram: bool,
 
+    /// Accepts `--load-state SLOT`.
+    ///
+    /// Restores the machine from the given save state
+    /// slot of the loaded ROM after loading everything
+    /// else.
+    pub load_state_slot: Option<u8>,
+
+    /// Accepts `--save-state SLOT`.
+    ///
+    /// Saves the machine into the given save state
+    /// slot of the loaded ROM before exiting.
+    pub save_state_slot: Option<u8>,
+
+    /// Accepts `--rewind-frames N`, which defaults to 1.
+    ///
+    /// The debug REPL takes a rewind snapshot every N frames.
+    pub rewind_frames: u32,
+
+    /// Accepts `--rewind-budget MIB`, which defaults to 32.
+    ///
+    /// The maximum memory used by rewind snapshots in MiB.
+    pub rewind_budget: usize,
+
+    /// Accepts `--gdb PORT`.
+    ///
+    /// If given, waits for GDB to connect on localhost
+    /// and serves it before anything else is run.
+    pub gdb_port: Option<u16>,
+
+    /// Accepts `--repl-script FILE`.
+    ///
+    /// Runs the commands of a script when entering
+    /// the debug REPL. Implies `-D`.
+    pub repl_script: Option<PathBuf>,
+
+    /// Accepts `--repl-batch` as `true`.
+    ///
+    /// If `true`, exits after running the REPL script,
+    /// with a failure status if any assertion or command failed.
+    pub repl_batch: bool,
+
+    /// Accepts `--repl-max-steps N`, which defaults to 16777216.
+    ///
+    /// In batch mode, `continue` and friends fail after N steps.
+    pub repl_max_steps: u32,
+
     /// Accepts `-D` or `--debug-repl` as `true`.
     ///
     /// If `true`, runs the emulator in a REPL-style
@@ -138,16 +249,33 @@ impl Default for CmdLineArgs {
         CmdLineArgs {
             bios_file_path: None,
             rom_file_path: None,
+            bios_zip_entry: None,
+            rom_zip_entry: None,
+            multiboot_file_path: None,
+            patch_file_paths: Vec::new(),
             log_file_path: PathBuf::from("./GBArs.log"),
             single_disasm_arm: None,
             single_disasm_thumb: None,
             disasm_bios_arm: None,
             disasm_bios_thumb: None,
+            rom_info: false,
+            fixed_rom_file_path: None,
+            decompress_rom_offset: None,
+            decompressed_file_path: None,
             verbose: cfg!(debug_assertions), // Default to `true` while testing.
             colour: true,
             exit: false,
             optimise_swi: false,
+            skip_bios: false,
             load_sram: false,
+            load_state_slot: None,
+            save_state_slot: None,
+            rewind_frames: 1,
+            rewind_budget: hardware::rewind::DEFAULT_REWIND_BUDGET >> 20,
+            gdb_port: None,
+            repl_script: None,
+            repl_batch: false,
+            repl_max_steps: repl::DEFAULT_BATCH_MAX_STEPS,
             run_repl: false,
         }
     }
@@ -189,16 +317,43 @@ fn main() {
     configure_gba_from_command_line(&mut gba, &args);
     handle_oneshot_commands(&args, &gba);
 
+    // Serve GDB?
+    if let Some(port) = args.gdb_port {
+        if let Err(e) = debug::gdb::GdbStub::new().serve(&mut gba, port) {
+            error!("{}", e);
+        }
+    }
+
     // Run REPL?
-    if args.run_repl {
-        if let Err(e) = repl::GbaRepl::new()
-                        .with_colour(args.colour)
-                        .run(&mut gba) {
+    let mut failed = false;
+    if args.run_repl || args.repl_script.is_some() {
+        let mut repl = repl::GbaRepl::new();
+        if let Err(e) = repl.with_colour(args.colour)
+                            .with_state_path(state_base_path(&args))
+                            .with_history_path(args.log_file_path.with_extension("history"))
+                            .with_rewind(args.rewind_frames, args.rewind_budget << 20)
+                            .with_script(args.repl_script.clone())
+                            .with_batch(args.repl_batch)
+                            .with_max_steps(args.repl_max_steps)
+                            .run(&mut gba) {
             error!("{}", e);
+            failed = true;
+        }
+        if args.repl_batch && (repl.failures() > 0) { error!("The REPL script failed {} time(s).", repl.failures()); failed = true; }
+    }
+
+    // Save the machine?
+    if let Some(slot) = args.save_state_slot {
+        if let Some(fp) = state_slot_path(&args, slot) {
+            match gba.save_state_file(fp.as_path()) {
+                Ok(_)  => info!("Saved the machine to `{}`.", fp.display()),
+                Err(e) => error!("Failed saving the machine state:\n{}", e),
+            }
         }
     }
 
     // Exit early?
+    if args.repl_batch { process::exit(if failed { 1 } else { 0 }); }
     if args.exit { trace!("Exiting early."); process::exit(0); }
 }
 
@@ -210,10 +365,30 @@ fn parse_command_line(args: &mut CmdLineArgs) {
                       Print(format!("GBArs v{}", env!("CARGO_PKG_VERSION"))),
                       "Show current version.");
     parser.refer(&mut args.bios_file_path)
-          .add_option(&["--bios"], ParseOption, "Path to a BIOS file to load.")
+          .add_option(&["--bios"], ParseOption, "Path to a BIOS file to load. May be a `.gz` \
+                                                 file or a `.zip` archive.")
           .metavar("PATH");
+    parser.refer(&mut args.bios_zip_entry)
+          .add_option(&["--bios-entry"], StoreOption, "Name of the file to load from a zipped \
+                                                       `--bios`. Defaults to the first `.bin` file.")
+          .metavar("NAME");
     parser.refer(&mut args.rom_file_path)
-          .add_option(&["--rom"], ParseOption, "Path to a ROM file to load.")
+          .add_option(&["--rom"], ParseOption, "Path to a ROM file to load. May be a `.gz` \
+                                                file, a `.zip` archive, or an ELF executable.")
+          .metavar("PATH");
+    parser.refer(&mut args.rom_zip_entry)
+          .add_option(&["--rom-entry"], StoreOption, "Name of the file to load from a zipped \
+                                                      `--rom`. Defaults to the first `.gba` file.")
+          .metavar("NAME");
+    parser.refer(&mut args.multiboot_file_path)
+          .add_option(&["--multiboot"], ParseOption, "Path to a multiboot image to load into \
+                                                      WRAM and run from `0x02000000`. May be a \
+                                                      `.gz` file or a `.zip` archive.")
+          .metavar("PATH");
+    parser.refer(&mut args.patch_file_paths)
+          .add_option(&["--patch"], Collect, "Path to an IPS, UPS, or BPS patch to apply to the \
+                                              loaded `--rom`. May be given multiple times. The \
+                                              ROM file itself is never modified.")
           .metavar("PATH");
     parser.refer(&mut args.log_file_path)
           .add_option(&["--log"], Parse, "Custom path for the log file.")
@@ -248,6 +423,22 @@ fn parse_command_line(args: &mut CmdLineArgs) {
                        address on the right is given, e.g. `00C4..`, it will be set to \
                        `4000` (16KiB).")
           .metavar("RANGE");
+    parser.refer(&mut args.rom_info)
+          .add_option(&["--rom-info"], StoreTrue, "Prints a validation report of the `--rom`'s header.");
+    parser.refer(&mut args.fixed_rom_file_path)
+          .add_option(&["--fix-header"], ParseOption,
+                      "Writes a copy of the `--rom` with a fixed header complement check \
+                       byte to PATH. The original ROM file is left untouched.")
+          .metavar("PATH");
+    parser.refer(&mut args.decompress_rom_offset)
+          .add_option(&["--decompress"], StoreOption,
+                      "Decompresses LZ77, Huffman, run-length, or diff filtered data like \
+                       the BIOS does. The data starts at the given hexadecimal offset into \
+                       the `--rom`, e.g. `1A2B3C`. Requires `--decompress-to`.")
+          .metavar("OFFSET");
+    parser.refer(&mut args.decompressed_file_path)
+          .add_option(&["--decompress-to"], ParseOption, "Path to write `--decompress`ed data to.")
+          .metavar("PATH");
     parser.refer(&mut args.verbose)
           .add_option(&["-v","--verbose"], StoreTrue, "Log extra messages and information.")
           .add_option(&["-q","--quiet"], StoreFalse, "Log with less messages and information. (default)");
@@ -259,8 +450,34 @@ fn parse_command_line(args: &mut CmdLineArgs) {
     parser.refer(&mut args.optimise_swi)
           .add_option(&["-S","--optimise-swi"], StoreTrue, "Enable optimised BIOS functions.")
           .add_option(&["-s","--emulate-swi"], StoreFalse, "Disable optimised BIOS functions. (default)");
+    parser.refer(&mut args.skip_bios)
+          .add_option(&["--skip-bios"], StoreTrue, "Skip the BIOS boot sequence and start the GamePak ROM directly.");
     parser.refer(&mut args.load_sram)
           .add_option(&["-l", "--load-sram"], StoreTrue, "Tries loading an SRAM file corresponding to a given `--rom`.");
+    parser.refer(&mut args.load_state_slot)
+          .add_option(&["--load-state"], StoreOption, "Restores the machine from a save state slot \
+                                                       (0-9) of the loaded ROM.");
+    parser.refer(&mut args.save_state_slot)
+          .add_option(&["--save-state"], StoreOption, "Saves the machine into a save state slot \
+                                                       (0-9) of the loaded ROM before exiting.");
+    parser.refer(&mut args.rewind_frames)
+          .add_option(&["--rewind-frames"], Store, "Take a rewind snapshot every N frames. (default 1)");
+    parser.refer(&mut args.rewind_budget)
+          .add_option(&["--rewind-budget"], Store, "Memory budget of rewind snapshots in MiB. (default 32)");
+    parser.refer(&mut args.gdb_port)
+          .add_option(&["--gdb"], StoreOption, "Serve the GDB remote protocol on localhost:PORT.")
+          .metavar("PORT");
+    parser.refer(&mut args.repl_script)
+          .add_option(&["--repl-script"], ParseOption, "Run the debug REPL commands in FILE first. \
+                                                        Implies `-D`.")
+          .metavar("FILE");
+    parser.refer(&mut args.repl_batch)
+          .add_option(&["--repl-batch"], StoreTrue, "Exit after the REPL script instead of prompting. \
+                                                     Fails if any assertion or command fails.");
+    parser.refer(&mut args.repl_max_steps)
+          .add_option(&["--repl-max-steps"], Store, "Fail `continue` and friends in `--repl-batch` mode \
+                                                      after N steps. (default 16777216)")
+          .metavar("N");
     parser.refer(&mut args.run_repl)
           .add_option(&["-D", "--debug-repl"], StoreTrue, "Enters a debug loop where each \
                                                            instruction is emulated step by step.");
@@ -283,6 +500,76 @@ fn handle_oneshot_commands(args: &CmdLineArgs, gba: &hardware::Gba) {
     // ROM sections to disassemble?
     if let Some(ref x) = args.disasm_bios_arm   { disasm_bios_arm(  x.as_str(), gba); }
     if let Some(ref x) = args.disasm_bios_thumb { disasm_bios_thumb(x.as_str(), gba); }
+
+    // ROM header to check or fix?
+    if args.rom_info { rom_info(args, gba); }
+    if let Some(ref fp) = args.fixed_rom_file_path { fix_rom_header(args, fp.as_path(), gba); }
+
+    // ROM assets to extract?
+    if let Some(ref x) = args.decompress_rom_offset { decompress_rom(x.as_str(), args, gba); }
+}
+
+fn rom_info(args: &CmdLineArgs, gba: &hardware::Gba) {
+    if args.rom_file_path.is_none() { error!("ROM INFO: No `--rom` given."); return; }
+    let game_pak = gba.game_pak();
+    info!("ROM header report:\n\n{}", game_pak.header().report());
+}
+
+fn fix_rom_header(args: &CmdLineArgs, fp: &Path, gba: &hardware::Gba) {
+    let rom_fp = if let Some(ref x) = args.rom_file_path { x } else {
+        error!("FIX HEADER: No `--rom` given."); return;
+    };
+    if is_same_file(rom_fp.as_path(), fp) {
+        error!("FIX HEADER: Refusing to overwrite the original ROM file."); return;
+    }
+    let game_pak = gba.game_pak();
+    if game_pak.header().complement_check() {
+        info!("FIX HEADER: The complement check is already valid. Writing an unchanged copy.");
+    }
+    match game_pak.rom().write_fixed_copy(fp) {
+        Ok(_)  => info!("FIX HEADER: Wrote the fixed ROM to `{}`.", fp.display()),
+        Err(e) => error!("FIX HEADER: Failed writing `{}`:\n{}", fp.display(), e),
+    }
+}
+
+// Checks whether two paths name the same file, e.g. via `..` or symlinks.
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(x), Ok(y)) => x == y,
+        _ => a == b,
+    }
+}
+
+fn decompress_rom(x: &str, args: &CmdLineArgs, gba: &hardware::Gba) {
+    use hardware::cpu::{Compression, decompress};
+    use std::fs::File;
+    use std::io::Write;
+    if args.rom_file_path.is_none() { error!("DECOMPRESS: No `--rom` given."); return; }
+    let fp = if let Some(ref x) = args.decompressed_file_path { x } else {
+        error!("DECOMPRESS: No `--decompress-to` given."); return;
+    };
+    let offs = match u32::from_str_radix(x, 16) {
+        Ok(i) => i,
+        Err(e) => { error!("DECOMPRESS: {}\nRun `GBArs --help` for details.", e); return; },
+    };
+    if (offs as usize) >= gba.game_pak().header().rom_size() {
+        error!("DECOMPRESS: The offset {:#08X} is beyond the ROM's end.", offs); return;
+    }
+
+    let bus = gba.bus();
+    let addr = hardware::memory::GAME_PAK_WS0_ROM_FIRST + offs;
+    let header = match bus.load_word(addr) { Ok(x) => x as u32, Err(e) => { error!("DECOMPRESS: {}", e); return; } };
+    let kind = if let Some(k) = Compression::from_header(header) { k } else {
+        error!("DECOMPRESS: No compressed data at {:#08X}, found header {:#010X}.", offs, header); return;
+    };
+    let data = match decompress(kind, addr, |a| bus.load_byte(a).map(|b| b as u8)) {
+        Ok(x) => x,
+        Err(e) => { error!("DECOMPRESS: {}", e); return; },
+    };
+    match File::create(fp).and_then(|mut f| f.write_all(&data)) {
+        Ok(_)  => info!("DECOMPRESS: Wrote {} bytes of {} data to `{}`.", data.len(), kind, fp.display()),
+        Err(e) => error!("DECOMPRESS: Failed writing `{}`:\n{}", fp.display(), e),
+    }
 }
 
 fn disasm_arm(x: &str) {
@@ -314,6 +601,9 @@ fn disasm_bios_arm(x: &str, gba: &hardware::Gba) {
     let mut i = r.start & 0xFFFFFFFC;
     while i < r.end {
         let w = bios.read_word(i);
+        if let Some(sym) = gba.symbols().symbol_at(i) {
+            if let Err(e) = write!(msg, "{}:\n", sym.name) { error!("{}", e); break; }
+        }
         let e = if let Ok(inst) = hardware::cpu::ArmInstruction::decode(w) { write!(msg, "{:06X} - {}\n", i, inst) }
                 else { write!(msg, "{:06X} - {:08X}\n", i, w) };
         if let Err(e) = e { error!("{}", e); break; }
@@ -331,6 +621,9 @@ fn disasm_bios_thumb(x: &str, gba: &hardware::Gba) {
     let mut i = r.start & 0xFFFFFFFE;
     while i < r.end {
         let h = bios.read_halfword(i);
+        if let Some(sym) = gba.symbols().symbol_at(i) {
+            if let Err(e) = write!(msg, "{}:\n", sym.name) { error!("{}", e); break; }
+        }
         let e = if let Ok(inst) = hardware::cpu::ThumbInstruction::decode(h) { write!(msg, "{:06X} - {}\n", i, inst) }
                 else { write!(msg, "{:06X} - {:#06X}\n", i, h) };
         if let Err(e) = e { error!("{}", e); break; }
@@ -341,23 +634,56 @@ fn disasm_bios_thumb(x: &str, gba: &hardware::Gba) {
 
 
 fn configure_gba_from_command_line(gba: &mut hardware::Gba, args: &CmdLineArgs) {
+    // Start at the reset vector, unless an executable boots.
+    gba.cpu_arm7tdmi_mut().reset();
+
     // If a BIOS file is given, load it into the BIOS ROM area.
     if let Some(ref fp) = args.bios_file_path {
-        if let Err(e) = gba.bios_mut().load_from_file(fp.as_path()) {
+        let entry = args.bios_zip_entry.as_ref().map(|x| x.as_str());
+        let res = gba.bios_mut().load_from_file_entry(fp.as_path(), entry);
+        if let Err(e) = res {
             error!("Failed loading the BIOS file:\n{}", e);
+            gba.bios_mut().load_builtin(); // Data might be broken.
         } else {
             info!("Loaded the BIOS ROM from file.");
         }
     }
+    if gba.bios().is_builtin() {
+        warn!("No real BIOS loaded, using the built-in replacement BIOS. It lacks most BIOS \
+               functions, so consider `--optimise-swi`, or a BIOS dump via `--bios`.");
+    }
+    if args.skip_bios {
+        gba.boot_post_bios(hardware::memory::GAME_PAK_WS0_ROM_FIRST);
+        info!("Skipping the BIOS boot sequence.");
+    }
 
     // Load ROM now if a path is given.
     if let Some(ref fp) = args.rom_file_path {
-        let res = gba.game_pak_mut().rom_mut().load_from_file(fp.as_path());
+        let entry = args.rom_zip_entry.as_ref().map(|x| x.as_str());
+        let res = if hardware::elf::ElfFile::is_elf_file(fp.as_path()) {
+            gba.load_elf_file(fp.as_path()).map(|_| {
+                info!("Loaded an ELF file with {} symbols.", gba.symbols().len());
+            })
+        } else {
+            gba.game_pak_mut().rom_mut().load_from_file_entry(fp.as_path(), entry)
+        };
         if let Err(e) = res {
             error!("Failed loading the GamePak ROM file:\n{}", e);
         } else {
+            for patch in &args.patch_file_paths {
+                let res = gba.game_pak_mut().rom_mut().apply_patch_file(patch.as_path());
+                match res {
+                    Ok(f)  => info!("Applied the {} patch `{}`.", f, patch.display()),
+                    Err(e) => error!("Failed applying the patch `{}`:\n{}", patch.display(), e),
+                }
+            }
             info!("Loaded the game {}.", gba.game_pak().header());
-            debug!("Header valid? {}", gba.game_pak().header().complement_check());
+            if !gba.game_pak().header().is_valid() {
+                warn!("The ROM header is invalid. Run with `--rom-info` for details.");
+            }
+            if gba.game_pak_mut().plug_detected_peripheral() {
+                info!("Plugged in the GamePak's {}.", gba.game_pak().peripheral());
+            }
             // Load SRAM if desired.
             if args.load_sram {
                 let fp   = fp.with_extension("sram");
@@ -373,8 +699,43 @@ fn configure_gba_from_command_line(gba: &mut hardware::Gba, args: &CmdLineArgs)
         }
     }
 
+    // Load a multiboot image now if a path is given.
+    if let Some(ref fp) = args.multiboot_file_path {
+        if let Err(e) = gba.load_multiboot_file(fp.as_path(), None) {
+            error!("Failed loading the multiboot image:\n{}", e);
+        } else {
+            info!("Loaded the multiboot image `{}`.", fp.display());
+        }
+    }
+
     // Configure the CPU.
     gba.cpu_arm7tdmi_mut().set_swi_optimised(args.optimise_swi);
+
+    // Restore a save state now that the ROM is known.
+    if let Some(slot) = args.load_state_slot {
+        if let Some(fp) = state_slot_path(args, slot) {
+            match gba.load_state_file(fp.as_path()) {
+                Ok(_)  => info!("Restored the machine from `{}`.", fp.display()),
+                Err(e) => error!("Failed loading the machine state:\n{}", e),
+            }
+        }
+    }
+}
+
+/// Get the base path of all save state slots.
+///
+/// Slots are stored next to the ROM file, or next to the
+/// log file if no ROM is given, e.g. `./GBArs.ss0`.
+fn state_base_path(args: &CmdLineArgs) -> PathBuf {
+    args.rom_file_path.clone().unwrap_or_else(|| args.log_file_path.clone())
+}
+
+fn state_slot_path(args: &CmdLineArgs, slot: u8) -> Option<PathBuf> {
+    if slot >= hardware::savestate::SAVE_STATE_SLOTS {
+        error!("There is no save state slot {}.", slot);
+        return None;
+    }
+    Some(hardware::savestate::slot_path(state_base_path(args).as_path(), slot))
 }
 
 
@@ -0,0 +1,836 @@
+// License below.
+//! Provides a debug REPL for the GBA emulator.
+#![cfg_attr(feature="clippy", warn(result_unwrap_used, option_unwrap_used, print_stdout))]
+#![cfg_attr(feature="clippy", warn(single_match_else, string_add, string_add_assign))]
+#![cfg_attr(feature="clippy", warn(wrong_pub_self_convention))]
+#![warn(missing_docs)]
+
+use super::hardware;
+use super::debug;
+use super::term_painter::ToStyle;
+use super::term_painter::Color::*;
+use super::term_painter::Attr::Plain;
+use std::cell::Cell;
+use std::fmt;
+use std::u32;
+use std::io;
+use std::io::Read;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::str::SplitWhitespace;
+use super::hardware::cpu::Mode;
+
+#[cfg(test)]
+mod test;
+
+
+/// All CPU mode names accepted by `set mode`.
+const MODE_NAMES: &'static [(&'static str, Mode)] = &[
+    ("usr", Mode::User),       ("user", Mode::User),
+    ("fiq", Mode::FIQ),        ("irq", Mode::IRQ),
+    ("svc", Mode::Supervisor), ("supervisor", Mode::Supervisor),
+    ("abt", Mode::Abort),      ("abort", Mode::Abort),
+    ("und", Mode::Undefined),  ("undefined", Mode::Undefined),
+    ("sys", Mode::System),     ("system", Mode::System),
+];
+
+/// How deeply `source` may nest scripts.
+const MAX_SCRIPT_DEPTH: usize = 16;
+
+/// How many pipeline steps `continue` and friends may run in batch mode by default.
+pub const DEFAULT_BATCH_MAX_STEPS: u32 = 0x1000000;
+
+/// How many pipeline steps to run between checking for Ctrl-C.
+const INTERRUPT_POLL_STEPS: u32 = 0x4000;
+
+/// All commands, for tab completion.
+const COMMANDS: &'static [&'static str] = &[
+    "?", "x", "p", "hex", "dis", "run", "toggle", "keys", "save", "load", "rewind",
+    "break", "delete", "enable", "disable", "watch", "unwatch", "list", "set",
+    "print", "poke", "fill", "continue", "next", "finish", "bt", "until", "source", "assert",
+];
+
+/// All CPU names accepted by `toggle`.
+const TOGGLE_TARGETS: &'static [&'static str] = &["all", "Arm7Tdmi"];
+
+
+/// Implements a debug REPL for the GBA emulator.
+///
+/// REPL stands for **R**ead, **E**val, **P**rint, **L**oop,
+/// so all it does is running the emulator step by step waiting
+/// for user inputs.
+pub struct GbaRepl {
+    diff_arm7tdmi: hardware::cpu::Arm7TdmiDiff,
+    keymap: hardware::KeyMap,
+    state_path: PathBuf,
+    rewind: hardware::rewind::RewindBuffer,
+    breakpoints: debug::BreakpointList,
+    editor: debug::lineedit::LineEditor,
+    script: Option<PathBuf>,
+    batch: bool,
+    max_steps: u32,
+    failures: usize,
+    complaints: Cell<usize>,
+    depth: usize,
+    colour: bool,
+    show_arm7tdmi: bool,
+}
+
+impl GbaRepl {
+    /// Creates a new REPL without running it.
+    pub fn new() -> GbaRepl {
+        GbaRepl {
+            diff_arm7tdmi: hardware::cpu::Arm7TdmiDiff::new(),
+            keymap: hardware::KeyMap::new(),
+            state_path: PathBuf::from("./GBArs"),
+            rewind: hardware::rewind::RewindBuffer::default(),
+            breakpoints: debug::BreakpointList::new(),
+            editor: debug::lineedit::LineEditor::new(),
+            script: None,
+            batch: false,
+            max_steps: DEFAULT_BATCH_MAX_STEPS,
+            failures: 0,
+            complaints: Cell::new(0),
+            depth: 0,
+            colour: true,
+            show_arm7tdmi: true,
+        }
+    }
+
+    /// Configure whether terminal outputs should be colourised.
+    pub fn with_colour(&mut self, c: bool) -> &mut GbaRepl { self.colour = c; self }
+
+    /// Configure the base path of the save state slots, usually the ROM's path.
+    pub fn with_state_path(&mut self, p: PathBuf) -> &mut GbaRepl { self.state_path = p; self }
+
+    /// Configure where to keep the history of entered commands.
+    pub fn with_history_path(&mut self, p: PathBuf) -> &mut GbaRepl {
+        self.editor.load_history(p.as_path());
+        self
+    }
+
+    /// Configure how often to take rewind snapshots and how much memory they may use.
+    pub fn with_rewind(&mut self, frames: u32, budget: usize) -> &mut GbaRepl {
+        self.rewind = hardware::rewind::RewindBuffer::new(frames, budget);
+        self
+    }
+
+    /// Configure a script to run before prompting for commands.
+    pub fn with_script(&mut self, p: Option<PathBuf>) -> &mut GbaRepl { self.script = p; self }
+
+    /// Configure whether to quit after the script instead of
+    /// prompting for commands. Failed assertions, commands,
+    /// and errors in scripts stop the REPL as well.
+    pub fn with_batch(&mut self, b: bool) -> &mut GbaRepl { self.batch = b; self }
+
+    /// Configure how many steps `continue` and friends may run in
+    /// batch mode before failing, such that scripts cannot hang.
+    pub fn with_max_steps(&mut self, n: u32) -> &mut GbaRepl { self.max_steps = n; self }
+
+    /// Get the number of failed assertions, commands, and script errors.
+    pub fn failures(&self) -> usize { self.failures }
+
+    /// Runs the REPL until the user quits, an error occurred,
+    /// or until the emulated program ends.
+    pub fn run(&mut self, gba: &mut hardware::Gba) -> Result<(), hardware::GbaError> {
+        // Prepare everything we need.
+        self.diff_arm7tdmi.diff(gba.cpu_arm7tdmi());
+        self.print_emu(gba);
+        let mut input = String::new();
+
+        // Run the script first, if any.
+        if let Some(p) = self.script.clone() {
+            if !try!(self.source(gba, p.as_path())) || self.batch { return Ok(()); }
+        }
+
+        // Now run the actual REPL.
+        loop {
+            match self.input_prompt(gba, &mut input) {
+                Ok(true) => {},
+                Ok(false) => break,
+                Err(e) => { error!("{}", e); break; },
+            }
+            if !try!(self.execute(gba, &input)) { break; }
+        }
+        Ok(())
+    }
+
+    /// Executes a single command.
+    ///
+    /// Commands complaining about anything count as failures.
+    ///
+    /// # Returns
+    /// `false` if the REPL should stop.
+    fn execute(&mut self, gba: &mut hardware::Gba, input: &str) -> Result<bool, hardware::GbaError> {
+        let mut s = input.trim().split_whitespace();
+        let complaints = self.complaints.get();
+
+        match s.next() {
+            Some("?") => self.print_help(),
+            Some("x") => return Ok(false),
+            Some("p") => self.print_emu(gba),
+            Some("hex") => if let Some(r) = s.next() { self.hexdump(r, gba); },
+            Some("dis") => self.disassemble(s, gba),
+            Some("run") => if let Some(n) = s.next() { try!(self.run_n_steps_str(gba, n)); },
+            Some("toggle") => if let Some(cpu) = s.next() { self.toggle_cpu(cpu); },
+            Some("keys") => self.set_keys(gba, s),
+            Some("save") => if let Some(n) = s.next() { self.save_state(gba, n); },
+            Some("load") => if let Some(n) = s.next() { self.load_state(gba, n); },
+            Some("rewind") => self.rewind_str(gba, s.next().unwrap_or("1")),
+            Some("break") => self.add_breakpoint(gba, s),
+            Some("delete") => self.delete_breakpoints(gba, s.next()),
+            Some("enable") => if let Some(id) = s.next() { self.enable_breakpoint(gba, id, true); },
+            Some("disable") => if let Some(id) = s.next() { self.enable_breakpoint(gba, id, false); },
+            Some("watch") => self.add_watchpoint(gba, s),
+            Some("unwatch") => self.delete_watchpoints(gba, s.next()),
+            Some("list") => self.list_breakpoints(gba),
+            Some("set") => self.set_str(gba, s),
+            Some(p) if p == "print" || p.starts_with("print/") => self.print_expr(gba, &p[5..], s),
+            Some("poke") => self.poke_str(gba, s),
+            Some("fill") => self.fill_str(gba, s),
+            Some("continue") => try!(self.run_steps(gba, None)),
+            Some("next") => try!(self.next(gba)),
+            Some("finish") => try!(self.finish(gba)),
+            Some("bt") => self.backtrace(gba),
+            Some("until") => try!(self.until_str(gba, s)),
+            Some("source") => if let Some(p) = s.next() { return self.source(gba, Path::new(p)); },
+            Some("assert") => return Ok(self.assert(gba, s)),
+            Some("") | None => try!(self.run_steps(gba, Some(1))),
+            _ => self.complain(format_args!("What?")),
+        }
+        if self.complaints.get() != complaints { return Ok(self.fail()); }
+        Ok(true)
+    }
+
+    /// Runs all commands of a script file.
+    ///
+    /// # Returns
+    /// `false` if the REPL should stop.
+    fn source(&mut self, gba: &mut hardware::Gba, path: &Path) -> Result<bool, hardware::GbaError> {
+        if self.depth >= MAX_SCRIPT_DEPTH {
+            self.complain(format_args!("Scripts are nested too deeply."));
+            return Ok(self.fail());
+        }
+        let mut text = String::new();
+        if let Err(e) = File::open(path).and_then(|mut f| f.read_to_string(&mut text)) {
+            self.complain(format_args!("Failed reading `{}`: {}", path.display(), e));
+            return Ok(self.fail());
+        }
+        let script = match debug::script::parse(&text) {
+            Ok(s) => s,
+            Err(e) => { self.complain(format_args!("{}: {}", path.display(), e)); return Ok(self.fail()); },
+        };
+
+        self.depth += 1;
+        let res = self.run_statements(gba, &script);
+        self.depth -= 1;
+        res
+    }
+
+    fn run_statements(&mut self, gba: &mut hardware::Gba, script: &[debug::script::Statement])
+    -> Result<bool, hardware::GbaError> {
+        for st in script {
+            match *st {
+                debug::script::Statement::Command(ref c) => {
+                    print!("\t> {}\n\n", c);
+                    if !try!(self.execute(gba, c)) { return Ok(false); }
+                },
+                debug::script::Statement::Repeat(ref n, ref body) => {
+                    let n = match debug::eval_str(n, gba) {
+                        Ok(n) => n,
+                        Err(e) => {
+                            self.complain(format_args!("{}", e));
+                            if !self.fail() { return Ok(false); }
+                            continue;
+                        },
+                    };
+                    for _ in 0..n {
+                        if !try!(self.run_statements(gba, body)) { return Ok(false); }
+                    }
+                },
+            }
+        }
+        Ok(true)
+    }
+
+    /// Checks an expression is non-zero.
+    ///
+    /// # Returns
+    /// `false` if the REPL should stop.
+    fn assert(&mut self, gba: &hardware::Gba, args: SplitWhitespace) -> bool {
+        let expr = args.collect::<Vec<_>>().join(" ");
+        match debug::eval_str(&expr, gba) {
+            Ok(0) => self.complain(format_args!("Assertion `{}` failed.", expr)),
+            Ok(_) => { print!("\t\tAssertion `{}` holds.\n\n", expr); return true; },
+            Err(e) => self.complain(format_args!("{}", e)),
+        }
+        self.fail()
+    }
+
+    /// Prints an error message about the current command.
+    fn complain(&self, msg: fmt::Arguments) {
+        print!("\t\t<{}>\n\n", msg);
+        self.complaints.set(self.complaints.get() + 1);
+    }
+
+    /// Counts a failure.
+    ///
+    /// # Returns
+    /// `false` if the REPL should stop, i.e. in batch mode.
+    fn fail(&mut self) -> bool {
+        self.failures += 1;
+        !self.batch
+    }
+
+    fn input_prompt(&mut self, gba: &hardware::Gba, input: &mut String) -> io::Result<bool> {
+        print!("\t{}\n", Black.bg(White).paint("[? = Help, x = Exit, p, hex A..B, run N, toggle CPU]"));
+        input.clear();
+        let line = try!(self.editor.read_line("\t> ", |before| GbaRepl::completions(gba, before)));
+        println!("");
+        match line {
+            Some(l) => { input.push_str(&l); Ok(true) },
+            None => Ok(false),
+        }
+    }
+
+    fn completions(gba: &hardware::Gba, before: &str) -> Vec<String> {
+        let mut words = before.split_whitespace();
+        let first = words.next();
+        let done = words.count() + (if before.ends_with(char::is_whitespace) { 1 } else { 0 });
+        match first {
+            None => COMMANDS.iter().map(|c| c.to_string()).collect(),
+            Some("toggle") if done == 1 => TOGGLE_TARGETS.iter().map(|c| c.to_string()).collect(),
+            _ => {
+                let mut v: Vec<String> = (0..16).map(|i| format!("r{}", i)).collect();
+                v.extend(debug::REGISTER_NAMES.iter().map(|&(n, _)| n.to_string()));
+                v.push("cpsr".to_string());
+                v.extend(debug::CPSR_BIT_NAMES.iter().map(|&(n, _)| format!("cpsr.{}", n.to_uppercase())));
+                v.extend(gba.symbols().iter().map(|s| s.name.clone()));
+                v
+            },
+        }
+    }
+
+    fn print_help(&self) {
+        println!("\t{}\n\t\
+            ?          - Print this help text.\n\t\
+            x          - Exit the debug REPL.\n\t\
+            p          - Print the current CPU state again.\n\t\
+            hex RANGE  - Hexdump a region of memory defined by RANGE.\n\t\
+            dis STATE RANGE\n\t             \
+                       - Disassemble a region of memory as STATE instructions.\n\t             \
+                         The default range covers the next instructions.\n\t\
+            run N      - Run N pipeline steps, where N is an EXPR like `#100`.\n\t\
+            toggle CPU - Show/hide the current state of CPU.\n\t\
+            keys KEYS  - Press exactly the given keys, or print the pressed keys.\n\t\
+            save SLOT  - Save the machine into a save state SLOT (0-9).\n\t\
+            load SLOT  - Restore the machine from a save state SLOT (0-9).\n\t\
+            rewind N   - Go back N snapshots in time. A snapshot is taken\n\t             \
+                         before running any command and then every frame.\n\t\
+            break ADDR - Stop before executing the instruction at ADDR. Append\n\t             \
+                         `if EXPR` to stop only if EXPR is non-zero.\n\t\
+            delete ID  - Delete a breakpoint, or all breakpoints without ID.\n\t\
+            enable ID  - Enable a breakpoint.\n\t\
+            disable ID - Disable a breakpoint.\n\t\
+            watch KIND RANGE\n\t             \
+                       - Stop after any instruction accessing RANGE. Append\n\t             \
+                         `== VALUE` to stop only if loading or storing VALUE.\n\t\
+            unwatch ID - Delete a watchpoint, or all watchpoints without ID.\n\t\
+            list       - List all breakpoints and watchpoints.\n\t\
+            set REG = EXPR\n\t             \
+                       - Set a register, `cpsr`, or a flag like `cpsr.Z`.\n\t\
+            set mode MODE\n\t             \
+                       - Switch the CPU mode, e.g. to `irq`.\n\t\
+            poke ADDR.W = EXPR\n\t             \
+                       - Store into memory, where W is `b`, `h`, or `w`.\n\t\
+            fill RANGE BYTE\n\t             \
+                       - Fill a region of memory with BYTE, an EXPR.\n\t\
+            print EXPR - Print the value of EXPR in all formats. Use e.g.\n\t             \
+                         `print/x` for just one of the formats FMT.\n\t\
+            continue   - Run until hitting a breakpoint or until the CPU sleeps\n\t             \
+                         with only key presses left to wake it up.\n\t\
+            next       - Run a single step, but step over calls like `bl`.\n\t\
+            finish     - Run until the current function returns.\n\t\
+            bt         - Print a backtrace of all tracked calls and exceptions.\n\t\
+            until ADDR - Run until reaching ADDR.\n\t\
+            source FILE\n\t             \
+                       - Run all commands of a script FILE.\n\t\
+            assert EXPR\n\t             \
+                       - Complain unless EXPR is non-zero. This fails the\n\t             \
+                         script in `--repl-batch` mode.\n\t\
+            [ENTER]    - Just hit the enter key to run a single pipeline step.\n\t\
+            \n\t{}\n\t\
+            RANGE - A pair of expressions without spaces, e.g. `A..B+4`.\n\t        \
+                    The default range is `0..80` and any omitted value\n\t        \
+                    will be interpreted as the default value. Thus, `..B`\n\t        \
+                    will be interpreted as `0..B`.\n\t\
+            CPU   - A CPU name. The possible values are:\n\t        \
+                    - all\n\t        \
+                    - Arm7Tdmi\n\t\
+            STATE - `arm`, `thumb`, or `auto` to use the CPU's state.\n\t        \
+                    Defaults to `auto`.\n\t\
+            MODE  - `usr`, `fiq`, `irq`, `svc`, `abt`, `und`, or `sys`.\n\t\
+            KEYS  - Any GBA key names, e.g. `a start up`, or `none`.\n\t\
+            KIND  - `read`, `write`, or `access`. Defaults to `write`.\n\t\
+            ADDR  - An address EXPR, e.g. `8000F20` or `main + 4`.\n\t\
+            EXPR  - An expression of hexadecimal numbers like `1F`,\n\t        \
+                    decimal numbers like `#31`, binary numbers like\n\t        \
+                    `%11111`, registers like `r0` or `sp`, CPSR flags\n\t        \
+                    like `cpsr.Z`, memory like `[3001000].h`, symbols,\n\t        \
+                    and C operators. Symbols named like numbers, e.g.\n\t        \
+                    `add`, take precedence, so write `0add` for the number.\n\t\
+            FILE  - A script with one command per line. Lines starting\n\t        \
+                    with `#` are comments, and commands enclosed by\n\t        \
+                    `repeat N {{` and `}}` lines run N times.\n\t\
+            FMT   - `x` for hex, `d` for decimal, `b` for binary, or\n\t        \
+                    `c` for a character.\n\t",
+            BrightWhite.paint("Commands:"), BrightWhite.paint("Arguments:"),
+        );
+    }
+
+    fn print_emu(&self, gba: &hardware::Gba) {
+        if self.show_arm7tdmi {
+            self.diff_arm7tdmi.print();
+            let addr = gba.cpu_arm7tdmi().executing_address();
+            if let Some(l) = gba.symbols().label(addr) { print!("\t\tExecuting: {:#010X} {}\n\n", addr, l); }
+        }
+    }
+
+    fn emu_step(&mut self, gba: &mut hardware::Gba) -> Result<(), hardware::GbaError> {
+        try!(gba.cpu_arm7tdmi_mut().pipeline_step());
+        if let Err(e) = self.rewind.step(gba) { error!("Failed taking a rewind snapshot:\n{}", e); }
+        Ok(())
+    }
+
+    fn diff(&mut self, gba: &hardware::Gba) {
+        self.diff_arm7tdmi.diff(gba.cpu_arm7tdmi());
+    }
+
+    /// Runs `n` pipeline steps, or until a breakpoint is hit
+    /// or the CPU sleeps until input. The first step never hits a breakpoint,
+    /// such that continuing from a breakpoint does not get stuck.
+    /// Temporary breakpoints are removed afterwards.
+    fn run_steps(&mut self, gba: &mut hardware::Gba, n: Option<u32>) -> Result<(), hardware::GbaError> {
+        if let Err(e) = self.rewind.record(gba) { error!("Failed taking a rewind snapshot:\n{}", e); }
+        let mut steps = 0;
+        let _ctrl_c = debug::interrupt::catch_interrupts();
+        while n.map_or(true, |n| steps < n) {
+            if n.is_none() && self.batch && (steps >= self.max_steps) {
+                self.complain(format_args!("Gave up after {} step(s) without stopping.", steps));
+                break;
+            }
+            let pc = gba.cpu_arm7tdmi().executing_address();
+            if let Err(e) = self.emu_step(gba) { self.breakpoints.clear_temporary(); return Err(e); }
+            steps += 1;
+            if let Some(hit) = gba.bus_mut().watchpoints_mut().take_hit() {
+                GbaRepl::print_watch_hit(pc, &hit);
+                break;
+            }
+            // Nothing but a key press would change anymore.
+            if let Some(m) = gba.cpu_arm7tdmi().low_power_mode().filter(|_| gba.cpu_arm7tdmi().sleeps_until_input()) {
+                print!("\t\tThe CPU sleeps in {:?} mode, waiting for a key press.\n\n", m);
+                break;
+            }
+            if let Some(id) = self.breakpoints.check(gba) {
+                if self.breakpoints.get(id).map_or(false, |b| b.temporary) {
+                    print!("\t\tStopped at {:#010X} after {} step(s).\n\n", gba.cpu_arm7tdmi().executing_address(), steps);
+                }
+                else { print!("\t\tHit breakpoint {:X} after {} step(s).\n\n", id, steps); }
+                break;
+            }
+            if (steps % INTERRUPT_POLL_STEPS == 0) && debug::interrupt::take_interrupt() {
+                print!("\t\tInterrupted after {} step(s).\n\n", steps);
+                break;
+            }
+        }
+        self.breakpoints.clear_temporary();
+        self.diff(gba);
+        self.print_emu(gba);
+        Ok(())
+    }
+
+    /// Runs until reaching `addr` with a stack at least as high
+    /// as the current one, i.e. in the current or a calling frame.
+    fn run_until(&mut self, gba: &mut hardware::Gba, addr: u32) -> Result<(), hardware::GbaError> {
+        let sp = gba.cpu_arm7tdmi().register(hardware::cpu::Arm7Tdmi::SP) as u32;
+        if let Err(e) = self.breakpoints.add_temporary(addr, Some(&format!("sp >= {:#X}", sp))) {
+            self.complain(format_args!("{}", e));
+            return Ok(());
+        }
+        self.run_steps(gba, None)
+    }
+
+    fn next(&mut self, gba: &mut hardware::Gba) -> Result<(), hardware::GbaError> {
+        match debug::call_return_address(gba) {
+            Some(addr) => self.run_until(gba, addr),
+            None => self.run_steps(gba, Some(1)),
+        }
+    }
+
+    fn finish(&mut self, gba: &mut hardware::Gba) -> Result<(), hardware::GbaError> {
+        // Prefer the call stack, as LR may have been reused since the call.
+        let ret = match gba.cpu_arm7tdmi().call_stack().top() {
+            Some(f) => f.return_addr,
+            None => gba.cpu_arm7tdmi().register(hardware::cpu::Arm7Tdmi::LR) as u32 & !1,
+        };
+        self.run_until(gba, ret)
+    }
+
+    fn backtrace(&self, gba: &hardware::Gba) {
+        let cpu = gba.cpu_arm7tdmi();
+        let label = |addr: u32| gba.symbols().label(addr).map(|l| format!(" {}", l)).unwrap_or_else(String::new);
+        let pc = cpu.executing_address();
+        print!("\t\t#0  {:08X}{}\n", pc, label(pc));
+        for (i, f) in cpu.call_stack().iter().rev().enumerate() {
+            let via = f.exception.map(|ex| format!(" via {:?}", ex)).unwrap_or_else(String::new);
+            print!("\t\t#{:<2} {:08X}{}\tentered {:08X}{} in {}{}\n",
+                   i + 1, f.call_site, label(f.call_site), f.target, label(f.target), f.mode, via);
+        }
+        if cpu.call_stack().is_empty() { print!("\t\tNo calls have been tracked yet.\n"); }
+        print!("\n");
+    }
+
+    fn until_str(&mut self, gba: &mut hardware::Gba, args: SplitWhitespace) -> Result<(), hardware::GbaError> {
+        match debug::eval_str(&args.collect::<Vec<_>>().join(" "), gba) {
+            Ok(addr) => {
+                if let Err(e) = self.breakpoints.add_temporary(addr, None) { self.complain(format_args!("{}", e)); return Ok(()); }
+                self.run_steps(gba, None)
+            },
+            Err(e) => { self.complain(format_args!("{}", e)); Ok(()) },
+        }
+    }
+
+    fn run_n_steps_str(&mut self, gba: &mut hardware::Gba, n: &str) -> Result<(), hardware::GbaError> {
+        match debug::eval_str(n, gba) {
+            Ok(n)  => self.run_steps(gba, Some(n)),
+            Err(e) => { self.complain(format_args!("{}", e)); Ok(()) },
+        }
+    }
+
+    fn hexdump(&self, s: &str, gba: &hardware::Gba) {
+        let r = debug::eval_range(s, gba, 0x00, 0x80);
+        if let Err(ref e) = r { self.complain(format_args!("{}", e)); }
+        if let Ok(mut r) = r {
+            r.start &= !31;
+            r.end   +=  31;
+            r.end   &= !31;
+            print!("\t\t           00           04           08           0C           \
+                                   10           14           18           1C");
+            for i in r {
+                if (i % 32) == 0 { print!("\n\t\t{:08X} -", i); }
+                else if (i % 4) == 0 { print!(" "); }
+                print!(" {:02X}", gba.bus().peek_byte(i).unwrap_or(0));
+            }
+            print!("\n\n");
+        }
+    }
+
+    fn disassemble(&self, mut args: SplitWhitespace, gba: &hardware::Gba) {
+        let cpu = gba.cpu_arm7tdmi();
+        let mut state = cpu.cpsr().state();
+        let mut range = args.next();
+        match range {
+            Some("arm")   => { state = hardware::cpu::State::ARM;   range = args.next(); },
+            Some("thumb") => { state = hardware::cpu::State::THUMB; range = args.next(); },
+            Some("auto")  => { range = args.next(); },
+            _ => {},
+        }
+        let start = cpu.executing_address();
+        let len = match state { hardware::cpu::State::ARM => 0x20, hardware::cpu::State::THUMB => 0x10 };
+        match debug::eval_range(range.unwrap_or(".."), gba, start, start.wrapping_add(len)) {
+            Ok(r) => {
+                for line in debug::disassemble(gba, r, state) { print!("\t\t{}\n", line); }
+                print!("\n");
+            },
+            Err(e) => self.complain(format_args!("{}", e)),
+        }
+    }
+
+    fn set_keys(&self, gba: &mut hardware::Gba, mut keys: SplitWhitespace) {
+        let first = match keys.next() {
+            Some(k) => k,
+            None => {
+                let pressed: Vec<_> = hardware::keypad::KEY_NAMES.iter()
+                    .filter(|&&(_, k)| 0 != (gba.keys() & k.mask()))
+                    .map(|&(n, _)| n).collect();
+                print!("\t\tPressed keys: {}\n\n", if pressed.is_empty() { "none".to_string() } else { pressed.join(" ") });
+                return;
+            },
+        };
+        let res = if first == "none" { Ok(0) }
+                  else { self.keymap.keys(Some(first).into_iter().chain(keys)) };
+        match res {
+            Ok(mask) => gba.set_keys(mask),
+            Err(k)   => self.complain(format_args!("Unknown key `{}`.", k)),
+        }
+    }
+
+    fn add_breakpoint(&mut self, gba: &hardware::Gba, args: SplitWhitespace) {
+        let args = args.collect::<Vec<_>>();
+        let (addr, condition) = match args.iter().position(|&a| a == "if") {
+            Some(i) => (args[..i].join(" "), Some(args[(i + 1)..].join(" "))),
+            None    => (args.join(" "), None),
+        };
+        if addr.is_empty() { self.complain(format_args!("Missing breakpoint address.")); return; }
+        let addr = match debug::eval_str(&addr, gba) {
+            Ok(a) => a,
+            Err(e) => { self.complain(format_args!("{}", e)); return; },
+        };
+        match self.breakpoints.add(addr, condition.as_ref().map(|c| &c[..])) {
+            Ok(id) => print!("\t\tBreakpoint {:X} at {:#010X}.\n\n", id, addr),
+            Err(e) => self.complain(format_args!("{}", e)),
+        }
+    }
+
+    fn parse_id(&self, id: &str, what: &str, gba: &hardware::Gba) -> Option<u32> {
+        match debug::eval_str(id, gba) {
+            Ok(id) => Some(id),
+            Err(_) => { self.complain(format_args!("There is no {} `{}`.", what, id)); None },
+        }
+    }
+
+    fn delete_breakpoints(&mut self, gba: &hardware::Gba, id: Option<&str>) {
+        match id {
+            Some(id) => if let Some(n) = self.parse_id(id, "breakpoint", gba) {
+                if !self.breakpoints.remove(n) { self.complain(format_args!("There is no breakpoint `{}`.", id)); }
+            },
+            None => self.breakpoints.clear(),
+        }
+    }
+
+    fn enable_breakpoint(&mut self, gba: &hardware::Gba, id: &str, enabled: bool) {
+        if let Some(n) = self.parse_id(id, "breakpoint", gba) {
+            if !self.breakpoints.set_enabled(n, enabled) { self.complain(format_args!("There is no breakpoint `{}`.", id)); }
+        }
+    }
+
+    fn list_breakpoints(&self, gba: &hardware::Gba) {
+        if self.breakpoints.is_empty() { print!("\t\tThere are no breakpoints.\n"); }
+        for b in self.breakpoints.iter() {
+            print!("\t\t{:>3X}  {:#010X}  {:<8}  {:>4} hit(s)", b.id, b.addr,
+                   if b.enabled { "enabled" } else { "disabled" }, b.hits);
+            if let Some((ref src, _)) = b.condition { print!("  if {}", src); }
+            if let Some(l) = gba.symbols().label(b.addr) { print!("  <{}>", l); }
+            print!("\n");
+        }
+        let bus = gba.bus();
+        if bus.watchpoints().is_empty() { print!("\t\tThere are no watchpoints.\n"); }
+        else { print!("\t\tWatchpoints:\n"); }
+        for w in bus.watchpoints().iter() {
+            print!("\t\t{:>3X}  {:08X}..{:08X}  {:<6}  {:<8}  {:>4} hit(s)", w.id, w.range.start, w.range.end,
+                   w.kind, if w.enabled { "enabled" } else { "disabled" }, w.hits);
+            if let Some(v) = w.value { print!("  == {:#X}", v); }
+            print!("\n");
+        }
+        print!("\n");
+    }
+
+    fn add_watchpoint(&mut self, gba: &mut hardware::Gba, mut args: SplitWhitespace) {
+        let mut arg = args.next();
+        let kind = match arg {
+            Some("read")   => Some(hardware::watchpoint::WatchKind::Read),
+            Some("write")  => Some(hardware::watchpoint::WatchKind::Write),
+            Some("access") => Some(hardware::watchpoint::WatchKind::Access),
+            _ => None,
+        };
+        if kind.is_some() { arg = args.next(); }
+        let kind = kind.unwrap_or(hardware::watchpoint::WatchKind::Write);
+        let range = match arg {
+            Some(r) if r.contains("..") => debug::eval_range(r, gba, 0, 0),
+            Some(a) => debug::eval_str(a, gba).map(|a| a..a.saturating_add(1)),
+            None => { self.complain(format_args!("Missing watchpoint range.")); return; },
+        };
+        let range = match range { Ok(r) => r, Err(e) => { self.complain(format_args!("{}", e)); return; } };
+        let value = match args.next() {
+            Some("==") => match debug::eval_str(&args.collect::<Vec<_>>().join(" "), gba) {
+                Ok(v)  => Some(v),
+                Err(e) => { self.complain(format_args!("{}", e)); return; },
+            },
+            None => None,
+            Some(x) => { self.complain(format_args!("Expected `== VALUE`, not `{}`.", x)); return; },
+        };
+        if range.start >= range.end { self.complain(format_args!("The watchpoint range is empty.")); return; }
+        let (start, end) = (range.start, range.end);
+        let id = gba.bus_mut().watchpoints_mut().add(range, kind, value);
+        print!("\t\tWatchpoint {:X} ({}) at {:08X}..{:08X}.\n\n", id, kind, start, end);
+    }
+
+    fn delete_watchpoints(&mut self, gba: &mut hardware::Gba, id: Option<&str>) {
+        match id {
+            Some(id) => if let Some(n) = self.parse_id(id, "watchpoint", gba) {
+                if !gba.bus_mut().watchpoints_mut().remove(n) { self.complain(format_args!("There is no watchpoint `{}`.", id)); }
+            },
+            None => gba.bus_mut().watchpoints_mut().clear(),
+        }
+    }
+
+    fn print_expr(&self, gba: &hardware::Gba, fmt: &str, args: SplitWhitespace) {
+        let formats = if fmt.is_empty() { vec![debug::Format::Hex, debug::Format::Dec, debug::Format::Bin, debug::Format::Char] }
+            else { match debug::Format::from_letter(&fmt[1..]) {
+                Some(f) => vec![f],
+                None => { self.complain(format_args!("Unknown format `{}`.", &fmt[1..])); return; },
+            }};
+        match debug::eval_str(&args.collect::<Vec<_>>().join(" "), gba) {
+            Ok(x) => print!("\t\t{}\n\n", formats.iter().map(|f| f.format(x)).collect::<Vec<_>>().join("  ")),
+            Err(e) => self.complain(format_args!("{}", e)),
+        }
+    }
+
+    fn set_str(&mut self, gba: &mut hardware::Gba, args: SplitWhitespace) {
+        let args = args.collect::<Vec<_>>().join(" ");
+        if args.starts_with("mode ") {
+            let name = args[5..].trim().to_lowercase();
+            let mode = match MODE_NAMES.iter().find(|&&(n, _)| n == name) {
+                Some(&(_, m)) => m,
+                None => { self.complain(format_args!("Unknown CPU mode `{}`.", name)); return; },
+            };
+            let cpsr = gba.cpu_arm7tdmi().cpsr().0;
+            self.assign(gba, &debug::Expr::Cpsr, (cpsr & !0x1F) | mode.as_bits());
+            return;
+        }
+        let mut parts = args.splitn(2, '=');
+        let (lhs, rhs) = match (parts.next(), parts.next()) {
+            (Some(l), Some(r)) => (l, r),
+            _ => { self.complain(format_args!("Expected `set REG = EXPR`.")); return; },
+        };
+        match debug::Expr::parse(lhs).and_then(|l| debug::Expr::parse(rhs).and_then(|r| r.eval(gba)).map(|x| (l, x))) {
+            Ok((l, x)) => self.assign(gba, &l, x),
+            Err(e) => self.complain(format_args!("{}", e)),
+        }
+    }
+
+    fn poke_str(&mut self, gba: &mut hardware::Gba, args: SplitWhitespace) {
+        let args = args.collect::<Vec<_>>().join(" ");
+        let mut parts = args.splitn(2, '=');
+        let (lhs, rhs) = match (parts.next(), parts.next()) {
+            (Some(l), Some(r)) => (l.trim(), r),
+            _ => { self.complain(format_args!("Expected `poke ADDR.W = EXPR`.")); return; },
+        };
+        let (addr, width) = match lhs.rfind('.').map(|i| lhs.split_at(i)) {
+            Some((a, ".b")) => (a, debug::Width::Byte),
+            Some((a, ".h")) => (a, debug::Width::Halfword),
+            Some((a, ".w")) => (a, debug::Width::Word),
+            _ => (lhs, debug::Width::Word),
+        };
+        let target = debug::Expr::parse(addr).map(|a| debug::Expr::Memory(Box::new(a), width));
+        match target.and_then(|t| debug::Expr::parse(rhs).and_then(|r| r.eval(gba)).map(|x| (t, x))) {
+            Ok((t, x)) => self.assign(gba, &t, x),
+            Err(e) => self.complain(format_args!("{}", e)),
+        }
+    }
+
+    fn fill_str(&mut self, gba: &mut hardware::Gba, mut args: SplitWhitespace) {
+        let r = match args.next().map(|r| debug::eval_range(r, gba, 0x00, 0x80)) {
+            Some(Ok(r)) => r,
+            Some(Err(e)) => { self.complain(format_args!("{}", e)); return; },
+            None => { self.complain(format_args!("Expected `fill RANGE BYTE`.")); return; },
+        };
+        let b = match debug::eval_str(&args.collect::<Vec<_>>().join(" "), gba) {
+            Ok(b) if b <= 0xFF => b as u8,
+            Ok(b) => { self.complain(format_args!("{:#X} is no byte.", b)); return; },
+            Err(e) => { self.complain(format_args!("{}", e)); return; },
+        };
+        if let Err(e) = self.rewind.record(gba) { error!("Failed taking a rewind snapshot:\n{}", e); }
+        for addr in r.clone() {
+            if let Err(e) = gba.bus_mut().poke_byte(addr, b as i32) { self.complain(format_args!("{}", e)); return; }
+        }
+        print!("\t\tFilled {:08X}..{:08X} with {:02X}.\n\n", r.start, r.end, b);
+    }
+
+    /// Stores a value and shows the changed CPU state.
+    fn assign(&mut self, gba: &mut hardware::Gba, target: &debug::Expr, x: u32) {
+        if let Err(e) = self.rewind.record(gba) { error!("Failed taking a rewind snapshot:\n{}", e); }
+        match target.assign(gba, x) {
+            Ok(()) => if let debug::Expr::Memory(..) = *target { print!("\t\tStored {:08X}.\n\n", x); }
+                      else { self.diff(gba); self.print_emu(gba); },
+            Err(e) => self.complain(format_args!("{}", e)),
+        }
+    }
+
+    fn print_watch_hit(pc: u32, hit: &hardware::watchpoint::WatchHit) {
+        if hit.write {
+            print!("\t\tWatchpoint {:X}: {}-bit write to {:#010X} by the instruction at {:#010X}, {:#X} -> {:#X}.\n\n",
+                   hit.id, hit.width, hit.addr, pc, hit.old, hit.new);
+        } else {
+            print!("\t\tWatchpoint {:X}: {}-bit read of {:#X} from {:#010X} by the instruction at {:#010X}.\n\n",
+                   hit.id, hit.width, hit.new, hit.addr, pc);
+        }
+    }
+
+    fn rewind_str(&mut self, gba: &mut hardware::Gba, n: &str) {
+        let n = match debug::eval_str(n, gba) {
+            Ok(n)  => n as usize,
+            Err(e) => { self.complain(format_args!("{}", e)); return; },
+        };
+        match self.rewind.rewind(gba, n) {
+            Ok(0)    => self.complain(format_args!("There is nothing to rewind.")),
+            Ok(done) => {
+                print!("\t\tWent back {} snapshot(s), {} left.\n\n", done, self.rewind.len() - 1);
+                self.diff(gba);
+                self.print_emu(gba);
+            },
+            Err(e)   => self.complain(format_args!("{}", e)),
+        }
+    }
+
+    fn slot_path(&self, slot: &str) -> Option<PathBuf> {
+        match u8::from_str_radix(slot, 10) {
+            Ok(n) if n < hardware::savestate::SAVE_STATE_SLOTS => {
+                Some(hardware::savestate::slot_path(self.state_path.as_path(), n))
+            },
+            _ => { self.complain(format_args!("There is no save state slot `{}`.", slot)); None },
+        }
+    }
+
+    fn save_state(&self, gba: &hardware::Gba, slot: &str) {
+        if let Some(fp) = self.slot_path(slot) {
+            match gba.save_state_file(fp.as_path()) {
+                Ok(_)  => print!("\t\tSaved the machine to `{}`.\n\n", fp.display()),
+                Err(e) => self.complain(format_args!("{}", e)),
+            }
+        }
+    }
+
+    fn load_state(&mut self, gba: &mut hardware::Gba, slot: &str) {
+        if let Some(fp) = self.slot_path(slot) {
+            match gba.load_state_file(fp.as_path()) {
+                Ok(_)  => {
+                    print!("\t\tRestored the machine from `{}`.\n\n", fp.display());
+                    self.diff(gba);
+                    self.print_emu(gba);
+                },
+                Err(e) => self.complain(format_args!("{}", e)),
+            }
+        }
+    }
+
+    fn toggle_cpu(&mut self, cpu: &str) {
+        match cpu {
+            "Arm7Tdmi" => { self.show_arm7tdmi = !self.show_arm7tdmi; },
+            "all" => {
+                self.show_arm7tdmi = !self.show_arm7tdmi;
+            },
+            _ => {},
+        }
+    }
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/
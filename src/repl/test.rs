@@ -0,0 +1,75 @@
+// License below.
+#![allow(missing_docs)]
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process;
+use super::*;
+use super::super::hardware::test_util::counting_gba;
+
+#[test]
+pub fn failures() {
+    let mut gba = counting_gba();
+    let mut repl = GbaRepl::new();
+
+    // Without batch mode, the REPL goes on after failures.
+    assert_eq!(repl.execute(&mut gba, "assert r0 == 0"), Ok(true));
+    assert_eq!(repl.failures(), 0);
+    assert_eq!(repl.execute(&mut gba, "assert r0 == 1"), Ok(true));
+    assert_eq!(repl.failures(), 1);
+    assert_eq!(repl.execute(&mut gba, "frobnicate"), Ok(true));
+    assert_eq!(repl.execute(&mut gba, "break nowhere"), Ok(true));
+    assert_eq!(repl.execute(&mut gba, "watch write #1F..20"), Ok(true));
+    assert_eq!(repl.execute(&mut gba, "print 1 + 1"), Ok(true));
+    assert_eq!(repl.failures(), 4);
+}
+
+#[test]
+pub fn batch_scripts() {
+    let path = env::temp_dir().join(format!("gbars-{}-test.script", process::id()));
+    fs::File::create(&path).unwrap().write_all(b"# Count to three.\n\
+                      break 0x02000008\n\
+                      repeat 3 {\n\
+                        continue\n\
+                      }\n\
+                      assert r0 == 3\n\
+                      assert r0 == 4\n\
+                      assert r0 == 3\n").unwrap();
+
+    // Batch mode stops at the first failure.
+    let mut gba = counting_gba();
+    let mut repl = GbaRepl::new();
+    repl.with_batch(true);
+    assert_eq!(repl.source(&mut gba, &path), Ok(false));
+    assert_eq!(repl.failures(), 1);
+    assert_eq!(gba.cpu_arm7tdmi().register(0), 3);
+    fs::remove_file(&path).unwrap();
+
+    // Running forever fails instead of hanging.
+    let mut gba = counting_gba();
+    let mut repl = GbaRepl::new();
+    repl.with_batch(true).with_max_steps(100);
+    assert_eq!(repl.execute(&mut gba, "continue"), Ok(false));
+    assert_eq!(repl.failures(), 1);
+}
+
+
+/*
+Licensed to the Apache Software Foundation (ASF) under one
+or more contributor license agreements.  See the NOTICE file
+distributed with this work for additional information
+regarding copyright ownership.  The ASF licenses this file
+to you under the Apache License, Version 2.0 (the
+"License"); you may not use this file except in compliance
+with the License.  You may obtain a copy of the License at
+
+  http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing,
+software distributed under the License is distributed on an
+"AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+KIND, either express or implied.  See the License for the
+specific language governing permissions and limitations
+under the License.
+*/